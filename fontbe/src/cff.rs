@@ -0,0 +1,742 @@
+//! Generates [CFF](https://learn.microsoft.com/en-us/typography/opentype/spec/cff) or
+//! [CFF2](https://learn.microsoft.com/en-us/typography/opentype/spec/cff2) outlines.
+//!
+//! Static fonts get CFF, variable fonts get CFF2 with blends for their deltas. Outlines
+//! stay cubic and components are flattened. There are no hints and no subroutines (yet).
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use fontdrasil::{orchestration::Work, types::GlyphName};
+use fontir::{
+    coords::NormalizedLocation,
+    ir::{self, NameKey, StaticMetadata},
+    orchestration::Flags,
+};
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape};
+use log::trace;
+use read_fonts::types::NameId;
+use write_fonts::{dump_table, OtRound};
+
+use crate::{
+    error::{Error, GlyphProblem},
+    orchestration::{BeWork, Bytes, Context},
+    var_store::VarStoreBuilder,
+};
+
+// Charstring operators
+const RLINETO: u8 = 5;
+const RRCURVETO: u8 = 8;
+const ENDCHAR: u8 = 14;
+const VSINDEX: u8 = 15;
+const BLEND: u8 = 16;
+const RMOVETO: u8 = 21;
+
+// DICT operators
+const FULL_NAME: &[u8] = &[2];
+const FAMILY_NAME: &[u8] = &[3];
+const FONT_BBOX: &[u8] = &[5];
+const FONT_MATRIX: &[u8] = &[12, 7];
+const CHARSET: &[u8] = &[15];
+const CHAR_STRINGS: &[u8] = &[17];
+const PRIVATE: &[u8] = &[18];
+const VSTORE: &[u8] = &[24];
+const FD_ARRAY: &[u8] = &[12, 36];
+
+/// The first string id not taken by a standard string
+const FIRST_CUSTOM_SID: usize = 391;
+
+/// The CFF2 argument stack limit
+const MAX_STACK: usize = 513;
+
+struct CffWork {}
+
+pub fn create_cff_work() -> Box<BeWork> {
+    Box::new(CffWork {})
+}
+
+/// The drawing operators we emit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Move,
+    Line,
+    Curve,
+}
+
+impl Command {
+    fn num_values(&self) -> usize {
+        match self {
+            Command::Move | Command::Line => 2,
+            Command::Curve => 6,
+        }
+    }
+
+    fn operator(&self) -> u8 {
+        match self {
+            Command::Move => RMOVETO,
+            Command::Line => RLINETO,
+            Command::Curve => RRCURVETO,
+        }
+    }
+}
+
+/// An outline as charstrings see it: commands and the rounded points they take.
+#[derive(Debug, Default)]
+struct Outline {
+    commands: Vec<Command>,
+    points: Vec<(i16, i16)>,
+}
+
+impl Outline {
+    fn new(path: &BezPath) -> Outline {
+        let mut outline = Outline::default();
+        let mut current = Point::ZERO;
+        for el in path.elements() {
+            current = match *el {
+                PathEl::MoveTo(p) => outline.push(Command::Move, &[p]),
+                PathEl::LineTo(p) => outline.push(Command::Line, &[p]),
+                PathEl::QuadTo(q, p) => {
+                    // Every quadratic has an exact cubic equivalent
+                    let c1 = current + (q - current) * (2.0 / 3.0);
+                    let c2 = p + (q - p) * (2.0 / 3.0);
+                    outline.push(Command::Curve, &[c1, c2, p])
+                }
+                PathEl::CurveTo(c1, c2, p) => outline.push(Command::Curve, &[c1, c2, p]),
+                // Closing is implicit and doesn't move the current point
+                PathEl::ClosePath => current,
+            };
+        }
+        outline
+    }
+
+    /// Returns the new current point
+    fn push(&mut self, command: Command, points: &[Point]) -> Point {
+        self.commands.push(command);
+        self.points.extend(points.iter().map(|p| p.ot_round()));
+        *points.last().unwrap()
+    }
+
+    /// Each point relative to the one before, which is what charstrings want.
+    fn values(&self) -> Vec<i32> {
+        let mut prev = (0, 0);
+        self.points
+            .iter()
+            .flat_map(|(x, y)| {
+                let (x, y) = (*x as i32, *y as i32);
+                let delta = [x - prev.0, y - prev.1];
+                prev = (x, y);
+                delta
+            })
+            .collect()
+    }
+}
+
+/// Draw the glyph at the location into path, components included.
+fn flatten(
+    glyphs: &impl Fn(&GlyphName) -> Arc<ir::Glyph>,
+    glyph: &ir::Glyph,
+    location: &NormalizedLocation,
+    transform: Affine,
+    path: &mut BezPath,
+) -> Result<(), Error> {
+    let instance = &glyph.sources()[location];
+    for contour in instance.contours.iter() {
+        path.extend(contour.elements().iter().map(|el| transform * *el));
+    }
+    for component in instance.components.iter() {
+        let base = glyphs(&component.base);
+        if !base.sources().contains_key(location) {
+            return Err(Error::ComponentError {
+                glyph: glyph.name.clone(),
+                referenced_glyph: component.base.clone(),
+                problem: GlyphProblem::MissingLocation,
+            });
+        }
+        flatten(
+            glyphs,
+            &base,
+            location,
+            transform * component.transform,
+            path,
+        )?;
+    }
+    Ok(())
+}
+
+/// The glyph outline at every location it has a source, the default first.
+fn outlines(
+    glyphs: &impl Fn(&GlyphName) -> Arc<ir::Glyph>,
+    glyph: &ir::Glyph,
+    default_location: &NormalizedLocation,
+) -> Result<(BezPath, Vec<(NormalizedLocation, Outline)>), Error> {
+    if !glyph.sources().contains_key(default_location) {
+        return Err(Error::GlyphError(
+            glyph.name.clone(),
+            GlyphProblem::MissingDefault,
+        ));
+    }
+    let mut default_path = BezPath::new();
+    flatten(
+        glyphs,
+        glyph,
+        default_location,
+        Affine::IDENTITY,
+        &mut default_path,
+    )?;
+    let default_outline = Outline::new(&default_path);
+
+    let mut outlines = vec![(default_location.clone(), default_outline)];
+    for location in glyph.sources().keys() {
+        if location == default_location {
+            continue;
+        }
+        let mut path = BezPath::new();
+        flatten(glyphs, glyph, location, Affine::IDENTITY, &mut path)?;
+        let outline = Outline::new(&path);
+        if outline.commands != outlines[0].1.commands {
+            return Err(Error::GlyphError(
+                glyph.name.clone(),
+                GlyphProblem::InconsistentPathElements,
+            ));
+        }
+        outlines.push((location.clone(), outline));
+    }
+    Ok((default_path, outlines))
+}
+
+/// Handles the encodings charstrings and DICTs share, None if the value needs something else.
+fn push_small_number(out: &mut Vec<u8>, value: i32) -> Option<()> {
+    match value {
+        -107..=107 => out.push((value + 139) as u8),
+        108..=1131 => {
+            let value = value - 108;
+            out.extend([(value >> 8) as u8 + 247, value as u8]);
+        }
+        -1131..=-108 => {
+            let value = -value - 108;
+            out.extend([(value >> 8) as u8 + 251, value as u8]);
+        }
+        -32768..=32767 => {
+            out.push(28);
+            out.extend((value as i16).to_be_bytes());
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn push_charstring_number(out: &mut Vec<u8>, value: i32) -> Result<(), Error> {
+    push_small_number(out, value).ok_or_else(|| Error::OutOfBounds {
+        what: "Charstring number".to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn push_dict_number(out: &mut Vec<u8>, value: i32) {
+    if push_small_number(out, value).is_none() {
+        push_dict_offset(out, value as u32);
+    }
+}
+
+/// Always five bytes, so DICTs can be sized before the offsets in them are known
+fn push_dict_offset(out: &mut Vec<u8>, value: u32) {
+    out.push(29);
+    out.extend(value.to_be_bytes());
+}
+
+/// A real, as a string of nibbles.
+fn push_dict_real(out: &mut Vec<u8>, value: f64) {
+    out.push(30);
+    let mut nibbles: Vec<u8> = format!("{value}")
+        .chars()
+        .map(|c| match c {
+            '0'..='9' => c as u8 - b'0',
+            '.' => 0xa,
+            '-' => 0xe,
+            _ => unreachable!("{c} in a formatted f64"),
+        })
+        .collect();
+    nibbles.push(0xf);
+    if nibbles.len() % 2 == 1 {
+        nibbles.push(0xf);
+    }
+    out.extend(nibbles.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+}
+
+/// An INDEX of the items. CFF counts them in 16 bits, CFF2 in 32.
+fn index(items: &[Vec<u8>], count_size: usize) -> Vec<u8> {
+    let mut out = (items.len() as u32).to_be_bytes()[4 - count_size..].to_vec();
+    if items.is_empty() {
+        return out;
+    }
+    let last_offset = 1 + items.iter().map(|item| item.len()).sum::<usize>();
+    let off_size = match last_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x10000..=0xFFFFFF => 3,
+        _ => 4,
+    };
+    out.push(off_size as u8);
+    let mut offset = 1u32;
+    out.extend(&offset.to_be_bytes()[4 - off_size..]);
+    for item in items.iter() {
+        offset += item.len() as u32;
+        out.extend(&offset.to_be_bytes()[4 - off_size..]);
+    }
+    for item in items.iter() {
+        out.extend(item);
+    }
+    out
+}
+
+/// Push the values, blended with one row of deltas per region.
+fn push_blend(
+    out: &mut Vec<u8>,
+    values: &[i32],
+    deltas: &[Vec<i32>],
+    range: Range<usize>,
+) -> Result<(), Error> {
+    for value in values[range.clone()].iter() {
+        push_charstring_number(out, *value)?;
+    }
+    for i in range.clone() {
+        for region_deltas in deltas.iter() {
+            push_charstring_number(out, region_deltas[i])?;
+        }
+    }
+    push_charstring_number(out, range.len() as i32)?;
+    out.push(BLEND);
+    Ok(())
+}
+
+/// The drawing part of a charstring, blending in deltas where there are any.
+///
+/// deltas has a row per region, each as long as values.
+fn draw(commands: &[Command], values: &[i32], deltas: &[Vec<i32>]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    for command in commands {
+        let range = start..start + command.num_values();
+        start = range.end;
+        let varies = deltas
+            .iter()
+            .any(|region_deltas| region_deltas[range.clone()].iter().any(|d| *d != 0));
+        if !varies {
+            for value in values[range].iter() {
+                push_charstring_number(&mut out, *value)?;
+            }
+        } else if range.len() * (deltas.len() + 1) < MAX_STACK {
+            // Everything fits on the stack with room for the count
+            push_blend(&mut out, values, deltas, range)?;
+        } else {
+            for i in range {
+                push_blend(&mut out, values, deltas, i..i + 1)?;
+            }
+        }
+        out.push(command.operator());
+    }
+    Ok(out)
+}
+
+/// A CFF charstring, which unlike CFF2 states the advance and ends with endchar.
+fn cff_charstring(outline: &Outline, width: i32) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    // Relative to a nominalWidthX of 0
+    if width != 0 {
+        push_charstring_number(&mut out, width)?;
+    }
+    out.extend(draw(&outline.commands, &outline.values(), &[])?);
+    out.push(ENDCHAR);
+    Ok(out)
+}
+
+/// A CFF2 charstring, with the regions any deltas are for added to var_store.
+fn cff2_charstring(
+    static_metadata: &StaticMetadata,
+    glyph_name: &GlyphName,
+    outlines: &[(NormalizedLocation, Outline)],
+    var_store: &mut VarStoreBuilder,
+) -> Result<Vec<u8>, Error> {
+    let default = &outlines[0].1;
+    let values: HashMap<_, _> = outlines
+        .iter()
+        .map(|(loc, outline)| {
+            let values = outline.values().into_iter().map(|v| v as f64).collect();
+            (loc.clone(), values)
+        })
+        .collect();
+    let mut regions = Vec::new();
+    let mut deltas = Vec::new();
+    for (region, region_deltas) in static_metadata
+        .variation_model_for(values.keys())
+        .map_err(|e| Error::GlyphVariationModelError(glyph_name.clone(), e))?
+        .deltas::<f64, f64>(&values)
+        .map_err(|e| Error::GlyphDeltaError(glyph_name.clone(), e))?
+    {
+        if region.is_default() {
+            continue;
+        }
+        let region_deltas: Vec<i32> = region_deltas
+            .into_iter()
+            .map(|d| OtRound::<i16>::ot_round(d) as i32)
+            .collect();
+        if region_deltas.iter().all(|d| *d == 0) {
+            continue;
+        }
+        regions.push(region);
+        deltas.push(region_deltas);
+    }
+
+    let mut out = Vec::new();
+    if !regions.is_empty() {
+        let vsindex = var_store.add_regions(&regions);
+        if vsindex != 0 {
+            push_charstring_number(&mut out, vsindex as i32)?;
+            out.push(VSINDEX);
+        }
+    }
+    out.extend(draw(&default.commands, &default.values(), &deltas)?);
+    Ok(out)
+}
+
+/// 1/upem, if that isn't the default of 1/1000.
+fn push_font_matrix(out: &mut Vec<u8>, units_per_em: u16) {
+    if units_per_em == 1000 {
+        return;
+    }
+    let scale = 1.0 / units_per_em as f64;
+    push_dict_real(out, scale);
+    push_dict_number(out, 0);
+    push_dict_number(out, 0);
+    push_dict_real(out, scale);
+    push_dict_number(out, 0);
+    push_dict_number(out, 0);
+    out.extend(FONT_MATRIX);
+}
+
+fn name(names: &HashMap<NameKey, String>, name_id: NameId) -> Option<&str> {
+    names
+        .iter()
+        .find(|(key, _)| key.name_id == name_id)
+        .map(|(_, value)| value.as_str())
+}
+
+/// The name CFF wants for the font, taken from name id 6 if there is one.
+fn postscript_name(names: &HashMap<NameKey, String>) -> String {
+    if let Some(name) = name(names, NameId::POSTSCRIPT_NAME) {
+        return name.to_string();
+    }
+    let family = name(names, NameId::FAMILY_NAME).unwrap_or("Untitled");
+    let subfamily = name(names, NameId::SUBFAMILY_NAME).unwrap_or("Regular");
+    format!("{family}-{subfamily}")
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+        .collect()
+}
+
+/// Assemble a CFF table. The first glyph is assumed to be .notdef.
+fn build_cff(
+    static_metadata: &StaticMetadata,
+    names: &HashMap<NameKey, String>,
+    charstrings: &[Vec<u8>],
+    bbox: Rect,
+) -> Vec<u8> {
+    let mut strings: Vec<Vec<u8>> = Vec::new();
+    let mut sid = |s: &str| {
+        strings.push(s.as_bytes().to_vec());
+        (FIRST_CUSTOM_SID + strings.len() - 1) as u16
+    };
+    let full_name = name(names, NameId::FULL_NAME).map(&mut sid);
+    let family_name = name(names, NameId::FAMILY_NAME).map(&mut sid);
+    let mut charset = vec![0u8]; // format 0
+    for glyph_name in static_metadata.glyph_order.iter().skip(1) {
+        charset.extend(sid(glyph_name.as_str()).to_be_bytes());
+    }
+
+    let top_dict = |charset_offset: u32, charstrings_offset: u32, private_offset: u32| {
+        let mut out = Vec::new();
+        if let Some(sid) = full_name {
+            push_dict_number(&mut out, sid as i32);
+            out.extend(FULL_NAME);
+        }
+        if let Some(sid) = family_name {
+            push_dict_number(&mut out, sid as i32);
+            out.extend(FAMILY_NAME);
+        }
+        push_font_matrix(&mut out, static_metadata.units_per_em);
+        for value in [bbox.min_x(), bbox.min_y(), bbox.max_x(), bbox.max_y()] {
+            push_dict_number(&mut out, OtRound::<i16>::ot_round(value) as i32);
+        }
+        out.extend(FONT_BBOX);
+        push_dict_offset(&mut out, charset_offset);
+        out.extend(CHARSET);
+        push_dict_offset(&mut out, charstrings_offset);
+        out.extend(CHAR_STRINGS);
+        // An empty Private DICT, we have no hints and our widths are relative to 0
+        push_dict_number(&mut out, 0);
+        push_dict_offset(&mut out, private_offset);
+        out.extend(PRIVATE);
+        out
+    };
+
+    let header = [1u8, 0, 4, 4];
+    let name_index = index(&[postscript_name(names).into_bytes()], 2);
+    let top_dict_index_len = index(&[top_dict(0, 0, 0)], 2).len();
+    let string_index = index(&strings, 2);
+    let global_subrs = index(&[], 2);
+    let charstrings_index = index(charstrings, 2);
+
+    let charset_offset = header.len()
+        + name_index.len()
+        + top_dict_index_len
+        + string_index.len()
+        + global_subrs.len();
+    let charstrings_offset = charset_offset + charset.len();
+    let private_offset = charstrings_offset + charstrings_index.len();
+    let top_dict_index = index(
+        &[top_dict(
+            charset_offset as u32,
+            charstrings_offset as u32,
+            private_offset as u32,
+        )],
+        2,
+    );
+
+    let mut cff = header.to_vec();
+    cff.extend(name_index);
+    cff.extend(top_dict_index);
+    cff.extend(string_index);
+    cff.extend(global_subrs);
+    cff.extend(charset);
+    cff.extend(charstrings_index);
+    cff
+}
+
+/// Assemble a CFF2 table.
+fn build_cff2(
+    static_metadata: &StaticMetadata,
+    charstrings: &[Vec<u8>],
+    var_store: Option<Vec<u8>>,
+) -> Vec<u8> {
+    let has_var_store = var_store.is_some();
+    let top_dict = |charstrings_offset: u32, fd_array_offset: u32, var_store_offset: u32| {
+        let mut out = Vec::new();
+        push_font_matrix(&mut out, static_metadata.units_per_em);
+        push_dict_offset(&mut out, charstrings_offset);
+        out.extend(CHAR_STRINGS);
+        push_dict_offset(&mut out, fd_array_offset);
+        out.extend(FD_ARRAY);
+        if has_var_store {
+            push_dict_offset(&mut out, var_store_offset);
+            out.extend(VSTORE);
+        }
+        out
+    };
+    // The one Font DICT points to an empty Private DICT at the very end
+    let font_dict = |private_offset: u32| {
+        let mut out = Vec::new();
+        push_dict_number(&mut out, 0);
+        push_dict_offset(&mut out, private_offset);
+        out.extend(PRIVATE);
+        out
+    };
+
+    let top_dict_len = top_dict(0, 0, 0).len();
+    let global_subrs = index(&[], 4);
+    let var_store = var_store
+        .map(|store| {
+            let mut out = (store.len() as u16).to_be_bytes().to_vec();
+            out.extend(store);
+            out
+        })
+        .unwrap_or_default();
+    let charstrings_index = index(charstrings, 4);
+    let fd_array_len = index(&[font_dict(0)], 4).len();
+
+    let var_store_offset = 5 + top_dict_len + global_subrs.len();
+    let charstrings_offset = var_store_offset + var_store.len();
+    let fd_array_offset = charstrings_offset + charstrings_index.len();
+    let private_offset = fd_array_offset + fd_array_len;
+
+    let mut cff2 = vec![2u8, 0, 5];
+    cff2.extend((top_dict_len as u16).to_be_bytes());
+    cff2.extend(top_dict(
+        charstrings_offset as u32,
+        fd_array_offset as u32,
+        var_store_offset as u32,
+    ));
+    cff2.extend(global_subrs);
+    cff2.extend(var_store);
+    cff2.extend(charstrings_index);
+    cff2.extend(index(&[font_dict(private_offset as u32)], 4));
+    cff2
+}
+
+fn generate_cff(
+    static_metadata: &StaticMetadata,
+    names: &HashMap<NameKey, String>,
+    glyphs: &impl Fn(&GlyphName) -> Arc<ir::Glyph>,
+) -> Result<Vec<u8>, Error> {
+    let default_location = static_metadata.default_location();
+    let mut bbox: Option<Rect> = None;
+    let mut charstrings = Vec::new();
+    for glyph_name in static_metadata.glyph_order.iter() {
+        let glyph = glyphs(glyph_name);
+        let (path, outlines) = outlines(glyphs, &glyph, default_location)?;
+        if !path.elements().is_empty() {
+            let glyph_bbox = path.bounding_box();
+            bbox = Some(bbox.map_or(glyph_bbox, |bbox| bbox.union(glyph_bbox)));
+        }
+        let width = OtRound::<u16>::ot_round(glyph.default_instance().width);
+        charstrings.push(cff_charstring(&outlines[0].1, width as i32)?);
+    }
+    Ok(build_cff(
+        static_metadata,
+        names,
+        &charstrings,
+        bbox.unwrap_or_default(),
+    ))
+}
+
+fn generate_cff2(
+    static_metadata: &StaticMetadata,
+    glyphs: &impl Fn(&GlyphName) -> Arc<ir::Glyph>,
+) -> Result<Vec<u8>, Error> {
+    let default_location = static_metadata.default_location();
+    let mut var_store = VarStoreBuilder::new(&static_metadata.variable_axes);
+    let mut charstrings = Vec::new();
+    for glyph_name in static_metadata.glyph_order.iter() {
+        let glyph = glyphs(glyph_name);
+        let (_, outlines) = outlines(glyphs, &glyph, default_location)?;
+        charstrings.push(cff2_charstring(
+            static_metadata,
+            glyph_name,
+            &outlines,
+            &mut var_store,
+        )?);
+    }
+    let var_store = if var_store.is_empty() {
+        None
+    } else {
+        Some(
+            dump_table(&var_store.build()).map_err(|e| Error::DumpTableError {
+                e,
+                context: "CFF2 variation store".to_string(),
+            })?,
+        )
+    };
+    Ok(build_cff2(static_metadata, &charstrings, var_store))
+}
+
+impl Work<Context, Error> for CffWork {
+    /// Generate [CFF](https://learn.microsoft.com/en-us/typography/opentype/spec/cff)
+    /// or [CFF2](https://learn.microsoft.com/en-us/typography/opentype/spec/cff2)
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        // Finalizing static metadata doesn't rerun when only names change, init has the current ones
+        let names = &context.ir.get_init_static_metadata().names;
+        let glyphs = |glyph_name: &GlyphName| context.ir.get_glyph_ir(glyph_name);
+
+        // Empty bytes tell font assembly to leave a table out
+        let (cff, cff2) = if !context.flags.contains(Flags::CFF_OUTLINES) {
+            trace!("Skip CFF and CFF2; we want TrueType outlines");
+            (Vec::new(), Vec::new())
+        } else if static_metadata.variable_axes.is_empty() {
+            (generate_cff(&static_metadata, names, &glyphs)?, Vec::new())
+        } else {
+            (Vec::new(), generate_cff2(&static_metadata, &glyphs)?)
+        };
+        context.set_cff(Bytes::new(cff));
+        context.set_cff2(Bytes::new(cff2));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::BezPath;
+
+    use super::{cff_charstring, draw, index, push_charstring_number, Command, Outline};
+
+    fn encode(value: i32) -> Vec<u8> {
+        let mut out = Vec::new();
+        push_charstring_number(&mut out, value).unwrap();
+        out
+    }
+
+    #[test]
+    fn charstring_numbers() {
+        assert_eq!(vec![139], encode(0));
+        assert_eq!(vec![32], encode(-107));
+        assert_eq!(vec![247, 0], encode(108));
+        assert_eq!(vec![250, 255], encode(1131));
+        assert_eq!(vec![251, 0], encode(-108));
+        assert_eq!(vec![254, 255], encode(-1131));
+        assert_eq!(vec![28, 0x04, 0x6c], encode(1132));
+        assert!(push_charstring_number(&mut Vec::new(), 40000).is_err());
+    }
+
+    #[test]
+    fn index_offsets() {
+        assert_eq!(vec![0, 0], index(&[], 2));
+        assert_eq!(vec![0, 0, 0, 0], index(&[], 4));
+        assert_eq!(
+            vec![0, 2, 1, 1, 3, 4, b'a', b'b', b'c'],
+            index(&[b"ab".to_vec(), b"c".to_vec()], 2)
+        );
+    }
+
+    #[test]
+    fn quadratics_become_cubics() {
+        let path = BezPath::from_svg("M0,0 Q30,60 60,0 Z").unwrap();
+        let outline = Outline::new(&path);
+        assert_eq!(vec![Command::Move, Command::Curve], outline.commands);
+        assert_eq!(vec![0, 0, 20, 40, 20, 0, 20, -40], outline.values());
+    }
+
+    #[test]
+    fn static_square() {
+        let path = BezPath::from_svg("M10,0 L110,0 L110,100 L10,100 Z").unwrap();
+        let charstring = cff_charstring(&Outline::new(&path), 100).unwrap();
+        assert_eq!(
+            vec![
+                100 + 139, // width
+                10 + 139,
+                139,
+                21, // rmoveto
+                100 + 139,
+                139,
+                5, // rlineto
+                139,
+                100 + 139,
+                5,
+                139 - 100,
+                139,
+                5,
+                14, // endchar
+            ],
+            charstring
+        );
+    }
+
+    #[test]
+    fn blend_only_what_varies() {
+        let commands = [Command::Move, Command::Line];
+        let values = [10, 0, 100, 0];
+        let deltas = vec![vec![0, 0, 20, 0]];
+        assert_eq!(
+            vec![
+                10 + 139,
+                139,
+                21, // rmoveto, no deltas
+                100 + 139,
+                139,
+                20 + 139,
+                139,
+                2 + 139,
+                16, // blend
+                5,  // rlineto
+            ],
+            draw(&commands, &values, &deltas).unwrap()
+        );
+    }
+}
@@ -2,10 +2,15 @@ use std::{fmt::Display, io};
 
 use fea_rs::compile::error::{BinaryCompilationError, CompilerError};
 use fontdrasil::types::GlyphName;
-use fontir::variations::DeltaError;
+use fontir::{
+    coords::NormalizedLocation,
+    error::{PathConversionError, VariationModelError},
+    ir::{GlobalMetric, KernPair},
+    variations::DeltaError,
+};
 use read_fonts::ReadError;
 use thiserror::Error;
-use write_fonts::tables::{glyf::BadKurbo, gvar::GvarInputError};
+use write_fonts::tables::glyf::BadKurbo;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -43,10 +48,28 @@ pub enum Error {
     OutOfBounds { what: String, value: String },
     #[error("Unable to compute deltas for {0}: {1}")]
     GlyphDeltaError(GlyphName, DeltaError),
-    #[error("Unable to assemble gvar")]
-    GvarError(#[from] GvarInputError),
+    #[error("Unable to create a variation model for {0}: {1}")]
+    GlyphVariationModelError(GlyphName, VariationModelError),
+    #[error("Unable to compute deltas for kerning {0:?}: {1}")]
+    KerningDeltaError(KernPair, DeltaError),
+    #[error("Unable to create a variation model for kerning {0:?}: {1}")]
+    KerningVariationModelError(KernPair, VariationModelError),
+    #[error("Unable to compute deltas for {0:?}: {1}")]
+    MetricDeltaError(GlobalMetric, DeltaError),
+    #[error("Unable to create a variation model for {0:?}: {1}")]
+    MetricVariationModelError(GlobalMetric, VariationModelError),
+    #[error("Unable to compute deltas for fea values: {0}")]
+    FeaDeltaError(DeltaError),
+    #[error("Unable to create a variation model for fea values: {0}")]
+    FeaVariationModelError(VariationModelError),
+    #[error("Fea compiled at {0:?} is incompatible with the default, {1} differs")]
+    IncompatibleFea(NormalizedLocation, String),
+    #[error("No fea for {0:?}")]
+    NoFeaAtLocation(NormalizedLocation),
     #[error("Unable to read")]
     ReadFontsReadError(#[from] ReadError),
+    #[error("Unable to convert to quadratic")]
+    QuadraticConversionError(#[from] PathConversionError),
 }
 
 #[derive(Debug)]
@@ -55,6 +78,7 @@ pub enum GlyphProblem {
     InconsistentPathElements,
     HasComponentsAndPath,
     MissingDefault,
+    MissingLocation,
     NoComponents,
     NotInGlyphOrder,
 }
@@ -68,6 +92,7 @@ impl Display for GlyphProblem {
             }
             GlyphProblem::InconsistentPathElements => "has interpolation-incompatible paths",
             GlyphProblem::MissingDefault => "has no default master",
+            GlyphProblem::MissingLocation => "has no source at every location it is used",
             GlyphProblem::NoComponents => "has no components",
             GlyphProblem::NotInGlyphOrder => "has no entry in glyph order",
         };
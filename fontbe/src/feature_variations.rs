@@ -0,0 +1,270 @@
+//! GSUB [FeatureVariations](https://learn.microsoft.com/en-us/typography/opentype/spec/chapter2#featurevariations-table)
+//! from substitution rules.
+//!
+//! Each rule becomes a single substitution lookup. Where the condition sets of several
+//! rules overlap the record for the overlap applies all their lookups; records are
+//! ordered most specific first because only the first match applies.
+//! See `addFeatureVariations` in <https://github.com/fonttools/fonttools/blob/main/Lib/fontTools/varLib/featureVars.py>.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use font_types::{F2Dot14, GlyphId};
+use fontdrasil::types::GlyphName;
+use fontir::ir::{Condition, StaticMetadata};
+use log::{debug, warn};
+use read_fonts::{FontRef, TableProvider, TopLevelTable};
+use write_fonts::{
+    dump_table,
+    from_obj::ToOwnedTable,
+    tables::{
+        gsub::{Gsub, SingleSubst, SubstitutionLookup},
+        layout::{
+            ConditionFormat1, ConditionSet, CoverageTableBuilder, FeatureTableSubstitution,
+            FeatureTableSubstitutionRecord, FeatureVariationRecord, FeatureVariations, Lookup,
+            LookupFlag,
+        },
+    },
+    FontBuilder, NullableOffsetMarker,
+};
+
+use crate::{error::Error, features::add_features};
+
+/// The (min, max) of each axis a box covers, axes that aren't mentioned are unbounded.
+type ConditionBox = BTreeMap<String, (F2Dot14, F2Dot14)>;
+
+fn to_box(condition_set: &[Condition]) -> ConditionBox {
+    let mut result = ConditionBox::new();
+    for condition in condition_set {
+        let (min, max) = (
+            F2Dot14::from_f32(condition.min.to_f32()),
+            F2Dot14::from_f32(condition.max.to_f32()),
+        );
+        let range = result
+            .entry(condition.axis.clone())
+            .or_insert((F2Dot14::from_f32(-1.0), F2Dot14::from_f32(1.0)));
+        range.0 = range.0.max(min);
+        range.1 = range.1.min(max);
+    }
+    result
+}
+
+fn intersect(a: &ConditionBox, b: &ConditionBox) -> Option<ConditionBox> {
+    let mut result = a.clone();
+    for (axis, (min, max)) in b {
+        let range = result.entry(axis.clone()).or_insert((*min, *max));
+        range.0 = range.0.max(*min);
+        range.1 = range.1.min(*max);
+        if range.0 > range.1 {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+/// Every region where some combination of rules applies, most rules first.
+fn overlay(rule_boxes: &[Vec<ConditionBox>]) -> Vec<(ConditionBox, BTreeSet<usize>)> {
+    let mut regions: Vec<(ConditionBox, BTreeSet<usize>)> = Vec::new();
+    for (rule_idx, boxes) in rule_boxes.iter().enumerate() {
+        for rule_box in boxes {
+            let mut new_regions = vec![(rule_box.clone(), BTreeSet::from([rule_idx]))];
+            for (region, rules) in regions.iter() {
+                if rules.contains(&rule_idx) {
+                    continue;
+                }
+                if let Some(overlap) = intersect(region, rule_box) {
+                    let mut rules = rules.clone();
+                    rules.insert(rule_idx);
+                    new_regions.push((overlap, rules));
+                }
+            }
+            for new_region in new_regions {
+                if !regions.contains(&new_region) {
+                    regions.push(new_region);
+                }
+            }
+        }
+    }
+    // Stable so among equals the order of the rules is kept
+    regions.sort_by_key(|(_, rules)| std::cmp::Reverse(rules.len()));
+    regions
+}
+
+/// Add the substitution rules of the source to GSUB, creating it if need be.
+pub(crate) fn add_feature_variations(
+    font: &mut FontBuilder,
+    static_metadata: &StaticMetadata,
+) -> Result<(), Error> {
+    let feature_variations = &static_metadata.feature_variations;
+    if feature_variations.is_empty() || static_metadata.variable_axes.is_empty() {
+        return Ok(());
+    }
+    let gid = |name: &GlyphName| {
+        static_metadata
+            .glyph_id(name)
+            .map(|gid| GlyphId::new(gid as u16))
+    };
+
+    // A lookup per rule, rules without any glyphs in the font are dropped
+    let mut lookups = Vec::new();
+    let mut rule_boxes = Vec::new();
+    for rule in feature_variations.rules.iter() {
+        let mut substitutions = BTreeMap::new();
+        for (from, to) in rule.substitutions.iter() {
+            match (gid(from), gid(to)) {
+                (Some(from), Some(to)) => {
+                    substitutions.insert(from, to);
+                }
+                _ => warn!(
+                    "Rule {:?} substitutes {from} with {to}, not both are in the font",
+                    rule.name
+                ),
+            }
+        }
+        if substitutions.is_empty() || rule.condition_sets.is_empty() {
+            debug!("Rule {:?} has nothing to do", rule.name);
+            continue;
+        }
+        let coverage = substitutions
+            .keys()
+            .copied()
+            .collect::<CoverageTableBuilder>();
+        lookups.push(SubstitutionLookup::Single(Lookup::new(
+            LookupFlag::empty(),
+            vec![SingleSubst::format_2(
+                coverage.build(),
+                substitutions.into_values().collect(),
+            )],
+            0,
+        )));
+        rule_boxes.push(
+            rule.condition_sets
+                .iter()
+                .map(|set| to_box(set))
+                .collect::<Vec<_>>(),
+        );
+    }
+    if lookups.is_empty() {
+        return Ok(());
+    }
+
+    let font_bytes = font.build();
+    let font_ref = FontRef::new(&font_bytes)?;
+    let mut gsub: Gsub = match font_ref.gsub() {
+        Ok(gsub) => gsub.to_owned_table(),
+        Err(..) => Default::default(),
+    };
+
+    let first_lookup = gsub.lookup_list.lookups.len() as u16;
+    gsub.lookup_list
+        .lookups
+        .extend(lookups.into_iter().map(Into::into));
+
+    // The feature has to exist, even if empty, for there to be something to substitute
+    let feature_tag = feature_variations.feature_tag;
+    if !gsub
+        .feature_list
+        .feature_records
+        .iter()
+        .any(|r| r.feature_tag == feature_tag)
+    {
+        add_features(
+            &mut gsub.script_list,
+            &mut gsub.feature_list,
+            vec![(feature_tag, Vec::new())],
+        );
+    }
+
+    let axis_index = |axis_name: &String| {
+        static_metadata
+            .variable_axes
+            .iter()
+            .position(|a| a.name == *axis_name)
+    };
+    let mut records = Vec::new();
+    for (region, rules) in overlay(&rule_boxes) {
+        let conditions = region
+            .iter()
+            .filter_map(|(axis_name, (min, max))| {
+                let Some(axis_index) = axis_index(axis_name) else {
+                    debug!("Dropping condition on {axis_name}, it doesn't vary");
+                    return None;
+                };
+                Some(ConditionFormat1::new(axis_index as u16, *min, *max))
+            })
+            .collect();
+        let substitutions = gsub
+            .feature_list
+            .feature_records
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.feature_tag == feature_tag)
+            .map(|(feature_index, record)| {
+                let mut feature = (*record.feature).clone();
+                feature
+                    .lookup_list_indices
+                    .extend(rules.iter().map(|rule| first_lookup + *rule as u16));
+                feature.lookup_list_indices.sort();
+                FeatureTableSubstitutionRecord::new(feature_index as u16, feature)
+            })
+            .collect();
+        records.push(FeatureVariationRecord::new(
+            ConditionSet::new(conditions),
+            FeatureTableSubstitution::new(substitutions),
+        ));
+    }
+    gsub.feature_variations = NullableOffsetMarker::new(Some(FeatureVariations::new(records)));
+
+    font.add_table(
+        Gsub::TAG,
+        dump_table(&gsub).map_err(|e| Error::DumpTableError {
+            e,
+            context: "GSUB".to_string(),
+        })?,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use font_types::F2Dot14;
+
+    use super::{overlay, ConditionBox};
+
+    fn rule_box(axis: &str, min: f32, max: f32) -> ConditionBox {
+        ConditionBox::from([(
+            axis.to_string(),
+            (F2Dot14::from_f32(min), F2Dot14::from_f32(max)),
+        )])
+    }
+
+    #[test]
+    fn overlaps_come_first() {
+        let regions = overlay(&[
+            vec![rule_box("Weight", 0.5, 1.0)],
+            vec![rule_box("Width", 0.5, 1.0)],
+        ]);
+        assert_eq!(
+            vec![
+                BTreeSet::from([0, 1]),
+                BTreeSet::from([0]),
+                BTreeSet::from([1])
+            ],
+            regions
+                .iter()
+                .map(|(_, rules)| rules.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, regions[0].0.len());
+    }
+
+    #[test]
+    fn disjoint_rules_dont_combine() {
+        let regions = overlay(&[
+            vec![rule_box("Weight", 0.5, 1.0)],
+            vec![rule_box("Weight", -1.0, 0.0)],
+        ]);
+        assert_eq!(2, regions.len());
+    }
+}
@@ -2,24 +2,42 @@
 
 use std::{
     ffi::{OsStr, OsString},
-    fmt::Display,
     fs,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
 use fea_rs::{
-    parse::{SourceLoadError, SourceResolver},
+    parse::{FileSystemResolver, SourceLoadError, SourceResolver},
     Compiler, GlyphMap, GlyphName as FeaRsGlyphName,
 };
-use fontir::{ir::Features, orchestration::Flags};
+use font_types::Tag;
+use fontir::{
+    coords::NormalizedLocation,
+    ir::{Axis, Features},
+    orchestration::Flags,
+};
 use log::{debug, error, trace, warn};
-use write_fonts::FontBuilder;
+use read_fonts::{FontRef, TableProvider, TopLevelTable};
+use write_fonts::{
+    dump_table,
+    from_obj::ToOwnedTable,
+    tables::{
+        gdef::Gdef,
+        gpos::{Gpos, PositionLookup},
+        layout::{Feature, FeatureList, FeatureRecord, LangSys, Script, ScriptList, ScriptRecord},
+    },
+    FontBuilder,
+};
 
 use fontdrasil::orchestration::Work;
 
 use crate::{
     error::Error,
+    feature_variations, kern, marks,
     orchestration::{BeWork, Context},
+    var_store::VarStoreBuilder,
+    variable_fea::{self, VariableValues},
 };
 
 pub struct FeatureWork {}
@@ -28,42 +46,44 @@ pub struct FeatureWork {}
 // I did not want to clone the content
 // I do not like this construct
 // I do find the need to lament
-struct InMemoryResolver {
-    content_path: OsString,
-    content: Arc<str>,
+/// Serves fea source, from memory or disk, with variable values resolved for one location.
+struct FeaResolver {
+    /// The root fea when it isn't a file, such as fea from a .glyphs file
+    in_memory: Option<(OsString, Arc<str>)>,
+    file_system: FileSystemResolver,
+    variable_values: VariableValues,
 }
 
-impl SourceResolver for InMemoryResolver {
-    fn get_contents(&self, path: &OsStr) -> Result<Arc<str>, SourceLoadError> {
-        if path == &*self.content_path {
-            return Ok(self.content.clone());
-        }
-        Err(SourceLoadError::new(
-            path.to_os_string(),
-            NotSupportedError::new(),
-        ))
+impl FeaResolver {
+    fn is_in_memory(&self, path: &OsStr) -> bool {
+        matches!(&self.in_memory, Some((content_path, _)) if content_path == path)
     }
 }
 
-#[derive(Debug)]
-struct NotSupportedError {}
-
-impl NotSupportedError {
-    fn new() -> NotSupportedError {
-        NotSupportedError {}
+impl SourceResolver for FeaResolver {
+    fn get_contents(&self, path: &OsStr) -> Result<Arc<str>, SourceLoadError> {
+        let content = match &self.in_memory {
+            Some((content_path, content)) if content_path == path => content.clone(),
+            _ => self.file_system.get_contents(path)?,
+        };
+        self.variable_values
+            .resolve(&content)
+            .map(Arc::from)
+            .map_err(|e| SourceLoadError::new(path.to_os_string(), e))
     }
-}
 
-impl std::error::Error for NotSupportedError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+    fn resolve_raw_path(&self, path: &OsStr, included_from: Option<&OsStr>) -> OsString {
+        if self.is_in_memory(path) {
+            return path.to_os_string();
+        }
+        self.file_system.resolve_raw_path(path, included_from)
     }
-}
 
-impl Display for NotSupportedError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("Not supported")?;
-        Ok(())
+    fn canonicalize(&self, path: &OsStr) -> Result<OsString, SourceLoadError> {
+        if self.is_in_memory(path) {
+            return Ok(path.to_os_string());
+        }
+        self.file_system.canonicalize(path)
     }
 }
 
@@ -72,25 +92,47 @@ impl FeatureWork {
         Box::new(FeatureWork {})
     }
 
-    fn compile(&self, features: &Features, glyph_order: GlyphMap) -> Result<FontBuilder, Error> {
-        let root_path = if let Features::File(file) = features {
-            OsString::from(file)
-        } else {
-            OsString::new()
+    /// Compile the fea as it applies at location.
+    ///
+    /// Also reports whether the fea uses any variable values.
+    fn compile(
+        &self,
+        features: &Features,
+        location: &NormalizedLocation,
+        axes: &[Axis],
+        glyph_order: &GlyphMap,
+    ) -> Result<(FontBuilder<'static>, bool), Error> {
+        let (root_path, in_memory) = match features {
+            Features::File(file) => (file.clone(), None),
+            Features::PerMaster(files) => {
+                let Some((_, file)) = files.iter().find(|(loc, _)| loc == location) else {
+                    return Err(Error::NoFeaAtLocation(location.clone()));
+                };
+                (file.clone(), None)
+            }
+            Features::Memory(fea_content) => {
+                (PathBuf::new(), Some(Arc::from(fea_content.as_str())))
+            }
+            Features::Empty => return Ok((FontBuilder::default(), false)),
         };
-        let mut compiler = Compiler::new(root_path.clone(), &glyph_order);
-        if let Features::Memory(fea_content) = features {
-            let resolver = InMemoryResolver {
-                content_path: root_path,
-                content: Arc::from(fea_content.as_str()),
-            };
-            compiler = compiler.with_resolver(resolver);
-        }
-        compiler
+        let project_root = root_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let variable_values = VariableValues::new(location.clone(), axes);
+        let has_variable_values = variable_values.seen();
+        let resolver = FeaResolver {
+            in_memory: in_memory.map(|content| (root_path.clone().into_os_string(), content)),
+            file_system: FileSystemResolver::new(project_root),
+            variable_values,
+        };
+        let font = Compiler::new(root_path, glyph_order)
+            .with_resolver(resolver)
             .compile()
             .map_err(Error::FeaCompileError)?
-            .assemble(&glyph_order, Default::default())
-            .map_err(Error::FeaAssembleError)
+            .assemble(glyph_order, Default::default())
+            .map_err(Error::FeaAssembleError)?;
+        Ok((font, has_variable_values.get()))
     }
 }
 
@@ -111,29 +153,192 @@ fn write_debug_fea(context: &Context, is_error: bool, why: &str, fea_content: &s
 impl Work<Context, Error> for FeatureWork {
     fn exec(&self, context: &Context) -> Result<(), Error> {
         let features = context.ir.get_features();
-        if let Features::Empty = *features {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let default_location = static_metadata.default_location();
+        let mut var_store = VarStoreBuilder::new(&static_metadata.variable_axes);
+        let mut font = if let Features::Empty = *features {
             // set a default in place so subsequent compiles skip this step
             trace!("No fea file, dull compile");
-            context.set_features(FontBuilder::default());
-            return Ok(());
-        }
-        let glyph_order = &context.ir.get_final_static_metadata().glyph_order;
-        if glyph_order.is_empty() {
-            warn!("Glyph order is empty; feature compile improbable");
-        }
-        let glyph_map = glyph_order
-            .iter()
-            .map(|n| Into::<FeaRsGlyphName>::into(n.as_str()))
-            .collect();
+            FontBuilder::default()
+        } else {
+            let glyph_order = &static_metadata.glyph_order;
+            if glyph_order.is_empty() {
+                warn!("Glyph order is empty; feature compile improbable");
+            }
+            let glyph_map = glyph_order
+                .iter()
+                .map(|n| Into::<FeaRsGlyphName>::into(n.as_str()))
+                .collect();
 
-        let result = self.compile(&features, glyph_map);
-        if result.is_err() || context.flags.contains(Flags::EMIT_DEBUG) {
-            if let Features::Memory(fea_content) = &*features {
-                write_debug_fea(context, result.is_err(), "compile failed", fea_content);
+            let result = self.compile(
+                &features,
+                default_location,
+                &static_metadata.axes,
+                &glyph_map,
+            );
+            if result.is_err() || context.flags.contains(Flags::EMIT_DEBUG) {
+                if let Features::Memory(fea_content) = &*features {
+                    write_debug_fea(context, result.is_err(), "compile failed", fea_content);
+                }
             }
-        }
-        let font = result?;
+            let (mut font, has_variable_values) = result?;
+
+            // Values that vary need the fea compiled at every master
+            let master_locations: Vec<_> = match &*features {
+                Features::PerMaster(files) => files.iter().map(|(loc, _)| loc).collect(),
+                _ if has_variable_values => static_metadata.variation_model.locations().collect(),
+                _ => Vec::new(),
+            };
+            let mut masters = Vec::new();
+            for location in master_locations {
+                if location == default_location {
+                    continue;
+                }
+                let (master, _) =
+                    self.compile(&features, location, &static_metadata.axes, &glyph_map)?;
+                masters.push((location.clone(), master));
+            }
+            if !masters.is_empty() {
+                variable_fea::merge_masters(&mut font, masters, &static_metadata, &mut var_store)?;
+            }
+            font
+        };
+
+        let mut lookups =
+            kern::create_lookups(&context.get_kerning(), &static_metadata, &mut var_store);
+        lookups.extend(marks::create_lookups(
+            &context.get_marks(),
+            &static_metadata,
+            &mut var_store,
+        ));
+        merge_gpos_lookups(&mut font, lookups, var_store)?;
+        feature_variations::add_feature_variations(&mut font, &static_metadata)?;
+
         context.set_features(font);
         Ok(())
     }
 }
+
+/// Add generated lookups to GPOS, creating it if the fea source didn't.
+///
+/// Features the fea source already defines are left alone, the source wins.
+/// Any variation data the lookups, or variable values in the fea, reference is stored in GDEF.
+fn merge_gpos_lookups(
+    font: &mut FontBuilder,
+    lookups: Vec<(Tag, PositionLookup)>,
+    var_store: VarStoreBuilder,
+) -> Result<(), Error> {
+    if lookups.is_empty() && var_store.is_empty() {
+        return Ok(());
+    }
+    let font_bytes = font.build();
+    let font_ref = FontRef::new(&font_bytes)?;
+    let mut gpos: Gpos = match font_ref.gpos() {
+        Ok(gpos) => gpos.to_owned_table(),
+        Err(..) => Default::default(),
+    };
+    let mut gdef: Option<Gdef> = font_ref.gdef().ok().map(|gdef| gdef.to_owned_table());
+
+    let mut new_features: Vec<(Tag, Vec<u16>)> = Vec::new();
+    for (tag, lookup) in lookups {
+        if gpos
+            .feature_list
+            .feature_records
+            .iter()
+            .any(|r| r.feature_tag == tag)
+        {
+            debug!("fea defines '{tag}', skipping generated lookup");
+            continue;
+        }
+        let lookup_idx = gpos.lookup_list.lookups.len() as u16;
+        gpos.lookup_list.lookups.push(lookup.into());
+        match new_features.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, indices)) => indices.push(lookup_idx),
+            None => new_features.push((tag, vec![lookup_idx])),
+        }
+    }
+    if new_features.is_empty() && var_store.is_empty() {
+        return Ok(());
+    }
+
+    if !new_features.is_empty() {
+        add_features(&mut gpos.script_list, &mut gpos.feature_list, new_features);
+        font.add_table(
+            Gpos::TAG,
+            dump_table(&gpos).map_err(|e| Error::DumpTableError {
+                e,
+                context: "GPOS".to_string(),
+            })?,
+        );
+    }
+
+    if !var_store.is_empty() {
+        gdef.get_or_insert_with(Default::default).item_var_store = var_store.build().into();
+    }
+    if let Some(gdef) = gdef {
+        font.add_table(
+            Gdef::TAG,
+            dump_table(&gdef).map_err(|e| Error::DumpTableError {
+                e,
+                context: "GDEF".to_string(),
+            })?,
+        );
+    }
+    Ok(())
+}
+
+/// Register features with every script and language system.
+///
+/// The feature list must be sorted by tag so indices into it are remapped.
+pub(crate) fn add_features(
+    script_list: &mut ScriptList,
+    feature_list: &mut FeatureList,
+    features: Vec<(Tag, Vec<u16>)>,
+) {
+    let feature_records = &mut feature_list.feature_records;
+    let first_new = feature_records.len();
+    feature_records.extend(
+        features
+            .into_iter()
+            .map(|(tag, lookups)| FeatureRecord::new(tag, Feature::new(None, lookups))),
+    );
+    let mut sorted: Vec<_> = std::mem::take(feature_records)
+        .into_iter()
+        .enumerate()
+        .collect();
+    sorted.sort_by_key(|(_, record)| record.feature_tag);
+
+    let mut new_index = vec![0u16; sorted.len()];
+    for (new_idx, (old_idx, _)) in sorted.iter().enumerate() {
+        new_index[*old_idx] = new_idx as u16;
+    }
+    feature_records.extend(sorted.into_iter().map(|(_, record)| record));
+
+    let added: Vec<u16> = new_index[first_new..].to_vec();
+    let update = |lang_sys: &mut LangSys| {
+        for idx in lang_sys.feature_indices.iter_mut() {
+            *idx = new_index[*idx as usize];
+        }
+        if lang_sys.required_feature_index != 0xFFFF {
+            lang_sys.required_feature_index = new_index[lang_sys.required_feature_index as usize];
+        }
+        lang_sys.feature_indices.extend(added.iter().copied());
+        lang_sys.feature_indices.sort();
+    };
+
+    let script_records = &mut script_list.script_records;
+    if script_records.is_empty() {
+        script_records.push(ScriptRecord::new(
+            Tag::new(b"DFLT"),
+            Script::new(Some(LangSys::default()), Vec::new()),
+        ));
+    }
+    for script_record in script_records.iter_mut() {
+        if let Some(lang_sys) = script_record.script.default_lang_sys.as_mut() {
+            update(lang_sys);
+        }
+        for lang_sys_record in script_record.script.lang_sys_records.iter_mut() {
+            update(&mut lang_sys_record.lang_sys);
+        }
+    }
+}
@@ -1,14 +1,17 @@
 //! Merge tables into a font
 
 use fontdrasil::orchestration::Work;
+use fontir::orchestration::Flags;
 use log::debug;
 use read_fonts::{
     tables::{
-        avar::Avar, cmap::Cmap, fvar::Fvar, glyf::Glyf, gvar::Gvar, head::Head, hhea::Hhea,
-        hmtx::Hmtx, loca::Loca, maxp::Maxp, name::Name, os2::Os2, post::Post,
+        avar::Avar, cmap::Cmap, fvar::Fvar, gdef::Gdef, glyf::Glyf, gpos::Gpos, gsub::Gsub,
+        gvar::Gvar, head::Head, hhea::Hhea, hmtx::Hmtx, hvar::Hvar, loca::Loca, maxp::Maxp,
+        mvar::Mvar, name::Name, os2::Os2, post::Post, stat::Stat, vhea::Vhea, vmtx::Vmtx,
+        vvar::Vvar,
     },
     types::Tag,
-    TopLevelTable,
+    FontRef, TopLevelTable,
 };
 use write_fonts::FontBuilder;
 
@@ -28,40 +31,130 @@ enum TableType {
     Variable,
 }
 
+const CFF: Tag = Tag::new(b"CFF ");
+const CFF2: Tag = Tag::new(b"CFF2");
+
+/// The tables that hold TrueType outlines, we leave them out if we have CFF outlines
+const TRUETYPE_OUTLINE_TABLES: &[Tag] = &[Glyf::TAG, Gvar::TAG, Loca::TAG];
+
 const TABLES_TO_MERGE: &[(WorkId, Tag, TableType)] = &[
     (WorkId::Avar, Avar::TAG, TableType::Variable),
+    (WorkId::Cff, CFF, TableType::Static),
+    (WorkId::Cff2, CFF2, TableType::Variable),
     (WorkId::Cmap, Cmap::TAG, TableType::Static),
+    (WorkId::Gdef, Gdef::TAG, TableType::Static),
+    (WorkId::Features, Gpos::TAG, TableType::Static),
+    (WorkId::Features, Gsub::TAG, TableType::Static),
     (WorkId::Fvar, Fvar::TAG, TableType::Variable),
     (WorkId::Head, Head::TAG, TableType::Static),
     (WorkId::Hhea, Hhea::TAG, TableType::Static),
     (WorkId::Hmtx, Hmtx::TAG, TableType::Static),
+    (WorkId::Hvar, Hvar::TAG, TableType::Variable),
     (WorkId::Glyf, Glyf::TAG, TableType::Static),
     (WorkId::Gvar, Gvar::TAG, TableType::Variable),
     (WorkId::Loca, Loca::TAG, TableType::Static),
     (WorkId::Maxp, Maxp::TAG, TableType::Static),
+    (WorkId::Mvar, Mvar::TAG, TableType::Variable),
     (WorkId::Name, Name::TAG, TableType::Static),
     (WorkId::Os2, Os2::TAG, TableType::Static),
     (WorkId::Post, Post::TAG, TableType::Static),
+    (WorkId::Stat, Stat::TAG, TableType::Variable),
+    (WorkId::Vhea, Vhea::TAG, TableType::Static),
+    (WorkId::Vmtx, Vmtx::TAG, TableType::Static),
+    (WorkId::Vvar, Vvar::TAG, TableType::Variable),
 ];
 
-fn bytes_for(context: &Context, id: WorkId) -> Result<Vec<u8>, Error> {
+/// The bytes for the table, None if the work that produces it chose not to.
+fn bytes_for(context: &Context, id: WorkId, tag: Tag) -> Result<Option<Vec<u8>>, Error> {
     let bytes = match id {
+        WorkId::Features => {
+            // Features are compiled into a font of their own, pluck out the table
+            let features = context.get_features();
+            if features.is_empty() {
+                return Ok(None);
+            }
+            let font = FontRef::new(&features)?;
+            return Ok(font.table_data(tag).map(|data| data.as_ref().to_vec()));
+        }
         WorkId::Avar => to_bytes(context.get_avar().as_ref()),
+        WorkId::Cff | WorkId::Cff2 => {
+            // Only one of them has anything in it
+            let cff = if id == WorkId::Cff {
+                context.get_cff()
+            } else {
+                context.get_cff2()
+            };
+            if cff.get().is_empty() {
+                return Ok(None);
+            }
+            cff.get().to_vec()
+        }
         WorkId::Cmap => to_bytes(&*context.get_cmap()),
         WorkId::Fvar => to_bytes(&*context.get_fvar()),
+        WorkId::Gdef => {
+            // Nothing classified, no carets, no variation; no GDEF
+            let gdef = context.get_gdef();
+            if gdef.glyph_class_def.is_none()
+                && gdef.attach_list.is_none()
+                && gdef.lig_caret_list.is_none()
+                && gdef.mark_attach_class_def.is_none()
+                && gdef.mark_glyph_sets_def.is_none()
+                && gdef.item_var_store.is_none()
+            {
+                return Ok(None);
+            }
+            to_bytes(&*gdef)
+        }
         WorkId::Head => to_bytes(&*context.get_head()),
         WorkId::Hhea => to_bytes(&*context.get_hhea()),
         WorkId::Hmtx => context.get_hmtx().get().to_vec(),
+        WorkId::Hvar => {
+            // No advance varies; no HVAR
+            let hvar = context.get_hvar();
+            if hvar.get().is_empty() {
+                return Ok(None);
+            }
+            hvar.get().to_vec()
+        }
         WorkId::Glyf => context.get_glyf_loca().glyf.clone(),
         WorkId::Gvar => context.get_gvar().get().to_vec(),
         WorkId::Loca => context.get_glyf_loca().raw_loca.clone(),
         WorkId::Maxp => to_bytes(&*context.get_maxp()),
+        WorkId::Mvar => {
+            // No metric varies; no MVAR
+            let mvar = context.get_mvar();
+            if mvar.get().is_empty() {
+                return Ok(None);
+            }
+            mvar.get().to_vec()
+        }
         WorkId::Name => to_bytes(&*context.get_name()),
         WorkId::Os2 => to_bytes(&*context.get_os2()),
         WorkId::Post => to_bytes(&*context.get_post()),
+        WorkId::Stat => to_bytes(&*context.get_stat()),
+        WorkId::Vhea => {
+            // No vertical metrics; no vhea
+            let vhea = context.get_vhea();
+            if vhea.number_of_long_ver_metrics == 0 {
+                return Ok(None);
+            }
+            to_bytes(&*vhea)
+        }
+        WorkId::Vmtx | WorkId::Vvar => {
+            // No vertical metrics, or no advance height varies; leave it out
+            let bytes = if id == WorkId::Vmtx {
+                context.get_vmtx()
+            } else {
+                context.get_vvar()
+            };
+            if bytes.get().is_empty() {
+                return Ok(None);
+            }
+            bytes.get().to_vec()
+        }
         _ => panic!("Missing a match for {id:?}"),
     };
-    Ok(bytes)
+    Ok(Some(bytes))
 }
 
 impl Work<Context, Error> for FontWork {
@@ -76,17 +169,33 @@ impl Work<Context, Error> for FontWork {
             .get_init_static_metadata()
             .variable_axes
             .is_empty();
+        let cff_outlines = context.flags.contains(Flags::CFF_OUTLINES);
         for (work_id, tag, table_type) in TABLES_TO_MERGE {
             if is_static && matches!(table_type, TableType::Variable) {
                 debug!("Skip {tag} because this is a static font");
                 continue;
             }
+            if cff_outlines && TRUETYPE_OUTLINE_TABLES.contains(tag) {
+                debug!("Skip {tag} because we have CFF outlines");
+                continue;
+            }
+            if !cff_outlines && matches!(work_id, WorkId::Cff | WorkId::Cff2) {
+                debug!("Skip {tag} because we have TrueType outlines");
+                continue;
+            }
             debug!("Grabbing {tag} for final font");
-            let bytes = bytes_for(context, work_id.clone())?;
+            let Some(bytes) = bytes_for(context, work_id.clone(), *tag)? else {
+                debug!("No {tag} for final font");
+                continue;
+            };
             builder.add_table(*tag, bytes);
         }
 
-        let font = builder.build();
+        let mut font = builder.build();
+        if cff_outlines {
+            // FontBuilder always says TrueType, CFF outlines say OTTO
+            font[..4].copy_from_slice(b"OTTO");
+        }
         debug!("Assembled {} byte font", font.len());
         context.set_font(Bytes::new(font));
         Ok(())
@@ -4,9 +4,9 @@ use std::collections::HashMap;
 
 use fontdrasil::orchestration::Work;
 use fontir::ir::StaticMetadata;
-use log::trace;
-use read_fonts::types::MajorMinor;
-use write_fonts::tables::fvar::{AxisInstanceArrays, Fvar, VariationAxisRecord};
+use log::{trace, warn};
+use read_fonts::types::{MajorMinor, NameId};
+use write_fonts::tables::fvar::{AxisInstanceArrays, Fvar, InstanceRecord, VariationAxisRecord};
 
 use crate::{
     error::Error,
@@ -53,7 +53,7 @@ fn generate_fvar(static_metadata: &StaticMetadata) -> Option<Fvar> {
                 var
             })
             .collect(),
-        Vec::new(),
+        instances(static_metadata),
     );
 
     let axis_count = axes_and_instances.axes.len().try_into().unwrap();
@@ -67,6 +67,58 @@ fn generate_fvar(static_metadata: &StaticMetadata) -> Option<Fvar> {
     ))
 }
 
+/// An [InstanceRecord] for each named instance within the axis ranges.
+fn instances(static_metadata: &StaticMetadata) -> Vec<InstanceRecord> {
+    // All or none of the instances have a postscript name id
+    let has_postscript_names = static_metadata
+        .named_instances()
+        .iter()
+        .any(|instance| instance.postscript_name.is_some());
+
+    static_metadata
+        .named_instances()
+        .iter()
+        .filter_map(|instance| {
+            let coordinates = static_metadata
+                .variable_axes
+                .iter()
+                .map(|axis| {
+                    let value = instance.location.get(&axis.name).unwrap_or(axis.default);
+                    if value < axis.min || value > axis.max {
+                        warn!(
+                            "Instance {} is at {:?} on {}, outside {:?}..{:?}; dropping it",
+                            instance.name, value, axis.name, axis.min, axis.max
+                        );
+                        return None;
+                    }
+                    Some(value.into())
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let post_script_name_id = has_postscript_names.then(|| {
+                instance
+                    .postscript_name
+                    .as_ref()
+                    .and_then(|name| static_metadata.font_specific_name_id(name))
+                    .unwrap_or(NameId::new(0xFFFF))
+            });
+            let Some(subfamily_name_id) = static_metadata.font_specific_name_id(&instance.name)
+            else {
+                warn!(
+                    "Instance {} has no name id for its name; dropping it",
+                    instance.name
+                );
+                return None;
+            };
+            Some(InstanceRecord {
+                subfamily_name_id,
+                coordinates,
+                post_script_name_id,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
 impl Work<Context, Error> for FvarWork {
     /// Generate [fvar](https://learn.microsoft.com/en-us/typography/opentype/spec/fvar)
     fn exec(&self, context: &Context) -> Result<(), Error> {
@@ -83,9 +135,9 @@ mod tests {
 
     use fontir::{
         coords::{CoordConverter, DesignCoord, UserCoord},
-        ir::{Axis, StaticMetadata},
+        ir::{Axis, NamedInstance, StaticMetadata},
     };
-    use read_fonts::types::Tag;
+    use read_fonts::types::{NameId, Tag};
 
     use super::generate_fvar;
 
@@ -153,4 +205,41 @@ mod tests {
                 .collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn fvar_includes_named_instances() {
+        let mut static_metadata = create_static_metadata(&[axis(400.0, 400.0, 700.0)]);
+        let instance = |name: &str, postscript_name: Option<&str>, weight: f32| NamedInstance {
+            name: name.to_string(),
+            postscript_name: postscript_name.map(|n| n.to_string()),
+            location: [("Test".to_string(), UserCoord::new(weight))]
+                .into_iter()
+                .collect(),
+        };
+        static_metadata.set_named_instances(vec![
+            instance("Regular", None, 400.0),
+            instance("Bold", Some("Test-Bold"), 700.0),
+            instance("Black", None, 900.0),
+        ]);
+        let fvar = generate_fvar(&static_metadata).unwrap();
+
+        let name_id = |name: &str| static_metadata.font_specific_name_id(name).unwrap();
+        // Black is outside the axis, and if one instance has a postscript name they all do
+        assert_eq!(
+            vec![
+                (name_id("Regular"), vec![400.0], Some(NameId::new(0xFFFF))),
+                (name_id("Bold"), vec![700.0], Some(name_id("Test-Bold"))),
+            ],
+            fvar.axis_instance_arrays
+                .instances
+                .iter()
+                .map(|inst| (
+                    inst.subfamily_name_id,
+                    inst.coordinates.iter().map(|c| c.to_f64()).collect(),
+                    inst.post_script_name_id
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, fvar.instance_count);
+    }
 }
@@ -0,0 +1,184 @@
+//! Generates a [GDEF](https://learn.microsoft.com/en-us/typography/opentype/spec/gdef) table.
+//!
+//! Starts from whatever GDEF the fea source and GPOS generation produced; the
+//! fea source wins where both have something to say.
+
+use font_types::GlyphId;
+use fontdrasil::orchestration::Work;
+use fontir::ir::{self, GdefCategory};
+use log::trace;
+use read_fonts::{FontRef, TableProvider};
+use write_fonts::{
+    from_obj::ToOwnedTable,
+    tables::{
+        gdef::{CaretValue, Gdef, LigCaretList, LigGlyph},
+        layout::{ClassDefBuilder, CoverageTableBuilder},
+    },
+};
+
+use crate::{
+    error::Error,
+    marks::{anchor_position, is_attachment_anchor},
+    orchestration::{BeWork, Context},
+    var_store::{variation_index, VarStoreBuilder},
+};
+
+const CARET_PREFIX: &str = "caret_";
+const VCARET_PREFIX: &str = "vcaret_";
+
+struct GdefWork {}
+
+pub fn create_gdef_work() -> Box<BeWork> {
+    Box::new(GdefWork {})
+}
+
+/// <https://learn.microsoft.com/en-us/typography/opentype/spec/gdef#glyph-class-definition-table>
+fn glyph_class(category: GdefCategory) -> u16 {
+    match category {
+        GdefCategory::Base => 1,
+        GdefCategory::Ligature => 2,
+        GdefCategory::Mark => 3,
+        GdefCategory::Component => 4,
+    }
+}
+
+fn is_caret_anchor(name: &str) -> bool {
+    name.starts_with(CARET_PREFIX) || name.starts_with(VCARET_PREFIX)
+}
+
+/// Guess the category of a glyph the source didn't categorize from its anchors.
+fn category_from_anchors(glyph: &ir::Glyph) -> Option<GdefCategory> {
+    let anchors = &glyph.default_instance().anchors;
+    let attachments = || {
+        anchors
+            .iter()
+            .map(|a| a.name.as_str())
+            .filter(|name| is_attachment_anchor(name))
+    };
+    if attachments().any(|name| name.starts_with('_')) {
+        Some(GdefCategory::Mark)
+    } else if anchors.iter().any(|a| is_caret_anchor(&a.name)) {
+        Some(GdefCategory::Ligature)
+    } else if attachments().next().is_some() {
+        Some(GdefCategory::Base)
+    } else {
+        None
+    }
+}
+
+/// Carets from caret_N (x) and vcaret_N (y) anchors, in the order they occur.
+fn ligature_carets(
+    static_metadata: &ir::StaticMetadata,
+    glyph: &ir::Glyph,
+    var_store: &mut VarStoreBuilder,
+) -> Result<Vec<CaretValue>, Error> {
+    let mut carets = Vec::new();
+    for anchor in glyph.default_instance().anchors.iter() {
+        if !is_caret_anchor(&anchor.name) {
+            continue;
+        }
+        let Some(pos) = anchor_position(static_metadata, glyph, &anchor.name)? else {
+            continue;
+        };
+        let is_vertical = anchor.name.starts_with(VCARET_PREFIX);
+        let coordinate = if is_vertical { pos.y } else { pos.x };
+        let deltas: Vec<_> = pos
+            .deltas
+            .iter()
+            .map(|(region, (x, y))| (region.clone(), if is_vertical { *y } else { *x }))
+            .collect();
+        carets.push((
+            coordinate,
+            var_store.add_deltas(&deltas).map(variation_index),
+        ));
+    }
+    carets.sort_by_key(|(coordinate, _)| *coordinate);
+    Ok(carets
+        .into_iter()
+        .map(|(coordinate, device)| match device {
+            Some(device) => CaretValue::format_3(coordinate, device),
+            None => CaretValue::format_1(coordinate),
+        })
+        .collect())
+}
+
+impl Work<Context, Error> for GdefWork {
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let features = context.get_features();
+        let mut gdef: Gdef = if features.is_empty() {
+            Default::default()
+        } else {
+            FontRef::new(&features)?
+                .gdef()
+                .map(|gdef| gdef.to_owned_table())
+                .unwrap_or_default()
+        };
+        let mut var_store = match gdef.item_var_store.as_ref() {
+            Some(store) => VarStoreBuilder::from_existing(&static_metadata.variable_axes, store),
+            None => VarStoreBuilder::new(&static_metadata.variable_axes),
+        };
+
+        let mut glyph_classes = Vec::new();
+        let mut lig_carets = Vec::new();
+        for (gid, glyph_name) in static_metadata.glyph_order.iter().enumerate() {
+            let gid = GlyphId::new(gid as u16);
+            let glyph = context.ir.get_glyph_ir(glyph_name);
+            let category = static_metadata
+                .gdef_categories
+                .get(glyph_name)
+                .copied()
+                .or_else(|| category_from_anchors(&glyph));
+            if let Some(category) = category {
+                glyph_classes.push((gid, glyph_class(category)));
+            }
+            let carets = ligature_carets(&static_metadata, &glyph, &mut var_store)?;
+            if !carets.is_empty() {
+                lig_carets.push((gid, LigGlyph::new(carets)));
+            }
+        }
+        trace!(
+            "GDEF has {} classified glyphs, {} with ligature carets",
+            glyph_classes.len(),
+            lig_carets.len()
+        );
+
+        if gdef.glyph_class_def.is_none() && !glyph_classes.is_empty() {
+            gdef.glyph_class_def = glyph_classes
+                .into_iter()
+                .collect::<ClassDefBuilder>()
+                .build()
+                .into();
+        }
+        if gdef.lig_caret_list.is_none() && !lig_carets.is_empty() {
+            let coverage = lig_carets
+                .iter()
+                .map(|(gid, _)| *gid)
+                .collect::<CoverageTableBuilder>();
+            gdef.lig_caret_list = LigCaretList::new(
+                coverage.build(),
+                lig_carets.into_iter().map(|(_, lig)| lig).collect(),
+            )
+            .into();
+        }
+        if !var_store.is_empty() {
+            gdef.item_var_store = var_store.build().into();
+        }
+
+        context.set_gdef(gdef);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_caret_anchor;
+
+    #[test]
+    fn caret_anchors() {
+        assert!(is_caret_anchor("caret_1"));
+        assert!(is_caret_anchor("vcaret_2"));
+        assert!(!is_caret_anchor("top"));
+        assert!(!is_caret_anchor("_caret_1"));
+    }
+}
@@ -6,8 +6,15 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
 
 use fontdrasil::{orchestration::Work, types::GlyphName};
-use fontir::{coords::NormalizedLocation, ir};
-use kurbo::{cubics_to_quadratic_splines, Affine, BezPath, CubicBez, PathEl, Point, Rect};
+use fontir::{
+    coords::NormalizedLocation,
+    glyph::components_overlap,
+    interpolatable::{check_glyph, report},
+    ir,
+    orchestration::Flags,
+    overlap, quadratic,
+};
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape, Vec2};
 use log::{trace, warn};
 
 use read_fonts::{
@@ -23,6 +30,8 @@ use write_fonts::{
 
 use crate::{
     error::{Error, GlyphProblem},
+    gvar::{deltas_size, PointDeltas},
+    iup::iup_delta_optimize,
     orchestration::{BeWork, Context, GlyfLoca, Glyph, GvarFragment},
 };
 
@@ -108,6 +117,7 @@ fn create_composite(
     glyph: &ir::Glyph,
     default_location: &NormalizedLocation,
     components: &[(GlyphName, NormalizedLocation, Affine)],
+    mut overlap: bool,
 ) -> Result<CompositeGlyph, Error> {
     let mut errors = vec![];
     let mut set_use_my_metrics = false;
@@ -137,6 +147,11 @@ fn create_composite(
                             component.flags.use_my_metrics = true;
                         }
                     }
+                    // OVERLAP_COMPOUND goes on the first component
+                    if overlap {
+                        component.flags.overlap_compound = true;
+                        overlap = false;
+                    }
                     (component, bbox)
                 })
                 .ok()
@@ -160,24 +175,52 @@ fn create_composite(
     Ok(composite.unwrap())
 }
 
+/// Whether the default master of a glyph overlaps, contour with contour or component with component.
+fn default_overlaps(context: &Context, glyph: &ir::Glyph) -> bool {
+    let inst = glyph.default_instance();
+    if inst.components.is_empty() {
+        return overlap::contours_overlap(&inst.contours);
+    }
+    components_overlap(&context.ir, glyph)
+}
+
 /// * <https://github.com/fonttools/fonttools/blob/3b9a73ff8379ab49d3ce35aaaaf04b3a7d9d1655/Lib/fontTools/ttLib/tables/_g_l_y_f.py#L335-L367>
 /// * <https://docs.microsoft.com/en-us/typography/opentype/spec/tt_instructing_glyphs#phantoms>
-fn add_phantom_points(advance: u16, points: &mut Vec<Point>) {
+///
+/// Vertical phantom points are only meaningful if the font has vertical metrics, pass
+/// the default metrics if it does.
+fn add_phantom_points(
+    instance: &ir::GlyphInstance,
+    vertical_metrics: Option<&ir::GlobalMetricsInstance>,
+    points: &mut Vec<Point>,
+) {
     // FontTools says
     //      leftSideX = glyph.xMin - leftSideBearing
     //      rightSideX = leftSideX + horizontalAdvanceWidth
     // We currently always set lsb to xMin so leftSideX = 0, rightSideX = advance.
+    let advance: u16 = instance.width.ot_round();
     points.push(Point::new(0.0, 0.0)); // leftSideX, 0
     points.push(Point::new(advance as f64, 0.0)); // rightSideX, 0
 
-    // TODO: vertical phantom points
-    points.push(Point::new(0.0, 0.0));
-    points.push(Point::new(0.0, 0.0));
+    // FontTools says
+    //      topSideY = glyph.yMax + topSideBearing
+    //      bottomSideY = topSideY - verticalAdvanceWidth
+    // We set tsb to origin - yMax so topSideY = origin, bottomSideY = origin - advance height.
+    let Some(metrics) = vertical_metrics else {
+        points.push(Point::new(0.0, 0.0));
+        points.push(Point::new(0.0, 0.0));
+        return;
+    };
+    let top: i16 = instance.vertical_origin_y(metrics).ot_round();
+    let advance_height: u16 = instance.advance_height(metrics).ot_round();
+    points.push(Point::new(0.0, top as f64)); // 0, topSideY
+    points.push(Point::new(0.0, top as f64 - advance_height as f64)); // 0, bottomSideY
 }
 
 fn point_seqs_for_simple_glyph(
     ir_glyph: &ir::Glyph,
     instances: HashMap<NormalizedLocation, SimpleGlyph>,
+    vertical_metrics: Option<&ir::GlobalMetricsInstance>,
 ) -> HashMap<NormalizedLocation, Vec<Point>> {
     instances
         .into_iter()
@@ -189,14 +232,17 @@ fn point_seqs_for_simple_glyph(
                 .map(|cp| Point::new(cp.x as f64, cp.y as f64))
                 .collect();
 
-            add_phantom_points(ir_glyph.sources()[&loc].width.ot_round(), &mut points);
+            add_phantom_points(&ir_glyph.sources()[&loc], vertical_metrics, &mut points);
 
             (loc, points)
         })
         .collect()
 }
 
-fn point_seqs_for_composite_glyph(ir_glyph: &ir::Glyph) -> HashMap<NormalizedLocation, Vec<Point>> {
+fn point_seqs_for_composite_glyph(
+    ir_glyph: &ir::Glyph,
+    vertical_metrics: Option<&ir::GlobalMetricsInstance>,
+) -> HashMap<NormalizedLocation, Vec<Point>> {
     ir_glyph
         .sources()
         .iter()
@@ -208,31 +254,82 @@ fn point_seqs_for_composite_glyph(ir_glyph: &ir::Glyph) -> HashMap<NormalizedLoc
                 let [.., dx, dy] = component.transform.as_coeffs();
                 points.push((dx, dy).into());
             }
-            add_phantom_points(inst.width.ot_round(), &mut points);
+            add_phantom_points(inst, vertical_metrics, &mut points);
 
             (loc.clone(), points)
         })
         .collect()
 }
 
+/// Round half up, as fonttools otRound does
+fn ot_round(v: f64) -> f64 {
+    (v + 0.5).floor()
+}
+
+/// The deltas to store in gvar, without those IUP can infer if we have a tolerance.
+///
+/// Like fonttools we only leave points out if it actually makes the deltas smaller.
+fn gvar_deltas(
+    deltas: &[Vec2],
+    default_points: &[Point],
+    contour_ends: &[usize],
+    iup_tolerance: Option<f64>,
+) -> PointDeltas {
+    let to_i16 = |v: &Vec2| (v.x as i16, v.y as i16);
+    let all: Vec<_> = deltas.iter().map(|v| Some(to_i16(v))).collect();
+    let Some(tolerance) = iup_tolerance else {
+        return all;
+    };
+    let optimized: Vec<_> = iup_delta_optimize(deltas, default_points, contour_ends, tolerance)
+        .iter()
+        .map(|v| v.as_ref().map(to_i16))
+        .collect();
+    if deltas_size(&optimized) < deltas_size(&all) {
+        optimized
+    } else {
+        all
+    }
+}
+
 impl Work<Context, Error> for GlyphWork {
     fn exec(&self, context: &Context) -> Result<(), Error> {
         trace!("BE glyph work for {}", self.glyph_name);
 
         let static_metadata = context.ir.get_final_static_metadata();
-        let var_model = &static_metadata.variation_model;
         let default_location = static_metadata.default_location();
         let ir_glyph = &*context.ir.get_glyph_ir(&self.glyph_name);
+        // Sparse glyphs need a model of just the locations they are defined at
+        let var_model = static_metadata
+            .variation_model_for(ir_glyph.sources().keys())
+            .map_err(|e| Error::GlyphVariationModelError(self.glyph_name.clone(), e))?;
         let glyph: CheckedGlyph = ir_glyph.try_into()?;
+        let default_metrics = context.ir.get_global_metrics().at(default_location);
+        let vertical_metrics = default_metrics.is_vertical().then_some(&default_metrics);
 
         // Hopefully in time https://github.com/harfbuzz/boring-expansion-spec means we can drop this
-        let glyph = cubics_to_quadratics(glyph);
-
-        let (name, point_seqs) = match glyph {
+        let max_error = context
+            .ir
+            .max_conversion_error
+            .in_units(static_metadata.units_per_em);
+        let glyph = cubics_to_quadratics(glyph, max_error)?;
+
+        // Overlaps need to be flagged for variable fonts to render correctly on macOS
+        let overlap = !static_metadata.variable_axes.is_empty()
+            && (context.flags.contains(Flags::OVERLAP_FLAG_ON_ALL_GLYPHS)
+                || default_overlaps(context, ir_glyph));
+
+        let (name, point_seqs, contour_ends) = match glyph {
             CheckedGlyph::Composite { name, components } => {
-                let composite = create_composite(context, ir_glyph, default_location, &components)?;
+                let composite =
+                    create_composite(context, ir_glyph, default_location, &components, overlap)?;
+                // For IUP each component is a contour of one point
+                let contour_ends: Vec<_> = (0..ir_glyph.default_instance().components.len()).collect();
                 context.set_glyph(name.clone(), composite.into());
-                (name, point_seqs_for_composite_glyph(ir_glyph))
+                (
+                    name,
+                    point_seqs_for_composite_glyph(ir_glyph, vertical_metrics),
+                    contour_ends,
+                )
             }
             CheckedGlyph::Contour { name, paths } => {
                 // Convert paths to SimpleGlyphs in parallel so we can get consistent point streams
@@ -256,133 +353,86 @@ impl Work<Context, Error> for GlyphWork {
                 let Some(base_glyph) = instances.get(default_location) else {
                     return Err(Error::GlyphError(ir_glyph.name.clone(), GlyphProblem::MissingDefault));
                 };
-                context.set_glyph(name.clone(), base_glyph.clone().into());
+                let mut base_glyph = base_glyph.clone();
+                // CFF keeps the cubics, metrics should describe those rather than our quadratics
+                if context.flags.contains(Flags::CFF_OUTLINES) {
+                    if let Some(bbox) = ir_glyph
+                        .default_instance()
+                        .contours
+                        .iter()
+                        .map(|path| path.bounding_box())
+                        .reduce(|acc, bbox| acc.union(bbox))
+                    {
+                        base_glyph.bbox = bbox.into();
+                    }
+                }
+                let contour_ends: Vec<_> = base_glyph
+                    .contours()
+                    .iter()
+                    .scan(0, |points, contour| {
+                        *points += contour.len();
+                        Some(*points - 1)
+                    })
+                    .collect();
+                context.set_glyph(name.clone(), Glyph::Simple(base_glyph, overlap));
 
-                (name, point_seqs_for_simple_glyph(ir_glyph, instances))
+                (
+                    name,
+                    point_seqs_for_simple_glyph(ir_glyph, instances, vertical_metrics),
+                    contour_ends,
+                )
             }
         };
 
         // Contour (aka Simple) and Composite both need gvar
+        // Round as we go, like fonttools, so rounding errors don't accumulate
         let deltas = var_model
-            .deltas(&point_seqs)
+            .rounded_deltas(&point_seqs, |v: Vec2| {
+                Vec2::new(ot_round(v.x), ot_round(v.y))
+            })
             .map_err(|e| Error::GlyphDeltaError(self.glyph_name.clone(), e))?;
 
+        let iup_tolerance = context
+            .flags
+            .contains(Flags::OPTIMIZE_GVAR)
+            .then_some(context.iup_tolerance);
+        let default_points = &point_seqs[default_location];
+        let deltas = deltas
+            .into_iter()
+            .filter(|(region, _)| !region.is_default())
+            .map(|(region, deltas)| {
+                let deltas = gvar_deltas(&deltas, default_points, &contour_ends, iup_tolerance);
+                (region, deltas)
+            })
+            .collect();
+
         context.set_gvar_fragment(name, GvarFragment { deltas });
 
         Ok(())
     }
 }
 
-fn cubics_to_quadratics(glyph: CheckedGlyph) -> CheckedGlyph {
+fn cubics_to_quadratics(glyph: CheckedGlyph, max_error: f64) -> Result<CheckedGlyph, Error> {
     let CheckedGlyph::Contour { name, paths: contours } = glyph else {
-        return glyph;  // nop for composite
+        return Ok(glyph);  // nop for composite
     };
 
     trace!("Convert '{name}' to quadratic");
 
-    // put all the loc + path iters into a vec
-    let mut loc_iters: Vec<_> = contours
-        .iter()
-        .map(|(loc, path)| (loc, path.iter()))
+    // Quadratic conversion works on contours, our paths hold all the contours at a location
+    let contours = contours
+        .into_iter()
+        .map(|(loc, path)| (loc, vec![path]))
         .collect();
-    let mut new_contours = HashMap::<NormalizedLocation, BezPath>::new();
-
-    let mut subpath_start_pts: Vec<_> = loc_iters
-        .iter_mut()
-        .filter_map(|(loc, iter)| match iter.next() {
-            Some(PathEl::MoveTo(p)) => {
-                new_contours.entry((*loc).clone()).or_default().move_to(p);
-                Some(p)
-            }
-            None => {
-                // keep the empty path for this location, but no start point
-                new_contours.entry((*loc).clone()).or_default();
-                None
-            }
-            Some(other) => panic!("'{name}': illegal start of path: {other:?}"),
-        })
+    let new_contours = quadratic::to_quadratic(&name, &contours, max_error)?
+        .into_iter()
+        .map(|(loc, mut paths)| (loc, paths.pop().unwrap()))
         .collect();
-    let mut prev_el_end_pts = subpath_start_pts.clone();
-    loop {
-        let elements: Vec<_> = loc_iters
-            .iter_mut()
-            .filter_map(|(_, iter)| iter.next())
-            .collect();
-        if elements.is_empty() {
-            break; // normal termination, we exhausted all the element iterators
-        }
-
-        // No work to be done if the segment isn't cubic
-        if let PathEl::CurveTo(..) = elements[0] {
-            if prev_el_end_pts.len() != elements.len() {
-                panic!("'{name}': not enough end points");
-            }
-            let cubics: Vec<_> = elements
-                .iter()
-                .zip(&prev_el_end_pts)
-                .map(|(el, p0)| match el {
-                    PathEl::CurveTo(p1, p2, p3) => CubicBez {
-                        p0: *p0,
-                        p1: *p1,
-                        p2: *p2,
-                        p3: *p3,
-                    },
-                    _ => unreachable!("'{name}': *must* all be cubic"),
-                })
-                .collect();
-
-            // At long last, actually convert something to quadratic
-            // TODO what should we pass for accuracy
-            let Some(quad_splines) = cubics_to_quadratic_splines(&cubics, 1.0) else {
-                panic!("'{name}': unable to convert to quadratic {cubics:?}");
-            };
-            if quad_splines.len() != loc_iters.len() {
-                panic!(
-                    "'{name}': needed {} splines, got {}",
-                    quad_splines.len(),
-                    loc_iters.len()
-                );
-            }
 
-            loc_iters
-                .iter()
-                .zip(&quad_splines)
-                .for_each(|((loc, _), quad_spline)| {
-                    let bez = new_contours.entry((*loc).clone()).or_default();
-                    quad_spline.to_quads().for_each(|quad| {
-                        bez.quad_to(quad.p1, quad.p2);
-                    })
-                });
-        } else {
-            loc_iters.iter().zip(&elements).for_each(|((loc, _), el)| {
-                new_contours.entry((*loc).clone()).or_default().push(*el)
-            });
-        };
-
-        // Update element end points
-        prev_el_end_pts.clear();
-        elements
-            .iter()
-            .zip(&subpath_start_pts)
-            .map(|(e, subpath_start)| match e {
-                PathEl::MoveTo(p)
-                | PathEl::LineTo(p)
-                | PathEl::QuadTo(_, p)
-                | PathEl::CurveTo(_, _, p) => p,
-                PathEl::ClosePath => subpath_start,
-            })
-            .for_each(|p| prev_el_end_pts.push(*p));
-
-        // If we are at a move then these are also our new subpath start points
-        if let PathEl::MoveTo(..) = elements[0] {
-            subpath_start_pts = prev_el_end_pts.clone();
-        }
-    }
-
-    CheckedGlyph::Contour {
+    Ok(CheckedGlyph::Contour {
         name,
         paths: new_contours,
-    }
+    })
 }
 
 /// An [ir::Glyph] that has been confirmed to maintain invariants:
@@ -413,7 +463,11 @@ impl TryFrom<&ir::Glyph> for CheckedGlyph {
             .map(|s| s.components.iter().map(|c| c.base.clone()).collect())
             .collect();
         if components.len() > 1 {
-            warn!("{} has inconsistent component glyph sequences; fontir is supposed to fix that for us", glyph.name);
+            warn!(
+                "{} has inconsistent component glyph sequences; fontir is supposed to fix that for us\n{}",
+                glyph.name,
+                report(&check_glyph(glyph))
+            );
             return Err(Error::GlyphError(
                 glyph.name.clone(),
                 GlyphProblem::InconsistentComponents,
@@ -433,8 +487,9 @@ impl TryFrom<&ir::Glyph> for CheckedGlyph {
             .collect();
         if path_els.len() > 1 {
             warn!(
-                "{} has inconsistent path elements: {path_els:?}",
-                glyph.name
+                "{} has inconsistent path elements: {path_els:?}\n{}",
+                glyph.name,
+                report(&check_glyph(glyph))
             );
             return Err(Error::GlyphError(
                 glyph.name.clone(),
@@ -589,7 +644,7 @@ fn compute_composite_bboxes(context: &Context) -> Result<(), Error> {
                             .map(|g| g.as_ref().clone())
                             .and_then(|g| match g {
                                 Glyph::Composite(..) => None,
-                                Glyph::Simple(simple_glyph) => Some(bbox2rect(simple_glyph.bbox)),
+                                Glyph::Simple(simple_glyph, _) => Some(bbox2rect(simple_glyph.bbox)),
                             })
                     });
                     if bbox.is_none() {
@@ -1,17 +1,377 @@
 //! Generates a [gvar](https://learn.microsoft.com/en-us/typography/opentype/spec/gvar) table.
+//!
+//! We compile gvar ourselves rather than with write-fonts because we want deltas for only
+//! some points, leaving the rest to be interpolated (IUP), which write-fonts doesn't support.
+
+use std::collections::HashMap;
 
-use font_types::GlyphId;
 use fontdrasil::orchestration::Work;
-use write_fonts::{
-    dump_table,
-    tables::gvar::{GlyphVariations, Gvar},
-};
+use write_fonts::tables::variations::Tuple;
 
 use crate::{
     error::Error,
     orchestration::{BeWork, Bytes, Context},
 };
 
+/// Rounded deltas for each point of a glyph, None where they can be interpolated
+pub type PointDeltas = Vec<Option<(i16, i16)>>;
+
+/// Most tuples a glyph may have, and most shared tuples a gvar may have
+const MAX_TUPLES: usize = 4095;
+
+// <https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#tuple-variation-store-header>
+const SHARED_POINT_NUMBERS: u16 = 0x8000;
+
+// <https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#tuplevariationheader>
+const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+const INTERMEDIATE_REGION: u16 = 0x4000;
+const PRIVATE_POINT_NUMBERS: u16 = 0x2000;
+
+// <https://learn.microsoft.com/en-us/typography/opentype/spec/gvar#gvar-header>
+const LONG_OFFSETS: u16 = 0x0001;
+const GVAR_HEADER_SIZE: usize = 20;
+
+// <https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#packed-deltas>
+const DELTAS_ARE_ZERO: u8 = 0x80;
+const DELTAS_ARE_WORDS: u8 = 0x40;
+const MAX_DELTAS_PER_RUN: usize = 64;
+
+// <https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#packed-point-numbers>
+const POINTS_ARE_WORDS: u8 = 0x80;
+const MAX_POINTS_PER_RUN: usize = 128;
+
+/// Glyph deltas for one region of the design space.
+///
+/// Points whose delta is None are to be inferred by interpolation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphDeltas {
+    peak: Tuple,
+    intermediate: Option<(Tuple, Tuple)>,
+    deltas: PointDeltas,
+}
+
+impl GlyphDeltas {
+    /// Create deltas for the region peaking at peak.
+    ///
+    /// The intermediate region is dropped if it is the one implied by the peak.
+    pub fn new(peak: Tuple, deltas: PointDeltas, intermediate: Option<(Tuple, Tuple)>) -> Self {
+        let intermediate = intermediate.filter(|(start, end)| {
+            assert!(
+                start.values.len() == peak.values.len() && end.values.len() == peak.values.len(),
+                "all tuples must have equal length"
+            );
+            peak.values
+                .iter()
+                .zip(start.values.iter().zip(end.values.iter()))
+                .any(|(peak, (start, end))| {
+                    *start != (*peak).min(Default::default())
+                        || *end != (*peak).max(Default::default())
+                })
+        });
+        GlyphDeltas {
+            peak,
+            intermediate,
+            deltas,
+        }
+    }
+
+    /// The points that have explicit deltas, None if they all do
+    fn points(&self) -> Option<Vec<u16>> {
+        if self.deltas.iter().all(Option::is_some) {
+            return None;
+        }
+        Some(
+            self.deltas
+                .iter()
+                .enumerate()
+                .filter_map(|(i, d)| d.map(|_| i as u16))
+                .collect(),
+        )
+    }
+
+    fn has_deltas(&self) -> bool {
+        self.deltas.iter().any(Option::is_some)
+    }
+}
+
+/// The number of bytes it takes to store deltas, with private point numbers.
+///
+/// Lets us see if leaving out some points actually saved anything.
+pub(crate) fn deltas_size(deltas: &[Option<(i16, i16)>]) -> usize {
+    let mut buf = Vec::new();
+    if deltas.iter().all(Option::is_some) {
+        pack_points(&mut buf, None);
+    } else {
+        let points: Vec<_> = deltas
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|_| i as u16))
+            .collect();
+        pack_points(&mut buf, Some(&points));
+    }
+    pack_xy_deltas(&mut buf, deltas);
+    buf.len()
+}
+
+/// Write point numbers, None meaning all points.
+fn pack_points(buf: &mut Vec<u8>, points: Option<&[u16]>) {
+    let Some(points) = points else {
+        buf.push(0);
+        return;
+    };
+    if points.len() < 0x80 {
+        buf.push(points.len() as u8);
+    } else {
+        buf.extend((points.len() as u16 | 0x8000).to_be_bytes());
+    }
+
+    let mut last = 0;
+    let mut pos = 0;
+    while pos < points.len() {
+        // Runs are of bytes if the first delta fits in a byte
+        let words = points[pos] - last > 0xff;
+        let header_pos = buf.len();
+        buf.push(0);
+        let mut run_length = 0;
+        while pos < points.len() && run_length < MAX_POINTS_PER_RUN {
+            let delta = points[pos] - last;
+            if !words && delta > 0xff {
+                break;
+            }
+            if words {
+                buf.extend(delta.to_be_bytes());
+            } else {
+                buf.push(delta as u8);
+            }
+            last = points[pos];
+            run_length += 1;
+            pos += 1;
+        }
+        buf[header_pos] = (run_length - 1) as u8 | if words { POINTS_ARE_WORDS } else { 0 };
+    }
+}
+
+/// Write the x deltas then the y deltas of the points that have them
+fn pack_xy_deltas(buf: &mut Vec<u8>, deltas: &[Option<(i16, i16)>]) {
+    let (x_deltas, y_deltas): (Vec<_>, Vec<_>) = deltas.iter().flatten().copied().unzip();
+    pack_deltas(buf, &x_deltas);
+    pack_deltas(buf, &y_deltas);
+}
+
+/// Rust version of fontTools TupleVariation.compileDeltaValues_
+fn pack_deltas(buf: &mut Vec<u8>, deltas: &[i16]) {
+    fn fits_in_byte(v: i16) -> bool {
+        (i8::MIN as i16..=i8::MAX as i16).contains(&v)
+    }
+
+    let mut pos = 0;
+    while pos < deltas.len() {
+        let run_start = pos;
+        let value = deltas[pos];
+        if value == 0 {
+            while pos < deltas.len() && pos - run_start < MAX_DELTAS_PER_RUN && deltas[pos] == 0 {
+                pos += 1;
+            }
+            buf.push(DELTAS_ARE_ZERO | (pos - run_start - 1) as u8);
+        } else if fits_in_byte(value) {
+            // A single zero is cheapest stored inline, two or more as a run of their own
+            while pos < deltas.len()
+                && pos - run_start < MAX_DELTAS_PER_RUN
+                && fits_in_byte(deltas[pos])
+                && !(deltas[pos] == 0 && deltas.get(pos + 1) == Some(&0))
+            {
+                pos += 1;
+            }
+            buf.push((pos - run_start - 1) as u8);
+            buf.extend(deltas[run_start..pos].iter().map(|v| *v as i8 as u8));
+        } else {
+            // Two values in a row that fit in a byte are cheaper stored as a run of bytes
+            while pos < deltas.len()
+                && deltas[pos] != 0
+                && !(fits_in_byte(deltas[pos])
+                    && deltas.get(pos + 1).map(|v| fits_in_byte(*v)) == Some(true))
+            {
+                pos += 1;
+            }
+            for run in deltas[run_start..pos].chunks(MAX_DELTAS_PER_RUN) {
+                buf.push(DELTAS_ARE_WORDS | (run.len() - 1) as u8);
+                for value in run {
+                    buf.extend(value.to_be_bytes());
+                }
+            }
+        }
+    }
+}
+
+fn write_tuple(buf: &mut Vec<u8>, tuple: &Tuple) {
+    for value in tuple.values.iter() {
+        buf.extend(value.to_bits().to_be_bytes());
+    }
+}
+
+/// Compile the variation data for one glyph.
+///
+/// Empty if the glyph has no variations.
+fn glyph_variation_data(
+    variations: &[GlyphDeltas],
+    shared_tuples: &HashMap<&Tuple, u16>,
+) -> Result<Vec<u8>, Error> {
+    let variations: Vec<_> = variations.iter().filter(|v| v.has_deltas()).collect();
+    if variations.is_empty() {
+        return Ok(Vec::new());
+    }
+    if variations.len() > MAX_TUPLES {
+        return Err(Error::OutOfBounds {
+            what: "Tuple variation count".to_string(),
+            value: variations.len().to_string(),
+        });
+    }
+
+    // Share the point numbers that save the most bytes if written once
+    let points: Vec<_> = variations.iter().map(|v| v.points()).collect();
+    let mut point_counts: HashMap<&Option<Vec<u16>>, usize> = HashMap::new();
+    for points in points.iter() {
+        *point_counts.entry(points).or_default() += 1;
+    }
+    let shared_points = point_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(points, count)| {
+            let mut buf = Vec::new();
+            pack_points(&mut buf, points.as_deref());
+            (buf.len() * (count - 1), points)
+        })
+        .max()
+        .map(|(_, points)| points);
+
+    let mut headers = Vec::new();
+    let mut data = Vec::new();
+    if let Some(shared_points) = shared_points {
+        pack_points(&mut data, shared_points.as_deref());
+    }
+    for (variation, points) in variations.iter().zip(points.iter()) {
+        let data_start = data.len();
+        let mut tuple_index = 0;
+        if Some(points) != shared_points {
+            tuple_index |= PRIVATE_POINT_NUMBERS;
+            pack_points(&mut data, points.as_deref());
+        }
+        pack_xy_deltas(&mut data, &variation.deltas);
+        let data_size = data.len() - data_start;
+        let data_size = u16::try_from(data_size).map_err(|_| Error::OutOfBounds {
+            what: "Tuple variation data size".to_string(),
+            value: data_size.to_string(),
+        })?;
+
+        match shared_tuples.get(&variation.peak) {
+            Some(idx) => tuple_index |= *idx,
+            None => tuple_index |= EMBEDDED_PEAK_TUPLE,
+        }
+        if variation.intermediate.is_some() {
+            tuple_index |= INTERMEDIATE_REGION;
+        }
+        headers.extend(data_size.to_be_bytes());
+        headers.extend(tuple_index.to_be_bytes());
+        if tuple_index & EMBEDDED_PEAK_TUPLE != 0 {
+            write_tuple(&mut headers, &variation.peak);
+        }
+        if let Some((start, end)) = &variation.intermediate {
+            write_tuple(&mut headers, start);
+            write_tuple(&mut headers, end);
+        }
+    }
+
+    let mut tuple_variation_count = variations.len() as u16;
+    if shared_points.is_some() {
+        tuple_variation_count |= SHARED_POINT_NUMBERS;
+    }
+    let data_offset = 4 + headers.len();
+    let mut buf = Vec::with_capacity(data_offset + data.len());
+    buf.extend(tuple_variation_count.to_be_bytes());
+    buf.extend(
+        u16::try_from(data_offset)
+            .map_err(|_| Error::OutOfBounds {
+                what: "Tuple variation data offset".to_string(),
+                value: data_offset.to_string(),
+            })?
+            .to_be_bytes(),
+    );
+    buf.extend(headers);
+    buf.extend(data);
+    Ok(buf)
+}
+
+/// Compile gvar from the variations of each glyph, in glyph order.
+///
+/// Rust version of fontTools ttLib.tables._g_v_a_r.table__g_v_a_r.compile.
+pub(crate) fn compile_gvar(axis_count: u16, glyphs: &[Vec<GlyphDeltas>]) -> Result<Vec<u8>, Error> {
+    let glyph_count: u16 = glyphs.len().try_into().map_err(|_| Error::OutOfBounds {
+        what: "Glyph count".to_string(),
+        value: glyphs.len().to_string(),
+    })?;
+
+    // Peaks used more than once are stored once and referenced by index
+    let mut peak_counts: HashMap<&Tuple, usize> = HashMap::new();
+    for variation in glyphs.iter().flatten().filter(|v| v.has_deltas()) {
+        *peak_counts.entry(&variation.peak).or_default() += 1;
+    }
+    let mut shared: Vec<_> = peak_counts.into_iter().filter(|(_, n)| *n > 1).collect();
+    shared.sort_by(|(t1, n1), (t2, n2)| n2.cmp(n1).then_with(|| t1.values.cmp(&t2.values)));
+    shared.truncate(MAX_TUPLES);
+    let shared_tuples: HashMap<_, _> = shared
+        .iter()
+        .enumerate()
+        .map(|(i, (tuple, _))| (*tuple, i as u16))
+        .collect();
+
+    let glyph_data = glyphs
+        .iter()
+        .map(|variations| glyph_variation_data(variations, &shared_tuples))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Short offsets store offset / 2 so need every glyph's data padded to an even length
+    let padded_size: usize = glyph_data.iter().map(|d| d.len() + d.len() % 2).sum();
+    let long_offsets = padded_size / 2 > u16::MAX as usize;
+
+    let offset_size = if long_offsets { 4 } else { 2 };
+    let shared_tuples_offset = GVAR_HEADER_SIZE + (glyph_data.len() + 1) * offset_size;
+    let data_array_offset = shared_tuples_offset + shared.len() * axis_count as usize * 2;
+
+    let flags = if long_offsets { LONG_OFFSETS } else { 0 };
+
+    let mut buf = Vec::new();
+    buf.extend(1u16.to_be_bytes()); // majorVersion
+    buf.extend(0u16.to_be_bytes()); // minorVersion
+    buf.extend(axis_count.to_be_bytes());
+    buf.extend((shared.len() as u16).to_be_bytes());
+    buf.extend((shared_tuples_offset as u32).to_be_bytes());
+    buf.extend(glyph_count.to_be_bytes());
+    buf.extend(flags.to_be_bytes());
+    buf.extend((data_array_offset as u32).to_be_bytes());
+
+    let mut offset = 0;
+    for data in std::iter::once(&Vec::new()).chain(glyph_data.iter()) {
+        if long_offsets {
+            offset += data.len();
+            buf.extend((offset as u32).to_be_bytes());
+        } else {
+            offset += data.len() + data.len() % 2;
+            buf.extend(((offset / 2) as u16).to_be_bytes());
+        }
+    }
+    for (tuple, _) in shared.iter() {
+        write_tuple(&mut buf, tuple);
+    }
+    for data in glyph_data {
+        let pad = !long_offsets && data.len() % 2 == 1;
+        buf.extend(data);
+        if pad {
+            buf.push(0);
+        }
+    }
+    Ok(buf)
+}
+
 struct GvarWork {}
 
 pub fn create_gvar_work() -> Box<BeWork> {
@@ -27,22 +387,183 @@ impl Work<Context, Error> for GvarWork {
         let variations: Vec<_> = static_metadata
             .glyph_order
             .iter()
-            .enumerate()
-            .map(|(gid, gn)| {
-                let gid = GlyphId::new(gid as u16);
-                let gvar_fragment = context.get_gvar_fragment(gn);
-                (gid, gvar_fragment.to_deltas())
-            })
-            .map(|(gid, deltas)| GlyphVariations::new(gid, deltas))
+            .map(|gn| context.get_gvar_fragment(gn).to_deltas())
             .collect();
-        let gvar = Gvar::new(variations).map_err(Error::GvarError)?;
-
-        let raw_gvar = Bytes::new(dump_table(&gvar).map_err(|e| Error::DumpTableError {
-            e,
-            context: "gvar".into(),
-        })?);
+        let raw_gvar = Bytes::new(compile_gvar(
+            static_metadata.axes.len() as u16,
+            &variations,
+        )?);
         context.set_gvar(raw_gvar);
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use font_types::{F2Dot14, GlyphId};
+    use read_fonts::{tables::gvar::Gvar, FontData, FontRead};
+
+    use super::*;
+
+    fn tuple(values: &[f32]) -> Tuple {
+        Tuple::new(values.iter().map(|v| F2Dot14::from_f32(*v)).collect())
+    }
+
+    fn unpack_deltas(packed: &[u8]) -> Vec<i16> {
+        read_fonts::tables::variations::PackedDeltas::new(FontData::new(packed))
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn implied_intermediate_is_dropped() {
+        let implied = GlyphDeltas::new(
+            tuple(&[1.0, -0.5]),
+            vec![Some((1, 1))],
+            Some((tuple(&[0.0, -0.5]), tuple(&[1.0, 0.0]))),
+        );
+        assert_eq!(None, implied.intermediate);
+
+        let intermediate = Some((tuple(&[0.5, -0.5]), tuple(&[1.0, 0.0])));
+        let explicit = GlyphDeltas::new(
+            tuple(&[1.0, -0.5]),
+            vec![Some((1, 1))],
+            intermediate.clone(),
+        );
+        assert_eq!(intermediate, explicit.intermediate);
+    }
+
+    #[test]
+    fn pack_deltas_round_trips() {
+        let mut deltas: Vec<i16> = vec![0, 0, 0, 1, 0, 2, 0, 0, 300, 2, 400, 0, -200, 5, 6];
+        deltas.extend((0..100).map(|i| i * 3 - 150));
+        deltas.extend([0; 70]);
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &deltas);
+        assert_eq!(deltas, unpack_deltas(&buf));
+    }
+
+    #[test]
+    fn pack_deltas_like_fonttools() {
+        // Examples from fontTools TupleVariationTest
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &[0, 0, 0, 0, 0]);
+        assert_eq!(vec![0x84], buf);
+
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &[1, 0, 2]);
+        assert_eq!(vec![0x02, 0x01, 0x00, 0x02], buf);
+
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &[1, 0, 0, 2]);
+        assert_eq!(vec![0x00, 0x01, 0x81, 0x00, 0x02], buf);
+
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &[0x6666, 0, 0x7777]);
+        assert_eq!(vec![0x40, 0x66, 0x66, 0x80, 0x40, 0x77, 0x77], buf);
+
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &[0x6666, 1, 0x7777]);
+        assert_eq!(vec![0x42, 0x66, 0x66, 0x00, 0x01, 0x77, 0x77], buf);
+
+        let mut buf = Vec::new();
+        pack_deltas(&mut buf, &[0x6666, 1, 2, 0x7777]);
+        assert_eq!(
+            vec![0x40, 0x66, 0x66, 0x01, 0x01, 0x02, 0x40, 0x77, 0x77],
+            buf
+        );
+    }
+
+    #[test]
+    fn pack_points_like_fonttools() {
+        let mut buf = Vec::new();
+        pack_points(&mut buf, None);
+        assert_eq!(vec![0x00], buf);
+
+        let mut buf = Vec::new();
+        pack_points(&mut buf, Some(&[7]));
+        assert_eq!(vec![0x01, 0x00, 0x07], buf);
+
+        let mut buf = Vec::new();
+        pack_points(&mut buf, Some(&[65535]));
+        assert_eq!(vec![0x01, 0x80, 0xff, 0xff], buf);
+
+        let mut buf = Vec::new();
+        pack_points(&mut buf, Some(&[17, 18, 300]));
+        assert_eq!(vec![0x03, 0x01, 0x11, 0x01, 0x80, 0x01, 0x1a], buf);
+    }
+
+    #[test]
+    fn compiled_gvar_reads_back() {
+        let glyphs = vec![
+            // No variations
+            Vec::new(),
+            // Every point explicit, shared peak
+            vec![GlyphDeltas::new(
+                tuple(&[1.0]),
+                vec![Some((1, 2)), Some((3, 4)), Some((0, 0))],
+                None,
+            )],
+            // Some points interpolated, shared peak and intermediate region
+            vec![
+                GlyphDeltas::new(
+                    tuple(&[1.0]),
+                    vec![Some((10, -10)), None, None, Some((300, 0))],
+                    None,
+                ),
+                GlyphDeltas::new(
+                    tuple(&[0.5]),
+                    vec![Some((5, 5)), None, None, Some((-5, 0))],
+                    Some((tuple(&[0.25]), tuple(&[1.0]))),
+                ),
+            ],
+        ];
+        let raw = compile_gvar(1, &glyphs).unwrap();
+        let gvar = Gvar::read(FontData::new(&raw)).unwrap();
+        assert_eq!(1, gvar.axis_count());
+        assert_eq!(3, gvar.glyph_count());
+        assert_eq!(1, gvar.shared_tuple_count());
+
+        assert!(gvar
+            .glyph_variation_data(GlyphId::new(0))
+            .map(|d| d.tuples().count() == 0)
+            .unwrap_or(true));
+
+        for (gid, expected) in glyphs.iter().enumerate().skip(1) {
+            let data = gvar.glyph_variation_data(GlyphId::new(gid as u16)).unwrap();
+            let tuples: Vec<_> = data.tuples().collect();
+            assert_eq!(expected.len(), tuples.len());
+            for (tuple, expected) in tuples.iter().zip(expected) {
+                assert_eq!(
+                    expected.peak.values,
+                    tuple
+                        .peak()
+                        .values()
+                        .iter()
+                        .map(|v| v.get())
+                        .collect::<Vec<_>>()
+                );
+                let actual: Vec<_> = tuple
+                    .deltas()
+                    .map(|d| (d.position as usize, (d.x_delta, d.y_delta)))
+                    .collect();
+                let expected: Vec<_> = expected
+                    .deltas
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, d)| d.map(|d| (i, d)))
+                    .collect();
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn sparse_deltas_are_smaller() {
+        let all = vec![Some((5, 5)); 20];
+        let mut sparse = vec![None; 20];
+        sparse[0] = Some((5, 5));
+        assert!(deltas_size(&sparse) < deltas_size(&all));
+    }
+}
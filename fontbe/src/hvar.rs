@@ -0,0 +1,184 @@
+//! Generates a [HVAR](https://learn.microsoft.com/en-us/typography/opentype/spec/hvar) table.
+//!
+//! Only advance widths vary; side bearings are left to gvar.
+
+use std::{borrow::Borrow, collections::HashMap};
+
+use fontdrasil::orchestration::Work;
+use fontir::ir::{Glyph, GlyphInstance, StaticMetadata};
+use log::trace;
+use read_fonts::types::MajorMinor;
+use write_fonts::{dump_table, tables::hvar::Hvar, OtRound};
+
+use crate::{
+    error::Error,
+    orchestration::{BeWork, Bytes, Context},
+    var_store::{delta_set_index_map, DeltaSetIndex, VarStoreBuilder},
+};
+
+struct HvarWork {}
+
+pub fn create_hvar_work() -> Box<BeWork> {
+    Box::new(HvarWork {})
+}
+
+/// Where to find the advance deltas for the glyph, if it has any.
+///
+/// Also used for the advance heights in VVAR.
+pub(crate) fn advance_deltas(
+    static_metadata: &StaticMetadata,
+    glyph: &Glyph,
+    advance: impl Fn(&GlyphInstance) -> f64,
+    var_store: &mut VarStoreBuilder,
+) -> Result<Option<DeltaSetIndex>, Error> {
+    let advances: HashMap<_, _> = glyph
+        .sources()
+        .iter()
+        .map(|(loc, instance)| (loc.clone(), vec![advance(instance)]))
+        .collect();
+    let deltas: Vec<_> = static_metadata
+        .variation_model_for(glyph.sources().keys())
+        .map_err(|e| Error::GlyphVariationModelError(glyph.name.clone(), e))?
+        .deltas::<f64, f64>(&advances)
+        .map_err(|e| Error::GlyphDeltaError(glyph.name.clone(), e))?
+        .into_iter()
+        .map(|(region, deltas)| (region, deltas[0].ot_round()))
+        .collect();
+    Ok(var_store.add_deltas(&deltas))
+}
+
+fn generate_hvar(
+    static_metadata: &StaticMetadata,
+    glyphs: &[impl Borrow<Glyph>],
+) -> Result<Option<Hvar>, Error> {
+    if static_metadata.variable_axes.is_empty() {
+        trace!("Skip HVAR; this is not a variable font");
+        return Ok(None);
+    }
+
+    let mut var_store = VarStoreBuilder::new(&static_metadata.variable_axes);
+    let advance_indices = glyphs
+        .iter()
+        .map(|glyph| {
+            advance_deltas(
+                static_metadata,
+                glyph.borrow(),
+                |instance| instance.width,
+                &mut var_store,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every glyph needs an entry, even if its advance is fixed
+    let Some(no_deltas) = var_store.add_no_deltas() else {
+        trace!("Skip HVAR; no advance varies");
+        return Ok(None);
+    };
+    let advance_indices: Vec<_> = advance_indices
+        .into_iter()
+        .map(|index| index.unwrap_or(no_deltas))
+        .collect();
+
+    Ok(Some(Hvar::new(
+        MajorMinor::VERSION_1_0,
+        var_store.build(),
+        Some(delta_set_index_map(&advance_indices)),
+        None,
+        None,
+    )))
+}
+
+impl Work<Context, Error> for HvarWork {
+    /// Generate [HVAR](https://learn.microsoft.com/en-us/typography/opentype/spec/hvar)
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let glyphs: Vec<_> = static_metadata
+            .glyph_order
+            .iter()
+            .map(|gn| context.ir.get_glyph_ir(gn))
+            .collect();
+        // Empty bytes tell font assembly to leave HVAR out
+        let bytes = match generate_hvar(&static_metadata, &glyphs)? {
+            Some(hvar) => dump_table(&hvar).map_err(|e| Error::DumpTableError {
+                e,
+                context: "HVAR".to_string(),
+            })?,
+            None => Vec::new(),
+        };
+        context.set_hvar(Bytes::new(bytes));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fontir::ir::{Glyph, GlyphInstance, StaticMetadata};
+    use write_fonts::tables::variations::DeltaSetIndexMap;
+
+    use crate::test_util::{self, location, weight_axis};
+
+    use super::generate_hvar;
+
+    fn glyph(name: &str, widths: &[(f32, f64)]) -> Glyph {
+        test_util::glyph(
+            name,
+            widths.iter().map(|(wght, width)| {
+                (
+                    *wght,
+                    GlyphInstance {
+                        width: *width,
+                        ..Default::default()
+                    },
+                )
+            }),
+        )
+    }
+
+    #[test]
+    fn advances_vary() {
+        let static_metadata = StaticMetadata::new(
+            1000,
+            Default::default(),
+            vec![weight_axis()],
+            ["a", "b", "c", "space"]
+                .iter()
+                .map(|n| (*n).into())
+                .collect(),
+            [location(0.0), location(1.0)].into(),
+        )
+        .unwrap();
+        let glyphs = vec![
+            glyph("a", &[(0.0, 500.0), (1.0, 550.0)]),
+            glyph("b", &[(0.0, 600.0), (1.0, 600.0)]),
+            glyph("c", &[(0.0, 400.0), (1.0, 450.0)]),
+            glyph("space", &[(0.0, 250.0)]),
+        ];
+
+        let hvar = generate_hvar(&static_metadata, &glyphs).unwrap().unwrap();
+
+        // a and c share a delta-set, b and space share the all-zero one
+        let store = &hvar.item_variation_store;
+        assert_eq!(1, store.variation_region_list.variation_regions.len());
+        assert_eq!(1, store.item_variation_datas.len());
+        let Some(DeltaSetIndexMap::Format0(map)) = hvar.advance_width_mapping.as_ref() else {
+            panic!("Expected a format 0 advance mapping");
+        };
+        assert_eq!(4, map.map_count);
+        assert_eq!(vec![0b00, 0b01, 0b00, 0b01], map.map_data);
+    }
+
+    #[test]
+    fn no_hvar_if_no_advance_varies() {
+        let static_metadata = StaticMetadata::new(
+            1000,
+            Default::default(),
+            vec![weight_axis()],
+            ["a"].iter().map(|n| (*n).into()).collect(),
+            [location(0.0), location(1.0)].into(),
+        )
+        .unwrap();
+        let glyphs = vec![glyph("a", &[(0.0, 500.0), (1.0, 500.0)])];
+
+        assert!(generate_hvar(&static_metadata, &glyphs).unwrap().is_none());
+    }
+}
@@ -0,0 +1,420 @@
+//! Interpolate Untouched Points (IUP) optimization of gvar deltas.
+//!
+//! A point whose delta can be inferred, to within a tolerance, from the deltas of the
+//! points around it on its contour needn't have a delta of its own in gvar. Rust version of
+//! <https://github.com/fonttools/fonttools/blob/main/Lib/fontTools/varLib/iup.py>.
+
+use std::collections::HashSet;
+
+use kurbo::{Point, Vec2};
+
+/// How far an interpolated delta may be from the actual one by default, as in fonttools
+pub const DEFAULT_TOLERANCE: f64 = 0.5;
+
+/// How far back along a contour we look for a point to interpolate from, as fonttools does
+const MAX_LOOKBACK: usize = 8;
+
+/// Drop the deltas of every point that can be interpolated from the points that keep theirs.
+///
+/// Coords and deltas are for every point in the glyph, plus the four phantom points.
+/// Contour ends are the indices of the last point of each contour, not counting phantom
+/// points. For composites each component is a contour of one point. No interpolated
+/// delta is further than tolerance from the actual one.
+///
+/// Returns None for every point whose delta was dropped.
+pub fn iup_delta_optimize(
+    deltas: &[Vec2],
+    coords: &[Point],
+    contour_ends: &[usize],
+    tolerance: f64,
+) -> Vec<Option<Vec2>> {
+    assert_eq!(deltas.len(), coords.len());
+    let n = coords.len();
+    assert!(n >= 4, "Phantom points are required");
+
+    // Phantom points are contours of their own
+    let mut result = Vec::with_capacity(n);
+    let mut start = 0;
+    for end in contour_ends.iter().copied().chain(n - 4..n) {
+        result.extend(iup_contour_optimize(
+            &deltas[start..=end],
+            &coords[start..=end],
+            tolerance,
+        ));
+        start = end + 1;
+    }
+    assert_eq!(n, result.len(), "Contour ends should cover every point");
+    result
+}
+
+fn iup_contour_optimize(deltas: &[Vec2], coords: &[Point], tolerance: f64) -> Vec<Option<Vec2>> {
+    let n = deltas.len();
+
+    // If every delta is within tolerance of zero encode nothing
+    if deltas.iter().all(|d| d.hypot() <= tolerance) {
+        return vec![None; n];
+    }
+    if n == 1 {
+        return vec![Some(deltas[0])];
+    }
+    // If every delta is the same one will do
+    if deltas.iter().all(|d| *d == deltas[0]) {
+        let mut result = vec![None; n];
+        result[0] = Some(deltas[0]);
+        return result;
+    }
+
+    let forced = forced_set(deltas, coords, tolerance);
+    let solution: HashSet<usize> = if let Some(last_forced) = forced.iter().max() {
+        // Rotate so the last point is forced, that's where the circle of the contour is cut
+        let k = (n - 1) - last_forced;
+        let deltas = rotate_right(deltas, k);
+        let coords = rotate_right(coords, k);
+        let forced = forced.iter().map(|i| (i + k) % n).collect();
+        let (chain, _) = optimize_dp(&deltas, &coords, &forced, tolerance, n);
+
+        let mut solution = HashSet::new();
+        let mut i = n as isize - 1;
+        while i >= 0 {
+            solution.insert((i as usize + n - k) % n);
+            i = chain[i as usize + 1];
+        }
+        solution
+    } else {
+        // Solve the contour repeated twice as a line, then look for the cheapest
+        // stretch of n points in that solution
+        let deltas_twice = [deltas, deltas].concat();
+        let coords_twice = [coords, coords].concat();
+        let (chain, costs) =
+            optimize_dp(&deltas_twice, &coords_twice, &HashSet::new(), tolerance, n);
+
+        let mut best = None;
+        let mut best_cost = n + 1;
+        for start in n - 1..2 * n {
+            let start = start as isize;
+            let mut solution = HashSet::new();
+            let mut i = start;
+            while i > start - n as isize {
+                solution.insert(i as usize % n);
+                i = chain[i as usize + 1];
+            }
+            if i == start - n as isize {
+                let cost = costs[start as usize + 1] - costs[(start - n as isize + 1) as usize];
+                if cost <= best_cost {
+                    best = Some(solution);
+                    best_cost = cost;
+                }
+            }
+        }
+        best.unwrap_or_else(|| (0..n).collect())
+    };
+
+    (0..n)
+        .map(|i| solution.contains(&i).then_some(deltas[i]))
+        .collect()
+}
+
+fn rotate_right<T: Clone>(items: &[T], k: usize) -> Vec<T> {
+    let mut items = items.to_vec();
+    items.rotate_right(k);
+    items
+}
+
+/// Interpolate deltas for coords from the reference points either side of them.
+fn iup_segment(coords: &[Point], rc1: Point, rd1: Vec2, rc2: Point, rd2: Vec2) -> Vec<Vec2> {
+    coords
+        .iter()
+        .map(|c| {
+            Vec2::new(
+                interpolate(rc1.x, rc2.x, rd1.x, rd2.x, c.x),
+                interpolate(rc1.y, rc2.y, rd1.y, rd2.y, c.y),
+            )
+        })
+        .collect()
+}
+
+/// The delta for x, interpolated between (x1, d1) and (x2, d2) and clamped outside of them.
+fn interpolate(x1: f64, x2: f64, d1: f64, d2: f64, x: f64) -> f64 {
+    if x1 == x2 {
+        return if d1 == d2 { d1 } else { 0.0 };
+    }
+    let (x1, x2, d1, d2) = if x1 > x2 {
+        (x2, x1, d2, d1)
+    } else {
+        (x1, x2, d1, d2)
+    };
+    if x <= x1 {
+        d1
+    } else if x >= x2 {
+        d2
+    } else {
+        let scale = (d2 - d1) / (x2 - x1);
+        d1 + (x - x1) * scale
+    }
+}
+
+/// Can the points between i and j be interpolated from them, to within tolerance?
+///
+/// An i of -1 refers to the last point, as it would in Python.
+fn can_iup_in_between(
+    deltas: &[Vec2],
+    coords: &[Point],
+    i: isize,
+    j: usize,
+    tolerance: f64,
+) -> bool {
+    let between = (i + 1) as usize..j;
+    let i = if i < 0 { deltas.len() - 1 } else { i as usize };
+    let interpolated = iup_segment(
+        &coords[between.clone()],
+        coords[i],
+        deltas[i],
+        coords[j],
+        deltas[j],
+    );
+    deltas[between]
+        .iter()
+        .zip(interpolated)
+        .all(|(d, interpolated)| (*d - interpolated).hypot() <= tolerance)
+}
+
+/// Points that can't possibly be interpolated from their neighbours so must have explicit deltas.
+///
+/// Cutting the contour at one of these lets us solve it as a line rather than a circle.
+fn forced_set(deltas: &[Vec2], coords: &[Point], tolerance: f64) -> HashSet<usize> {
+    let n = deltas.len();
+    let mut forced = HashSet::new();
+    for i in (0..n).rev() {
+        let (prev, next) = ((i + n - 1) % n, (i + 1) % n);
+        let per_axis = [
+            (
+                coords[i].x,
+                deltas[i].x,
+                coords[prev].x,
+                deltas[prev].x,
+                coords[next].x,
+                deltas[next].x,
+            ),
+            (
+                coords[i].y,
+                deltas[i].y,
+                coords[prev].y,
+                deltas[prev].y,
+                coords[next].y,
+                deltas[next].y,
+            ),
+        ];
+        for (c, d, prev_c, prev_d, next_c, next_d) in per_axis {
+            let (c1, c2, d1, d2) = if prev_c <= next_c {
+                (prev_c, next_c, prev_d, next_d)
+            } else {
+                (next_c, prev_c, next_d, prev_d)
+            };
+            let force = if c1 == c2 {
+                // Interpolation gives the delta if both are equal, zero otherwise
+                (d1 - d2).abs() > tolerance && d.abs() > tolerance
+            } else if c1 <= c && c <= c2 {
+                // Between the neighbours the delta must be between theirs
+                !(d1.min(d2) - tolerance <= d && d <= d1.max(d2) + tolerance)
+            } else if d1 != d2 {
+                // Outside them it must match the closest one
+                if c < c1 {
+                    d.abs() > tolerance
+                        && (d - d1).abs() > tolerance
+                        && ((d - tolerance < d1) != (d1 < d2))
+                } else {
+                    d.abs() > tolerance
+                        && (d - d2).abs() > tolerance
+                        && ((d2 < d + tolerance) != (d1 < d2))
+                }
+            } else {
+                false
+            };
+            if force {
+                forced.insert(i);
+                break;
+            }
+        }
+    }
+    forced
+}
+
+/// For each point find the cheapest encoding of the points up to it in which it's explicit.
+///
+/// Returns, offset by one so that index 0 is for the point before the first, the
+/// previous explicit point and the cost for each point.
+fn optimize_dp(
+    deltas: &[Vec2],
+    coords: &[Point],
+    forced: &HashSet<usize>,
+    tolerance: f64,
+    lookback: usize,
+) -> (Vec<isize>, Vec<usize>) {
+    let n = deltas.len();
+    let lookback = lookback.min(MAX_LOOKBACK) as isize;
+    let mut chain = vec![-2; n + 1];
+    let mut costs = vec![0; n + 1];
+    for i in 0..n {
+        let mut best_cost = costs[i] + 1;
+        costs[i + 1] = best_cost;
+        chain[i + 1] = i as isize - 1;
+        if i > 0 && forced.contains(&(i - 1)) {
+            continue;
+        }
+        let i_signed = i as isize;
+        let mut j = i_signed - 2;
+        while j > (i_signed - lookback).max(-2) {
+            let cost = costs[(j + 1) as usize] + 1;
+            if cost < best_cost && can_iup_in_between(deltas, coords, j, i, tolerance) {
+                best_cost = cost;
+                costs[i + 1] = cost;
+                chain[i + 1] = j;
+            }
+            if j >= 0 && forced.contains(&(j as usize)) {
+                break;
+            }
+            j -= 1;
+        }
+    }
+    (chain, costs)
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Point, Vec2};
+
+    use super::*;
+
+    /// Fill in the deltas IUP drops, as a renderer would.
+    fn iup_contour(deltas: &[Option<Vec2>], coords: &[Point]) -> Vec<Vec2> {
+        let explicit: Vec<_> = (0..deltas.len()).filter(|i| deltas[*i].is_some()).collect();
+        if explicit.is_empty() {
+            return vec![Vec2::ZERO; deltas.len()];
+        }
+        (0..deltas.len())
+            .map(|i| {
+                if let Some(delta) = deltas[i] {
+                    return delta;
+                }
+                let prev = *explicit
+                    .iter()
+                    .rev()
+                    .find(|e| **e < i)
+                    .unwrap_or(explicit.last().unwrap());
+                let next = *explicit.iter().find(|e| **e > i).unwrap_or(&explicit[0]);
+                iup_segment(
+                    &[coords[i]],
+                    coords[prev],
+                    deltas[prev].unwrap(),
+                    coords[next],
+                    deltas[next].unwrap(),
+                )[0]
+            })
+            .collect()
+    }
+
+    fn square() -> Vec<Point> {
+        vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 50.0),
+            Point::new(0.0, 100.0),
+            Point::new(50.0, 100.0),
+            Point::new(100.0, 100.0),
+            Point::new(100.0, 50.0),
+            Point::new(100.0, 0.0),
+            Point::new(50.0, 0.0),
+        ]
+    }
+
+    fn with_phantoms(mut points: Vec<Point>) -> Vec<Point> {
+        points.extend([
+            Point::ZERO,
+            Point::new(100.0, 0.0),
+            Point::ZERO,
+            Point::ZERO,
+        ]);
+        points
+    }
+
+    #[test]
+    fn no_deltas_for_a_contour_that_doesnt_move() {
+        let coords = square();
+        assert_eq!(
+            vec![None; coords.len()],
+            iup_contour_optimize(&vec![Vec2::ZERO; coords.len()], &coords, 0.0)
+        );
+    }
+
+    #[test]
+    fn one_delta_for_a_contour_that_moves_as_one() {
+        let coords = square();
+        let deltas = vec![Vec2::new(5.0, 3.0); coords.len()];
+        let optimized = iup_contour_optimize(&deltas, &coords, 0.0);
+        assert_eq!(1, optimized.iter().filter(|d| d.is_some()).count());
+        assert_eq!(deltas, iup_contour(&optimized, &coords));
+    }
+
+    #[test]
+    fn opposite_corners_of_a_scaled_square_imply_the_rest() {
+        // Scale the square by 2, two opposite corners are enough to interpolate the rest
+        let coords = square();
+        let deltas: Vec<_> = coords.iter().map(|p| p.to_vec2()).collect();
+        let optimized = iup_contour_optimize(&deltas, &coords, 0.0);
+        assert_eq!(
+            vec![false, false, true, false, false, false, true, false],
+            optimized.iter().map(|d| d.is_some()).collect::<Vec<_>>()
+        );
+        assert_eq!(deltas, iup_contour(&optimized, &coords));
+    }
+
+    #[test]
+    fn tolerance_allows_more_to_be_implied() {
+        let coords = square();
+        let mut deltas: Vec<_> = coords.iter().map(|p| p.to_vec2()).collect();
+        // Nudge the midpoint of the left side off where it would interpolate to
+        deltas[1].x += 0.4;
+        let strict = iup_contour_optimize(&deltas, &coords, 0.0);
+        assert!(strict[1].is_some());
+        assert_eq!(deltas, iup_contour(&strict, &coords));
+
+        let tolerant = iup_contour_optimize(&deltas, &coords, 0.5);
+        assert!(tolerant[1].is_none());
+        for (actual, interpolated) in deltas.iter().zip(iup_contour(&tolerant, &coords)) {
+            assert!((*actual - interpolated).hypot() <= 0.5);
+        }
+    }
+
+    #[test]
+    fn phantom_points_are_their_own_contours() {
+        let coords = with_phantoms(square());
+        let mut deltas = vec![Vec2::ZERO; coords.len()];
+        // Only the advance moves
+        deltas[9] = Vec2::new(20.0, 0.0);
+        let optimized = iup_delta_optimize(&deltas, &coords, &[7], 0.0);
+        let explicit: Vec<_> = (0..optimized.len())
+            .filter(|i| optimized[*i].is_some())
+            .collect();
+        assert_eq!(vec![9], explicit);
+    }
+
+    #[test]
+    fn contours_are_optimized_separately() {
+        let mut coords = square();
+        coords.extend(square().iter().map(|p| *p + Vec2::new(200.0, 0.0)));
+        let coords = with_phantoms(coords);
+        let mut deltas = vec![Vec2::ZERO; coords.len()];
+        // Move the second contour as a whole
+        for delta in deltas[8..16].iter_mut() {
+            *delta = Vec2::new(10.0, 0.0);
+        }
+        let optimized = iup_delta_optimize(&deltas, &coords, &[7, 15], 0.0);
+        let explicit: Vec<_> = (0..optimized.len())
+            .filter(|i| optimized[*i].is_some())
+            .collect();
+        assert_eq!(vec![8], explicit);
+        assert_eq!(
+            deltas[8..16],
+            iup_contour(&optimized[8..16], &coords[8..16])
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! Pair positioning from kerning IR.
+//!
+//! Produces the data behind the `kern` feature; as with marks the GPOS lookup
+//! is built when features are compiled so it can be merged with the fea source.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use font_types::{GlyphId, Tag};
+use fontdrasil::{orchestration::Work, types::GlyphName};
+use fontir::{
+    coords::NormalizedLocation,
+    ir::{self, KernPair, KernParticipant},
+    variations::VariationRegion,
+};
+use log::{trace, warn};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use write_fonts::{
+    tables::{
+        gpos::{
+            Class1Record, Class2Record, PairPos, PairSet, PairValueRecord, PositionLookup,
+            ValueRecord,
+        },
+        layout::{ClassDefBuilder, CoverageTableBuilder, Lookup, LookupFlag},
+    },
+    NullableOffsetMarker, OtRound,
+};
+
+use crate::{
+    error::Error,
+    orchestration::{BeWork, Context},
+    var_store::{variation_index, VarStoreBuilder, NO_VARIATION_INDEX},
+};
+
+struct KerningWork {}
+
+pub fn create_kerning_work() -> Box<BeWork> {
+    Box::new(KerningWork {})
+}
+
+/// A kerning value, plus deltas if it varies.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KernAdjustment {
+    pub value: i16,
+    /// Deltas by region, empty if the value doesn't vary
+    pub deltas: Vec<(VariationRegion, i16)>,
+}
+
+/// Kerning organized for PairPos construction.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Kerning {
+    pub groups: BTreeMap<String, BTreeSet<GlyphName>>,
+    pub kerns: BTreeMap<KernPair, KernAdjustment>,
+}
+
+impl Kerning {
+    pub fn is_empty(&self) -> bool {
+        self.kerns.is_empty()
+    }
+}
+
+fn kern_adjustment(
+    static_metadata: &ir::StaticMetadata,
+    pair: &KernPair,
+    values: &BTreeMap<NormalizedLocation, OrderedFloat<f32>>,
+) -> Result<KernAdjustment, Error> {
+    let default = values
+        .get(static_metadata.default_location())
+        .map(|v| v.into_inner() as f64)
+        .unwrap_or_default();
+    let value: i16 = default.ot_round();
+
+    if static_metadata.variable_axes.is_empty() {
+        return Ok(KernAdjustment {
+            value,
+            deltas: Vec::new(),
+        });
+    }
+
+    let values: HashMap<_, _> = values
+        .iter()
+        .map(|(loc, value)| (loc.clone(), vec![value.into_inner() as f64]))
+        .collect();
+    // Kerning exists only at masters, not at brace or sparse glyph locations
+    let deltas = static_metadata
+        .variation_model_for(values.keys())
+        .map_err(|e| Error::KerningVariationModelError(pair.clone(), e))?
+        .deltas::<f64, f64>(&values)
+        .map_err(|e| Error::KerningDeltaError(pair.clone(), e))?
+        .into_iter()
+        .filter(|(region, _)| !region.is_default())
+        .map(|(region, deltas)| (region, deltas[0].ot_round()))
+        .filter(|(_, delta)| *delta != 0)
+        .collect();
+
+    Ok(KernAdjustment { value, deltas })
+}
+
+impl Work<Context, Error> for KerningWork {
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let ir_kerning = context.ir.get_kerning();
+
+        let mut kerning = Kerning {
+            groups: ir_kerning.groups.clone(),
+            ..Default::default()
+        };
+        for (pair, values) in ir_kerning.kerns.iter() {
+            kerning.kerns.insert(
+                pair.clone(),
+                kern_adjustment(&static_metadata, pair, values)?,
+            );
+        }
+        trace!("{} kerning pairs", kerning.kerns.len());
+
+        context.set_kerning(kerning);
+        Ok(())
+    }
+}
+
+/// Specific pairs win over class pairs, the first member being specific wins ties.
+fn precedence(pair: &KernPair) -> u8 {
+    match pair {
+        (KernParticipant::Glyph(..), KernParticipant::Glyph(..)) => 0,
+        (KernParticipant::Glyph(..), KernParticipant::Group(..)) => 1,
+        (KernParticipant::Group(..), KernParticipant::Glyph(..)) => 2,
+        (KernParticipant::Group(..), KernParticipant::Group(..)) => 3,
+    }
+}
+
+/// Every record in a subtable has to have the same format so the advance,
+/// and device if the font is variable, are always present.
+fn value_record(
+    adjustment: Option<&KernAdjustment>,
+    is_variable: bool,
+    var_store: &mut VarStoreBuilder,
+) -> ValueRecord {
+    let mut record = ValueRecord {
+        x_advance: Some(adjustment.map(|a| a.value).unwrap_or_default()),
+        ..Default::default()
+    };
+    if is_variable {
+        let index = adjustment
+            .and_then(|a| var_store.add_deltas(&a.deltas))
+            .unwrap_or(NO_VARIATION_INDEX);
+        record.x_advance_device = NullableOffsetMarker::new(Some(variation_index(index)));
+    }
+    record
+}
+
+/// Number the groups 1..n, skipping any that end up with no glyphs.
+///
+/// A glyph can only be in one class so the first group to claim it wins.
+fn assign_classes<'a>(
+    names: impl Iterator<Item = &'a str>,
+    groups: &HashMap<&str, Vec<GlyphId>>,
+) -> (HashMap<&'a str, u16>, BTreeMap<GlyphId, u16>) {
+    let mut classes = HashMap::new();
+    let mut glyph_classes: BTreeMap<GlyphId, u16> = BTreeMap::new();
+    for name in names {
+        let gids: Vec<_> = groups
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|gid| {
+                if glyph_classes.contains_key(gid) {
+                    warn!("{gid:?} is in more than one kerning group, ignoring it in {name}");
+                    return false;
+                }
+                true
+            })
+            .copied()
+            .collect();
+        if gids.is_empty() {
+            continue;
+        }
+        let class = classes.len() as u16 + 1;
+        classes.insert(name, class);
+        glyph_classes.extend(gids.into_iter().map(|gid| (gid, class)));
+    }
+    (classes, glyph_classes)
+}
+
+/// Build the `kern` lookup, paired with its feature tag.
+///
+/// Pairs that involve a specific glyph are expanded into format 1 subtables,
+/// group to group pairs go into a format 2 subtable after them.
+pub(crate) fn create_lookups(
+    kerning: &Kerning,
+    static_metadata: &ir::StaticMetadata,
+    var_store: &mut VarStoreBuilder,
+) -> Vec<(Tag, PositionLookup)> {
+    if kerning.is_empty() {
+        return Vec::new();
+    }
+    let is_variable = !static_metadata.variable_axes.is_empty();
+    let gid = |name: &GlyphName| {
+        static_metadata
+            .glyph_id(name)
+            .map(|gid| GlyphId::new(gid as u16))
+    };
+
+    // Group members that made it into the font
+    let groups: HashMap<_, _> = kerning
+        .groups
+        .iter()
+        .map(|(name, members)| {
+            let mut gids: Vec<_> = members.iter().filter_map(gid).collect();
+            gids.sort();
+            (name.as_str(), gids)
+        })
+        .collect();
+    let members = |participant: &KernParticipant| match participant {
+        KernParticipant::Glyph(name) => gid(name).into_iter().collect(),
+        KernParticipant::Group(name) => groups.get(name.as_str()).cloned().unwrap_or_default(),
+    };
+
+    let mut pairs: Vec<_> = kerning.kerns.iter().collect();
+    pairs.sort_by_key(|(pair, _)| precedence(pair));
+
+    let mut glyph_pairs: BTreeMap<GlyphId, BTreeMap<GlyphId, &KernAdjustment>> = BTreeMap::new();
+    let mut group_pairs = Vec::new();
+    for ((side1, side2), adjustment) in pairs {
+        if let (KernParticipant::Group(group1), KernParticipant::Group(group2)) = (side1, side2) {
+            group_pairs.push((group1.as_str(), group2.as_str(), adjustment));
+            continue;
+        }
+        for first in members(side1) {
+            let seconds = glyph_pairs.entry(first).or_default();
+            for second in members(side2) {
+                seconds.entry(second).or_insert(adjustment);
+            }
+        }
+    }
+    glyph_pairs.retain(|_, seconds| !seconds.is_empty());
+
+    let mut subtables = Vec::new();
+    if !glyph_pairs.is_empty() {
+        let coverage = glyph_pairs
+            .keys()
+            .copied()
+            .collect::<CoverageTableBuilder>();
+        let pair_sets = glyph_pairs
+            .values()
+            .map(|seconds| {
+                PairSet::new(
+                    seconds
+                        .iter()
+                        .map(|(second, adjustment)| {
+                            PairValueRecord::new(
+                                *second,
+                                value_record(Some(adjustment), is_variable, var_store),
+                                ValueRecord::default(),
+                            )
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+        subtables.push(PairPos::format_1(coverage.build(), pair_sets));
+    }
+
+    let (classes1, class_def1) = assign_classes(
+        group_pairs
+            .iter()
+            .map(|(group1, _, _)| *group1)
+            .collect::<BTreeSet<_>>()
+            .into_iter(),
+        &groups,
+    );
+    let (classes2, class_def2) = assign_classes(
+        group_pairs
+            .iter()
+            .map(|(_, group2, _)| *group2)
+            .collect::<BTreeSet<_>>()
+            .into_iter(),
+        &groups,
+    );
+    let class_values: HashMap<_, _> = group_pairs
+        .iter()
+        .filter_map(|(group1, group2, adjustment)| {
+            let class1 = classes1.get(group1)?;
+            let class2 = classes2.get(group2)?;
+            Some(((*class1, *class2), *adjustment))
+        })
+        .collect();
+    if !class_values.is_empty() {
+        let coverage = class_def1.keys().copied().collect::<CoverageTableBuilder>();
+        // Class 0 is everything not in a class, it never kerns
+        let class1_records = (0..=classes1.len() as u16)
+            .map(|class1| {
+                Class1Record::new(
+                    (0..=classes2.len() as u16)
+                        .map(|class2| {
+                            Class2Record::new(
+                                value_record(
+                                    class_values.get(&(class1, class2)).copied(),
+                                    is_variable,
+                                    var_store,
+                                ),
+                                ValueRecord::default(),
+                            )
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+        subtables.push(PairPos::format_2(
+            coverage.build(),
+            class_def1.into_iter().collect::<ClassDefBuilder>().build(),
+            class_def2.into_iter().collect::<ClassDefBuilder>().build(),
+            class1_records,
+        ));
+    }
+
+    if subtables.is_empty() {
+        return Vec::new();
+    }
+    let mut lookup_flag = LookupFlag::empty();
+    lookup_flag.set_ignore_marks(true);
+    vec![(
+        Tag::new(b"kern"),
+        PositionLookup::Pair(Lookup::new(lookup_flag, subtables, 0)),
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fontir::ir::{KernParticipant, StaticMetadata};
+    use ordered_float::OrderedFloat;
+
+    use crate::test_util::{location, weight_axis};
+
+    use super::{kern_adjustment, precedence};
+
+    #[test]
+    fn kerning_ignores_intermediate_glyph_locations() {
+        // Some glyph has an intermediate master at 0.5, kerning is only at the masters
+        let static_metadata = StaticMetadata::new(
+            1000,
+            Default::default(),
+            vec![weight_axis()],
+            Default::default(),
+            [location(0.0), location(0.5), location(1.0)].into(),
+        )
+        .unwrap();
+        let pair = (
+            KernParticipant::Glyph("a".into()),
+            KernParticipant::Glyph("b".into()),
+        );
+        let values = BTreeMap::from([
+            (location(0.0), OrderedFloat(0.0)),
+            (location(1.0), OrderedFloat(100.0)),
+        ]);
+
+        let adjustment = kern_adjustment(&static_metadata, &pair, &values).unwrap();
+
+        let at = |wght: f32| {
+            adjustment.value as f32
+                + adjustment
+                    .deltas
+                    .iter()
+                    .map(|(region, delta)| region.scalar_at(&location(wght)).0 * *delta as f32)
+                    .sum::<f32>()
+        };
+        assert_eq!(
+            vec![0.0, 25.0, 50.0, 100.0],
+            vec![at(0.0), at(0.25), at(0.5), at(1.0)]
+        );
+    }
+
+    #[test]
+    fn specific_pairs_first() {
+        let glyph = || KernParticipant::Glyph("a".into());
+        let group = || KernParticipant::Group("public.kern1.a".to_string());
+        let mut pairs = vec![
+            (group(), group()),
+            (group(), glyph()),
+            (glyph(), group()),
+            (glyph(), glyph()),
+        ];
+        pairs.sort_by_key(precedence);
+        assert_eq!(
+            vec![
+                (glyph(), glyph()),
+                (glyph(), group()),
+                (group(), glyph()),
+                (group(), group()),
+            ],
+            pairs
+        );
+    }
+}
@@ -1,16 +1,32 @@
 pub mod avar;
+pub mod cff;
 pub mod cmap;
 pub mod error;
+pub mod feature_variations;
 pub mod features;
 pub mod font;
 pub mod fvar;
+pub mod gdef;
 pub mod glyphs;
 pub mod gvar;
 pub mod head;
+pub mod hvar;
+pub mod iup;
+pub mod kern;
+pub mod marks;
 pub mod maxp;
 pub mod metrics_and_limits;
+pub mod mvar;
 pub mod name;
 pub mod orchestration;
 pub mod os2;
 pub mod paths;
 pub mod post;
+pub mod stat;
+#[cfg(test)]
+mod test_util;
+pub mod var_store;
+pub mod variable_fea;
+pub mod vvar;
+pub mod woff;
+pub mod woff2;
@@ -0,0 +1,375 @@
+//! Mark attachment, mark to mark and cursive attachment from glyph anchors.
+//!
+//! Produces the data behind the `mark`, `mkmk` and `curs` features; the GPOS
+//! lookups themselves are built when features are compiled so they can be
+//! merged with whatever the fea source defines.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use font_types::{GlyphId, Tag};
+use fontdrasil::{orchestration::Work, types::GlyphName};
+use fontir::{coords::NormalizedLocation, ir, variations::VariationRegion};
+use kurbo::{Point, Vec2};
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+use write_fonts::{
+    tables::{
+        gpos::{
+            AnchorTable, BaseArray, BaseRecord, CursivePosFormat1, EntryExitRecord, Mark2Array,
+            Mark2Record, MarkArray, MarkBasePosFormat1, MarkMarkPosFormat1, MarkRecord,
+            PositionLookup,
+        },
+        layout::{CoverageTableBuilder, Lookup, LookupFlag},
+    },
+    OtRound,
+};
+
+use crate::{
+    error::Error,
+    orchestration::{BeWork, Context},
+    var_store::{variation_index, VarStoreBuilder},
+};
+
+const ENTRY: &str = "entry";
+const EXIT: &str = "exit";
+
+struct MarkWork {}
+
+pub fn create_mark_work() -> Box<BeWork> {
+    Box::new(MarkWork {})
+}
+
+/// An anchor position, plus deltas if it varies.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnchorPosition {
+    pub x: i16,
+    pub y: i16,
+    /// (x, y) deltas by region, empty if the anchor doesn't vary
+    pub deltas: Vec<(VariationRegion, (i16, i16))>,
+}
+
+/// Anchors organized for mark, mkmk and curs lookup construction.
+///
+/// Anchor classes are anchor names, minus the leading underscore for marks.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Marks {
+    /// Anchor class => mark glyph => position of the mark's `_class` anchor
+    pub marks: BTreeMap<String, BTreeMap<GlyphName, AnchorPosition>>,
+    /// Anchor class => glyph => position of the glyph's `class` anchor
+    pub bases: BTreeMap<String, BTreeMap<GlyphName, AnchorPosition>>,
+    /// Glyph => (entry, exit)
+    pub cursive: BTreeMap<GlyphName, (Option<AnchorPosition>, Option<AnchorPosition>)>,
+}
+
+impl Marks {
+    /// Every glyph that has at least one mark (`_class`) anchor.
+    pub fn mark_glyphs(&self) -> BTreeSet<&GlyphName> {
+        self.marks.values().flat_map(|m| m.keys()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty() && self.cursive.is_empty()
+    }
+}
+
+/// Glyphs uses anchors named `*origin` and `caret_N` for other purposes.
+pub(crate) fn is_attachment_anchor(name: &str) -> bool {
+    !name.is_empty() && name != "_" && !name.starts_with('*') && !name.starts_with("caret_")
+}
+
+pub(crate) fn anchor_position(
+    static_metadata: &ir::StaticMetadata,
+    glyph: &ir::Glyph,
+    anchor_name: &str,
+) -> Result<Option<AnchorPosition>, Error> {
+    let default = glyph.default_instance();
+    let Some(default_anchor) = default.anchors.iter().find(|a| a.name == anchor_name) else {
+        return Ok(None);
+    };
+    let x: i16 = default_anchor.pos.x.ot_round();
+    let y: i16 = default_anchor.pos.y.ot_round();
+
+    if static_metadata.variable_axes.is_empty() {
+        return Ok(Some(AnchorPosition {
+            x,
+            y,
+            deltas: Vec::new(),
+        }));
+    }
+
+    let mut positions: HashMap<NormalizedLocation, Vec<Point>> = HashMap::new();
+    for (loc, inst) in glyph.sources() {
+        let Some(anchor) = inst.anchors.iter().find(|a| a.name == anchor_name) else {
+            warn!(
+                "'{}' anchor {anchor_name} is missing at {loc:?}, it will not vary",
+                glyph.name
+            );
+            return Ok(Some(AnchorPosition {
+                x,
+                y,
+                deltas: Vec::new(),
+            }));
+        };
+        positions.insert(loc.clone(), vec![anchor.pos]);
+    }
+
+    let deltas = static_metadata
+        .variation_model_for(glyph.sources().keys())
+        .map_err(|e| Error::GlyphVariationModelError(glyph.name.clone(), e))?
+        .deltas::<Point, Vec2>(&positions)
+        .map_err(|e| Error::GlyphDeltaError(glyph.name.clone(), e))?
+        .into_iter()
+        .filter(|(region, _)| !region.is_default())
+        .map(|(region, deltas)| {
+            let delta = deltas[0];
+            (region, (delta.x.ot_round(), delta.y.ot_round()))
+        })
+        .filter(|(_, delta)| *delta != (0, 0))
+        .collect();
+
+    Ok(Some(AnchorPosition { x, y, deltas }))
+}
+
+impl Work<Context, Error> for MarkWork {
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let mut marks = Marks::default();
+
+        for glyph_name in static_metadata.glyph_order.iter() {
+            let glyph = context.ir.get_glyph_ir(glyph_name);
+            let anchor_names: BTreeSet<_> = glyph
+                .default_instance()
+                .anchors
+                .iter()
+                .map(|a| a.name.as_str())
+                .filter(|name| is_attachment_anchor(name))
+                .collect();
+
+            let mut entry = None;
+            let mut exit = None;
+            for anchor_name in anchor_names {
+                let Some(pos) = anchor_position(&static_metadata, &glyph, anchor_name)? else {
+                    continue;
+                };
+                match anchor_name {
+                    ENTRY => entry = Some(pos),
+                    EXIT => exit = Some(pos),
+                    _ => {
+                        let (target, class) = match anchor_name.strip_prefix('_') {
+                            Some(class) => (&mut marks.marks, class),
+                            None => (&mut marks.bases, anchor_name),
+                        };
+                        target
+                            .entry(class.to_string())
+                            .or_default()
+                            .insert(glyph_name.clone(), pos);
+                    }
+                }
+            }
+            if entry.is_some() || exit.is_some() {
+                marks.cursive.insert(glyph_name.clone(), (entry, exit));
+            }
+        }
+
+        // Bases for a class no mark uses are of no interest
+        marks
+            .bases
+            .retain(|class, _| marks.marks.contains_key(class));
+        trace!(
+            "{} mark classes, {} cursive glyphs",
+            marks.marks.len(),
+            marks.cursive.len()
+        );
+
+        context.set_marks(marks);
+        Ok(())
+    }
+}
+
+fn anchor_table(pos: &AnchorPosition, var_store: &mut VarStoreBuilder) -> AnchorTable {
+    let x_deltas: Vec<_> = pos
+        .deltas
+        .iter()
+        .map(|(r, (x, _))| (r.clone(), *x))
+        .collect();
+    let y_deltas: Vec<_> = pos
+        .deltas
+        .iter()
+        .map(|(r, (_, y))| (r.clone(), *y))
+        .collect();
+    let x_device = var_store.add_deltas(&x_deltas).map(variation_index);
+    let y_device = var_store.add_deltas(&y_deltas).map(variation_index);
+    if x_device.is_none() && y_device.is_none() {
+        AnchorTable::format_1(pos.x, pos.y)
+    } else {
+        AnchorTable::format_3(pos.x, pos.y, x_device, y_device)
+    }
+}
+
+struct GlyphIds<'a>(&'a ir::StaticMetadata);
+
+impl GlyphIds<'_> {
+    fn get(&self, glyph_name: &GlyphName) -> Option<GlyphId> {
+        self.0
+            .glyph_id(glyph_name)
+            .map(|gid| GlyphId::new(gid as u16))
+    }
+}
+
+fn mark_array(
+    gids: &GlyphIds,
+    marks: &BTreeMap<GlyphName, AnchorPosition>,
+    var_store: &mut VarStoreBuilder,
+) -> (CoverageTableBuilder, MarkArray) {
+    let mut by_gid: Vec<_> = marks
+        .iter()
+        .filter_map(|(name, pos)| gids.get(name).map(|gid| (gid, pos)))
+        .collect();
+    by_gid.sort_by_key(|(gid, _)| *gid);
+    let coverage = by_gid.iter().map(|(gid, _)| *gid).collect();
+    let records = by_gid
+        .iter()
+        .map(|(_, pos)| MarkRecord::new(0, anchor_table(pos, var_store)))
+        .collect();
+    (coverage, MarkArray::new(records))
+}
+
+fn base_anchors<'a>(
+    gids: &GlyphIds,
+    bases: &'a BTreeMap<GlyphName, AnchorPosition>,
+    keep: impl Fn(&GlyphName) -> bool,
+) -> Vec<(GlyphId, &'a AnchorPosition)> {
+    let mut by_gid: Vec<_> = bases
+        .iter()
+        .filter(|(name, _)| keep(name))
+        .filter_map(|(name, pos)| gids.get(name).map(|gid| (gid, pos)))
+        .collect();
+    by_gid.sort_by_key(|(gid, _)| *gid);
+    by_gid
+}
+
+/// Build lookups, each paired with the tag of the feature that should reference it.
+///
+/// One mark to base and one mark to mark lookup is built per anchor class.
+pub(crate) fn create_lookups(
+    marks: &Marks,
+    static_metadata: &ir::StaticMetadata,
+    var_store: &mut VarStoreBuilder,
+) -> Vec<(Tag, PositionLookup)> {
+    let gids = GlyphIds(static_metadata);
+    let mark_glyphs = marks.mark_glyphs();
+    let mut mark_lookups = Vec::new();
+    let mut mkmk_lookups = Vec::new();
+
+    for (class, class_marks) in marks.marks.iter() {
+        let Some(bases) = marks.bases.get(class) else {
+            continue;
+        };
+
+        // Marks attach to non-marks in mark, to other marks in mkmk
+        let mark_bases = base_anchors(&gids, bases, |name| mark_glyphs.contains(name));
+        let bases = base_anchors(&gids, bases, |name| !mark_glyphs.contains(name));
+        if !bases.is_empty() {
+            let (mark_coverage, mark_array) = mark_array(&gids, class_marks, var_store);
+            let base_coverage = bases
+                .iter()
+                .map(|(gid, _)| *gid)
+                .collect::<CoverageTableBuilder>();
+            let base_array = BaseArray::new(
+                bases
+                    .iter()
+                    .map(|(_, pos)| BaseRecord::new(vec![Some(anchor_table(pos, var_store))]))
+                    .collect(),
+            );
+            mark_lookups.push(PositionLookup::MarkToBase(Lookup::new(
+                LookupFlag::empty(),
+                vec![MarkBasePosFormat1::new(
+                    mark_coverage.build(),
+                    base_coverage.build(),
+                    mark_array,
+                    base_array,
+                )],
+                0,
+            )));
+        }
+
+        if !mark_bases.is_empty() {
+            let (mark_coverage, mark_array) = mark_array(&gids, class_marks, var_store);
+            let mark2_coverage = mark_bases
+                .iter()
+                .map(|(gid, _)| *gid)
+                .collect::<CoverageTableBuilder>();
+            let mark2_array = Mark2Array::new(
+                mark_bases
+                    .iter()
+                    .map(|(_, pos)| Mark2Record::new(vec![Some(anchor_table(pos, var_store))]))
+                    .collect(),
+            );
+            mkmk_lookups.push(PositionLookup::MarkToMark(Lookup::new(
+                LookupFlag::empty(),
+                vec![MarkMarkPosFormat1::new(
+                    mark_coverage.build(),
+                    mark2_coverage.build(),
+                    mark_array,
+                    mark2_array,
+                )],
+                0,
+            )));
+        }
+    }
+
+    let mut lookups: Vec<_> = mark_lookups
+        .into_iter()
+        .map(|l| (Tag::new(b"mark"), l))
+        .chain(mkmk_lookups.into_iter().map(|l| (Tag::new(b"mkmk"), l)))
+        .collect();
+
+    let mut cursive: Vec<_> = marks
+        .cursive
+        .iter()
+        .filter_map(|(name, entry_exit)| gids.get(name).map(|gid| (gid, entry_exit)))
+        .collect();
+    cursive.sort_by_key(|(gid, _)| *gid);
+    if !cursive.is_empty() {
+        let coverage = cursive
+            .iter()
+            .map(|(gid, _)| *gid)
+            .collect::<CoverageTableBuilder>();
+        let records = cursive
+            .iter()
+            .map(|(_, (entry, exit))| {
+                EntryExitRecord::new(
+                    entry.as_ref().map(|pos| anchor_table(pos, var_store)),
+                    exit.as_ref().map(|pos| anchor_table(pos, var_store)),
+                )
+            })
+            .collect();
+        let mut lookup_flag = LookupFlag::empty();
+        lookup_flag.set_ignore_marks(true);
+        lookups.push((
+            Tag::new(b"curs"),
+            PositionLookup::Cursive(Lookup::new(
+                lookup_flag,
+                vec![CursivePosFormat1::new(coverage.build(), records)],
+                0,
+            )),
+        ));
+    }
+
+    lookups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_attachment_anchor;
+
+    #[test]
+    fn attachment_anchors() {
+        assert!(is_attachment_anchor("top"));
+        assert!(is_attachment_anchor("_top"));
+        assert!(is_attachment_anchor("entry"));
+        assert!(!is_attachment_anchor("_"));
+        assert!(!is_attachment_anchor("*origin"));
+        assert!(!is_attachment_anchor("caret_1"));
+    }
+}
@@ -1,6 +1,7 @@
 //! Generates a [maxp](https://learn.microsoft.com/en-us/typography/opentype/spec/maxp) table.
 
 use fontdrasil::orchestration::Work;
+use fontir::orchestration::Flags;
 use write_fonts::tables::maxp::Maxp;
 
 use crate::{
@@ -18,9 +19,19 @@ impl Work<Context, Error> for MaxpWork {
     /// Generate [maxp](https://learn.microsoft.com/en-us/typography/opentype/spec/maxp)
     fn exec(&self, context: &Context) -> Result<(), Error> {
         let static_metadata = context.ir.get_final_static_metadata();
+        let num_glyphs = static_metadata.glyph_order.len().try_into().unwrap();
+
+        // CFF outlines want version 0.5, which is just the glyph count
+        if context.flags.contains(Flags::CFF_OUTLINES) {
+            context.set_maxp(Maxp {
+                num_glyphs,
+                ..Default::default()
+            });
+            return Ok(());
+        }
 
         let maxp = Maxp {
-            num_glyphs: static_metadata.glyph_order.len().try_into().unwrap(),
+            num_glyphs,
             // maxp computes it's version based on whether fields are set
             // if you fail to set any of them it gets angry with you so set all of them
             max_points: Some(0),
@@ -1,13 +1,23 @@
 //! Generates the [hmtx](https://learn.microsoft.com/en-us/typography/opentype/spec/hmtx) and
-//! [hhea](https://learn.microsoft.com/en-us/typography/opentype/spec/hhea) tables
+//! [hhea](https://learn.microsoft.com/en-us/typography/opentype/spec/hhea) tables, plus
+//! [vmtx](https://learn.microsoft.com/en-us/typography/opentype/spec/vmtx) and
+//! [vhea](https://learn.microsoft.com/en-us/typography/opentype/spec/vhea) if the font
+//! has vertical metrics
 
 use std::cmp::{max, min};
 
 use fontdrasil::orchestration::Work;
+use ordered_float::OrderedFloat;
 use read_fonts::types::FWord;
 use write_fonts::{
     dump_table,
-    tables::{glyf::Contour, hhea::Hhea, hmtx::Hmtx, vmtx::LongMetric},
+    tables::{
+        glyf::Contour,
+        hhea::Hhea,
+        hmtx::Hmtx,
+        vhea::Vhea,
+        vmtx::{LongMetric, Vmtx},
+    },
     OtRound,
 };
 
@@ -31,6 +41,15 @@ struct GlyphLimits {
     max_points: u16,
     max_contours: u16,
     max_component_elements: u16,
+    min_top_side_bearing: Option<i16>,
+    min_bottom_side_bearing: Option<i16>,
+    y_max_extent: Option<i16>,
+    advance_height_max: u16,
+}
+
+/// Clamp to the range of an i16
+fn saturating_i16(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
 }
 
 impl GlyphLimits {
@@ -44,11 +63,7 @@ impl GlyphLimits {
         let bbox = glyph.bbox();
         let left_side_bearing = bbox.x_min;
         // aw - (lsb + xMax - xMin) ... but if lsb == xMin then just advance - xMax?
-        let right_side_bearing: i16 = match advance as i32 - bbox.x_max as i32 {
-            value if value < i16::MIN as i32 => i16::MIN,
-            value if value > i16::MAX as i32 => i16::MAX,
-            value => value as i16,
-        };
+        let right_side_bearing = saturating_i16(advance as i32 - bbox.x_max as i32);
         self.min_left_side_bearing = self
             .min_left_side_bearing
             .map(|v| min(v, left_side_bearing))
@@ -64,7 +79,7 @@ impl GlyphLimits {
         self.advance_width_max = max(self.advance_width_max, advance);
 
         match glyph {
-            Glyph::Simple(simple) => {
+            Glyph::Simple(simple, _) => {
                 let points = simple.contours().iter().map(Contour::len).sum::<usize>() as u16;
                 self.max_points = max(self.max_points, points);
                 self.max_contours = max(self.max_contours, simple.contours().len() as u16);
@@ -75,12 +90,73 @@ impl GlyphLimits {
             }
         };
     }
+
+    /// Track the vertical limits, origin is the y coordinate of the vertical origin.
+    fn update_vertical(&mut self, advance: u16, origin: i16, glyph: &Glyph) {
+        if glyph.is_empty() {
+            return;
+        }
+
+        let bbox = glyph.bbox();
+        let top_side_bearing = saturating_i16(origin as i32 - bbox.y_max as i32);
+        let bottom_side_bearing =
+            saturating_i16(bbox.y_min as i32 - (origin as i32 - advance as i32));
+        let y_max_extent =
+            saturating_i16(top_side_bearing as i32 + bbox.y_max as i32 - bbox.y_min as i32);
+        self.min_top_side_bearing = self
+            .min_top_side_bearing
+            .map(|v| min(v, top_side_bearing))
+            .or(Some(top_side_bearing));
+        self.min_bottom_side_bearing = self
+            .min_bottom_side_bearing
+            .map(|v| min(v, bottom_side_bearing))
+            .or(Some(bottom_side_bearing));
+        self.y_max_extent = self
+            .y_max_extent
+            .map(|v| max(v, y_max_extent))
+            .or(Some(y_max_extent));
+        self.advance_height_max = max(self.advance_height_max, advance);
+    }
+}
+
+/// Drop the side bearings off the trailing run of metrics that share an advance.
+///
+/// The last retained metric has the advance that repeats, so the run can be written
+/// as bare side bearings.
+fn split_off_side_bearings(long_metrics: &mut Vec<LongMetric>) -> Vec<i16> {
+    // If there's a run at the end with matching advances we can save some bytes
+    let num_sb_only = if !long_metrics.is_empty() {
+        let last_advance = long_metrics.last().unwrap().advance;
+        let mut sb_run = 0;
+        for metric in long_metrics.iter().rev() {
+            if metric.advance != last_advance {
+                break;
+            }
+            sb_run += 1;
+        }
+
+        // Carve 1 less than the length of the run off so the last metric retained has the advance
+        // that repeats
+        sb_run - 1
+    } else {
+        0
+    };
+
+    long_metrics
+        .split_off(long_metrics.len() - num_sb_only)
+        .into_iter()
+        .map(|metric| metric.side_bearing)
+        .collect()
 }
 
 impl Work<Context, Error> for MetricAndLimitWork {
     /// Generate [hmtx](https://learn.microsoft.com/en-us/typography/opentype/spec/hmtx)
     /// and [hhea](https://learn.microsoft.com/en-us/typography/opentype/spec/hhea)
     ///
+    /// Generate [vmtx](https://learn.microsoft.com/en-us/typography/opentype/spec/vmtx)
+    /// and [vhea](https://learn.microsoft.com/en-us/typography/opentype/spec/vhea) if
+    /// the font has vertical metrics
+    ///
     /// Touchup [maxp](https://learn.microsoft.com/en-us/typography/opentype/spec/maxp)
     fn exec(&self, context: &Context) -> Result<(), Error> {
         let static_metadata = context.ir.get_final_static_metadata();
@@ -90,49 +166,39 @@ impl Work<Context, Error> for MetricAndLimitWork {
             .at(static_metadata.default_location());
 
         let mut glyph_limits = GlyphLimits::default();
+        let vertical = default_metrics.is_vertical();
 
-        let mut long_metrics: Vec<LongMetric> = static_metadata
-            .glyph_order
-            .iter()
-            .map(|gn| {
-                let advance: u16 = context
-                    .ir
-                    .get_glyph_ir(gn)
-                    .default_instance()
-                    .width
-                    .ot_round();
-                let glyph = context.get_glyph(gn);
-                glyph_limits.update(advance, &glyph);
-                LongMetric {
+        let mut long_metrics: Vec<LongMetric> = Vec::new();
+        let mut long_ver_metrics: Vec<LongMetric> = Vec::new();
+        for gn in static_metadata.glyph_order.iter() {
+            let ir_glyph = context.ir.get_glyph_ir(gn);
+            let instance = ir_glyph.default_instance();
+            let advance: u16 = instance.width.ot_round();
+            let glyph = context.get_glyph(gn);
+            glyph_limits.update(advance, &glyph);
+            long_metrics.push(LongMetric {
+                advance,
+                side_bearing: glyph.bbox().x_min,
+            });
+
+            if vertical {
+                // Like ufo2ft tsb is the distance from the vertical origin to the top of the glyph
+                let advance: u16 = instance.advance_height(&default_metrics).ot_round();
+                let origin: i16 = instance.vertical_origin_y(&default_metrics).ot_round();
+                glyph_limits.update_vertical(advance, origin, &glyph);
+                let side_bearing = if glyph.is_empty() {
+                    0
+                } else {
+                    saturating_i16(origin as i32 - glyph.bbox().y_max as i32)
+                };
+                long_ver_metrics.push(LongMetric {
                     advance,
-                    side_bearing: glyph.bbox().x_min,
-                }
-            })
-            .collect();
-
-        // If there's a run at the end with matching advances we can save some bytes
-        let num_lsb_only = if !long_metrics.is_empty() {
-            let last_advance = long_metrics.last().unwrap().advance;
-            let mut lsb_run = 0;
-            for metric in long_metrics.iter().rev() {
-                if metric.advance != last_advance {
-                    break;
-                }
-                lsb_run += 1;
+                    side_bearing,
+                });
             }
+        }
 
-            // Carve 1 less than the length of the run off so the last metric retained has the advance
-            // that repeats
-            lsb_run - 1
-        } else {
-            0
-        };
-
-        let lsbs = long_metrics
-            .split_off(long_metrics.len() - num_lsb_only)
-            .into_iter()
-            .map(|metric| metric.side_bearing)
-            .collect();
+        let lsbs = split_off_side_bearings(&mut long_metrics);
 
         // Before we cede ownership of Hmtx grab a few notes for Hhea
         let min_left_side_bearing = glyph_limits
@@ -167,8 +233,50 @@ impl Work<Context, Error> for MetricAndLimitWork {
         })?);
         context.set_hmtx(raw_hmtx);
 
-        // Tell maxp a little more about the world
+        // vhea and vmtx only if we have vertical metrics; empty tells font assembly to leave them out
+        if vertical {
+            let tsbs = split_off_side_bearings(&mut long_ver_metrics);
+            let vhea_metric =
+                |value: Option<OrderedFloat<f32>>| FWord::new(value.unwrap_or_default().ot_round());
+            let vhea = Vhea {
+                ascender: vhea_metric(default_metrics.vhea_ascender),
+                descender: vhea_metric(default_metrics.vhea_descender),
+                line_gap: vhea_metric(default_metrics.vhea_line_gap),
+                advance_height_max: glyph_limits.advance_height_max.into(),
+                min_top_side_bearing: glyph_limits.min_top_side_bearing.unwrap_or_default().into(),
+                min_bottom_side_bearing: glyph_limits
+                    .min_bottom_side_bearing
+                    .unwrap_or_default()
+                    .into(),
+                y_max_extent: glyph_limits.y_max_extent.unwrap_or_default().into(),
+                caret_slope_rise: 0,
+                caret_slope_run: 1,
+                caret_offset: 0,
+                number_of_long_ver_metrics: long_ver_metrics.len().try_into().map_err(|_| {
+                    Error::OutOfBounds {
+                        what: "number_of_long_ver_metrics".into(),
+                        value: format!("{}", long_ver_metrics.len()),
+                    }
+                })?,
+            };
+            context.set_vhea(vhea);
+
+            let vmtx = Vmtx::new(long_ver_metrics, tsbs);
+            let raw_vmtx = Bytes::new(dump_table(&vmtx).map_err(|e| Error::DumpTableError {
+                e,
+                context: "vmtx".into(),
+            })?);
+            context.set_vmtx(raw_vmtx);
+        } else {
+            context.set_vhea(Vhea::default());
+            context.set_vmtx(Bytes::new(Vec::new()));
+        }
+
+        // Tell maxp a little more about the world, unless it's version 0.5 and has no fields for it
         let mut maxp = (*context.get_maxp()).clone();
+        if maxp.max_points.is_none() {
+            return Ok(());
+        }
         maxp.max_points = Some(glyph_limits.max_points);
         maxp.max_contours = Some(glyph_limits.max_contours);
         maxp.max_component_elements = Some(glyph_limits.max_component_elements);
@@ -201,6 +309,7 @@ mod tests {
                     &BezPath::from_svg("M-437,611 L-334,715 L-334,611 Z").unwrap(),
                 )
                 .unwrap(),
+                false,
             ),
         );
         assert_eq!(
@@ -0,0 +1,224 @@
+//! Generates a [MVAR](https://learn.microsoft.com/en-us/typography/opentype/spec/mvar) table.
+
+use std::collections::HashMap;
+
+use fontdrasil::orchestration::Work;
+use fontir::{
+    coords::NormalizedLocation,
+    ir::{GlobalMetric, GlobalMetrics, StaticMetadata},
+};
+use log::trace;
+use read_fonts::{
+    tables::mvar::tags,
+    types::{MajorMinor, Tag},
+};
+use write_fonts::{
+    dump_table,
+    tables::variations::ItemVariationStore,
+    validate::{Validate, ValidationCtx},
+    FontWrite, OffsetMarker, OtRound, TableWriter,
+};
+
+use crate::{
+    error::Error,
+    orchestration::{BeWork, Bytes, Context},
+    var_store::{DeltaSetIndex, VarStoreBuilder},
+};
+
+struct MvarWork {}
+
+pub fn create_mvar_work() -> Box<BeWork> {
+    Box::new(MvarWork {})
+}
+
+/// The value tag for each metric that can vary, None if MVAR has no place for it.
+///
+/// hasc and friends are the OS/2 typo metrics; the hhea ascender and descender
+/// aren't in MVAR.
+fn value_tag(metric: GlobalMetric) -> Option<Tag> {
+    let tag = match metric {
+        GlobalMetric::Ascender | GlobalMetric::Descender => return None,
+        GlobalMetric::CapHeight => tags::CPHT,
+        GlobalMetric::XHeight => tags::XHGT,
+        GlobalMetric::Os2TypoAscender => tags::HASC,
+        GlobalMetric::Os2TypoDescender => tags::HDSC,
+        GlobalMetric::Os2TypoLineGap => tags::HLGP,
+        GlobalMetric::Os2WinAscent => tags::HCLA,
+        GlobalMetric::Os2WinDescent => tags::HCLD,
+        GlobalMetric::SubscriptXSize => tags::SBXS,
+        GlobalMetric::SubscriptYSize => tags::SBYS,
+        GlobalMetric::SubscriptXOffset => tags::SBXO,
+        GlobalMetric::SubscriptYOffset => tags::SBYO,
+        GlobalMetric::SuperscriptXSize => tags::SPXS,
+        GlobalMetric::SuperscriptYSize => tags::SPYS,
+        GlobalMetric::SuperscriptXOffset => tags::SPXO,
+        GlobalMetric::SuperscriptYOffset => tags::SPYO,
+        GlobalMetric::StrikeoutSize => tags::STRS,
+        GlobalMetric::StrikeoutPosition => tags::STRO,
+        GlobalMetric::VheaAscender => tags::VASC,
+        GlobalMetric::VheaDescender => tags::VDSC,
+        GlobalMetric::VheaLineGap => tags::VLGP,
+    };
+    Some(tag)
+}
+
+/// write-fonts doesn't do MVAR (yet) so we write it ourselves.
+struct Mvar {
+    /// Sorted by tag
+    value_records: Vec<(Tag, DeltaSetIndex)>,
+    item_variation_store: OffsetMarker<ItemVariationStore>,
+}
+
+impl FontWrite for Mvar {
+    fn write_into(&self, writer: &mut TableWriter) {
+        MajorMinor::VERSION_1_0.write_into(writer);
+        0u16.write_into(writer); // reserved
+        8u16.write_into(writer); // value record size
+        (self.value_records.len() as u16).write_into(writer);
+        self.item_variation_store.write_into(writer);
+        for (tag, (outer, inner)) in self.value_records.iter() {
+            tag.write_into(writer);
+            outer.write_into(writer);
+            inner.write_into(writer);
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Mvar"
+    }
+}
+
+impl Validate for Mvar {
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        ctx.in_table("Mvar", |ctx| {
+            ctx.in_field("item_variation_store", |ctx| {
+                self.item_variation_store.validate_impl(ctx);
+            });
+        })
+    }
+}
+
+/// Deltas for the metric, None if it doesn't vary.
+fn metric_deltas(
+    static_metadata: &StaticMetadata,
+    metric: GlobalMetric,
+    values: &HashMap<NormalizedLocation, Vec<f64>>,
+    var_store: &mut VarStoreBuilder,
+) -> Result<Option<DeltaSetIndex>, Error> {
+    let deltas: Vec<_> = static_metadata
+        .variation_model_for(values.keys())
+        .map_err(|e| Error::MetricVariationModelError(metric, e))?
+        .deltas::<f64, f64>(values)
+        .map_err(|e| Error::MetricDeltaError(metric, e))?
+        .into_iter()
+        .map(|(region, deltas)| (region, deltas[0].ot_round()))
+        .collect();
+    Ok(var_store.add_deltas(&deltas))
+}
+
+fn generate_mvar(
+    static_metadata: &StaticMetadata,
+    metrics: &GlobalMetrics,
+) -> Result<Option<Mvar>, Error> {
+    if static_metadata.variable_axes.is_empty() {
+        trace!("Skip MVAR; this is not a variable font");
+        return Ok(None);
+    }
+
+    let mut values: HashMap<_, HashMap<_, _>> = HashMap::new();
+    for (loc, metric, value) in metrics.iter() {
+        values
+            .entry(metric)
+            .or_default()
+            .insert(loc, vec![value as f64]);
+    }
+
+    // Records have to be sorted by tag, adding them in that order also keeps the store stable
+    let mut values: Vec<_> = values
+        .into_iter()
+        .filter_map(|(metric, values)| value_tag(metric).map(|tag| (tag, metric, values)))
+        .collect();
+    values.sort_by_key(|(tag, ..)| *tag);
+
+    let mut var_store = VarStoreBuilder::new(&static_metadata.variable_axes);
+    let mut value_records = Vec::new();
+    for (tag, metric, values) in values.iter() {
+        if let Some(index) = metric_deltas(static_metadata, *metric, values, &mut var_store)? {
+            value_records.push((*tag, index));
+        }
+    }
+    if value_records.is_empty() {
+        trace!("Skip MVAR; no metric varies");
+        return Ok(None);
+    }
+
+    Ok(Some(Mvar {
+        value_records,
+        item_variation_store: var_store.build().into(),
+    }))
+}
+
+impl Work<Context, Error> for MvarWork {
+    /// Generate [MVAR](https://learn.microsoft.com/en-us/typography/opentype/spec/mvar)
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let metrics = context.ir.get_global_metrics();
+        // Empty bytes tell font assembly to leave MVAR out
+        let bytes = match generate_mvar(&static_metadata, &metrics)? {
+            Some(mvar) => dump_table(&mvar).map_err(|e| Error::DumpTableError {
+                e,
+                context: "MVAR".to_string(),
+            })?,
+            None => Vec::new(),
+        };
+        context.set_mvar(Bytes::new(bytes));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fontir::ir::{GlobalMetric, GlobalMetrics, StaticMetadata};
+    use read_fonts::tables::mvar::tags;
+
+    use crate::test_util::{location, weight_axis};
+
+    use super::generate_mvar;
+
+    fn static_metadata() -> StaticMetadata {
+        StaticMetadata::new(
+            1000,
+            Default::default(),
+            vec![weight_axis()],
+            Default::default(),
+            [location(0.0), location(1.0)].into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_varying_metrics() {
+        let mut metrics = GlobalMetrics::new(location(0.0), 1000);
+        metrics.set(GlobalMetric::XHeight, location(1.0), 520.0);
+        metrics.set(GlobalMetric::CapHeight, location(1.0), 720.0);
+        metrics.set(GlobalMetric::Ascender, location(1.0), 800.0);
+
+        let mvar = generate_mvar(&static_metadata(), &metrics)
+            .unwrap()
+            .unwrap();
+
+        // Sorted by tag, cap height and x-height both move by +20 so they share a delta-set
+        assert_eq!(
+            vec![(tags::CPHT, (0, 0)), (tags::XHGT, (0, 0))],
+            mvar.value_records
+        );
+    }
+
+    #[test]
+    fn no_mvar_if_no_metric_varies() {
+        let metrics = GlobalMetrics::new(location(0.0), 1000);
+        assert!(generate_mvar(&static_metadata(), &metrics)
+            .unwrap()
+            .is_none());
+    }
+}
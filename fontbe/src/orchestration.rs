@@ -12,11 +12,10 @@ use fontir::{
     orchestration::{Context as FeContext, ContextItem, Flags, WorkId as FeWorkIdentifier},
     variations::VariationRegion,
 };
-use kurbo::Vec2;
 use log::trace;
 use parking_lot::RwLock;
 use read_fonts::{FontData, FontRead};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use write_fonts::{
     dump_table,
@@ -24,22 +23,31 @@ use write_fonts::{
         avar::Avar,
         cmap::Cmap,
         fvar::Fvar,
+        gdef::Gdef,
         glyf::{Bbox, SimpleGlyph},
-        gvar::GlyphDeltas,
         head::Head,
         hhea::Hhea,
         maxp::Maxp,
         name::Name,
         os2::Os2,
         post::Post,
+        stat::Stat,
         variations::Tuple,
+        vhea::Vhea,
     },
     validate::Validate,
     FontBuilder, FontWrite,
 };
 use write_fonts::{from_obj::FromTableRef, tables::glyf::CompositeGlyph};
 
-use crate::{error::Error, paths::Paths};
+use crate::{
+    error::Error,
+    gvar::{GlyphDeltas, PointDeltas},
+    iup,
+    kern::Kerning,
+    marks::Marks,
+    paths::Paths,
+};
 
 /// What exactly is being assembled from glyphs?
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -57,8 +65,11 @@ pub enum GlyphMerge {
 pub enum WorkId {
     Features,
     Avar,
+    Cff,
+    Cff2,
     Cmap,
     Fvar,
+    Gdef,
     Glyf,
     GlyfFragment(GlyphName),
     Gvar,
@@ -66,12 +77,20 @@ pub enum WorkId {
     Head,
     Hhea,
     Hmtx,
+    Hvar,
+    Kerning,
     Loca,
     LocaFormat,
+    Marks,
     Maxp,
+    Mvar,
     Name,
     Os2,
     Post,
+    Stat,
+    Vhea,
+    Vmtx,
+    Vvar,
     Font,
 }
 
@@ -114,13 +133,14 @@ impl From<WorkId> for AnyWorkId {
 /// <https://learn.microsoft.com/en-us/typography/opentype/spec/glyf>
 #[derive(Debug, Clone)]
 pub enum Glyph {
-    Simple(SimpleGlyph),
+    /// A simple glyph and whether its contours overlap, see OVERLAP_SIMPLE
+    Simple(SimpleGlyph, bool),
     Composite(CompositeGlyph),
 }
 
 impl From<SimpleGlyph> for Glyph {
     fn from(value: SimpleGlyph) -> Self {
-        Glyph::Simple(value)
+        Glyph::Simple(value, false)
     }
 }
 
@@ -133,34 +153,111 @@ impl From<CompositeGlyph> for Glyph {
 impl Glyph {
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
-            Glyph::Simple(table) => dump_table(table),
-            Glyph::Composite(table) => dump_table(table),
+            Glyph::Simple(table, false) => dump_table(table).unwrap(),
+            Glyph::Simple(table, true) => set_overlap_simple(dump_table(table).unwrap()),
+            Glyph::Composite(table) => dump_table(table).unwrap(),
         }
-        .unwrap()
     }
 
     pub fn bbox(&self) -> Bbox {
         match self {
-            Glyph::Simple(table) => table.bbox,
+            Glyph::Simple(table, _) => table.bbox,
             Glyph::Composite(table) => table.bbox,
         }
     }
 
     pub fn is_empty(&self) -> bool {
         match self {
-            Glyph::Simple(table) => table.contours().is_empty(),
+            Glyph::Simple(table, _) => table.contours().is_empty(),
             Glyph::Composite(table) => table.components().is_empty(),
         }
     }
 }
 
+const REPEAT_FLAG: u8 = 0x08;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+/// Set OVERLAP_SIMPLE on the first flag of a binary simple glyph.
+///
+/// write-fonts has no way to ask for it. If the first flag repeats it's split so the bit
+/// is set on the first point only, like fontTools.
+fn set_overlap_simple(glyph: Vec<u8>) -> Vec<u8> {
+    with_overlap_simple(&glyph).unwrap_or(glyph)
+}
+
+/// None if there are no points to flag or glyph is too short to hold the points it claims to.
+fn with_overlap_simple(glyph: &[u8]) -> Option<Vec<u8>> {
+    let read_u8 = |pos: usize| glyph.get(pos).copied();
+    let read_u16 = |pos: usize| {
+        glyph
+            .get(pos..pos + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    };
+    let num_contours = read_u16(0)? as i16;
+    if num_contours <= 0 {
+        return None;
+    }
+    let num_contours = num_contours as usize;
+    let num_points = read_u16(10 + 2 * (num_contours - 1))? + 1;
+    let instructions_len = read_u16(10 + 2 * num_contours)?;
+    let flags_start = 10 + 2 * num_contours + 2 + instructions_len;
+
+    // Find where the point data ends, anything after that is padding
+    let mut pos = flags_start;
+    let mut points = 0;
+    let mut coords_len = 0;
+    while points < num_points {
+        let flag = read_u8(pos)?;
+        pos += 1;
+        let mut repeat = 1;
+        if flag & REPEAT_FLAG != 0 {
+            repeat += read_u8(pos)? as usize;
+            pos += 1;
+        }
+        // x then y: short vectors take 1 byte, same-or-positive long vectors take 0
+        for (short, same) in [(0x02, 0x10), (0x04, 0x20)] {
+            coords_len += repeat
+                * match (flag & short != 0, flag & same != 0) {
+                    (true, _) => 1,
+                    (false, true) => 0,
+                    (false, false) => 2,
+                };
+        }
+        points += repeat;
+    }
+    let data_end = pos + coords_len;
+    if data_end > glyph.len() {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(glyph.len() + 2);
+    result.extend(&glyph[..flags_start]);
+    let first = glyph[flags_start];
+    let mut rest = flags_start + 1;
+    result.push((first & !REPEAT_FLAG) | OVERLAP_SIMPLE);
+    if first & REPEAT_FLAG != 0 {
+        match glyph[rest] {
+            0 => (),
+            1 => result.push(first & !REPEAT_FLAG),
+            repeat => result.extend([first, repeat - 1]),
+        }
+        rest += 1;
+    }
+    result.extend(&glyph[rest..data_end]);
+    if result.len() % 2 != 0 {
+        result.push(0);
+    }
+    Some(result)
+}
+
 /// Unusually we store something other than the binary gvar per glyph.
 ///
+/// Deltas are rounded, points whose delta can be interpolated are None.
 ///
 /// <https://learn.microsoft.com/en-us/typography/opentype/spec/gvar>
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GvarFragment {
-    pub deltas: Vec<(VariationRegion, Vec<Vec2>)>,
+    pub deltas: Vec<(VariationRegion, PointDeltas)>,
 }
 
 impl GvarFragment {
@@ -177,12 +274,9 @@ impl GvarFragment {
                     return None;
                 }
 
-                // TODO: nice rounding on deltas
-                let deltas: Vec<_> = deltas.iter().map(|v| (v.x as i16, v.y as i16)).collect();
-
                 let tuple_builder: TupleBuilder = region.into();
                 let (min, peak, max) = tuple_builder.build();
-                Some(GlyphDeltas::new(peak, deltas, Some((min, max))))
+                Some(GlyphDeltas::new(peak, deltas.clone(), Some((min, max))))
             })
             .collect()
     }
@@ -329,6 +423,9 @@ fn persist(file: &Path, content: &[u8]) {
 pub struct Context {
     pub flags: Flags,
 
+    /// How far an interpolated gvar delta may be from the actual one, if [Flags::OPTIMIZE_GVAR]
+    pub iup_tolerance: f64,
+
     pub paths: Arc<Paths>,
 
     // The final, fully populated, read-only FE context
@@ -345,17 +442,28 @@ pub struct Context {
 
     glyf_loca: ContextItem<GlyfLoca>,
     avar: ContextItem<Avar>,
+    cff: ContextItem<Bytes>,
+    cff2: ContextItem<Bytes>,
     cmap: ContextItem<Cmap>,
     fvar: ContextItem<Fvar>,
+    gdef: ContextItem<Gdef>,
     gvar: ContextItem<Bytes>,
     post: ContextItem<Post>,
+    stat: ContextItem<Stat>,
     loca_format: ContextItem<LocaFormat>,
+    kerning: ContextItem<Kerning>,
+    marks: ContextItem<Marks>,
     maxp: ContextItem<Maxp>,
+    mvar: ContextItem<Bytes>,
     name: ContextItem<Name>,
     os2: ContextItem<Os2>,
     head: ContextItem<Head>,
     hhea: ContextItem<Hhea>,
     hmtx: ContextItem<Bytes>,
+    hvar: ContextItem<Bytes>,
+    vhea: ContextItem<Vhea>,
+    vmtx: ContextItem<Bytes>,
+    vvar: ContextItem<Bytes>,
     font: ContextItem<Bytes>,
 }
 
@@ -363,6 +471,7 @@ impl Context {
     fn copy(&self, acl: AccessControlList<AnyWorkId>) -> Context {
         Context {
             flags: self.flags,
+            iup_tolerance: self.iup_tolerance,
             paths: self.paths.clone(),
             ir: self.ir.clone(),
             acl,
@@ -371,17 +480,28 @@ impl Context {
             gvar_fragments: self.gvar_fragments.clone(),
             glyf_loca: self.glyf_loca.clone(),
             avar: self.avar.clone(),
+            cff: self.cff.clone(),
+            cff2: self.cff2.clone(),
             cmap: self.cmap.clone(),
             fvar: self.fvar.clone(),
+            gdef: self.gdef.clone(),
             gvar: self.gvar.clone(),
             post: self.post.clone(),
+            stat: self.stat.clone(),
             loca_format: self.loca_format.clone(),
+            kerning: self.kerning.clone(),
+            marks: self.marks.clone(),
             maxp: self.maxp.clone(),
+            mvar: self.mvar.clone(),
             name: self.name.clone(),
             os2: self.os2.clone(),
             head: self.head.clone(),
             hhea: self.hhea.clone(),
             hmtx: self.hmtx.clone(),
+            hvar: self.hvar.clone(),
+            vhea: self.vhea.clone(),
+            vmtx: self.vmtx.clone(),
+            vvar: self.vvar.clone(),
             font: self.font.clone(),
         }
     }
@@ -389,6 +509,7 @@ impl Context {
     pub fn new_root(flags: Flags, paths: Paths, ir: &fontir::orchestration::Context) -> Context {
         Context {
             flags,
+            iup_tolerance: iup::DEFAULT_TOLERANCE,
             paths: Arc::from(paths),
             ir: Arc::from(ir.read_only()),
             acl: AccessControlList::read_only(),
@@ -397,21 +518,44 @@ impl Context {
             gvar_fragments: Arc::from(RwLock::new(HashMap::new())),
             glyf_loca: Arc::from(RwLock::new(None)),
             avar: Arc::from(RwLock::new(None)),
+            cff: Arc::from(RwLock::new(None)),
+            cff2: Arc::from(RwLock::new(None)),
             cmap: Arc::from(RwLock::new(None)),
             fvar: Arc::from(RwLock::new(None)),
+            gdef: Arc::from(RwLock::new(None)),
             gvar: Arc::from(RwLock::new(None)),
             post: Arc::from(RwLock::new(None)),
+            stat: Arc::from(RwLock::new(None)),
             loca_format: Arc::from(RwLock::new(None)),
+            kerning: Arc::from(RwLock::new(None)),
+            marks: Arc::from(RwLock::new(None)),
             maxp: Arc::from(RwLock::new(None)),
+            mvar: Arc::from(RwLock::new(None)),
             name: Arc::from(RwLock::new(None)),
             os2: Arc::from(RwLock::new(None)),
             head: Arc::from(RwLock::new(None)),
             hhea: Arc::from(RwLock::new(None)),
             hmtx: Arc::from(RwLock::new(None)),
+            hvar: Arc::from(RwLock::new(None)),
+            vhea: Arc::from(RwLock::new(None)),
+            vmtx: Arc::from(RwLock::new(None)),
+            vvar: Arc::from(RwLock::new(None)),
             font: Arc::from(RwLock::new(None)),
         }
     }
 
+    pub fn with_iup_tolerance(mut self, iup_tolerance: f64) -> Context {
+        self.iup_tolerance = iup_tolerance;
+        self
+    }
+
+    /// A root context for ir that keeps everything computed so far.
+    pub fn with_ir(&self, ir: &fontir::orchestration::Context) -> Context {
+        let mut context = self.copy_read_only();
+        context.ir = Arc::from(ir.read_only());
+        context
+    }
+
     pub fn copy_for_work(
         &self,
         read_access: Access<AnyWorkId>,
@@ -487,9 +631,14 @@ impl Context {
         // Vec[u8] => read type => write type == all the right type
         let glyph = read_entire_file(&self.paths.target_file(&id));
         let glyph = read_fonts::tables::glyf::SimpleGlyph::read(FontData::new(&glyph)).unwrap();
+        let overlap = glyph
+            .glyph_data()
+            .first()
+            .map(|flag| flag & OVERLAP_SIMPLE != 0)
+            .unwrap_or_default();
         let glyph = SimpleGlyph::from_table_ref(&glyph);
 
-        self.set_cached_glyph(glyph_name.clone(), glyph.into());
+        self.set_cached_glyph(glyph_name.clone(), Glyph::Simple(glyph, overlap));
         let rl = self.glyphs.read();
         rl.get(glyph_name).expect(MISSING_DATA).clone()
     }
@@ -581,17 +730,28 @@ impl Context {
     context_accessors! { get_avar, set_avar, avar, Avar, WorkId::Avar, from_file, to_bytes }
     context_accessors! { get_cmap, set_cmap, cmap, Cmap, WorkId::Cmap, from_file, to_bytes }
     context_accessors! { get_fvar, set_fvar, fvar, Fvar, WorkId::Fvar, from_file, to_bytes }
+    context_accessors! { get_gdef, set_gdef, gdef, Gdef, WorkId::Gdef, from_file, to_bytes }
     context_accessors! { get_loca_format, set_loca_format, loca_format, LocaFormat, WorkId::LocaFormat, loca_format_from_file, loca_format_to_bytes }
+    context_accessors! { get_kerning, set_kerning, kerning, Kerning, WorkId::Kerning, bincode_from_file, bincode_to_bytes }
+    context_accessors! { get_marks, set_marks, marks, Marks, WorkId::Marks, bincode_from_file, bincode_to_bytes }
     context_accessors! { get_maxp, set_maxp, maxp, Maxp, WorkId::Maxp, from_file, to_bytes }
     context_accessors! { get_name, set_name, name, Name, WorkId::Name, from_file, to_bytes }
     context_accessors! { get_os2, set_os2, os2, Os2, WorkId::Os2, from_file, to_bytes }
     context_accessors! { get_post, set_post, post, Post, WorkId::Post, from_file, to_bytes }
+    context_accessors! { get_stat, set_stat, stat, Stat, WorkId::Stat, from_file, to_bytes }
     context_accessors! { get_head, set_head, head, Head, WorkId::Head, from_file, to_bytes }
     context_accessors! { get_hhea, set_hhea, hhea, Hhea, WorkId::Hhea, from_file, to_bytes }
+    context_accessors! { get_vhea, set_vhea, vhea, Vhea, WorkId::Vhea, from_file, to_bytes }
 
     // Accessors where value is raw bytes
+    context_accessors! { get_cff, set_cff, cff, Bytes, WorkId::Cff, raw_from_file, raw_to_bytes }
+    context_accessors! { get_cff2, set_cff2, cff2, Bytes, WorkId::Cff2, raw_from_file, raw_to_bytes }
     context_accessors! { get_gvar, set_gvar, gvar, Bytes, WorkId::Gvar, raw_from_file, raw_to_bytes }
+    context_accessors! { get_mvar, set_mvar, mvar, Bytes, WorkId::Mvar, raw_from_file, raw_to_bytes }
     context_accessors! { get_hmtx, set_hmtx, hmtx, Bytes, WorkId::Hmtx, raw_from_file, raw_to_bytes }
+    context_accessors! { get_hvar, set_hvar, hvar, Bytes, WorkId::Hvar, raw_from_file, raw_to_bytes }
+    context_accessors! { get_vmtx, set_vmtx, vmtx, Bytes, WorkId::Vmtx, raw_from_file, raw_to_bytes }
+    context_accessors! { get_vvar, set_vvar, vvar, Bytes, WorkId::Vvar, raw_from_file, raw_to_bytes }
     context_accessors! { get_font, set_font, font, Bytes, WorkId::Font, raw_from_file, raw_to_bytes }
 }
 
@@ -610,6 +770,21 @@ where
     T::read(FontData::new(&buf)).unwrap()
 }
 
+fn bincode_from_file<T>(file: &Path) -> T
+where
+    T: DeserializeOwned,
+{
+    let buf = read_entire_file(file);
+    bincode::deserialize(&buf).unwrap()
+}
+
+fn bincode_to_bytes<T>(value: &T) -> Vec<u8>
+where
+    T: Serialize,
+{
+    bincode::serialize(value).unwrap()
+}
+
 pub struct Bytes {
     buf: Vec<u8>,
 }
@@ -649,11 +824,14 @@ fn read_entire_file(file: &Path) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
+    use kurbo::BezPath;
+    use read_fonts::{tables::glyf, FontData, FontRead};
     use tempfile::tempdir;
+    use write_fonts::tables::glyf::SimpleGlyph;
 
     use crate::{orchestration::LocaFormat, paths::Paths};
 
-    use super::GlyfLoca;
+    use super::{set_overlap_simple, GlyfLoca, Glyph, OVERLAP_SIMPLE};
 
     #[test]
     fn no_glyphs_is_short() {
@@ -690,4 +868,98 @@ mod tests {
         let gl = GlyfLoca::read(LocaFormat::Short, &paths);
         assert_eq!((glyf, loca), (gl.glyf, gl.loca));
     }
+
+    #[test]
+    fn overlap_simple_is_set_on_the_first_point_only() {
+        // The first two points have the same flag so write-fonts compresses them into a repeat
+        let path = BezPath::from_svg("M5,0 L10,0 L10,10 L0,10 Z").unwrap();
+        let simple = SimpleGlyph::from_kurbo(&path).unwrap();
+        let plain = Glyph::Simple(simple.clone(), false).to_bytes();
+        let overlap = Glyph::Simple(simple, true).to_bytes();
+
+        let plain = glyf::SimpleGlyph::read(FontData::new(&plain)).unwrap();
+        let overlap_glyph = glyf::SimpleGlyph::read(FontData::new(&overlap)).unwrap();
+        assert_eq!(
+            plain.points().collect::<Vec<_>>(),
+            overlap_glyph.points().collect::<Vec<_>>()
+        );
+        let flags = overlap_glyph.glyph_data();
+        assert_ne!(0, flags[0] & OVERLAP_SIMPLE);
+        assert_eq!(0, flags[1] & OVERLAP_SIMPLE);
+        assert_eq!(0, overlap.len() % 2);
+    }
+
+    /// Binary simple glyph, made by write-fonts, with instructions spliced in after the contour ends
+    fn with_instructions(glyph: &[u8], instructions: &[u8]) -> Vec<u8> {
+        let num_contours = i16::from_be_bytes([glyph[0], glyph[1]]) as usize;
+        let instructions_len_pos = 10 + 2 * num_contours;
+        assert_eq!(
+            [0, 0],
+            glyph[instructions_len_pos..instructions_len_pos + 2]
+        );
+        let mut result = glyph[..instructions_len_pos].to_vec();
+        result.extend((instructions.len() as u16).to_be_bytes());
+        result.extend(instructions);
+        result.extend(&glyph[instructions_len_pos + 2..]);
+        result
+    }
+
+    #[test]
+    fn overlap_simple_keeps_instructions() {
+        let path = BezPath::from_svg("M5,0 L10,0 L10,10 L0,10 Z").unwrap();
+        let plain = Glyph::Simple(SimpleGlyph::from_kurbo(&path).unwrap(), false).to_bytes();
+        let instructions = [0xb0, 0x01, 0x2f];
+        let plain = with_instructions(&plain, &instructions);
+
+        let overlap = set_overlap_simple(plain.clone());
+
+        let plain = glyf::SimpleGlyph::read(FontData::new(&plain)).unwrap();
+        let overlap_glyph = glyf::SimpleGlyph::read(FontData::new(&overlap)).unwrap();
+        assert_eq!(instructions, overlap_glyph.instructions());
+        assert_eq!(
+            plain.points().collect::<Vec<_>>(),
+            overlap_glyph.points().collect::<Vec<_>>()
+        );
+        let flags = overlap_glyph.glyph_data();
+        assert_ne!(0, flags[0] & OVERLAP_SIMPLE);
+        assert_eq!(0, flags[1] & OVERLAP_SIMPLE);
+        assert_eq!(0, overlap.len() % 2);
+    }
+
+    #[test]
+    fn overlap_simple_multiple_contours() {
+        let path =
+            BezPath::from_svg("M0,0 L10,0 L10,10 L0,10 Z M5,5 L15,5 L15,15 L5,15 Z").unwrap();
+        let simple = SimpleGlyph::from_kurbo(&path).unwrap();
+        let plain = Glyph::Simple(simple.clone(), false).to_bytes();
+        let overlap = Glyph::Simple(simple, true).to_bytes();
+
+        let plain = glyf::SimpleGlyph::read(FontData::new(&plain)).unwrap();
+        let overlap_glyph = glyf::SimpleGlyph::read(FontData::new(&overlap)).unwrap();
+        assert_eq!(2, overlap_glyph.number_of_contours());
+        assert_eq!(
+            plain.end_pts_of_contours(),
+            overlap_glyph.end_pts_of_contours()
+        );
+        assert_eq!(
+            plain.points().collect::<Vec<_>>(),
+            overlap_glyph.points().collect::<Vec<_>>()
+        );
+        assert_ne!(0, overlap_glyph.glyph_data()[0] & OVERLAP_SIMPLE);
+        assert_eq!(0, overlap.len() % 2);
+    }
+
+    #[test]
+    fn overlap_simple_leaves_truncated_glyph_alone() {
+        let path = BezPath::from_svg("M5,0 L10,0 L10,10 L0,10 Z").unwrap();
+        let plain = Glyph::Simple(SimpleGlyph::from_kurbo(&path).unwrap(), false).to_bytes();
+        // Lose the last coordinates, and any padding
+        let truncated = plain[..plain.len() - 3].to_vec();
+        assert_eq!(truncated, set_overlap_simple(truncated.clone()));
+
+        // Running out of bytes anywhere mustn't panic
+        for len in 0..plain.len() {
+            set_overlap_simple(plain[..len].to_vec());
+        }
+    }
 }
@@ -1,9 +1,22 @@
 //! Generates a [OS/2](https://learn.microsoft.com/en-us/typography/opentype/spec/os2) table.
 
+use std::collections::BTreeSet;
+
 use fontdrasil::orchestration::Work;
-use fontir::ir::GlobalMetricsInstance;
-use read_fonts::types::Tag;
-use write_fonts::{tables::os2::Os2, OtRound};
+use fontir::ir::{GlobalMetricsInstance, Os2Metadata};
+use read_fonts::{
+    tables::{
+        gpos::{self, Gpos, PositionLookup},
+        gsub::{self, Gsub, LigatureSubstFormat1, SubstitutionLookup},
+        layout::{ChainedSequenceContext, SequenceContext},
+    },
+    types::Tag,
+    FontRef, ReadError, TableProvider,
+};
+use write_fonts::{
+    tables::os2::{Os2, SelectionFlags},
+    OtRound,
+};
 
 use crate::{
     error::Error,
@@ -16,19 +29,542 @@ pub fn create_os2_work() -> Box<BeWork> {
     Box::new(Os2Work {})
 }
 
-fn build_os2(vendor_id: Tag, metrics: &GlobalMetricsInstance) -> Os2 {
+/// Unicode blocks by the ulUnicodeRange bit they set.
+///
+/// Bit 57, non-plane 0, is set for any codepoint outside the BMP.
+/// See <https://learn.microsoft.com/en-us/typography/opentype/spec/os2#ur>.
+const UNICODE_RANGES: &[(u32, u32, u32)] = &[
+    (0x0000, 0x007F, 0),
+    (0x0080, 0x00FF, 1),
+    (0x0100, 0x017F, 2),
+    (0x0180, 0x024F, 3),
+    (0x0250, 0x02AF, 4),
+    (0x1D00, 0x1D7F, 4),
+    (0x1D80, 0x1DBF, 4),
+    (0x02B0, 0x02FF, 5),
+    (0xA700, 0xA71F, 5),
+    (0x0300, 0x036F, 6),
+    (0x1DC0, 0x1DFF, 6),
+    (0x0370, 0x03FF, 7),
+    (0x2C80, 0x2CFF, 8),
+    (0x0400, 0x04FF, 9),
+    (0x0500, 0x052F, 9),
+    (0x2DE0, 0x2DFF, 9),
+    (0xA640, 0xA69F, 9),
+    (0x0530, 0x058F, 10),
+    (0x0590, 0x05FF, 11),
+    (0xA500, 0xA63F, 12),
+    (0x0600, 0x06FF, 13),
+    (0x0750, 0x077F, 13),
+    (0x07C0, 0x07FF, 14),
+    (0x0900, 0x097F, 15),
+    (0x0980, 0x09FF, 16),
+    (0x0A00, 0x0A7F, 17),
+    (0x0A80, 0x0AFF, 18),
+    (0x0B00, 0x0B7F, 19),
+    (0x0B80, 0x0BFF, 20),
+    (0x0C00, 0x0C7F, 21),
+    (0x0C80, 0x0CFF, 22),
+    (0x0D00, 0x0D7F, 23),
+    (0x0E00, 0x0E7F, 24),
+    (0x0E80, 0x0EFF, 25),
+    (0x10A0, 0x10FF, 26),
+    (0x2D00, 0x2D2F, 26),
+    (0x1B00, 0x1B7F, 27),
+    (0x1100, 0x11FF, 28),
+    (0x1E00, 0x1EFF, 29),
+    (0x2C60, 0x2C7F, 29),
+    (0xA720, 0xA7FF, 29),
+    (0x1F00, 0x1FFF, 30),
+    (0x2000, 0x206F, 31),
+    (0x2E00, 0x2E7F, 31),
+    (0x2070, 0x209F, 32),
+    (0x20A0, 0x20CF, 33),
+    (0x20D0, 0x20FF, 34),
+    (0x2100, 0x214F, 35),
+    (0x2150, 0x218F, 36),
+    (0x2190, 0x21FF, 37),
+    (0x27F0, 0x27FF, 37),
+    (0x2900, 0x297F, 37),
+    (0x2B00, 0x2BFF, 37),
+    (0x2200, 0x22FF, 38),
+    (0x2A00, 0x2AFF, 38),
+    (0x27C0, 0x27EF, 38),
+    (0x2980, 0x29FF, 38),
+    (0x2300, 0x23FF, 39),
+    (0x2400, 0x243F, 40),
+    (0x2440, 0x245F, 41),
+    (0x2460, 0x24FF, 42),
+    (0x2500, 0x257F, 43),
+    (0x2580, 0x259F, 44),
+    (0x25A0, 0x25FF, 45),
+    (0x2600, 0x26FF, 46),
+    (0x2700, 0x27BF, 47),
+    (0x3000, 0x303F, 48),
+    (0x3040, 0x309F, 49),
+    (0x30A0, 0x30FF, 50),
+    (0x31F0, 0x31FF, 50),
+    (0x3100, 0x312F, 51),
+    (0x31A0, 0x31BF, 51),
+    (0x3130, 0x318F, 52),
+    (0xA840, 0xA87F, 53),
+    (0x3200, 0x32FF, 54),
+    (0x3300, 0x33FF, 55),
+    (0xAC00, 0xD7AF, 56),
+    (0xD800, 0xDFFF, 57),
+    (0x10900, 0x1091F, 58),
+    (0x4E00, 0x9FFF, 59),
+    (0x2E80, 0x2EFF, 59),
+    (0x2F00, 0x2FDF, 59),
+    (0x2FF0, 0x2FFF, 59),
+    (0x3400, 0x4DBF, 59),
+    (0x20000, 0x2A6DF, 59),
+    (0x3190, 0x319F, 59),
+    (0xE000, 0xF8FF, 60),
+    (0x31C0, 0x31EF, 61),
+    (0xF900, 0xFAFF, 61),
+    (0x2F800, 0x2FA1F, 61),
+    (0xFB00, 0xFB4F, 62),
+    (0xFB50, 0xFDFF, 63),
+    (0xFE20, 0xFE2F, 64),
+    (0xFE10, 0xFE1F, 65),
+    (0xFE30, 0xFE4F, 65),
+    (0xFE50, 0xFE6F, 66),
+    (0xFE70, 0xFEFF, 67),
+    (0xFF00, 0xFFEF, 68),
+    (0xFFF0, 0xFFFF, 69),
+    (0x0F00, 0x0FFF, 70),
+    (0x0700, 0x074F, 71),
+    (0x0780, 0x07BF, 72),
+    (0x0D80, 0x0DFF, 73),
+    (0x1000, 0x109F, 74),
+    (0x1200, 0x137F, 75),
+    (0x1380, 0x139F, 75),
+    (0x2D80, 0x2DDF, 75),
+    (0x13A0, 0x13FF, 76),
+    (0x1400, 0x167F, 77),
+    (0x1680, 0x169F, 78),
+    (0x16A0, 0x16FF, 79),
+    (0x1780, 0x17FF, 80),
+    (0x19E0, 0x19FF, 80),
+    (0x1800, 0x18AF, 81),
+    (0x2800, 0x28FF, 82),
+    (0xA000, 0xA48F, 83),
+    (0xA490, 0xA4CF, 83),
+    (0x1700, 0x171F, 84),
+    (0x1720, 0x173F, 84),
+    (0x1740, 0x175F, 84),
+    (0x1760, 0x177F, 84),
+    (0x10300, 0x1032F, 85),
+    (0x10330, 0x1034F, 86),
+    (0x10400, 0x1044F, 87),
+    (0x1D000, 0x1D0FF, 88),
+    (0x1D100, 0x1D1FF, 88),
+    (0x1D200, 0x1D24F, 88),
+    (0x1D400, 0x1D7FF, 89),
+    (0xF0000, 0xFFFFD, 90),
+    (0x100000, 0x10FFFD, 90),
+    (0xFE00, 0xFE0F, 91),
+    (0xE0100, 0xE01EF, 91),
+    (0xE0000, 0xE007F, 92),
+    (0x1900, 0x194F, 93),
+    (0x1950, 0x197F, 94),
+    (0x1980, 0x19DF, 95),
+    (0x1A00, 0x1A1F, 96),
+    (0x2C00, 0x2C5F, 97),
+    (0x2D30, 0x2D7F, 98),
+    (0x4DC0, 0x4DFF, 99),
+    (0xA800, 0xA82F, 100),
+    (0x10000, 0x1007F, 101),
+    (0x10080, 0x100FF, 101),
+    (0x10100, 0x1013F, 101),
+    (0x10140, 0x1018F, 102),
+    (0x10380, 0x1039F, 103),
+    (0x103A0, 0x103DF, 104),
+    (0x10450, 0x1047F, 105),
+    (0x10480, 0x104AF, 106),
+    (0x10800, 0x1083F, 107),
+    (0x10A00, 0x10A5F, 108),
+    (0x1D300, 0x1D35F, 109),
+    (0x12000, 0x123FF, 110),
+    (0x12400, 0x1247F, 110),
+    (0x1D360, 0x1D37F, 111),
+    (0x1B80, 0x1BBF, 112),
+    (0x1C00, 0x1C4F, 113),
+    (0x1C50, 0x1C7F, 114),
+    (0xA880, 0xA8DF, 115),
+    (0xA900, 0xA92F, 116),
+    (0xA930, 0xA95F, 117),
+    (0xAA00, 0xAA5F, 118),
+    (0x10190, 0x101CF, 119),
+    (0x101D0, 0x101FF, 120),
+    (0x102A0, 0x102DF, 121),
+    (0x10280, 0x1029F, 121),
+    (0x10920, 0x1093F, 121),
+    (0x1F030, 0x1F09F, 122),
+    (0x1F000, 0x1F02F, 122),
+];
+
+/// The ulUnicodeRange bits for a set of codepoints, as fontTools calcUnicodeRanges.
+fn unicode_range_bits(codepoints: &BTreeSet<u32>) -> BTreeSet<u32> {
+    let mut bits: BTreeSet<_> = codepoints
+        .iter()
+        .filter_map(|cp| {
+            UNICODE_RANGES
+                .iter()
+                .find(|(start, end, _)| (*start..=*end).contains(cp))
+                .map(|(_, _, bit)| *bit)
+        })
+        .collect();
+    if codepoints.iter().any(|cp| *cp > 0xFFFF) {
+        bits.insert(57);
+    }
+    bits
+}
+
+/// The ulCodePageRange bits for a set of codepoints, as fontTools calcCodePageRanges.
+///
+/// A code page is claimed if a characteristic character of it is present.
+fn codepage_range_bits(codepoints: &BTreeSet<u32>) -> BTreeSet<u32> {
+    let has = |c: char| codepoints.contains(&(c as u32));
+    let has_ascii = (0x20..0x7E).all(|cp| codepoints.contains(&cp));
+    let has_lineart = has('┤');
+
+    let mut bits = BTreeSet::new();
+    if has('Þ') && has_ascii {
+        bits.insert(0); // Latin 1
+    }
+    if has('Ľ') && has_ascii {
+        bits.insert(1); // Latin 2: Eastern Europe
+        if has_lineart {
+            bits.insert(58); // Latin 2
+        }
+    }
+    if has('Б') {
+        bits.insert(2); // Cyrillic
+        if has('Ѕ') && has_lineart {
+            bits.insert(57); // IBM Cyrillic
+        }
+        if has('╜') && has_lineart {
+            bits.insert(49); // MS-DOS Russian
+        }
+    }
+    if has('Ά') {
+        bits.insert(3); // Greek
+        if has_lineart && has('½') {
+            bits.insert(48); // IBM Greek
+        }
+        if has_lineart && has('√') {
+            bits.insert(60); // Greek, former 437 G
+        }
+    }
+    if has('İ') && has_ascii {
+        bits.insert(4); // Turkish
+        if has_lineart {
+            bits.insert(56); // IBM turkish
+        }
+    }
+    if has('א') {
+        bits.insert(5); // Hebrew
+        if has_lineart && has('√') {
+            bits.insert(53); // Hebrew
+        }
+    }
+    if has('ر') {
+        bits.insert(6); // Arabic
+        if has('√') {
+            bits.insert(51); // Arabic
+        }
+        if has_lineart {
+            bits.insert(61); // Arabic; ASMO 708
+        }
+    }
+    if has('ŗ') && has_ascii {
+        bits.insert(7); // Windows Baltic
+        if has_lineart {
+            bits.insert(59); // MS-DOS Baltic
+        }
+    }
+    if has('₫') && has_ascii {
+        bits.insert(8); // Vietnamese
+    }
+    if has('ๅ') {
+        bits.insert(16); // Thai
+    }
+    if has('エ') {
+        bits.insert(17); // JIS/Japan
+    }
+    if has('ㄅ') {
+        bits.insert(18); // Chinese: Simplified chars
+    }
+    if has('ㄱ') {
+        bits.insert(19); // Korean wansung
+    }
+    if has('央') {
+        bits.insert(20); // Chinese: Traditional chars
+    }
+    if has('곴') {
+        bits.insert(21); // Korean Johab
+    }
+    if has('♥') && has_ascii {
+        bits.insert(30); // OEM Character Set
+    }
+    if has('þ') && has_ascii && has_lineart {
+        bits.insert(54); // MS-DOS Icelandic
+    }
+    if has('╚') && has_ascii {
+        bits.insert(62); // WE/Latin 1
+        bits.insert(63); // US
+    }
+    if has_ascii && has_lineart && has('√') {
+        if has('Å') {
+            bits.insert(50); // MS-DOS Nordic
+        }
+        if has('é') {
+            bits.insert(52); // MS-DOS Canadian French
+        }
+        if has('õ') {
+            bits.insert(55); // MS-DOS Portuguese
+        }
+    }
+    if has_ascii && has('‰') && has('∑') {
+        bits.insert(29); // Macintosh Character Set (US Roman)
+    }
+
+    // Claim Latin 1 if nothing else so the font works in MS Word,
+    // https://github.com/googlefonts/fontmake/issues/468
+    if bits.is_empty() {
+        bits.insert(0);
+    }
+    bits
+}
+
+/// Spread bit numbers over 32-bit fields, ulUnicodeRange1-4 or ulCodePageRange1-2.
+fn bit_fields<const N: usize>(bits: &BTreeSet<u32>) -> [u32; N] {
+    let mut fields = [0; N];
+    for bit in bits {
+        if let Some(field) = fields.get_mut(*bit as usize / 32) {
+            *field |= 1 << (bit % 32);
+        }
+    }
+    fields
+}
+
+/// The arithmetic average of the nonzero advance widths
+fn x_avg_char_width(advances: impl Iterator<Item = u16>) -> i16 {
+    let (count, sum) = advances
+        .filter(|advance| *advance > 0)
+        .fold((0u32, 0u64), |(count, sum), advance| {
+            (count + 1, sum + advance as u64)
+        });
+    if count == 0 {
+        return 0;
+    }
+    (sum as f64 / count as f64).ot_round()
+}
+
+fn max_of<T>(
+    items: impl Iterator<Item = Result<T, ReadError>>,
+    context: impl Fn(T) -> Result<u16, ReadError>,
+) -> Result<u16, ReadError> {
+    items
+        .map(|item| context(item?))
+        .try_fold(0, |max, value| value.map(|value| max.max(value)))
+}
+
+fn ligature_context(ligatures: LigatureSubstFormat1) -> Result<u16, ReadError> {
+    max_of(ligatures.ligature_sets(), |ligature_set| {
+        max_of(ligature_set.ligatures(), |ligature| {
+            Ok(ligature.component_count())
+        })
+    })
+}
+
+fn sequence_context(context: SequenceContext) -> Result<u16, ReadError> {
+    match context {
+        SequenceContext::Format1(table) => max_of(table.seq_rule_sets().flatten(), |rule_set| {
+            max_of(rule_set.seq_rules(), |rule| Ok(rule.glyph_count()))
+        }),
+        SequenceContext::Format2(table) => {
+            max_of(table.class_seq_rule_sets().flatten(), |rule_set| {
+                max_of(rule_set.class_seq_rules(), |rule| Ok(rule.glyph_count()))
+            })
+        }
+        SequenceContext::Format3(table) => Ok(table.glyph_count()),
+    }
+}
+
+fn chained_sequence_context(context: ChainedSequenceContext) -> Result<u16, ReadError> {
+    match context {
+        ChainedSequenceContext::Format1(table) => {
+            max_of(table.chained_seq_rule_sets().flatten(), |rule_set| {
+                max_of(rule_set.chained_seq_rules(), |rule| {
+                    Ok(rule.input_glyph_count() + rule.lookahead_glyph_count())
+                })
+            })
+        }
+        ChainedSequenceContext::Format2(table) => {
+            max_of(table.chained_class_seq_rule_sets().flatten(), |rule_set| {
+                max_of(rule_set.chained_class_seq_rules(), |rule| {
+                    Ok(rule.input_glyph_count() + rule.lookahead_glyph_count())
+                })
+            })
+        }
+        ChainedSequenceContext::Format3(table) => {
+            Ok(table.input_glyph_count() + table.lookahead_glyph_count())
+        }
+    }
+}
+
+/// How many glyphs any GSUB lookup looks at, as fontTools maxContextCalc.
+fn gsub_max_context(gsub: &Gsub) -> Result<u16, ReadError> {
+    max_of(gsub.lookup_list()?.lookups(), |lookup| match lookup {
+        SubstitutionLookup::Single(..)
+        | SubstitutionLookup::Multiple(..)
+        | SubstitutionLookup::Alternate(..) => Ok(1),
+        SubstitutionLookup::Ligature(lookup) => max_of(lookup.subtables(), ligature_context),
+        SubstitutionLookup::Contextual(lookup) => max_of(lookup.subtables(), sequence_context),
+        SubstitutionLookup::ChainContextual(lookup) => {
+            max_of(lookup.subtables(), chained_sequence_context)
+        }
+        SubstitutionLookup::Reverse(lookup) => max_of(lookup.subtables(), |reverse| {
+            Ok(1 + reverse.lookahead_glyph_count())
+        }),
+        SubstitutionLookup::Extension(lookup) => {
+            max_of(lookup.subtables(), |extension| match extension {
+                gsub::ExtensionSubtable::Single(..)
+                | gsub::ExtensionSubtable::Multiple(..)
+                | gsub::ExtensionSubtable::Alternate(..) => Ok(1),
+                gsub::ExtensionSubtable::Ligature(ext) => ligature_context(ext.extension()?),
+                gsub::ExtensionSubtable::Contextual(ext) => sequence_context(ext.extension()?),
+                gsub::ExtensionSubtable::ChainContextual(ext) => {
+                    chained_sequence_context(ext.extension()?)
+                }
+                gsub::ExtensionSubtable::Reverse(ext) => {
+                    Ok(1 + ext.extension()?.lookahead_glyph_count())
+                }
+            })
+        }
+    })
+}
+
+/// How many glyphs any GPOS lookup looks at, as fontTools maxContextCalc.
+fn gpos_max_context(gpos: &Gpos) -> Result<u16, ReadError> {
+    max_of(gpos.lookup_list()?.lookups(), |lookup| match lookup {
+        PositionLookup::Pair(..) => Ok(2),
+        PositionLookup::Single(..)
+        | PositionLookup::Cursive(..)
+        | PositionLookup::MarkToBase(..)
+        | PositionLookup::MarkToLig(..)
+        | PositionLookup::MarkToMark(..) => Ok(1),
+        PositionLookup::Contextual(lookup) => max_of(lookup.subtables(), sequence_context),
+        PositionLookup::ChainContextual(lookup) => {
+            max_of(lookup.subtables(), chained_sequence_context)
+        }
+        PositionLookup::Extension(lookup) => {
+            max_of(lookup.subtables(), |extension| match extension {
+                gpos::ExtensionSubtable::Pair(..) => Ok(2),
+                gpos::ExtensionSubtable::Single(..)
+                | gpos::ExtensionSubtable::Cursive(..)
+                | gpos::ExtensionSubtable::MarkToBase(..)
+                | gpos::ExtensionSubtable::MarkToLig(..)
+                | gpos::ExtensionSubtable::MarkToMark(..) => Ok(1),
+                gpos::ExtensionSubtable::Contextual(ext) => sequence_context(ext.extension()?),
+                gpos::ExtensionSubtable::ChainContextual(ext) => {
+                    chained_sequence_context(ext.extension()?)
+                }
+            })
+        }
+    })
+}
+
+/// usMaxContext, the longest context any GSUB or GPOS lookup in the font uses.
+fn max_context(font: &FontRef) -> Result<u16, ReadError> {
+    let gsub = match font.gsub() {
+        Ok(gsub) => gsub_max_context(&gsub)?,
+        Err(ReadError::TableIsMissing(..)) => 0,
+        Err(e) => return Err(e),
+    };
+    let gpos = match font.gpos() {
+        Ok(gpos) => gpos_max_context(&gpos)?,
+        Err(ReadError::TableIsMissing(..)) => 0,
+        Err(e) => return Err(e),
+    };
+    Ok(gsub.max(gpos))
+}
+
+fn build_os2(
+    vendor_id: Tag,
+    os2: &Os2Metadata,
+    metrics: &GlobalMetricsInstance,
+    codepoints: &BTreeSet<u32>,
+    x_avg_char_width: i16,
+    max_context: u16,
+) -> Os2 {
+    // https://github.com/googlefonts/ufo2ft/blob/fca66fe3ea1ea88ffb36f8264b21ce042d3afd05/Lib/ufo2ft/outlineCompiler.py
+    // only lets the style map decide on REGULAR
+    let mut fs_selection = SelectionFlags::from_bits_truncate(os2.selection_flags);
+    if !fs_selection.intersects(SelectionFlags::ITALIC | SelectionFlags::BOLD) {
+        fs_selection |= SelectionFlags::REGULAR;
+    }
+
+    let unicode_ranges: [u32; 4] = bit_fields(
+        &os2.unicode_range_bits
+            .clone()
+            .unwrap_or_else(|| unicode_range_bits(codepoints)),
+    );
+    let codepage_ranges: [u32; 2] = bit_fields(
+        &os2.codepage_range_bits
+            .clone()
+            .unwrap_or_else(|| codepage_range_bits(codepoints)),
+    );
+
     Os2 {
+        x_avg_char_width,
+        us_weight_class: os2.weight_class.unwrap_or(400),
+        us_width_class: os2.width_class.unwrap_or(5),
+        // ufo2ft defaults openTypeOS2Type to [3], editable embedding
+        fs_type: os2.fs_type.unwrap_or(1 << 3),
+        y_subscript_x_size: metrics.subscript_x_size.ot_round(),
+        y_subscript_y_size: metrics.subscript_y_size.ot_round(),
+        y_subscript_x_offset: metrics.subscript_x_offset.ot_round(),
+        y_subscript_y_offset: metrics.subscript_y_offset.ot_round(),
+        y_superscript_x_size: metrics.superscript_x_size.ot_round(),
+        y_superscript_y_size: metrics.superscript_y_size.ot_round(),
+        y_superscript_x_offset: metrics.superscript_x_offset.ot_round(),
+        y_superscript_y_offset: metrics.superscript_y_offset.ot_round(),
+        y_strikeout_size: metrics.strikeout_size.ot_round(),
+        y_strikeout_position: metrics.strikeout_position.ot_round(),
+        s_family_class: os2.family_class.unwrap_or_default(),
+        panose_10: os2.panose.unwrap_or_default(),
+        ul_unicode_range_1: unicode_ranges[0],
+        ul_unicode_range_2: unicode_ranges[1],
+        ul_unicode_range_3: unicode_ranges[2],
+        ul_unicode_range_4: unicode_ranges[3],
         ach_vend_id: vendor_id,
+        fs_selection,
+        us_first_char_index: codepoints
+            .first()
+            .map(|cp| (*cp).min(0xFFFF) as u16)
+            .unwrap_or_default(),
+        us_last_char_index: codepoints
+            .last()
+            .map(|cp| (*cp).min(0xFFFF) as u16)
+            .unwrap_or_default(),
+        s_typo_ascender: metrics.os2_typo_ascender.ot_round(),
+        s_typo_descender: metrics.os2_typo_descender.ot_round(),
+        s_typo_line_gap: metrics.os2_typo_line_gap.ot_round(),
+        us_win_ascent: metrics.os2_win_ascent.ot_round(),
+        us_win_descent: metrics.os2_win_descent.ot_round(),
+
+        ul_code_page_range_1: Some(codepage_ranges[0]),
+        ul_code_page_range_2: Some(codepage_ranges[1]),
 
         s_cap_height: Some(metrics.cap_height.ot_round()),
         sx_height: Some(metrics.x_height.ot_round()),
-
-        // Avoid "field must be present for version 2"
-        ul_code_page_range_1: Some(0),
-        ul_code_page_range_2: Some(0),
         us_default_char: Some(0),
-        us_break_char: Some(0),
-        us_max_context: Some(0),
+        us_break_char: Some(32),
+        us_max_context: Some(max_context),
 
         ..Default::default()
     }
@@ -37,25 +573,61 @@ fn build_os2(vendor_id: Tag, metrics: &GlobalMetricsInstance) -> Os2 {
 impl Work<Context, Error> for Os2Work {
     /// Generate [OS/2](https://learn.microsoft.com/en-us/typography/opentype/spec/os2)
     fn exec(&self, context: &Context) -> Result<(), Error> {
-        let static_metadata = context.ir.get_init_static_metadata();
+        let static_metadata = context.ir.get_final_static_metadata();
         let metrics = context
             .ir
             .get_global_metrics()
             .at(static_metadata.default_location());
-        context.set_os2(build_os2(static_metadata.vendor_id, &metrics));
+
+        let glyphs: Vec<_> = static_metadata
+            .glyph_order
+            .iter()
+            .map(|glyph_name| context.ir.get_glyph_ir(glyph_name))
+            .collect();
+        let codepoints = glyphs
+            .iter()
+            .flat_map(|glyph| glyph.codepoints.iter().copied())
+            .collect();
+        let x_avg_char_width = x_avg_char_width(
+            glyphs
+                .iter()
+                .map(|glyph| glyph.default_instance().width.ot_round()),
+        );
+
+        let features = context.get_features();
+        let max_context = if features.is_empty() {
+            0
+        } else {
+            max_context(&FontRef::new(&features)?)?
+        };
+
+        // Finalizing static metadata doesn't rerun when only custom parameters change, init
+        // has the current ones
+        let init_static_metadata = context.ir.get_init_static_metadata();
+        context.set_os2(build_os2(
+            init_static_metadata.vendor_id,
+            &init_static_metadata.os2,
+            &metrics,
+            &codepoints,
+            x_avg_char_width,
+            max_context,
+        ));
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use fontir::{
         coords::NormalizedLocation,
-        ir::{GlobalMetric, GlobalMetrics},
+        ir::{GlobalMetric, GlobalMetrics, Os2Metadata},
     };
     use read_fonts::types::Tag;
+    use write_fonts::tables::os2::SelectionFlags;
 
-    use super::build_os2;
+    use super::{bit_fields, build_os2, codepage_range_bits, unicode_range_bits, x_avg_char_width};
 
     #[test]
     fn build_basic_os2() {
@@ -64,11 +636,138 @@ mod tests {
 
         global_metrics.set(GlobalMetric::CapHeight, default_location.clone(), 37.5);
         global_metrics.set(GlobalMetric::XHeight, default_location.clone(), 112.2);
+        global_metrics.populate_defaults(&default_location, 1000);
 
-        let os2 = build_os2(Tag::new(b"DUCK"), &global_metrics.at(&default_location));
+        let os2 = build_os2(
+            Tag::new(b"DUCK"),
+            &Os2Metadata::default(),
+            &global_metrics.at(&default_location),
+            &BTreeSet::new(),
+            0,
+            0,
+        );
 
         assert_eq!(Tag::new(b"DUCK"), os2.ach_vend_id);
         assert_eq!(Some(38), os2.s_cap_height);
         assert_eq!(Some(112), os2.sx_height);
     }
+
+    #[test]
+    fn os2_defaults_match_ufo2ft() {
+        let default_location = NormalizedLocation::new();
+        let mut global_metrics = GlobalMetrics::new(default_location.clone(), 1000);
+        global_metrics.populate_defaults(&default_location, 1000);
+
+        let os2 = build_os2(
+            Tag::new(b"NONE"),
+            &Os2Metadata::default(),
+            &global_metrics.at(&default_location),
+            &[0x20, 0x41, 0x1F600].into(),
+            0,
+            0,
+        );
+
+        assert_eq!(
+            (400, 5, 8, SelectionFlags::REGULAR),
+            (
+                os2.us_weight_class,
+                os2.us_width_class,
+                os2.fs_type,
+                os2.fs_selection
+            )
+        );
+        assert_eq!(
+            (800, -200, 200, 1000, 200),
+            (
+                os2.s_typo_ascender,
+                os2.s_typo_descender,
+                os2.s_typo_line_gap,
+                os2.us_win_ascent,
+                os2.us_win_descent
+            )
+        );
+        assert_eq!(
+            (0x20, 0xFFFF, Some(32)),
+            (
+                os2.us_first_char_index,
+                os2.us_last_char_index,
+                os2.us_break_char
+            )
+        );
+        // Basic Latin, plus non-plane 0 for the emoji
+        assert_eq!(
+            (1, 1 << (57 - 32)),
+            (os2.ul_unicode_range_1, os2.ul_unicode_range_2)
+        );
+    }
+
+    #[test]
+    fn explicit_os2_metadata_wins() {
+        let default_location = NormalizedLocation::new();
+        let mut global_metrics = GlobalMetrics::new(default_location.clone(), 1000);
+        global_metrics.populate_defaults(&default_location, 1000);
+
+        let os2 = build_os2(
+            Tag::new(b"NONE"),
+            &Os2Metadata {
+                weight_class: Some(700),
+                fs_type: Some(0),
+                selection_flags: (SelectionFlags::BOLD | SelectionFlags::USE_TYPO_METRICS).bits(),
+                unicode_range_bits: Some([1, 33].into()),
+                codepage_range_bits: Some([29].into()),
+                ..Default::default()
+            },
+            &global_metrics.at(&default_location),
+            &[0x41].into(),
+            0,
+            0,
+        );
+
+        assert_eq!(
+            (
+                700,
+                0,
+                SelectionFlags::BOLD | SelectionFlags::USE_TYPO_METRICS
+            ),
+            (os2.us_weight_class, os2.fs_type, os2.fs_selection)
+        );
+        assert_eq!(
+            (2, 2, Some(1 << 29)),
+            (
+                os2.ul_unicode_range_1,
+                os2.ul_unicode_range_2,
+                os2.ul_code_page_range_1
+            )
+        );
+    }
+
+    #[test]
+    fn unicode_ranges_from_codepoints() {
+        assert_eq!(
+            BTreeSet::from([0, 1, 9, 59]),
+            unicode_range_bits(&[0x41, 0xE9, 0x0411, 0x592E].into())
+        );
+    }
+
+    #[test]
+    fn latin_1_codepage() {
+        let mut codepoints: BTreeSet<_> = (0x20..0x7F).collect();
+        assert_eq!(BTreeSet::from([0]), codepage_range_bits(&codepoints));
+
+        codepoints.insert('Þ' as u32);
+        codepoints.insert('Б' as u32);
+        assert_eq!(BTreeSet::from([0, 2]), codepage_range_bits(&codepoints));
+    }
+
+    #[test]
+    fn high_bits_land_in_later_fields() {
+        assert_eq!([1, 0, 1 << 26, 0], bit_fields(&[0, 90].into()));
+        assert_eq!([0, 0x80000000], bit_fields(&[63, 99].into()));
+    }
+
+    #[test]
+    fn average_ignores_zero_width() {
+        assert_eq!(300, x_avg_char_width([0, 200, 400, 0].into_iter()));
+        assert_eq!(0, x_avg_char_width([0].into_iter()));
+    }
 }
@@ -11,20 +11,37 @@ pub struct Paths {
     build_dir: PathBuf,
     glyph_dir: PathBuf,
     debug_dir: PathBuf,
+    font_file: PathBuf,
 }
 
 impl Paths {
     pub fn new(build_dir: &Path) -> Paths {
         let glyph_dir = build_dir.join("glyphs");
         let debug_dir = build_dir.join("debug");
+        let font_file = build_dir.join("font.ttf");
         let build_dir = build_dir.to_path_buf();
         Paths {
             build_dir,
             glyph_dir,
             debug_dir,
+            font_file,
         }
     }
 
+    /// Name the final font font.otf, as is customary for CFF outlines.
+    pub fn with_cff_outlines(mut self) -> Paths {
+        self.font_file = self.build_dir.join("font.otf");
+        self
+    }
+
+    pub fn woff_file(&self) -> PathBuf {
+        self.font_file.with_extension("woff")
+    }
+
+    pub fn woff2_file(&self) -> PathBuf {
+        self.font_file.with_extension("woff2")
+    }
+
     pub fn build_dir(&self) -> &Path {
         &self.build_dir
     }
@@ -51,20 +68,31 @@ impl Paths {
             WorkId::GlyfFragment(name) => self.glyph_glyf_file(name.as_str()),
             WorkId::GvarFragment(name) => self.glyph_gvar_file(name.as_str()),
             WorkId::Avar => self.build_dir.join("avar.table"),
+            WorkId::Cff => self.build_dir.join("cff.table"),
+            WorkId::Cff2 => self.build_dir.join("cff2.table"),
             WorkId::Glyf => self.build_dir.join("glyf.table"),
             WorkId::Gvar => self.build_dir.join("gvar.table"),
             WorkId::Loca => self.build_dir.join("loca.table"),
             WorkId::LocaFormat => self.build_dir.join("loca.format"),
             WorkId::Cmap => self.build_dir.join("cmap.table"),
             WorkId::Fvar => self.build_dir.join("fvar.table"),
+            WorkId::Gdef => self.build_dir.join("gdef.table"),
             WorkId::Head => self.build_dir.join("head.table"),
             WorkId::Hhea => self.build_dir.join("hhea.table"),
             WorkId::Hmtx => self.build_dir.join("hmtx.table"),
+            WorkId::Hvar => self.build_dir.join("hvar.table"),
+            WorkId::Kerning => self.build_dir.join("kerning.bin"),
+            WorkId::Marks => self.build_dir.join("marks.bin"),
             WorkId::Maxp => self.build_dir.join("maxp.table"),
+            WorkId::Mvar => self.build_dir.join("mvar.table"),
             WorkId::Name => self.build_dir.join("name.table"),
             WorkId::Os2 => self.build_dir.join("os2.table"),
             WorkId::Post => self.build_dir.join("post.table"),
-            WorkId::Font => self.build_dir.join("font.ttf"),
+            WorkId::Stat => self.build_dir.join("stat.table"),
+            WorkId::Vhea => self.build_dir.join("vhea.table"),
+            WorkId::Vmtx => self.build_dir.join("vmtx.table"),
+            WorkId::Vvar => self.build_dir.join("vvar.table"),
+            WorkId::Font => self.font_file.clone(),
         }
     }
 }
@@ -0,0 +1,201 @@
+//! Generates a [STAT](https://learn.microsoft.com/en-us/typography/opentype/spec/stat) table.
+
+use fontdrasil::orchestration::Work;
+use fontir::{coords::UserCoord, ir::StaticMetadata};
+use log::trace;
+use read_fonts::types::{Fixed, NameId};
+use write_fonts::tables::stat::{
+    AxisRecord, AxisValue, AxisValueRecord, AxisValueTableFlags, Stat,
+};
+
+use crate::{
+    error::Error,
+    orchestration::{BeWork, Context},
+};
+
+struct StatWork {}
+
+pub fn create_stat_work() -> Box<BeWork> {
+    Box::new(StatWork {})
+}
+
+fn fixed(value: UserCoord) -> Fixed {
+    Fixed::from_f64(value.into_inner().into_inner() as f64)
+}
+
+fn flags(elidable: bool, older_sibling: bool) -> AxisValueTableFlags {
+    let mut flags = AxisValueTableFlags::empty();
+    if elidable {
+        flags |= AxisValueTableFlags::ELIDABLE_AXIS_VALUE_NAME;
+    }
+    if older_sibling {
+        flags |= AxisValueTableFlags::OLDER_SIBLING_FONT_ATTRIBUTE;
+    }
+    flags
+}
+
+fn generate_stat(static_metadata: &StaticMetadata) -> Option<Stat> {
+    // Guard clause: don't produce STAT for a static font
+    if static_metadata.variable_axes.is_empty() {
+        trace!("Skip STAT; this is not a variable font");
+        return None;
+    }
+
+    // Static metadata claims names for axes and labels
+    let name_id = |name: &str| static_metadata.font_specific_name_id(name).unwrap();
+    let stat_labels = static_metadata.stat_labels();
+
+    let design_axes = static_metadata
+        .axes
+        .iter()
+        .enumerate()
+        .map(|(idx, axis)| AxisRecord::new(axis.tag, name_id(&axis.name), idx as u16))
+        .collect();
+
+    // Format 1, 2 or 3 depending on whether the label covers a range or links to another style
+    let mut axis_values = Vec::new();
+    for (axis_index, axis) in static_metadata.axes.iter().enumerate() {
+        let axis_index = axis_index as u16;
+        for label in stat_labels
+            .axis_labels
+            .get(&axis.name)
+            .into_iter()
+            .flatten()
+        {
+            let flags = flags(label.elidable, label.older_sibling);
+            let name_id = name_id(&label.name);
+            let value = fixed(label.value);
+            axis_values.push(match (label.min, label.max, label.linked_value) {
+                (None, None, Some(linked_value)) => {
+                    AxisValue::format_3(axis_index, flags, name_id, value, fixed(linked_value))
+                }
+                (None, None, None) => AxisValue::format_1(axis_index, flags, name_id, value),
+                (min, max, _) => AxisValue::format_2(
+                    axis_index,
+                    flags,
+                    name_id,
+                    value,
+                    fixed(min.unwrap_or(axis.min)),
+                    fixed(max.unwrap_or(axis.max)),
+                ),
+            });
+        }
+    }
+
+    // Format 4 for labels that span axes
+    for label in stat_labels.location_labels.iter() {
+        let values = static_metadata
+            .axes
+            .iter()
+            .enumerate()
+            .filter_map(|(axis_index, axis)| {
+                label
+                    .location
+                    .get(&axis.name)
+                    .map(|value| AxisValueRecord::new(axis_index as u16, fixed(value)))
+            })
+            .collect();
+        axis_values.push(AxisValue::format_4(
+            flags(label.elidable, label.older_sibling),
+            name_id(&label.name),
+            values,
+        ));
+    }
+
+    let elided_fallback_name_id = stat_labels
+        .elided_fallback_name
+        .as_ref()
+        .map(|name| name_id(name))
+        .unwrap_or(NameId::SUBFAMILY_NAME);
+
+    Some(Stat::new(design_axes, axis_values, elided_fallback_name_id))
+}
+
+impl Work<Context, Error> for StatWork {
+    /// Generate [STAT](https://learn.microsoft.com/en-us/typography/opentype/spec/stat)
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        if let Some(stat) = generate_stat(&context.ir.get_init_static_metadata()) {
+            context.set_stat(stat);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fontir::{
+        coords::UserCoord,
+        ir::{AxisLabel, StatLabels, StaticMetadata},
+    };
+    use read_fonts::types::NameId;
+    use write_fonts::tables::stat::AxisValue;
+
+    use crate::test_util::weight_axis;
+
+    use super::generate_stat;
+
+    fn label(name: &str, value: f32) -> AxisLabel {
+        AxisLabel {
+            name: name.to_string(),
+            value: UserCoord::new(value),
+            min: None,
+            max: None,
+            linked_value: None,
+            elidable: false,
+            older_sibling: false,
+        }
+    }
+
+    #[test]
+    fn axis_value_formats() {
+        let mut static_metadata = StaticMetadata::new(
+            1000,
+            Default::default(),
+            vec![weight_axis()],
+            Default::default(),
+            Default::default(),
+        )
+        .unwrap();
+        let mut medium = label("Medium", 500.0);
+        medium.min = Some(UserCoord::new(450.0));
+        let mut regular = label("Regular", 400.0);
+        regular.linked_value = Some(UserCoord::new(700.0));
+        static_metadata.set_stat_labels(StatLabels {
+            axis_labels: [(
+                "wght".to_string(),
+                vec![regular, medium, label("Bold", 700.0)],
+            )]
+            .into(),
+            ..Default::default()
+        });
+
+        let stat = generate_stat(&static_metadata).unwrap();
+        assert_eq!(
+            NameId::SUBFAMILY_NAME,
+            stat.elided_fallback_name_id.unwrap()
+        );
+        assert_eq!(
+            vec![3, 2, 1],
+            stat.offset_to_axis_values
+                .iter()
+                .map(|value| match &**value {
+                    AxisValue::Format1(..) => 1,
+                    AxisValue::Format2(..) => 2,
+                    AxisValue::Format3(..) => 3,
+                    AxisValue::Format4(..) => 4,
+                })
+                .collect::<Vec<_>>()
+        );
+        // The range is open above, it extends to the axis max
+        let AxisValue::Format2(medium) = &*stat.offset_to_axis_values[1] else {
+            panic!("Medium should be a range");
+        };
+        assert_eq!(
+            (450.0, 700.0),
+            (
+                medium.range_min_value.to_f64(),
+                medium.range_max_value.to_f64()
+            )
+        );
+    }
+}
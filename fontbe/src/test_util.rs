@@ -0,0 +1,48 @@
+//! Helpers shared by the tests of several tables
+
+use std::collections::HashMap;
+
+use fontir::{
+    coords::{CoordConverter, NormalizedCoord, NormalizedLocation, UserCoord},
+    ir::{Axis, Glyph, GlyphInstance},
+};
+use read_fonts::types::Tag;
+
+/// A weight axis from 400 to 700, defaulting to 400, without mappings
+pub(crate) fn weight_axis() -> Axis {
+    let min = UserCoord::new(400.0);
+    let default = UserCoord::new(400.0);
+    let max = UserCoord::new(700.0);
+    Axis {
+        name: "wght".to_string(),
+        tag: Tag::new(b"wght"),
+        min,
+        default,
+        max,
+        hidden: false,
+        converter: CoordConverter::unmapped(min, default, max),
+    }
+}
+
+/// The location of wght on [weight_axis], in normalized coordinates
+pub(crate) fn location(wght: f32) -> NormalizedLocation {
+    let mut loc = NormalizedLocation::new();
+    loc.set_pos("wght", NormalizedCoord::new(wght));
+    loc
+}
+
+/// A glyph with an instance at each normalized wght
+pub(crate) fn glyph(
+    name: &str,
+    instances: impl IntoIterator<Item = (f32, GlyphInstance)>,
+) -> Glyph {
+    Glyph::new(
+        name.into(),
+        Default::default(),
+        instances
+            .into_iter()
+            .map(|(wght, instance)| (location(wght), instance))
+            .collect::<HashMap<_, _>>(),
+    )
+    .unwrap()
+}
@@ -0,0 +1,419 @@
+//! Helps build an [ItemVariationStore](https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#item-variation-store)
+//!
+//! Tables such as GDEF, HVAR and MVAR reference variable values by (outer, inner)
+//! index into a store of delta-sets. The builder hands out those indices as values
+//! are added, sharing regions and identical delta-sets where possible.
+
+use std::collections::HashMap;
+
+use font_types::F2Dot14;
+use fontir::{ir::Axis, variations::VariationRegion};
+use indexmap::IndexSet;
+use write_fonts::tables::{
+    layout::{DeltaFormat, Device},
+    variations::{
+        DeltaSetIndexMap, EntryFormat, ItemVariationData, ItemVariationStore,
+        RegionAxisCoordinates, VariationRegion as WriteVariationRegion, VariationRegionList,
+    },
+};
+
+/// The (outer, inner) index of a delta-set in an [ItemVariationStore].
+pub type DeltaSetIndex = (u16, u16);
+
+/// Marks a value that doesn't vary where a [DeltaSetIndex] is required.
+pub const NO_VARIATION_INDEX: DeltaSetIndex = (0xFFFF, 0xFFFF);
+
+/// Region as (start, peak, end) per axis, in fvar axis order.
+type RegionKey = Vec<(F2Dot14, F2Dot14, F2Dot14)>;
+
+#[derive(Debug, Default)]
+struct ItemVariationDataBuilder {
+    region_indexes: Vec<u16>,
+    rows: IndexSet<Vec<i16>>,
+}
+
+impl ItemVariationDataBuilder {
+    fn build(&self) -> ItemVariationData {
+        // Columns that don't fit in a byte must come first, see wordDeltaCount
+        let mut columns: Vec<_> = (0..self.region_indexes.len()).collect();
+        let is_word = |col: usize| self.rows.iter().any(|row| i8::try_from(row[col]).is_err());
+        columns.sort_by_key(|col| !is_word(*col));
+        let word_delta_count = columns.iter().filter(|col| is_word(**col)).count();
+
+        let mut delta_sets = Vec::new();
+        for row in self.rows.iter() {
+            for (i, col) in columns.iter().enumerate() {
+                if i < word_delta_count {
+                    delta_sets.extend(row[*col].to_be_bytes());
+                } else {
+                    delta_sets.extend((row[*col] as i8).to_be_bytes());
+                }
+            }
+        }
+
+        ItemVariationData::new(
+            self.rows.len() as u16,
+            word_delta_count as u16,
+            columns
+                .iter()
+                .map(|col| self.region_indexes[*col])
+                .collect(),
+            delta_sets,
+        )
+    }
+}
+
+/// Accumulates delta-sets for an [ItemVariationStore].
+///
+/// Delta-sets that use the same regions share an [ItemVariationData] and identical
+/// delta-sets are stored once.
+#[derive(Debug)]
+pub struct VarStoreBuilder {
+    axis_names: Vec<String>,
+    regions: IndexSet<RegionKey>,
+    // item variation data of a store we are adding to, kept as-is
+    existing: Vec<Option<ItemVariationData>>,
+    datas: Vec<ItemVariationDataBuilder>,
+    // region indexes => index into datas that is accepting new rows
+    data_for_regions: HashMap<Vec<u16>, usize>,
+}
+
+impl VarStoreBuilder {
+    /// Create a builder for the variable axes of the font, in fvar order.
+    pub fn new(axes: &[Axis]) -> VarStoreBuilder {
+        VarStoreBuilder {
+            axis_names: axes.iter().map(|a| a.name.clone()).collect(),
+            regions: IndexSet::new(),
+            existing: Vec::new(),
+            datas: Vec::new(),
+            data_for_regions: HashMap::new(),
+        }
+    }
+
+    /// Create a builder that adds to an existing store.
+    ///
+    /// Indices into the existing store remain valid, new delta-sets go into
+    /// [ItemVariationData] after what is already there.
+    pub fn from_existing(axes: &[Axis], store: &ItemVariationStore) -> VarStoreBuilder {
+        let mut builder = VarStoreBuilder::new(axes);
+        for region in store.variation_region_list.variation_regions.iter() {
+            builder.regions.insert(
+                region
+                    .region_axes
+                    .iter()
+                    .map(|c| (c.start_coord, c.peak_coord, c.end_coord))
+                    .collect(),
+            );
+        }
+        builder.existing = store
+            .item_variation_datas
+            .iter()
+            .map(|data| data.as_ref().cloned())
+            .collect();
+        builder
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.existing.is_empty() && self.datas.is_empty()
+    }
+
+    fn region_key(&self, region: &VariationRegion) -> RegionKey {
+        let tents: HashMap<_, _> = region.iter().collect();
+        self.axis_names
+            .iter()
+            .map(|axis_name| match tents.get(axis_name) {
+                Some(tent) => (
+                    F2Dot14::from_f32(tent.min.to_f32()),
+                    F2Dot14::from_f32(tent.peak.to_f32()),
+                    F2Dot14::from_f32(tent.max.to_f32()),
+                ),
+                None => Default::default(),
+            })
+            .collect()
+    }
+
+    /// Add a delta-set, returning where to find it in the final store.
+    ///
+    /// Deltas for the default region and zero deltas are ignored. If nothing is
+    /// left there is nothing to vary and None is returned.
+    pub fn add_deltas(&mut self, deltas: &[(VariationRegion, i16)]) -> Option<DeltaSetIndex> {
+        let mut deltas: Vec<_> = deltas
+            .iter()
+            .filter(|(region, delta)| !region.is_default() && *delta != 0)
+            .map(|(region, delta)| {
+                let key = self.region_key(region);
+                let (region_idx, _) = self.regions.insert_full(key);
+                (region_idx as u16, *delta)
+            })
+            .collect();
+        if deltas.is_empty() {
+            return None;
+        }
+        deltas.sort();
+
+        let region_indexes: Vec<_> = deltas.iter().map(|(idx, _)| *idx).collect();
+        let row: Vec<_> = deltas.iter().map(|(_, delta)| *delta).collect();
+        Some(self.add_row(region_indexes, row))
+    }
+
+    /// A delta-set whose deltas are all zero, None if there are no regions yet.
+    ///
+    /// For tables such as HVAR that need an index even for values that don't vary.
+    /// Item variation data without regions is legal but some readers choke on it
+    /// so this zeroes out the first region instead.
+    pub fn add_no_deltas(&mut self) -> Option<DeltaSetIndex> {
+        if self.regions.is_empty() {
+            return None;
+        }
+        Some(self.add_row(vec![0], vec![0]))
+    }
+
+    /// Item variation data for the regions, in the order given, with no delta-sets.
+    ///
+    /// CFF2 charstrings carry their own deltas, all they need from the store is
+    /// which regions those deltas are for. Returns the outer index, which CFF2 calls vsindex.
+    pub fn add_regions(&mut self, regions: &[VariationRegion]) -> u16 {
+        let region_indexes: Vec<_> = regions
+            .iter()
+            .map(|region| {
+                let key = self.region_key(region);
+                self.regions.insert_full(key).0 as u16
+            })
+            .collect();
+        let outer = match self.data_for_regions.get(&region_indexes) {
+            Some(outer) => *outer,
+            None => {
+                self.datas.push(ItemVariationDataBuilder {
+                    region_indexes: region_indexes.clone(),
+                    ..Default::default()
+                });
+                let outer = self.datas.len() - 1;
+                self.data_for_regions.insert(region_indexes, outer);
+                outer
+            }
+        };
+        (self.existing.len() + outer) as u16
+    }
+
+    fn add_row(&mut self, region_indexes: Vec<u16>, row: Vec<i16>) -> DeltaSetIndex {
+        let outer = match self.data_for_regions.get(&region_indexes) {
+            Some(outer) if self.datas[*outer].rows.len() < u16::MAX as usize => *outer,
+            _ => {
+                self.datas.push(ItemVariationDataBuilder {
+                    region_indexes: region_indexes.clone(),
+                    ..Default::default()
+                });
+                let outer = self.datas.len() - 1;
+                self.data_for_regions.insert(region_indexes, outer);
+                outer
+            }
+        };
+        let (inner, _) = self.datas[outer].rows.insert_full(row);
+        ((self.existing.len() + outer) as u16, inner as u16)
+    }
+
+    pub fn build(&self) -> ItemVariationStore {
+        let regions = self
+            .regions
+            .iter()
+            .map(|key| {
+                WriteVariationRegion::new(
+                    key.iter()
+                        .map(|(start, peak, end)| RegionAxisCoordinates::new(*start, *peak, *end))
+                        .collect(),
+                )
+            })
+            .collect();
+        ItemVariationStore::new(
+            1,
+            VariationRegionList::new(regions),
+            self.existing
+                .iter()
+                .cloned()
+                .chain(self.datas.iter().map(|data| Some(data.build())))
+                .collect(),
+        )
+    }
+}
+
+/// A [VariationIndex](https://learn.microsoft.com/en-us/typography/opentype/spec/chapter2#variation-index-table)
+/// table, for use wherever a Device table is referenced.
+///
+/// The offset types for anchors and value records are to Device so we
+/// write a Device that has the same binary representation as a VariationIndex.
+pub fn variation_index(index: DeltaSetIndex) -> Device {
+    Device {
+        start_size: index.0,
+        end_size: index.1,
+        delta_format: DeltaFormat::VariationIndex,
+        delta_value: Vec::new(),
+    }
+}
+
+/// A [DeltaSetIndexMap](https://learn.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#associating-target-items-to-variation-data)
+/// with one entry per item, using the smallest entries that fit.
+pub fn delta_set_index_map(indices: &[DeltaSetIndex]) -> DeltaSetIndexMap {
+    let bits = |value: u16| (u16::BITS - value.leading_zeros()).max(1);
+    let inner_bits = indices
+        .iter()
+        .map(|(_, inner)| bits(*inner))
+        .max()
+        .unwrap_or(1);
+    let outer_bits = indices
+        .iter()
+        .map(|(outer, _)| bits(*outer))
+        .max()
+        .unwrap_or(1);
+    let entry_size = (inner_bits + outer_bits).div_ceil(8);
+
+    let mut map_data = Vec::with_capacity(indices.len() * entry_size as usize);
+    for (outer, inner) in indices {
+        let entry = ((*outer as u32) << inner_bits) | *inner as u32;
+        map_data.extend(&entry.to_be_bytes()[(4 - entry_size as usize)..]);
+    }
+
+    let entry_format =
+        EntryFormat::from_bits((((entry_size - 1) << 4) | (inner_bits - 1)) as u8).unwrap();
+    match u16::try_from(indices.len()) {
+        Ok(map_count) => DeltaSetIndexMap::format_0(entry_format, map_count, map_data),
+        Err(..) => DeltaSetIndexMap::format_1(entry_format, indices.len() as u32, map_data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fontir::{
+        coords::{CoordConverter, UserCoord},
+        ir::Axis,
+        variations::{Tent, VariationRegion},
+    };
+    use read_fonts::{FontData, FontRead};
+    use write_fonts::dump_table;
+
+    use super::{delta_set_index_map, VarStoreBuilder};
+
+    fn axis(name: &str) -> Axis {
+        let min = UserCoord::new(100.0);
+        let default = UserCoord::new(400.0);
+        let max = UserCoord::new(900.0);
+        Axis {
+            name: name.to_string(),
+            tag: font_types::Tag::new(b"wght"),
+            hidden: false,
+            min,
+            default,
+            max,
+            converter: CoordConverter::unmapped(min, default, max),
+        }
+    }
+
+    fn region(tents: &[(&str, (f32, f32, f32))]) -> VariationRegion {
+        let mut region = VariationRegion::default();
+        for (name, tent) in tents {
+            region.insert(name.to_string(), Tent::from(*tent));
+        }
+        region
+    }
+
+    #[test]
+    fn shares_rows_and_regions() {
+        let mut builder = VarStoreBuilder::new(&[axis("A"), axis("B")]);
+        let a = region(&[("A", (0.0, 1.0, 1.0)), ("B", (0.0, 0.0, 0.0))]);
+        let b = region(&[("A", (0.0, 0.0, 0.0)), ("B", (0.0, 1.0, 1.0))]);
+
+        assert_eq!(None, builder.add_no_deltas());
+        assert_eq!(Some((0, 0)), builder.add_deltas(&[(a.clone(), 10)]));
+        assert_eq!(Some((0, 1)), builder.add_deltas(&[(a.clone(), 20)]));
+        assert_eq!(Some((0, 0)), builder.add_deltas(&[(a.clone(), 10)]));
+        assert_eq!(
+            Some((1, 0)),
+            builder.add_deltas(&[(b.clone(), 500), (a.clone(), 10)])
+        );
+        assert_eq!(None, builder.add_deltas(&[(a, 0), (b, 0)]));
+        assert_eq!(Some((0, 2)), builder.add_no_deltas());
+        assert_eq!(Some((0, 2)), builder.add_no_deltas());
+
+        let store = builder.build();
+        assert_eq!(2, store.variation_region_list.variation_regions.len());
+        assert_eq!(2, store.item_variation_datas.len());
+    }
+
+    #[test]
+    fn regions_in_given_order() {
+        let mut builder = VarStoreBuilder::new(&[axis("A"), axis("B")]);
+        let a = region(&[("A", (0.0, 1.0, 1.0))]);
+        let b = region(&[("B", (0.0, 1.0, 1.0))]);
+        assert_eq!(0, builder.add_regions(&[b.clone(), a.clone()]));
+        assert_eq!(1, builder.add_regions(std::slice::from_ref(&a)));
+        assert_eq!(0, builder.add_regions(&[b, a]));
+
+        let store = builder.build();
+        let data = store.item_variation_datas[0].as_ref().unwrap();
+        assert_eq!(vec![0, 1], data.region_indexes);
+        assert_eq!(0, data.item_count);
+    }
+
+    #[test]
+    fn add_to_existing() {
+        let mut builder = VarStoreBuilder::new(&[axis("A"), axis("B")]);
+        let a = region(&[("A", (0.0, 1.0, 1.0))]);
+        let b = region(&[("B", (0.0, 1.0, 1.0))]);
+        builder.add_deltas(&[(a.clone(), 10)]);
+        let existing = builder.build();
+
+        let mut builder = VarStoreBuilder::from_existing(&[axis("A"), axis("B")], &existing);
+        assert!(!builder.is_empty());
+        assert_eq!(Some((1, 0)), builder.add_deltas(&[(a, 10)]));
+        assert_eq!(Some((2, 0)), builder.add_deltas(&[(b, 20)]));
+
+        let store = builder.build();
+        assert_eq!(2, store.variation_region_list.variation_regions.len());
+        assert_eq!(3, store.item_variation_datas.len());
+        assert_eq!(
+            existing.item_variation_datas[0]
+                .as_ref()
+                .unwrap()
+                .delta_sets,
+            store.item_variation_datas[0].as_ref().unwrap().delta_sets
+        );
+    }
+
+    #[test]
+    fn word_columns_first() {
+        let mut builder = VarStoreBuilder::new(&[axis("A"), axis("B")]);
+        let a = region(&[("A", (0.0, 1.0, 1.0))]);
+        let b = region(&[("B", (0.0, 1.0, 1.0))]);
+        builder.add_deltas(&[(a, 1), (b, 1000)]);
+
+        let store = builder.build();
+        let bytes = dump_table(&store).unwrap();
+        let store = read_fonts::tables::variations::ItemVariationStore::read(FontData::new(&bytes))
+            .unwrap();
+        let data = store
+            .item_variation_datas()
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(1, data.word_delta_count());
+        assert_eq!(
+            vec![1, 0],
+            data.region_indexes()
+                .iter()
+                .map(|idx| idx.get())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn smallest_map_entries() {
+        let map = delta_set_index_map(&[(0, 0), (0, 3), (1, 2)]);
+        let write_fonts::tables::variations::DeltaSetIndexMap::Format0(map) = map else {
+            panic!("Expected format 0");
+        };
+        // 2 bits of inner, 1 of outer, fits in a byte
+        assert_eq!(0x01, map.entry_format.bits());
+        assert_eq!(3, map.map_count);
+        assert_eq!(vec![0b000, 0b011, 0b110], map.map_data);
+    }
+}
@@ -0,0 +1,745 @@
+//! Variable values in fea.
+//!
+//! fea-rs doesn't understand variable values such as `(wght=400:-10 wght=700:-30)`
+//! so the fea is compiled once per master, with each variable value replaced by its
+//! value there, and the resulting GPOS tables are merged into one that varies.
+//! Per-master fea files that differ only in values are merged the same way.
+
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use fontir::{
+    coords::{NormalizedCoord, NormalizedLocation, UserCoord},
+    ir::{Axis, StaticMetadata},
+    variations::{VariationModel, VariationRegion},
+};
+use log::warn;
+use read_fonts::{FontRef, TableProvider, TopLevelTable};
+use regex::{Captures, Regex};
+use write_fonts::{
+    from_obj::ToOwnedTable,
+    tables::{
+        gpos::{
+            AnchorTable, CursivePosFormat1, Gpos, MarkArray, MarkBasePosFormat1, MarkLigPosFormat1,
+            MarkMarkPosFormat1, PairPos, PositionLookup, SinglePos, ValueRecord,
+        },
+        gsub::Gsub,
+        layout::{CoverageTable, Device},
+    },
+    FontBuilder, NullableOffsetMarker, OtRound,
+};
+
+use crate::{
+    error::Error,
+    var_store::{variation_index, VarStoreBuilder, NO_VARIATION_INDEX},
+};
+
+const NUMBER: &str = r"-?\d+(?:\.\d+)?";
+
+/// Resolves variable values in fea source to their value at one location.
+pub(crate) struct VariableValues {
+    location: NormalizedLocation,
+    axes: Vec<Axis>,
+    variable_value: Regex,
+    entry: Regex,
+    axis_pos: Regex,
+    /// Set once any variable value has been seen
+    seen: Rc<Cell<bool>>,
+}
+
+impl VariableValues {
+    pub(crate) fn new(location: NormalizedLocation, axes: &[Axis]) -> VariableValues {
+        let axis_pos = format!(r"([A-Za-z0-9_]{{1,4}})\s*=\s*({NUMBER})");
+        let entry = format!(r"({axis_pos}(?:\s*,\s*{axis_pos})*)\s*:\s*({NUMBER})");
+        let variable_value = format!(r"\(\s*{entry}(?:\s+{entry})*\s*\)");
+        VariableValues {
+            location,
+            axes: axes.iter().filter(|a| !a.is_point()).cloned().collect(),
+            variable_value: Regex::new(&variable_value).unwrap(),
+            entry: Regex::new(&entry).unwrap(),
+            axis_pos: Regex::new(&axis_pos).unwrap(),
+            seen: Default::default(),
+        }
+    }
+
+    /// Lets the caller find out whether the fea had any variable values once compiled.
+    pub(crate) fn seen(&self) -> Rc<Cell<bool>> {
+        self.seen.clone()
+    }
+
+    /// Replace every variable value in fea with its value at our location.
+    ///
+    /// Comments and strings are left alone. Each value is padded to the length of what
+    /// it replaces, keeping its newlines, so fea errors point at the right line and column.
+    pub(crate) fn resolve(&self, fea: &str) -> Result<String, String> {
+        let mut error = None;
+        let mut resolved = String::with_capacity(fea.len());
+        for (code, text) in code_and_other(fea) {
+            if !code {
+                resolved.push_str(text);
+                continue;
+            }
+            let code = self.variable_value.replace_all(text, |caps: &Captures| {
+                self.seen.set(true);
+                match self.value_at_location(&caps[0]) {
+                    Ok(value) => pad_like(value.to_string(), &caps[0]),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        caps[0].to_string()
+                    }
+                }
+            });
+            resolved.push_str(&code);
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(resolved),
+        }
+    }
+
+    fn value_at_location(&self, variable_value: &str) -> Result<i16, String> {
+        let mut values = HashMap::new();
+        for entry in self.entry.captures_iter(variable_value) {
+            let mut location = NormalizedLocation::new();
+            for axis in self.axes.iter() {
+                location.set_pos(axis.name.clone(), NormalizedCoord::new(0.0));
+            }
+            for axis_pos in self.axis_pos.captures_iter(&entry[1]) {
+                let tag = &axis_pos[1];
+                let Some(axis) = self
+                    .axes
+                    .iter()
+                    .find(|a| a.tag.to_string().trim_end() == tag)
+                else {
+                    return Err(format!("{variable_value} uses {tag}, not a variable axis"));
+                };
+                let user: f32 = axis_pos[2].parse().unwrap();
+                location.set_pos(
+                    axis.name.clone(),
+                    UserCoord::new(user).to_normalized(&axis.converter),
+                );
+            }
+            let value: f64 = entry[entry.len() - 1].parse().unwrap();
+            values.insert(location, vec![value]);
+        }
+
+        let mut location = self.location.clone();
+        location.retain(|name, _| self.axes.iter().any(|a| a.name == *name));
+        if let Some(value) = values.get(&location) {
+            return Ok(value[0].ot_round());
+        }
+
+        // Not defined here, interpolate from where it is defined
+        let model = VariationModel::new(values.keys().cloned().collect(), self.axes.clone())
+            .map_err(|e| format!("{variable_value}: {e:?}"))?;
+        let value: f64 = model
+            .deltas::<f64, f64>(&values)
+            .map_err(|e| format!("{variable_value}: {e}"))?
+            .into_iter()
+            .map(|(region, deltas)| deltas[0] * region.scalar_at(&location).into_inner() as f64)
+            .sum();
+        Ok(value.ot_round())
+    }
+}
+
+/// Splits fea into runs of code and of everything else, comments and strings.
+///
+/// Comments run from # to the end of the line, strings from " to the next ".
+fn code_and_other(fea: &str) -> Vec<(bool, &str)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut chars = fea.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        let end = match c {
+            '#' => fea[pos..].find('\n').map(|i| pos + i).unwrap_or(fea.len()),
+            '"' => fea[pos + 1..]
+                .find('"')
+                .map(|i| pos + 1 + i + 1)
+                .unwrap_or(fea.len()),
+            _ => continue,
+        };
+        if pos > start {
+            runs.push((true, &fea[start..pos]));
+        }
+        runs.push((false, &fea[pos..end]));
+        start = end;
+        while chars.offset() < end {
+            chars.next();
+        }
+    }
+    if start < fea.len() {
+        runs.push((true, &fea[start..]));
+    }
+    runs
+}
+
+/// Value padded with spaces to the length of replaced, keeping any newlines in replaced.
+fn pad_like(mut value: String, replaced: &str) -> String {
+    value.extend(
+        replaced
+            .chars()
+            .skip(value.len())
+            .map(|c| if c == '\n' { c } else { ' ' }),
+    );
+    value
+}
+
+/// Merges the GPOS of fea compiled at each master into the GPOS of the default.
+struct Merger<'a> {
+    static_metadata: &'a StaticMetadata,
+    /// The default location, then the location of each other master
+    locations: Vec<NormalizedLocation>,
+    var_store: &'a mut VarStoreBuilder,
+}
+
+/// The default and other masters must have compiled to the same structure.
+fn check(location: &NormalizedLocation, what: &str, compatible: bool) -> Result<(), Error> {
+    if compatible {
+        return Ok(());
+    }
+    Err(Error::IncompatibleFea(location.clone(), what.to_string()))
+}
+
+fn same_coverage(a: &CoverageTable, b: &CoverageTable) -> bool {
+    a.iter().eq(b.iter())
+}
+
+fn value_record_values(record: &ValueRecord) -> [Option<i16>; 4] {
+    [
+        record.x_placement,
+        record.y_placement,
+        record.x_advance,
+        record.y_advance,
+    ]
+}
+
+fn value_record_devices(record: &mut ValueRecord) -> [&mut NullableOffsetMarker<Device>; 4] {
+    [
+        &mut record.x_placement_device,
+        &mut record.y_placement_device,
+        &mut record.x_advance_device,
+        &mut record.y_advance_device,
+    ]
+}
+
+/// Value records in groups that must share a format.
+fn single_pos_records(subtable: &mut SinglePos) -> Vec<Vec<&mut ValueRecord>> {
+    match subtable {
+        SinglePos::Format1(table) => vec![vec![&mut table.value_record]],
+        SinglePos::Format2(table) => vec![table.value_records.iter_mut().collect()],
+    }
+}
+
+/// Value records in groups that must share a format, the firsts then the seconds of each pair.
+fn pair_pos_records(subtable: &mut PairPos) -> Vec<Vec<&mut ValueRecord>> {
+    let (firsts, seconds) = match subtable {
+        PairPos::Format1(table) => table
+            .pair_sets
+            .iter_mut()
+            .flat_map(|pair_set| pair_set.pair_value_records.iter_mut())
+            .map(|record| (&mut record.value_record1, &mut record.value_record2))
+            .unzip(),
+        PairPos::Format2(table) => table
+            .class1_records
+            .iter_mut()
+            .flat_map(|class1| class1.class2_records.iter_mut())
+            .map(|record| (&mut record.value_record1, &mut record.value_record2))
+            .unzip(),
+    };
+    vec![firsts, seconds]
+}
+
+fn mark_array_anchors(
+    mark_array: &mut MarkArray,
+) -> impl Iterator<Item = Option<&mut AnchorTable>> {
+    mark_array
+        .mark_records
+        .iter_mut()
+        .map(|record| Some(&mut *record.mark_anchor))
+}
+
+fn cursive_anchors(subtable: &mut CursivePosFormat1) -> Vec<Option<&mut AnchorTable>> {
+    subtable
+        .entry_exit_record
+        .iter_mut()
+        .flat_map(|record| [record.entry_anchor.as_mut(), record.exit_anchor.as_mut()])
+        .collect()
+}
+
+fn mark_base_anchors(subtable: &mut MarkBasePosFormat1) -> Vec<Option<&mut AnchorTable>> {
+    let MarkBasePosFormat1 {
+        mark_array,
+        base_array,
+        ..
+    } = subtable;
+    mark_array_anchors(mark_array)
+        .chain(
+            base_array
+                .base_records
+                .iter_mut()
+                .flat_map(|record| record.base_anchors.iter_mut().map(|a| a.as_mut())),
+        )
+        .collect()
+}
+
+fn mark_lig_anchors(subtable: &mut MarkLigPosFormat1) -> Vec<Option<&mut AnchorTable>> {
+    let MarkLigPosFormat1 {
+        mark_array,
+        ligature_array,
+        ..
+    } = subtable;
+    mark_array_anchors(mark_array)
+        .chain(
+            ligature_array
+                .ligature_attaches
+                .iter_mut()
+                .flat_map(|attach| attach.component_records.iter_mut())
+                .flat_map(|record| record.ligature_anchors.iter_mut().map(|a| a.as_mut())),
+        )
+        .collect()
+}
+
+fn mark_mark_anchors(subtable: &mut MarkMarkPosFormat1) -> Vec<Option<&mut AnchorTable>> {
+    let MarkMarkPosFormat1 {
+        mark1_array,
+        mark2_array,
+        ..
+    } = subtable;
+    mark_array_anchors(mark1_array)
+        .chain(
+            mark2_array
+                .mark2_records
+                .iter_mut()
+                .flat_map(|record| record.mark2_anchors.iter_mut().map(|a| a.as_mut())),
+        )
+        .collect()
+}
+
+fn anchor_xy(anchor: &AnchorTable) -> (i16, i16) {
+    match anchor {
+        AnchorTable::Format1(table) => (table.x_coordinate, table.y_coordinate),
+        AnchorTable::Format2(table) => (table.x_coordinate, table.y_coordinate),
+        AnchorTable::Format3(table) => (table.x_coordinate, table.y_coordinate),
+    }
+}
+
+impl Merger<'_> {
+    /// Deltas for a value at each location, empty if it doesn't vary.
+    fn deltas(&self, values: &[i16]) -> Result<Vec<(VariationRegion, i16)>, Error> {
+        if values.iter().all(|v| *v == values[0]) {
+            return Ok(Vec::new());
+        }
+        let values: HashMap<_, _> = self
+            .locations
+            .iter()
+            .zip(values)
+            .map(|(loc, value)| (loc.clone(), vec![*value as f64]))
+            .collect();
+        Ok(self
+            .static_metadata
+            .variation_model_for(self.locations.iter())
+            .map_err(Error::FeaVariationModelError)?
+            .deltas::<f64, f64>(&values)
+            .map_err(Error::FeaDeltaError)?
+            .into_iter()
+            .filter(|(region, _)| !region.is_default())
+            .map(|(region, deltas)| (region, deltas[0].ot_round()))
+            .filter(|(_, delta)| *delta != 0)
+            .collect())
+    }
+
+    fn merge_value_record_groups(
+        &mut self,
+        default: Vec<Vec<&mut ValueRecord>>,
+        masters: Vec<Vec<Vec<&mut ValueRecord>>>,
+    ) -> Result<(), Error> {
+        let mut masters: Vec<_> = masters.into_iter().map(|m| m.into_iter()).collect();
+        for group in default {
+            let master_groups = masters.iter_mut().map(|m| m.next().unwrap()).collect();
+            self.merge_value_records(group, master_groups)?;
+        }
+        Ok(())
+    }
+
+    /// Records in a group share a format so a field that varies anywhere
+    /// gets a device everywhere.
+    fn merge_value_records(
+        &mut self,
+        default: Vec<&mut ValueRecord>,
+        masters: Vec<Vec<&mut ValueRecord>>,
+    ) -> Result<(), Error> {
+        for (loc, records) in self.locations[1..].iter().zip(masters.iter()) {
+            check(loc, "value record count", records.len() == default.len())?;
+            for (a, b) in default.iter().zip(records) {
+                check(loc, "value record format", a.format() == b.format())?;
+            }
+        }
+
+        let mut indices = Vec::with_capacity(default.len());
+        let mut varies = [false; 4];
+        for (idx, record) in default.iter().enumerate() {
+            let mut record_indices = [None; 4];
+            for (field, value) in value_record_values(record).into_iter().enumerate() {
+                let Some(value) = value else {
+                    continue;
+                };
+                let values: Vec<_> = std::iter::once(value)
+                    .chain(
+                        masters
+                            .iter()
+                            .map(|records| value_record_values(records[idx])[field].unwrap()),
+                    )
+                    .collect();
+                record_indices[field] = self.var_store.add_deltas(&self.deltas(&values)?);
+                varies[field] |= record_indices[field].is_some();
+            }
+            indices.push(record_indices);
+        }
+
+        for (record, record_indices) in default.into_iter().zip(indices) {
+            for (field, device) in value_record_devices(record).into_iter().enumerate() {
+                if varies[field] {
+                    let index = record_indices[field].unwrap_or(NO_VARIATION_INDEX);
+                    *device = NullableOffsetMarker::new(Some(variation_index(index)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_anchors(
+        &mut self,
+        default: Vec<Option<&mut AnchorTable>>,
+        masters: Vec<Vec<Option<&mut AnchorTable>>>,
+    ) -> Result<(), Error> {
+        for (loc, anchors) in self.locations[1..].iter().zip(masters.iter()) {
+            check(loc, "anchor count", anchors.len() == default.len())?;
+            for (a, b) in default.iter().zip(anchors) {
+                check(loc, "anchor presence", a.is_some() == b.is_some())?;
+            }
+        }
+
+        for (idx, anchor) in default.into_iter().enumerate() {
+            let Some(anchor) = anchor else {
+                continue;
+            };
+            let (x, y) = anchor_xy(anchor);
+            let mut xs = vec![x];
+            let mut ys = vec![y];
+            for anchors in masters.iter() {
+                let (x, y) = anchor_xy(anchors[idx].as_ref().unwrap());
+                xs.push(x);
+                ys.push(y);
+            }
+            let x_device = self.var_store.add_deltas(&self.deltas(&xs)?);
+            let y_device = self.var_store.add_deltas(&self.deltas(&ys)?);
+            if x_device.is_some() || y_device.is_some() {
+                *anchor = AnchorTable::format_3(
+                    x,
+                    y,
+                    x_device.map(variation_index),
+                    y_device.map(variation_index),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_lookup(
+        &mut self,
+        lookup: &mut PositionLookup,
+        masters: &mut [&mut PositionLookup],
+    ) -> Result<(), Error> {
+        macro_rules! merge_subtables {
+            ($variant:ident, $lookup:expr, $subtables:expr, $check_coverage:expr, $merge:expr) => {{
+                let mut master_subtables = Vec::new();
+                for (loc, master) in self.locations[1..].iter().zip(masters.iter_mut()) {
+                    let PositionLookup::$variant(master) = &mut **master else {
+                        return Err(Error::IncompatibleFea(
+                            loc.clone(),
+                            "lookup type".to_string(),
+                        ));
+                    };
+                    check(
+                        loc,
+                        "subtable count",
+                        master.subtables.len() == $lookup.subtables.len(),
+                    )?;
+                    master_subtables.push(master.subtables.iter_mut().collect::<Vec<_>>());
+                }
+                for (idx, subtable) in $lookup.subtables.iter_mut().enumerate() {
+                    for (loc, subtables) in self.locations[1..].iter().zip(master_subtables.iter())
+                    {
+                        check(
+                            loc,
+                            "coverage",
+                            $check_coverage(&**subtable, &**subtables[idx]),
+                        )?;
+                    }
+                    let masters = master_subtables
+                        .iter_mut()
+                        .map(|subtables| $subtables(&mut **subtables[idx]))
+                        .collect();
+                    $merge(self, $subtables(&mut **subtable), masters)?;
+                }
+            }};
+        }
+
+        match lookup {
+            PositionLookup::Single(lookup) => merge_subtables!(
+                Single,
+                lookup,
+                single_pos_records,
+                |a: &SinglePos, b: &SinglePos| same_coverage(
+                    single_pos_coverage(a),
+                    single_pos_coverage(b)
+                ),
+                Self::merge_value_record_groups
+            ),
+            PositionLookup::Pair(lookup) => merge_subtables!(
+                Pair,
+                lookup,
+                pair_pos_records,
+                |a: &PairPos, b: &PairPos| same_coverage(
+                    pair_pos_coverage(a),
+                    pair_pos_coverage(b)
+                ),
+                Self::merge_value_record_groups
+            ),
+            PositionLookup::Cursive(lookup) => merge_subtables!(
+                Cursive,
+                lookup,
+                cursive_anchors,
+                |a: &CursivePosFormat1, b: &CursivePosFormat1| same_coverage(
+                    &a.coverage,
+                    &b.coverage
+                ),
+                Self::merge_anchors
+            ),
+            PositionLookup::MarkToBase(lookup) => merge_subtables!(
+                MarkToBase,
+                lookup,
+                mark_base_anchors,
+                |a: &MarkBasePosFormat1, b: &MarkBasePosFormat1| same_coverage(
+                    &a.mark_coverage,
+                    &b.mark_coverage
+                ) && same_coverage(
+                    &a.base_coverage,
+                    &b.base_coverage
+                ),
+                Self::merge_anchors
+            ),
+            PositionLookup::MarkToLig(lookup) => merge_subtables!(
+                MarkToLig,
+                lookup,
+                mark_lig_anchors,
+                |a: &MarkLigPosFormat1, b: &MarkLigPosFormat1| same_coverage(
+                    &a.mark_coverage,
+                    &b.mark_coverage
+                ) && same_coverage(
+                    &a.ligature_coverage,
+                    &b.ligature_coverage
+                ),
+                Self::merge_anchors
+            ),
+            PositionLookup::MarkToMark(lookup) => merge_subtables!(
+                MarkToMark,
+                lookup,
+                mark_mark_anchors,
+                |a: &MarkMarkPosFormat1, b: &MarkMarkPosFormat1| same_coverage(
+                    &a.mark1_coverage,
+                    &b.mark1_coverage
+                ) && same_coverage(
+                    &a.mark2_coverage,
+                    &b.mark2_coverage
+                ),
+                Self::merge_anchors
+            ),
+            // No values of their own, the lookups they reference are merged
+            PositionLookup::Contextual(..) | PositionLookup::ChainContextual(..) => (),
+            PositionLookup::Extension(..) => {
+                warn!("Variable values in extension lookups are not supported, using the default");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn single_pos_coverage(subtable: &SinglePos) -> &CoverageTable {
+    match subtable {
+        SinglePos::Format1(table) => &table.coverage,
+        SinglePos::Format2(table) => &table.coverage,
+    }
+}
+
+fn pair_pos_coverage(subtable: &PairPos) -> &CoverageTable {
+    match subtable {
+        PairPos::Format1(table) => &table.coverage,
+        PairPos::Format2(table) => &table.coverage,
+    }
+}
+
+/// Owned tables don't implement Eq, their debug output is a stand-in
+fn gsub_debug(font: &FontRef) -> Option<String> {
+    font.gsub().ok().map(|gsub| {
+        let gsub: Gsub = gsub.to_owned_table();
+        format!("{gsub:?}")
+    })
+}
+
+/// Make the GPOS of the default font vary using the fea compiled at other masters.
+///
+/// Deltas go into var_store, which the caller is responsible for writing to GDEF.
+pub(crate) fn merge_masters(
+    font: &mut FontBuilder,
+    masters: Vec<(NormalizedLocation, FontBuilder)>,
+    static_metadata: &StaticMetadata,
+    var_store: &mut VarStoreBuilder,
+) -> Result<(), Error> {
+    let default_bytes = font.build();
+    let default_font = FontRef::new(&default_bytes)?;
+    let default_gsub = gsub_debug(&default_font);
+    let Ok(gpos) = default_font.gpos() else {
+        return Ok(());
+    };
+    let mut gpos: Gpos = gpos.to_owned_table();
+
+    let mut locations = vec![static_metadata.default_location().clone()];
+    let mut master_gpos = Vec::new();
+    for (location, mut master) in masters {
+        let bytes = master.build();
+        let master_font = FontRef::new(&bytes)?;
+        if gsub_debug(&master_font) != default_gsub {
+            warn!("GSUB at {location:?} differs from the default, only the default is used");
+        }
+        let Ok(master) = master_font.gpos() else {
+            return Err(Error::IncompatibleFea(location, "no GPOS".to_string()));
+        };
+        let master: Gpos = master.to_owned_table();
+        check(
+            &location,
+            "lookup count",
+            master.lookup_list.lookups.len() == gpos.lookup_list.lookups.len(),
+        )?;
+        master_gpos.push(master);
+        locations.push(location);
+    }
+
+    // Variable values only make sense at locations the model knows
+    let known: HashSet<_> = static_metadata.variation_model.locations().collect();
+    for location in locations.iter() {
+        if !known.contains(location) {
+            warn!("Fea compiled at {location:?}, which is not a master location");
+        }
+    }
+
+    let mut merger = Merger {
+        static_metadata,
+        locations,
+        var_store,
+    };
+    for (idx, lookup) in gpos.lookup_list.lookups.iter_mut().enumerate() {
+        let mut masters: Vec<_> = master_gpos
+            .iter_mut()
+            .map(|gpos| &mut *gpos.lookup_list.lookups[idx])
+            .collect();
+        merger.merge_lookup(lookup, &mut masters)?;
+    }
+
+    font.add_table(
+        Gpos::TAG,
+        write_fonts::dump_table(&gpos).map_err(|e| Error::DumpTableError {
+            e,
+            context: "GPOS".to_string(),
+        })?,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fontir::{
+        coords::{CoordConverter, NormalizedCoord, NormalizedLocation, UserCoord},
+        ir::Axis,
+    };
+
+    use super::VariableValues;
+
+    fn wght() -> Axis {
+        let min = UserCoord::new(400.0);
+        let max = UserCoord::new(700.0);
+        Axis {
+            name: "Weight".to_string(),
+            tag: font_types::Tag::new(b"wght"),
+            hidden: false,
+            min,
+            default: min,
+            max,
+            converter: CoordConverter::unmapped(min, min, max),
+        }
+    }
+
+    fn at(wght: f32) -> VariableValues {
+        let mut location = NormalizedLocation::new();
+        location.set_pos("Weight", NormalizedCoord::new(wght));
+        VariableValues::new(location, &[self::wght()])
+    }
+
+    #[test]
+    fn resolves_at_masters() {
+        let fea = "pos a b (wght=400:-10 wght=700:-30);";
+        assert_eq!(
+            format!("pos a b {:<27};", -10),
+            at(0.0).resolve(fea).unwrap()
+        );
+        assert_eq!(
+            format!("pos a b {:<27};", -30),
+            at(1.0).resolve(fea).unwrap()
+        );
+    }
+
+    #[test]
+    fn interpolates_between_masters() {
+        let fea = "pos a <( wght=400 : 10 wght=700 : 30 ) 0 0 0>;";
+        assert_eq!(
+            format!("pos a <{:<31} 0 0 0>;", 20),
+            at(0.5).resolve(fea).unwrap()
+        );
+    }
+
+    #[test]
+    fn leaves_other_parens_alone() {
+        let values = at(1.0);
+        let fea = "name \"(c) 2023\"; pos a b -5;";
+        assert_eq!(fea, values.resolve(fea).unwrap());
+        assert!(!values.seen().get());
+    }
+
+    #[test]
+    fn leaves_commented_values_alone() {
+        let values = at(1.0);
+        let fea = "# pos a b (wght=400:-10 wght=700:-30);\npos a b -5;";
+        assert_eq!(fea, values.resolve(fea).unwrap());
+        assert!(!values.seen().get());
+    }
+
+    #[test]
+    fn keeps_lines_and_columns() {
+        let fea = "pos a b (wght=400:-10\n    wght=700:-30);\npos a c (wght=400:1 wght=700:2);";
+        let resolved = at(0.0).resolve(fea).unwrap();
+        assert_eq!(fea.len(), resolved.len());
+        assert_eq!(
+            fea.lines().map(str::len).collect::<Vec<_>>(),
+            resolved.lines().map(str::len).collect::<Vec<_>>()
+        );
+        assert!(resolved.starts_with("pos a b -10"));
+    }
+
+    #[test]
+    fn default_required() {
+        assert!(at(0.5).resolve("pos a b (wght=700:-30);").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Generates a [VVAR](https://learn.microsoft.com/en-us/typography/opentype/spec/vvar) table.
+//!
+//! Only advance heights vary; side bearings and vertical origins are left to gvar.
+
+use std::borrow::Borrow;
+
+use fontdrasil::orchestration::Work;
+use fontir::ir::{GlobalMetricsInstance, Glyph, StaticMetadata};
+use log::trace;
+use read_fonts::types::MajorMinor;
+use write_fonts::{
+    dump_table,
+    tables::variations::{DeltaSetIndexMap, ItemVariationStore},
+    validate::{Validate, ValidationCtx},
+    FontWrite, OffsetMarker, TableWriter,
+};
+
+use crate::{
+    error::Error,
+    hvar::advance_deltas,
+    orchestration::{BeWork, Bytes, Context},
+    var_store::{delta_set_index_map, VarStoreBuilder},
+};
+
+struct VvarWork {}
+
+pub fn create_vvar_work() -> Box<BeWork> {
+    Box::new(VvarWork {})
+}
+
+/// write-fonts doesn't do VVAR (yet) so we write it ourselves.
+struct Vvar {
+    item_variation_store: OffsetMarker<ItemVariationStore, 4>,
+    advance_height_mapping: OffsetMarker<DeltaSetIndexMap, 4>,
+}
+
+impl FontWrite for Vvar {
+    fn write_into(&self, writer: &mut TableWriter) {
+        MajorMinor::VERSION_1_0.write_into(writer);
+        self.item_variation_store.write_into(writer);
+        self.advance_height_mapping.write_into(writer);
+        0u32.write_into(writer); // no top side bearing mapping
+        0u32.write_into(writer); // no bottom side bearing mapping
+        0u32.write_into(writer); // no vertical origin mapping
+    }
+
+    fn name(&self) -> &'static str {
+        "Vvar"
+    }
+}
+
+impl Validate for Vvar {
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        ctx.in_table("Vvar", |ctx| {
+            ctx.in_field("item_variation_store", |ctx| {
+                self.item_variation_store.validate_impl(ctx);
+            });
+            ctx.in_field("advance_height_mapping", |ctx| {
+                self.advance_height_mapping.validate_impl(ctx);
+            });
+        })
+    }
+}
+
+fn generate_vvar(
+    static_metadata: &StaticMetadata,
+    default_metrics: &GlobalMetricsInstance,
+    glyphs: &[impl Borrow<Glyph>],
+) -> Result<Option<Vvar>, Error> {
+    if static_metadata.variable_axes.is_empty() {
+        trace!("Skip VVAR; this is not a variable font");
+        return Ok(None);
+    }
+    if !default_metrics.is_vertical() {
+        trace!("Skip VVAR; there are no vertical metrics");
+        return Ok(None);
+    }
+
+    let mut var_store = VarStoreBuilder::new(&static_metadata.variable_axes);
+    let advance_indices = glyphs
+        .iter()
+        .map(|glyph| {
+            advance_deltas(
+                static_metadata,
+                glyph.borrow(),
+                |instance| instance.advance_height(default_metrics),
+                &mut var_store,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every glyph needs an entry, even if its advance is fixed
+    let Some(no_deltas) = var_store.add_no_deltas() else {
+        trace!("Skip VVAR; no advance height varies");
+        return Ok(None);
+    };
+    let advance_indices: Vec<_> = advance_indices
+        .into_iter()
+        .map(|index| index.unwrap_or(no_deltas))
+        .collect();
+
+    Ok(Some(Vvar {
+        item_variation_store: var_store.build().into(),
+        advance_height_mapping: delta_set_index_map(&advance_indices).into(),
+    }))
+}
+
+impl Work<Context, Error> for VvarWork {
+    /// Generate [VVAR](https://learn.microsoft.com/en-us/typography/opentype/spec/vvar)
+    fn exec(&self, context: &Context) -> Result<(), Error> {
+        let static_metadata = context.ir.get_final_static_metadata();
+        let default_metrics = context
+            .ir
+            .get_global_metrics()
+            .at(static_metadata.default_location());
+        let glyphs: Vec<_> = static_metadata
+            .glyph_order
+            .iter()
+            .map(|gn| context.ir.get_glyph_ir(gn))
+            .collect();
+        // Empty bytes tell font assembly to leave VVAR out
+        let bytes = match generate_vvar(&static_metadata, &default_metrics, &glyphs)? {
+            Some(vvar) => dump_table(&vvar).map_err(|e| Error::DumpTableError {
+                e,
+                context: "VVAR".to_string(),
+            })?,
+            None => Vec::new(),
+        };
+        context.set_vvar(Bytes::new(bytes));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fontir::ir::{GlobalMetric, GlobalMetrics, Glyph, GlyphInstance, StaticMetadata};
+    use write_fonts::tables::variations::DeltaSetIndexMap;
+
+    use crate::test_util::{self, location, weight_axis};
+
+    use super::generate_vvar;
+
+    fn static_metadata(glyph_names: &[&str]) -> StaticMetadata {
+        StaticMetadata::new(
+            1000,
+            Default::default(),
+            vec![weight_axis()],
+            glyph_names.iter().map(|n| (*n).into()).collect(),
+            [location(0.0), location(1.0)].into(),
+        )
+        .unwrap()
+    }
+
+    fn metrics(vertical: bool) -> GlobalMetrics {
+        let mut metrics = GlobalMetrics::new(location(0.0), 1000);
+        if vertical {
+            metrics.set(GlobalMetric::VheaAscender, location(0.0), 500.0);
+        }
+        metrics.populate_defaults(&location(0.0), 1000);
+        metrics
+    }
+
+    fn glyph(name: &str, heights: &[(f32, Option<f64>)]) -> Glyph {
+        test_util::glyph(
+            name,
+            heights.iter().map(|(wght, height)| {
+                (
+                    *wght,
+                    GlyphInstance {
+                        height: *height,
+                        ..Default::default()
+                    },
+                )
+            }),
+        )
+    }
+
+    #[test]
+    fn advance_heights_vary() {
+        let static_metadata = static_metadata(&["a", "b", "c"]);
+        let glyphs = vec![
+            glyph("a", &[(0.0, Some(1000.0)), (1.0, Some(1100.0))]),
+            // Without a height the advance is the ascender - descender everywhere
+            glyph("b", &[(0.0, None), (1.0, None)]),
+            glyph("c", &[(0.0, Some(900.0)), (1.0, Some(1000.0))]),
+        ];
+
+        let vvar = generate_vvar(&static_metadata, &metrics(true).at(&location(0.0)), &glyphs)
+            .unwrap()
+            .unwrap();
+
+        // a and c share a delta-set, b uses the all-zero one
+        let DeltaSetIndexMap::Format0(map) = &*vvar.advance_height_mapping else {
+            panic!("Expected a format 0 advance mapping");
+        };
+        assert_eq!(3, map.map_count);
+        assert_eq!(vec![0b00, 0b01, 0b00], map.map_data);
+    }
+
+    #[test]
+    fn no_vvar_without_vertical_metrics() {
+        let static_metadata = static_metadata(&["a"]);
+        let glyphs = vec![glyph("a", &[(0.0, Some(1000.0)), (1.0, Some(1100.0))])];
+
+        assert!(generate_vvar(
+            &static_metadata,
+            &metrics(false).at(&location(0.0)),
+            &glyphs
+        )
+        .unwrap()
+        .is_none());
+    }
+}
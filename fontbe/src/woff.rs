@@ -0,0 +1,142 @@
+//! Wrap a compiled font in [WOFF](https://www.w3.org/TR/WOFF/)
+
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use read_fonts::{FontRef, ReadError};
+
+use crate::error::Error;
+
+const SIGNATURE: &[u8; 4] = b"wOFF";
+const HEADER_SIZE: usize = 44;
+const TABLE_ENTRY_SIZE: usize = 20;
+
+/// The size of the sfnt header and table directory of a font with num_tables tables.
+pub(crate) fn sfnt_directory_size(num_tables: usize) -> usize {
+    12 + 16 * num_tables
+}
+
+pub(crate) fn padded_len(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(padded_len(data.len()), 0);
+}
+
+fn zlib(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Produce a WOFF file holding the tables of font.
+///
+/// Each table is zlib compressed unless that doesn't make it smaller. We
+/// don't write extended metadata or a private data block.
+pub fn woff(font: &[u8]) -> Result<Vec<u8>, Error> {
+    let font = FontRef::new(font)?;
+    let mut records: Vec<_> = font.table_directory.table_records().iter().collect();
+    records.sort_by_key(|r| r.tag());
+
+    let mut directory = Vec::with_capacity(records.len() * TABLE_ENTRY_SIZE);
+    let mut table_data = Vec::new();
+    let mut total_sfnt_size = sfnt_directory_size(records.len());
+    let data_start = HEADER_SIZE + records.len() * TABLE_ENTRY_SIZE;
+    for record in records {
+        let data = font
+            .table_data(record.tag())
+            .ok_or(ReadError::OutOfBounds)?;
+        let data = data.as_ref();
+        let compressed = zlib(data)?;
+        let stored = if compressed.len() < data.len() {
+            &compressed[..]
+        } else {
+            data
+        };
+
+        directory.extend(record.tag().to_be_bytes());
+        directory.extend(((data_start + table_data.len()) as u32).to_be_bytes());
+        directory.extend((stored.len() as u32).to_be_bytes());
+        directory.extend((data.len() as u32).to_be_bytes());
+        directory.extend(record.checksum().to_be_bytes());
+
+        table_data.extend(stored);
+        pad(&mut table_data);
+        total_sfnt_size += padded_len(data.len());
+    }
+
+    let num_tables = font.table_directory.table_records().len();
+    let mut woff = Vec::with_capacity(data_start + table_data.len());
+    woff.extend(SIGNATURE);
+    woff.extend(font.table_directory.sfnt_version().to_be_bytes());
+    woff.extend(((data_start + table_data.len()) as u32).to_be_bytes());
+    woff.extend((num_tables as u16).to_be_bytes());
+    woff.extend(0u16.to_be_bytes()); // reserved
+    woff.extend((total_sfnt_size as u32).to_be_bytes());
+    woff.extend(1u16.to_be_bytes()); // majorVersion
+    woff.extend(0u16.to_be_bytes()); // minorVersion
+    woff.extend([0u8; 20]); // no metadata or private data
+    debug_assert_eq!(HEADER_SIZE, woff.len());
+    woff.extend(directory);
+    woff.extend(table_data);
+    Ok(woff)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+    use read_fonts::{types::Tag, FontRef};
+    use write_fonts::FontBuilder;
+
+    use super::woff;
+
+    fn test_font() -> Vec<u8> {
+        let mut builder = FontBuilder::default();
+        builder.add_table(Tag::new(b"zzzz"), vec![7u8; 5]);
+        builder.add_table(Tag::new(b"aaaa"), vec![1u8; 400]);
+        builder.build()
+    }
+
+    fn read_u32(data: &[u8], pos: usize) -> u32 {
+        u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn woff_round_trips_tables() {
+        let font = test_font();
+        let woff = woff(&font).unwrap();
+        let font = FontRef::new(&font).unwrap();
+
+        assert_eq!(b"wOFF", &woff[0..4]);
+        assert_eq!(woff.len() as u32, read_u32(&woff, 8));
+        assert_eq!(2, u16::from_be_bytes([woff[12], woff[13]]));
+
+        let mut tags = Vec::new();
+        for entry in woff[44..84].chunks(20) {
+            let tag = Tag::new(&entry[0..4]);
+            let offset = read_u32(entry, 4) as usize;
+            let comp_len = read_u32(entry, 8) as usize;
+            let orig_len = read_u32(entry, 12) as usize;
+            assert_eq!(0, offset % 4, "{tag} is not aligned");
+
+            let stored = &woff[offset..offset + comp_len];
+            let data = if comp_len < orig_len {
+                let mut data = Vec::new();
+                ZlibDecoder::new(stored).read_to_end(&mut data).unwrap();
+                data
+            } else {
+                stored.to_vec()
+            };
+            assert_eq!(font.table_data(tag).unwrap().as_ref(), &data[..]);
+            tags.push((tag, comp_len < orig_len));
+        }
+        // only the table that compresses well is compressed
+        assert_eq!(
+            vec![(Tag::new(b"aaaa"), true), (Tag::new(b"zzzz"), false)],
+            tags
+        );
+    }
+}
@@ -0,0 +1,771 @@
+//! Wrap a compiled font in [WOFF2](https://www.w3.org/TR/WOFF2/)
+
+use brotli::enc::{backward_references::BrotliEncoderMode, BrotliEncoderParams};
+use read_fonts::{
+    tables::{
+        glyf::{CompositeGlyphFlags, Glyf, Glyph, SimpleGlyphFlags},
+        head::Head,
+        loca::Loca,
+    },
+    types::GlyphId,
+    FontRef, ReadError, TableProvider, TopLevelTable,
+};
+
+use crate::{
+    error::Error,
+    woff::{padded_len, sfnt_directory_size},
+};
+
+const SIGNATURE: &[u8; 4] = b"wOF2";
+const HEADER_SIZE: usize = 48;
+
+/// Tags that are written as their index in the table directory, in index order.
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// Flags value for a tag that is spelled out after the flags byte.
+const ARBITRARY_TAG: u8 = 63;
+
+/// Transform version for glyf and loca stored as they are; version 0 is the glyf transform.
+const NULL_GLYF_LOCA_TRANSFORM: u8 = 3 << 6;
+
+/// head.flags bit set when the font has been through a lossless modifying transform.
+const LOSSLESS_TRANSFORM: u16 = 1 << 11;
+
+/// The head.flags offset within head.
+const HEAD_FLAGS_OFFSET: usize = 16;
+
+/// Append value as a UIntBase128, big-endian groups of 7 bits.
+fn push_base128(data: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        groups.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    data.extend(groups.iter().rev());
+}
+
+/// Append value as a 255UInt16.
+fn push_255_u16(data: &mut Vec<u8>, value: u16) {
+    match value {
+        0..=252 => data.push(value as u8),
+        253..=505 => data.extend([255, (value - 253) as u8]),
+        506..=761 => data.extend([254, (value - 506) as u8]),
+        _ => {
+            data.push(253);
+            data.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn brotli(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let params = BrotliEncoderParams {
+        quality: 11,
+        lgwin: 22,
+        mode: BrotliEncoderMode::BROTLI_MODE_FONT,
+        size_hint: data.len(),
+        ..Default::default()
+    };
+    let mut compressed = Vec::new();
+    brotli::BrotliCompress(&mut &data[..], &mut compressed, &params)?;
+    Ok(compressed)
+}
+
+/// Produce a WOFF2 file holding the tables of font.
+///
+/// TrueType outlines get the glyf and loca transform, every other table is
+/// stored as is. We don't write extended metadata or a private data block.
+pub fn woff2(font: &[u8]) -> Result<Vec<u8>, Error> {
+    let font = FontRef::new(font)?;
+    let transform_glyf =
+        font.table_data(Glyf::TAG).is_some() && font.table_data(Loca::TAG).is_some();
+
+    // Tag order, except that a transformed loca must follow glyf
+    let mut tags: Vec<_> = font
+        .table_directory
+        .table_records()
+        .iter()
+        .map(|r| r.tag())
+        .collect();
+    tags.sort();
+    if transform_glyf {
+        tags.retain(|tag| *tag != Loca::TAG);
+        let glyf_idx = tags.iter().position(|tag| *tag == Glyf::TAG).unwrap();
+        tags.insert(glyf_idx + 1, Loca::TAG);
+    }
+
+    let mut directory = Vec::new();
+    let mut table_data = Vec::new();
+    let mut total_sfnt_size = sfnt_directory_size(tags.len());
+    for tag in tags.iter() {
+        let data = font.table_data(*tag).ok_or(ReadError::OutOfBounds)?;
+        let data = data.as_ref();
+        total_sfnt_size += padded_len(data.len());
+
+        let known_tag = KNOWN_TAGS
+            .iter()
+            .position(|known| tag.to_be_bytes() == **known);
+        let mut flags = known_tag.map(|idx| idx as u8).unwrap_or(ARBITRARY_TAG);
+        if !transform_glyf && (*tag == Glyf::TAG || *tag == Loca::TAG) {
+            flags |= NULL_GLYF_LOCA_TRANSFORM;
+        }
+        directory.push(flags);
+        if known_tag.is_none() {
+            directory.extend(tag.to_be_bytes());
+        }
+        push_base128(&mut directory, data.len() as u32);
+
+        match *tag {
+            Glyf::TAG if transform_glyf => {
+                let transformed = transform_glyf_table(&font)?;
+                push_base128(&mut directory, transformed.len() as u32);
+                table_data.extend(transformed);
+            }
+            // Rebuilt from the transformed glyf so it has no data of its own
+            Loca::TAG if transform_glyf => push_base128(&mut directory, 0),
+            Head::TAG if transform_glyf => {
+                let flags_at = table_data.len() + HEAD_FLAGS_OFFSET;
+                table_data.extend(data);
+                let flags = u16::from_be_bytes([table_data[flags_at], table_data[flags_at + 1]]);
+                table_data[flags_at..flags_at + 2]
+                    .copy_from_slice(&(flags | LOSSLESS_TRANSFORM).to_be_bytes());
+            }
+            _ => table_data.extend(data),
+        }
+    }
+
+    let compressed = brotli(&table_data)?;
+    let length = padded_len(HEADER_SIZE + directory.len() + compressed.len());
+
+    let mut woff2 = Vec::with_capacity(length);
+    woff2.extend(SIGNATURE);
+    woff2.extend(font.table_directory.sfnt_version().to_be_bytes());
+    woff2.extend((length as u32).to_be_bytes());
+    woff2.extend((tags.len() as u16).to_be_bytes());
+    woff2.extend(0u16.to_be_bytes()); // reserved
+    woff2.extend((total_sfnt_size as u32).to_be_bytes());
+    woff2.extend((compressed.len() as u32).to_be_bytes());
+    woff2.extend(1u16.to_be_bytes()); // majorVersion
+    woff2.extend(0u16.to_be_bytes()); // minorVersion
+    woff2.extend([0u8; 20]); // no metadata or private data
+    debug_assert_eq!(HEADER_SIZE, woff2.len());
+    woff2.extend(directory);
+    woff2.extend(compressed);
+    woff2.resize(length, 0);
+    Ok(woff2)
+}
+
+/// The streams of a transformed glyf table, see
+/// <https://www.w3.org/TR/WOFF2/#glyf_table_format>.
+#[derive(Default)]
+struct GlyfStreams {
+    n_contours: Vec<u8>,
+    n_points: Vec<u8>,
+    flags: Vec<u8>,
+    glyphs: Vec<u8>,
+    composites: Vec<u8>,
+    bbox_bitmap: Vec<u8>,
+    bboxes: Vec<u8>,
+    instructions: Vec<u8>,
+    overlap_bitmap: Vec<u8>,
+    has_overlap: bool,
+}
+
+impl GlyfStreams {
+    fn new(num_glyphs: u16) -> GlyfStreams {
+        GlyfStreams {
+            bbox_bitmap: vec![0; 4 * (num_glyphs as usize).div_ceil(32)],
+            overlap_bitmap: vec![0; (num_glyphs as usize).div_ceil(8)],
+            ..Default::default()
+        }
+    }
+
+    fn push_bbox(&mut self, gid: u16, bbox: [i16; 4]) {
+        self.bbox_bitmap[gid as usize >> 3] |= 0x80 >> (gid & 7);
+        for value in bbox {
+            self.bboxes.extend(value.to_be_bytes());
+        }
+    }
+
+    fn push_instructions(&mut self, instructions: &[u8]) {
+        push_255_u16(&mut self.glyphs, instructions.len() as u16);
+        self.instructions.extend(instructions);
+    }
+
+    /// Append a point, relative to the previous one, as a flag and a triplet.
+    fn push_point(&mut self, dx: i32, dy: i32, on_curve: bool) {
+        let on_curve_bit = if on_curve { 0 } else { 0x80 };
+        let x_sign = (dx >= 0) as u8;
+        let y_sign = (dy >= 0) as u8;
+        let xy_signs = x_sign + 2 * y_sign;
+        let (x, y) = (dx.unsigned_abs(), dy.unsigned_abs());
+        let (flag, triplet): (u8, &[u8]) = if dx == 0 && y < 1280 {
+            (((y & 0xF00) >> 7) as u8 + y_sign, &[y as u8])
+        } else if dy == 0 && x < 1280 {
+            (10 + ((x & 0xF00) >> 7) as u8 + x_sign, &[x as u8])
+        } else if x < 65 && y < 65 {
+            let (x, y) = (x - 1, y - 1);
+            (
+                20 + (x & 0x30) as u8 + ((y & 0x30) >> 2) as u8 + xy_signs,
+                &[(((x & 0xF) << 4) | (y & 0xF)) as u8],
+            )
+        } else if x < 769 && y < 769 {
+            let (x, y) = (x - 1, y - 1);
+            (
+                84 + 12 * ((x & 0x300) >> 8) as u8 + ((y & 0x300) >> 6) as u8 + xy_signs,
+                &[x as u8, y as u8],
+            )
+        } else if x < 4096 && y < 4096 {
+            (
+                120 + xy_signs,
+                &[(x >> 4) as u8, (((x & 0xF) << 4) | (y >> 8)) as u8, y as u8],
+            )
+        } else {
+            (
+                124 + xy_signs,
+                &[(x >> 8) as u8, x as u8, (y >> 8) as u8, y as u8],
+            )
+        };
+        self.flags.push(on_curve_bit | flag);
+        self.glyphs.extend(triplet);
+    }
+
+    fn push_glyph(&mut self, gid: u16, glyph: Option<Glyph>) {
+        let glyph = match glyph {
+            Some(glyph) if glyph.number_of_contours() != 0 => glyph,
+            _ => {
+                self.n_contours.extend(0i16.to_be_bytes());
+                return;
+            }
+        };
+        self.n_contours
+            .extend(glyph.number_of_contours().to_be_bytes());
+        let header_bbox = [glyph.x_min(), glyph.y_min(), glyph.x_max(), glyph.y_max()];
+        match glyph {
+            Glyph::Simple(glyph) => {
+                let mut last_end = -1;
+                for end in glyph.end_pts_of_contours() {
+                    let end = end.get() as i32;
+                    push_255_u16(&mut self.n_points, (end - last_end) as u16);
+                    last_end = end;
+                }
+
+                let mut bbox: Option<[i16; 4]> = None;
+                let (mut x, mut y) = (0i32, 0i32);
+                for point in glyph.points() {
+                    self.push_point(point.x as i32 - x, point.y as i32 - y, point.on_curve);
+                    (x, y) = (point.x as i32, point.y as i32);
+                    bbox = Some(match bbox {
+                        None => [point.x, point.y, point.x, point.y],
+                        Some([x0, y0, x1, y1]) => [
+                            x0.min(point.x),
+                            y0.min(point.y),
+                            x1.max(point.x),
+                            y1.max(point.y),
+                        ],
+                    });
+                }
+                self.push_instructions(glyph.instructions());
+
+                if bbox != Some(header_bbox) {
+                    self.push_bbox(gid, header_bbox);
+                }
+                let first_flag = glyph.glyph_data().first().copied().unwrap_or_default();
+                if SimpleGlyphFlags::from_bits_truncate(first_flag)
+                    .contains(SimpleGlyphFlags::OVERLAP_SIMPLE)
+                {
+                    self.overlap_bitmap[gid as usize >> 3] |= 0x80 >> (gid & 7);
+                    self.has_overlap = true;
+                }
+            }
+            Glyph::Composite(glyph) => {
+                // The component records are copied as they are, less any instructions
+                let mut records_len = 0;
+                let mut any_flags = CompositeGlyphFlags::empty();
+                for component in glyph.components() {
+                    let flags = component.flags;
+                    any_flags |= flags;
+                    records_len += 4;
+                    records_len += if flags.contains(CompositeGlyphFlags::ARG_1_AND_2_ARE_WORDS) {
+                        4
+                    } else {
+                        2
+                    };
+                    if flags.contains(CompositeGlyphFlags::WE_HAVE_A_SCALE) {
+                        records_len += 2;
+                    } else if flags.contains(CompositeGlyphFlags::WE_HAVE_AN_X_AND_Y_SCALE) {
+                        records_len += 4;
+                    } else if flags.contains(CompositeGlyphFlags::WE_HAVE_A_TWO_BY_TWO) {
+                        records_len += 8;
+                    }
+                }
+                self.composites
+                    .extend(&glyph.component_data()[..records_len]);
+                if any_flags.contains(CompositeGlyphFlags::WE_HAVE_INSTRUCTIONS) {
+                    self.push_instructions(glyph.instructions().unwrap_or_default());
+                }
+                self.push_bbox(gid, header_bbox);
+            }
+        }
+    }
+}
+
+/// Apply the glyf transform to the glyf table of font.
+fn transform_glyf_table(font: &FontRef) -> Result<Vec<u8>, Error> {
+    let index_format = font.head()?.index_to_loc_format();
+    let num_glyphs = font.maxp()?.num_glyphs();
+    let loca = font.loca(None)?;
+    let glyf = font.glyf()?;
+
+    let mut streams = GlyfStreams::new(num_glyphs);
+    for gid in 0..num_glyphs {
+        streams.push_glyph(gid, loca.get_glyf(GlyphId::new(gid), &glyf)?);
+    }
+    let bbox_stream_len = streams.bbox_bitmap.len() + streams.bboxes.len();
+
+    let mut transformed = Vec::new();
+    transformed.extend(0u16.to_be_bytes()); // reserved
+    transformed.extend((streams.has_overlap as u16).to_be_bytes()); // optionFlags
+    transformed.extend(num_glyphs.to_be_bytes());
+    transformed.extend(index_format.to_be_bytes());
+    for stream_len in [
+        streams.n_contours.len(),
+        streams.n_points.len(),
+        streams.flags.len(),
+        streams.glyphs.len(),
+        streams.composites.len(),
+        bbox_stream_len,
+        streams.instructions.len(),
+    ] {
+        transformed.extend((stream_len as u32).to_be_bytes());
+    }
+    transformed.extend(streams.n_contours);
+    transformed.extend(streams.n_points);
+    transformed.extend(streams.flags);
+    transformed.extend(streams.glyphs);
+    transformed.extend(streams.composites);
+    transformed.extend(streams.bbox_bitmap);
+    transformed.extend(streams.bboxes);
+    transformed.extend(streams.instructions);
+    if streams.has_overlap {
+        transformed.extend(streams.overlap_bitmap);
+    }
+    Ok(transformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use kurbo::{BezPath, Rect, Shape};
+    use read_fonts::{
+        tables::glyf::{Anchor, Glyph, SimpleGlyphFlags, Transform},
+        types::{GlyphId, Tag},
+        FontRef, TableProvider,
+    };
+    use write_fonts::{
+        dump_table,
+        tables::{
+            glyf::{Bbox, Component, ComponentFlags, CompositeGlyph, SimpleGlyph},
+            head::Head,
+            maxp::Maxp,
+        },
+        FontBuilder,
+    };
+
+    use super::{push_255_u16, push_base128, woff2, GlyfStreams, KNOWN_TAGS};
+
+    fn read_u16(data: &[u8], pos: usize) -> u16 {
+        u16::from_be_bytes(data[pos..pos + 2].try_into().unwrap())
+    }
+
+    fn read_u32(data: &[u8], pos: usize) -> u32 {
+        u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn read_base128(data: &[u8], pos: &mut usize) -> u32 {
+        let mut value = 0;
+        loop {
+            let byte = data[*pos];
+            *pos += 1;
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    fn read_255_u16(data: &[u8], pos: &mut usize) -> u16 {
+        let code = data[*pos];
+        *pos += 1;
+        let value = match code {
+            253 => {
+                *pos += 2;
+                return read_u16(data, *pos - 2);
+            }
+            254 => data[*pos] as u16 + 506,
+            255 => data[*pos] as u16 + 253,
+            _ => return code as u16,
+        };
+        *pos += 1;
+        value
+    }
+
+    /// Decode a triplet per <https://www.w3.org/TR/WOFF2/#triplet_decoding>
+    fn read_point(flag: u8, data: &[u8], pos: &mut usize) -> (i32, i32, bool) {
+        let on_curve = flag & 0x80 == 0;
+        let flag = flag & 0x7f;
+        let with_sign = |flag: u8, value: i32| if flag & 1 == 1 { value } else { -value };
+        let b = |i: usize| data[*pos + i] as i32;
+        let (dx, dy, len) = if flag < 10 {
+            (0, with_sign(flag, (((flag & 14) as i32) << 7) + b(0)), 1)
+        } else if flag < 20 {
+            (
+                with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + b(0)),
+                0,
+                1,
+            )
+        } else if flag < 84 {
+            let b0 = (flag - 20) as i32;
+            (
+                with_sign(flag, 1 + (b0 & 0x30) + (b(0) >> 4)),
+                with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b(0) & 0x0f)),
+                1,
+            )
+        } else if flag < 120 {
+            let b0 = (flag - 84) as i32;
+            (
+                with_sign(flag, 1 + ((b0 / 12) << 8) + b(0)),
+                with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b(1)),
+                2,
+            )
+        } else if flag < 124 {
+            (
+                with_sign(flag, (b(0) << 4) + (b(1) >> 4)),
+                with_sign(flag >> 1, ((b(1) & 0xf) << 8) + b(2)),
+                3,
+            )
+        } else {
+            (
+                with_sign(flag, (b(0) << 8) + b(1)),
+                with_sign(flag >> 1, (b(2) << 8) + b(3)),
+                4,
+            )
+        };
+        *pos += len;
+        (dx, dy, on_curve)
+    }
+
+    #[test]
+    fn base128() {
+        let mut data = Vec::new();
+        for value in [0, 63, 127, 128, 16384, u32::MAX] {
+            push_base128(&mut data, value);
+        }
+        assert_eq!(
+            vec![0, 63, 127, 0x81, 0, 0x81, 0x80, 0, 0x8f, 0xff, 0xff, 0xff, 0x7f],
+            data
+        );
+    }
+
+    #[test]
+    fn u16_255() {
+        let values = [0, 252, 253, 505, 506, 761, 762, u16::MAX];
+        let mut data = Vec::new();
+        for value in values {
+            push_255_u16(&mut data, value);
+        }
+        let mut pos = 0;
+        let decoded: Vec<_> = values
+            .iter()
+            .map(|_| read_255_u16(&data, &mut pos))
+            .collect();
+        assert_eq!(values.to_vec(), decoded);
+        assert_eq!(data.len(), pos);
+    }
+
+    #[test]
+    fn triplets_round_trip() {
+        let deltas = [
+            0, 1, -1, 63, 64, 65, -65, 300, 768, 769, -769, 1279, 1280, 4095, 4096, -4096, 32767,
+            -32768,
+        ];
+        for dx in deltas {
+            for dy in deltas {
+                for on_curve in [true, false] {
+                    let mut streams = GlyfStreams::default();
+                    streams.push_point(dx, dy, on_curve);
+                    let mut pos = 0;
+                    assert_eq!(
+                        (dx, dy, on_curve),
+                        read_point(streams.flags[0], &streams.glyphs, &mut pos),
+                        "{streams:?}",
+                        streams = (&streams.flags, &streams.glyphs)
+                    );
+                    assert_eq!(streams.glyphs.len(), pos);
+                }
+            }
+        }
+    }
+
+    fn test_font() -> Vec<u8> {
+        let mut square = Rect::new(-20.0, 0.0, 700.0, 5000.0).to_path(0.0);
+        square.extend(Rect::new(100.0, 100.0, 120.0, 130.0).to_path(0.0));
+        let mut curve = BezPath::new();
+        curve.move_to((0.0, 0.0));
+        curve.quad_to((50.0, 100.0), (100.0, 0.0));
+        curve.close_path();
+
+        let square = SimpleGlyph::from_kurbo(&square).unwrap();
+        let curve = SimpleGlyph::from_kurbo(&curve).unwrap();
+        let mut composite = CompositeGlyph::new(
+            Component::new(
+                GlyphId::new(1),
+                Anchor::Offset { x: 10, y: -300 },
+                Transform::default(),
+                ComponentFlags::default(),
+            ),
+            Bbox {
+                x_min: -10,
+                y_min: -300,
+                x_max: 710,
+                y_max: 4700,
+            },
+        );
+        composite.add_component(
+            Component::new(
+                GlyphId::new(2),
+                Anchor::Offset { x: 0, y: 0 },
+                Transform::default(),
+                ComponentFlags::default(),
+            ),
+            Bbox {
+                x_min: 0,
+                y_min: 0,
+                x_max: 100,
+                y_max: 50,
+            },
+        );
+
+        let glyphs = [
+            Vec::new(),
+            dump_table(&square).unwrap(),
+            dump_table(&curve).unwrap(),
+            dump_table(&composite).unwrap(),
+        ];
+        let mut glyf = Vec::new();
+        let mut loca = vec![0u32];
+        for glyph in glyphs.iter() {
+            glyf.extend(glyph);
+            glyf.resize((glyf.len() + 3) & !3, 0);
+            loca.push(glyf.len() as u32);
+        }
+        let loca: Vec<u8> = loca
+            .iter()
+            .flat_map(|offset| offset.to_be_bytes())
+            .collect();
+        let head = Head {
+            index_to_loc_format: 1,
+            ..Default::default()
+        };
+
+        let mut builder = FontBuilder::default();
+        builder.add_table(Tag::new(b"glyf"), glyf);
+        builder.add_table(Tag::new(b"loca"), loca);
+        builder.add_table(Tag::new(b"head"), dump_table(&head).unwrap());
+        builder.add_table(
+            Tag::new(b"maxp"),
+            dump_table(&Maxp::new(glyphs.len() as u16)).unwrap(),
+        );
+        builder.add_table(Tag::new(b"zzzz"), vec![1, 2, 3]);
+        builder.build()
+    }
+
+    #[test]
+    fn woff2_tables() {
+        let font = test_font();
+        let woff2 = woff2(&font).unwrap();
+        let font = FontRef::new(&font).unwrap();
+
+        assert_eq!(b"wOF2", &woff2[0..4]);
+        assert_eq!(woff2.len() as u32, read_u32(&woff2, 8));
+        assert_eq!(0, woff2.len() % 4);
+        let num_tables = read_u16(&woff2, 12) as usize;
+        assert_eq!(5, num_tables);
+
+        let mut pos = 48;
+        let mut entries = Vec::new();
+        for _ in 0..num_tables {
+            let flags = woff2[pos];
+            pos += 1;
+            let tag = if flags & 63 == 63 {
+                pos += 4;
+                Tag::new(&woff2[pos - 4..pos])
+            } else {
+                Tag::new(KNOWN_TAGS[(flags & 63) as usize])
+            };
+            let transformed = matches!(&tag.to_be_bytes(), b"glyf" | b"loca") && flags >> 6 == 0;
+            let orig_len = read_base128(&woff2, &mut pos);
+            let stored_len = if transformed {
+                read_base128(&woff2, &mut pos)
+            } else {
+                orig_len
+            };
+            entries.push((tag, orig_len, stored_len));
+        }
+        assert_eq!(
+            vec!["glyf", "loca", "head", "maxp", "zzzz"],
+            entries
+                .iter()
+                .map(|(tag, ..)| tag.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!((Tag::new(b"loca"), 20, 0), entries[1]);
+
+        let compressed_len = read_u32(&woff2, 20) as usize;
+        let mut data = Vec::new();
+        brotli::Decompressor::new(&woff2[pos..pos + compressed_len], 4096)
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|(_, _, len)| *len as usize)
+                .sum::<usize>(),
+            data.len()
+        );
+
+        let mut tables = Vec::new();
+        let mut offset = 0;
+        for (tag, _, len) in entries.iter() {
+            tables.push((*tag, &data[offset..offset + *len as usize]));
+            offset += *len as usize;
+        }
+        for (tag, table) in tables.iter().skip(3) {
+            assert_eq!(font.table_data(*tag).unwrap().as_ref(), *table);
+        }
+        // head is marked as having been transformed
+        let head = font.table_data(Tag::new(b"head")).unwrap();
+        let mut expected_head = head.as_ref().to_vec();
+        expected_head[16] |= 0x08;
+        assert_eq!(expected_head, tables[2].1);
+
+        assert_glyf_decodes(&font, tables[0].1);
+    }
+
+    /// Decode a transformed glyf far enough to compare it with the original.
+    fn assert_glyf_decodes(font: &FontRef, glyf: &[u8]) {
+        let num_glyphs = read_u16(glyf, 4);
+        assert_eq!(font.maxp().unwrap().num_glyphs(), num_glyphs);
+        assert_eq!(1, read_u16(glyf, 6));
+        let mut starts = vec![36];
+        for i in 0..7 {
+            starts.push(starts[i] + read_u32(glyf, 8 + 4 * i) as usize);
+        }
+        let [mut n_contours, mut n_points, mut flags, mut glyphs, mut composites, bboxes, mut instructions, end] =
+            starts[..].try_into().unwrap();
+        assert_eq!(glyf.len(), end);
+        let bitmap_len = 4 * (num_glyphs as usize).div_ceil(32);
+        let mut bbox_pos = bboxes + bitmap_len;
+
+        let loca = font.loca(None).unwrap();
+        let original_glyf = font.glyf().unwrap();
+        for gid in 0..num_glyphs {
+            let expected = loca.get_glyf(GlyphId::new(gid), &original_glyf).unwrap();
+            let contours = read_u16(glyf, n_contours) as i16;
+            n_contours += 2;
+            let has_bbox = glyf[bboxes + (gid as usize >> 3)] & (0x80 >> (gid & 7)) != 0;
+            let mut bbox = None;
+            if has_bbox {
+                bbox = Some(
+                    (0..4)
+                        .map(|i| read_u16(glyf, bbox_pos + 2 * i) as i16)
+                        .collect::<Vec<_>>(),
+                );
+                bbox_pos += 8;
+            }
+            match expected {
+                None => assert_eq!(0, contours),
+                Some(Glyph::Simple(expected)) => {
+                    assert_eq!(expected.number_of_contours(), contours);
+                    let mut end = -1i32;
+                    let mut ends = Vec::new();
+                    for _ in 0..contours {
+                        end += read_255_u16(glyf, &mut n_points) as i32;
+                        ends.push(end as u16);
+                    }
+                    assert_eq!(
+                        expected
+                            .end_pts_of_contours()
+                            .iter()
+                            .map(|e| e.get())
+                            .collect::<Vec<_>>(),
+                        ends
+                    );
+                    let (mut x, mut y) = (0, 0);
+                    for point in expected.points() {
+                        let (dx, dy, on_curve) = read_point(glyf[flags], glyf, &mut glyphs);
+                        flags += 1;
+                        (x, y) = (x + dx, y + dy);
+                        assert_eq!(
+                            (point.x as i32, point.y as i32, point.on_curve),
+                            (x, y, on_curve)
+                        );
+                    }
+                    let num_instructions = read_255_u16(glyf, &mut glyphs) as usize;
+                    assert_eq!(
+                        expected.instructions(),
+                        &glyf[instructions..instructions + num_instructions]
+                    );
+                    instructions += num_instructions;
+                    assert!(
+                        !SimpleGlyphFlags::from_bits_truncate(expected.glyph_data()[0])
+                            .contains(SimpleGlyphFlags::OVERLAP_SIMPLE)
+                    );
+                    // bboxes that can be computed from the points are left out
+                    let header = vec![
+                        expected.x_min(),
+                        expected.y_min(),
+                        expected.x_max(),
+                        expected.y_max(),
+                    ];
+                    let points: Vec<_> = expected.points().collect();
+                    let computed = vec![
+                        points.iter().map(|p| p.x).min().unwrap(),
+                        points.iter().map(|p| p.y).min().unwrap(),
+                        points.iter().map(|p| p.x).max().unwrap(),
+                        points.iter().map(|p| p.y).max().unwrap(),
+                    ];
+                    assert_eq!((header != computed).then_some(header), bbox);
+                }
+                Some(Glyph::Composite(expected)) => {
+                    assert_eq!(-1, contours);
+                    let records = expected.component_data();
+                    assert_eq!(records, &glyf[composites..composites + records.len()]);
+                    composites += records.len();
+                    assert_eq!(
+                        Some(vec![
+                            expected.x_min(),
+                            expected.y_min(),
+                            expected.x_max(),
+                            expected.y_max()
+                        ]),
+                        bbox
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            (glyphs, composites, bbox_pos, instructions),
+            (starts[4], starts[5], starts[6], starts[7])
+        );
+    }
+}
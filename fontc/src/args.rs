@@ -3,7 +3,7 @@
 use std::path::PathBuf;
 
 use clap::{ArgAction, Parser};
-use fontir::orchestration::Flags;
+use fontir::{orchestration::Flags, quadratic::MaxConversionError};
 use serde::{Deserialize, Serialize};
 
 /// What font can we build for you today?
@@ -31,6 +31,71 @@ pub struct Args {
     #[arg(long, default_value = "false")]
     pub flatten_components: bool,
 
+    /// Emit CFF (static) or CFF2 (variable) outlines, keeping cubics as they are, instead of
+    /// converting to quadratic TrueType outlines.
+    #[arg(long, default_value = "false")]
+    pub cff: bool,
+
+    /// How far, relative to units per em, quadratics converted from cubics may stray from
+    /// the original curve.
+    #[arg(long, default_value = "0.001")]
+    pub conversion_error: f64,
+
+    /// How far, in font units, quadratics converted from cubics may stray from the original
+    /// curve. Overrides --conversion-error.
+    #[arg(long, conflicts_with = "conversion_error")]
+    pub conversion_error_units: Option<f64>,
+
+    /// Convert cubics to quadratics for all glyphs at once, so the same curve is split the same
+    /// way in every glyph it appears in, rather than glyph by glyph.
+    #[arg(long, default_value = "false")]
+    pub font_wide_quadratics: bool,
+
+    /// Set OVERLAP_SIMPLE or OVERLAP_COMPOUND on every glyph of a variable font, rather than
+    /// only on glyphs whose default master overlaps.
+    #[arg(long, default_value = "false")]
+    pub overlap_flag_on_all_glyphs: bool,
+
+    /// Replace overlapping contours with their union in static fonts, decomposing components
+    /// that overlap one another.
+    #[arg(long, default_value = "false")]
+    pub remove_overlaps: bool,
+
+    /// Check that the sources of every glyph can interpolate, reporting each way they can't,
+    /// before any binary tables are built.
+    #[arg(long, default_value = "false")]
+    pub check_interpolation: bool,
+
+    /// Reorder contours and rotate start points in non-default masters to best fit the
+    /// default master, logging each fix.
+    #[arg(long, default_value = "false")]
+    pub match_contours: bool,
+
+    /// Omit gvar deltas that can be interpolated from those of neighbouring points (IUP).
+    #[arg(long, default_value = "true", action = ArgAction::Set)]
+    pub optimize_gvar: bool,
+
+    /// How far, in font units, an interpolated gvar delta may be from the actual one.
+    #[arg(long, default_value = "0.5")]
+    pub iup_tolerance: f64,
+
+    /// Also write the font as WOFF, font.woff alongside the font file.
+    #[arg(long, default_value = "false")]
+    pub woff: bool,
+
+    /// Also write the font as WOFF2, font.woff2 alongside the font file.
+    #[arg(long, default_value = "false")]
+    pub woff2: bool,
+
+    /// Keep running after the build, rebuilding whatever a change to the source affects.
+    #[arg(long, default_value = "false")]
+    pub watch: bool,
+
+    /// Compare the content of source files whose mtime changed rather than assuming they
+    /// changed, so a git checkout or cache restore doesn't force a rebuild.
+    #[arg(long, default_value = "false")]
+    pub hash_files: bool,
+
     /// Working directory for the build process. If emit-ir is on, written here.
     #[arg(short, long, default_value = "build")]
     pub build_dir: PathBuf,
@@ -38,6 +103,10 @@ pub struct Args {
     /// Glyph names must match this regex to be processed
     #[arg(short, long, default_value = None)]
     pub glyph_name_filter: Option<String>,
+
+    /// Also build a static font for each named instance, in instances/ under the build dir.
+    #[arg(long, default_value = "false")]
+    pub instances: bool,
 }
 
 impl Args {
@@ -49,10 +118,27 @@ impl Args {
         flags.set(Flags::EMIT_DEBUG, self.emit_debug);
         flags.set(Flags::PREFER_SIMPLE_GLYPHS, self.prefer_simple_glyphs);
         flags.set(Flags::FLATTEN_COMPONENTS, self.flatten_components);
+        flags.set(Flags::CFF_OUTLINES, self.cff);
+        flags.set(Flags::CHECK_INTERPOLATION, self.check_interpolation);
+        flags.set(Flags::MATCH_CONTOURS, self.match_contours);
+        flags.set(Flags::OPTIMIZE_GVAR, self.optimize_gvar);
+        flags.set(Flags::FONT_WIDE_QUADRATICS, self.font_wide_quadratics);
+        flags.set(
+            Flags::OVERLAP_FLAG_ON_ALL_GLYPHS,
+            self.overlap_flag_on_all_glyphs,
+        );
+        flags.set(Flags::REMOVE_OVERLAPS, self.remove_overlaps);
 
         flags
     }
 
+    pub fn max_conversion_error(&self) -> MaxConversionError {
+        match self.conversion_error_units {
+            Some(units) => MaxConversionError::Units(units),
+            None => MaxConversionError::Em(self.conversion_error),
+        }
+    }
+
     /// Manually create args for testing
     #[cfg(test)]
     pub fn for_test(build_dir: &std::path::Path, source: &str) -> Args {
@@ -71,6 +157,22 @@ impl Args {
             build_dir: build_dir.to_path_buf(),
             prefer_simple_glyphs: Flags::default().contains(Flags::PREFER_SIMPLE_GLYPHS),
             flatten_components: Flags::default().contains(Flags::FLATTEN_COMPONENTS),
+            cff: Flags::default().contains(Flags::CFF_OUTLINES),
+            conversion_error: 0.001,
+            conversion_error_units: None,
+            font_wide_quadratics: Flags::default().contains(Flags::FONT_WIDE_QUADRATICS),
+            overlap_flag_on_all_glyphs: Flags::default()
+                .contains(Flags::OVERLAP_FLAG_ON_ALL_GLYPHS),
+            remove_overlaps: Flags::default().contains(Flags::REMOVE_OVERLAPS),
+            check_interpolation: Flags::default().contains(Flags::CHECK_INTERPOLATION),
+            match_contours: Flags::default().contains(Flags::MATCH_CONTOURS),
+            optimize_gvar: Flags::default().contains(Flags::OPTIMIZE_GVAR),
+            iup_tolerance: fontbe::iup::DEFAULT_TOLERANCE,
+            woff: false,
+            woff2: false,
+            watch: false,
+            hash_files: false,
+            instances: false,
         }
     }
 }
@@ -10,6 +10,7 @@ use fontir::{
     orchestration::WorkId as FeWorkIdentifier,
     paths::Paths as IrPaths,
     source::{Input, Source},
+    stateset::StateIdentifier,
 };
 use glyphs2fontir::source::GlyphsIrSource;
 use ufo2fontir::source::DesignSpaceIrSource;
@@ -17,6 +18,26 @@ use ufo2fontir::source::DesignSpaceIrSource;
 use indexmap::IndexSet;
 use regex::Regex;
 
+/// Slices of static metadata, for sources that track it piecemeal, that place glyphs in designspace
+const GLYPH_LOCATION_SLICES: &[&str] = &["/axes", "/masters", "/brace_layers"];
+
+/// Slices of static metadata, for sources that track it piecemeal, that name the font and its instances
+const NAME_SLICES: &[&str] = &["/names", "/instances"];
+
+/// Slices of static metadata, for sources that track it piecemeal, that only OS/2 uses
+const CUSTOM_PARAMETER_SLICES: &[&str] = &["/custom_parameters"];
+
+/// Slices of static metadata, for sources that track it piecemeal, that finalized static metadata
+/// depends on: where glyphs are, which glyphs there are and how they are classified
+const FINALIZE_SLICES: &[&str] = &[
+    "/axes",
+    "/masters",
+    "/brace_layers",
+    "/glyph_order",
+    "/bracket_layers",
+    "/gdef_categories",
+];
+
 //FIXME: clarify the role of this type.
 /// Tracks changes during incremental compilation and... what, exactly?
 pub struct ChangeDetector {
@@ -27,6 +48,9 @@ pub struct ChangeDetector {
     current_inputs: Input,
     be_paths: BePaths,
     emit_ir: bool,
+    cff_outlines: bool,
+    font_wide_quadratics: bool,
+    outputs_in_memory: bool,
 }
 
 impl ChangeDetector {
@@ -36,9 +60,22 @@ impl ChangeDetector {
         prev_inputs: Input,
     ) -> Result<ChangeDetector, Error> {
         // What sources are we dealing with?
-        let mut ir_source = ir_source(&config.args.source)?;
+        let ir_source = ir_source(&config.args.source)?;
+        ChangeDetector::with_ir_source(config, ir_paths, prev_inputs, ir_source)
+    }
+
+    /// Detect changes in the inputs of ir_source rather than those of the source in config.
+    pub fn with_ir_source(
+        config: Config,
+        ir_paths: IrPaths,
+        prev_inputs: Input,
+        mut ir_source: Box<dyn Source>,
+    ) -> Result<ChangeDetector, Error> {
         let mut current_inputs = ir_source.inputs().map_err(Error::FontIrError)?;
-        let be_paths = BePaths::new(ir_paths.build_dir());
+        let mut be_paths = BePaths::new(ir_paths.build_dir());
+        if config.args.cff {
+            be_paths = be_paths.with_cff_outlines();
+        }
 
         let glyph_name_filter = config
             .args
@@ -57,6 +94,12 @@ impl ChangeDetector {
             });
         }
 
+        if config.args.hash_files {
+            current_inputs
+                .hash_files(&prev_inputs)
+                .map_err(Error::IoError)?;
+        }
+
         Ok(ChangeDetector {
             glyph_name_filter,
             ir_paths,
@@ -65,9 +108,29 @@ impl ChangeDetector {
             current_inputs,
             be_paths,
             emit_ir: config.args.emit_ir,
+            cff_outlines: config.args.cff,
+            font_wide_quadratics: config.args.font_wide_quadratics && !config.args.cff,
+            outputs_in_memory: false,
         })
     }
 
+    /// Trust that the output of every previous build is still held in memory.
+    ///
+    /// For a rebuild in the same process, where work is only redone if its
+    /// inputs changed, whether or not its output was written to disk.
+    pub fn with_outputs_in_memory(mut self) -> ChangeDetector {
+        self.outputs_in_memory = true;
+        self
+    }
+
+    fn ir_missing(&self, id: &FeWorkIdentifier) -> bool {
+        !self.outputs_in_memory && !self.ir_paths.target_file(id).is_file()
+    }
+
+    fn be_missing(&self, id: &BeWorkIdentifier) -> bool {
+        !self.outputs_in_memory && !self.be_paths.target_file(id).is_file()
+    }
+
     pub fn glyph_name_filter(&self) -> Option<&Regex> {
         self.glyph_name_filter.as_ref()
     }
@@ -88,85 +151,163 @@ impl ChangeDetector {
         &self.be_paths
     }
 
+    /// Whether we want CFF outlines rather than TrueType ones
+    pub fn cff_outlines(&self) -> bool {
+        self.cff_outlines
+    }
+
+    /// Whether any of the named slices of static metadata changed.
+    ///
+    /// Sources that don't track static metadata in slices, such as designspaces,
+    /// have all of it compared.
+    fn static_metadata_change(&self, slices: &[&str]) -> bool {
+        let slices: Vec<_> = slices
+            .iter()
+            .map(|slice| StateIdentifier::Memory(slice.to_string()))
+            .collect();
+        let current = self.current_inputs.static_metadata.subset(&slices);
+        if current.is_empty() {
+            return self.current_inputs.static_metadata != self.prev_inputs.static_metadata;
+        }
+        current != self.prev_inputs.static_metadata.subset(&slices)
+    }
+
+    /// Static metadata is built from all of its inputs so any change means a rebuild.
     pub fn init_static_metadata_ir_change(&self) -> bool {
         self.current_inputs.static_metadata != self.prev_inputs.static_metadata
-            || !self
-                .ir_paths
-                .target_file(&FeWorkIdentifier::InitStaticMetadata)
-                .is_file()
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
     }
 
+    /// Finalized static metadata is left alone when only names or custom parameters change,
+    /// so work that uses those reads them from init static metadata.
     pub fn final_static_metadata_ir_change(&self) -> bool {
-        self.current_inputs.static_metadata != self.prev_inputs.static_metadata
-            || !self
-                .ir_paths
-                .target_file(&FeWorkIdentifier::FinalizeStaticMetadata)
-                .is_file()
+        self.static_metadata_change(FINALIZE_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::FinalizeStaticMetadata)
+    }
+
+    /// Whether the axes or masters moved, in which case every glyph has to be rebuilt.
+    ///
+    /// Other changes, such as to names, leave glyph IR alone.
+    pub fn glyph_locations_ir_change(&self) -> bool {
+        self.static_metadata_change(GLYPH_LOCATION_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+    }
+
+    pub fn name_be_change(&self) -> bool {
+        self.static_metadata_change(NAME_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+            || self.be_missing(&BeWorkIdentifier::Name)
     }
 
     pub fn global_metrics_ir_change(&self) -> bool {
         self.current_inputs.global_metrics != self.prev_inputs.global_metrics
-            || !self
-                .ir_paths
-                .target_file(&FeWorkIdentifier::GlobalMetrics)
-                .is_file()
+            || self.ir_missing(&FeWorkIdentifier::GlobalMetrics)
     }
 
     pub fn feature_ir_change(&self) -> bool {
         self.final_static_metadata_ir_change()
             || self.current_inputs.features != self.prev_inputs.features
-            || !self
-                .ir_paths
-                .target_file(&FeWorkIdentifier::Features)
-                .is_file()
+            || self.ir_missing(&FeWorkIdentifier::Features)
     }
 
     pub fn feature_be_change(&self) -> bool {
-        self.feature_ir_change()
-            || !self
-                .be_paths
-                .target_file(&BeWorkIdentifier::Features)
-                .is_file()
+        self.feature_ir_change() || self.be_missing(&BeWorkIdentifier::Features)
     }
 
-    pub fn avar_be_change(&self) -> bool {
+    pub fn kerning_ir_change(&self) -> bool {
         self.final_static_metadata_ir_change()
-            || !self.be_paths.target_file(&BeWorkIdentifier::Avar).is_file()
+            || self.current_inputs.kerning != self.prev_inputs.kerning
+            || self.ir_missing(&FeWorkIdentifier::Kerning)
+    }
+
+    pub fn kerning_be_change(&self) -> bool {
+        self.kerning_ir_change() || self.be_missing(&BeWorkIdentifier::Kerning)
+    }
+
+    pub fn avar_be_change(&self) -> bool {
+        self.static_metadata_change(GLYPH_LOCATION_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+            || self.be_missing(&BeWorkIdentifier::Avar)
     }
 
     pub fn fvar_be_change(&self) -> bool {
+        self.static_metadata_change(GLYPH_LOCATION_SLICES)
+            || self.static_metadata_change(NAME_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+            || self.be_missing(&BeWorkIdentifier::Fvar)
+    }
+
+    pub fn stat_be_change(&self) -> bool {
+        self.static_metadata_change(GLYPH_LOCATION_SLICES)
+            || self.static_metadata_change(NAME_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+            || self.be_missing(&BeWorkIdentifier::Stat)
+    }
+
+    pub fn hvar_be_change(&self) -> bool {
+        self.final_static_metadata_ir_change() || self.be_missing(&BeWorkIdentifier::Hvar)
+    }
+
+    pub fn vvar_be_change(&self) -> bool {
         self.final_static_metadata_ir_change()
-            || !self.be_paths.target_file(&BeWorkIdentifier::Fvar).is_file()
+            || self.global_metrics_ir_change()
+            || self.be_missing(&BeWorkIdentifier::Vvar)
     }
 
-    pub fn post_be_change(&self) -> bool {
+    pub fn mvar_be_change(&self) -> bool {
         self.final_static_metadata_ir_change()
-            || !self.be_paths.target_file(&BeWorkIdentifier::Post).is_file()
+            || self.global_metrics_ir_change()
+            || self.be_missing(&BeWorkIdentifier::Mvar)
+    }
+
+    pub fn cff_be_change(&self) -> bool {
+        self.final_static_metadata_ir_change()
+            || self.static_metadata_change(NAME_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+            || self.be_missing(&BeWorkIdentifier::Cff)
+    }
+
+    pub fn os2_be_change(&self) -> bool {
+        self.final_static_metadata_ir_change()
+            || self.static_metadata_change(CUSTOM_PARAMETER_SLICES)
+            || self.ir_missing(&FeWorkIdentifier::InitStaticMetadata)
+            || self.global_metrics_ir_change()
+            || self.feature_be_change()
+            || self.be_missing(&BeWorkIdentifier::Os2)
+    }
+
+    pub fn post_be_change(&self) -> bool {
+        self.final_static_metadata_ir_change() || self.be_missing(&BeWorkIdentifier::Post)
     }
 
     pub fn glyphs_changed(&self) -> IndexSet<GlyphName> {
         let glyph_iter = self.current_inputs.glyphs.iter();
 
-        if self.init_static_metadata_ir_change() {
+        if self.glyph_locations_ir_change() {
             return glyph_iter.map(|(name, _)| name).cloned().collect();
         }
-        glyph_iter
+        let changed: IndexSet<_> = glyph_iter
+            .clone()
             .filter_map(
                 |(glyph_name, curr_state)| match self.prev_inputs.glyphs.get(glyph_name) {
                     Some(prev_state) => {
                         // If the input changed or the output doesn't exist a rebuild is probably in order
                         (prev_state != curr_state
-                            || !self
-                                .ir_paths
-                                .target_file(&FeWorkIdentifier::Glyph(glyph_name.clone()))
-                                .exists())
+                            || self.ir_missing(&FeWorkIdentifier::Glyph(glyph_name.clone())))
                         .then_some(glyph_name)
                     }
                     None => Some(glyph_name),
                 },
             )
             .cloned()
-            .collect()
+            .collect();
+
+        // Font-wide quadratics split curves to suit every glyph that shares them, which
+        // takes the cubics of every glyph, not just of those that changed
+        if self.font_wide_quadratics && (!changed.is_empty() || !self.glyphs_deleted().is_empty()) {
+            return glyph_iter.map(|(name, _)| name).cloned().collect();
+        }
+        changed
     }
 
     pub fn glyphs_deleted(&self) -> IndexSet<GlyphName> {
@@ -57,7 +57,13 @@ impl Config {
         }
 
         let yml = fs::read_to_string(ir_input_file)?;
-        serde_yaml::from_str(&yml).map_err(Into::into)
+        match serde_yaml::from_str(&yml) {
+            Ok(prior_inputs) => Ok(prior_inputs),
+            Err(err) => {
+                log::warn!("Unable to parse prior inputs, rebuilding everything {err:#?}");
+                Ok(Input::new())
+            }
+        }
     }
 
     /// Compare this config to the saved config at the provided path.
@@ -119,4 +125,32 @@ mod tests {
         );
         assert!(!Config::new(args).unwrap().has_changed());
     }
+
+    #[test]
+    fn unreadable_prior_inputs_mean_no_prior_inputs() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let args = Args::for_test(build_dir, "wght_var.designspace");
+        let config = Config::new(args).unwrap();
+        config.init().unwrap();
+
+        let mut inputs = Input::new();
+        inputs
+            .static_metadata
+            .track_memory("/axes".to_string(), &"wght")
+            .unwrap();
+        let paths = IrPaths::new(build_dir);
+        let ir_input_file = paths.ir_input_file();
+        let yml = serde_yaml::to_string(&inputs).unwrap();
+        fs::write(ir_input_file, &yml).unwrap();
+        assert_eq!(inputs, config.init().unwrap());
+
+        // A hash that isn't one is no different to having no prior inputs at all
+        let hash = yml
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("hash: "))
+            .unwrap();
+        fs::write(ir_input_file, yml.replace(hash, "not a hash")).unwrap();
+        assert_eq!(Input::new(), config.init().unwrap());
+    }
 }
@@ -13,12 +13,16 @@ pub enum Error {
     IoError(#[from] io::Error),
     #[error("Font IR error")]
     FontIrError(#[from] fontir::error::Error),
+    #[error("Font BE error")]
+    FontBeError(#[from] fontbe::error::Error),
     #[error("Unable to produce IR")]
     IrGenerationError,
     #[error("Does not exist")]
     FileExpected(PathBuf),
     #[error("At least one work item failed")]
     TasksFailed(Vec<(AnyWorkId, String)>),
+    #[error("Watching for changes is only supported on Linux")]
+    WatchUnsupported,
     #[error("Invalid regex")]
     BadRegex(#[from] regex::Error),
 }
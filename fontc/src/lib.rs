@@ -4,6 +4,7 @@ mod args;
 mod change_detector;
 mod config;
 mod error;
+mod watch;
 pub mod work;
 mod workload;
 
@@ -11,6 +12,7 @@ pub use args::Args;
 pub use change_detector::ChangeDetector;
 pub use config::Config;
 pub use error::Error;
+pub use watch::{watch, IncrementalBuild};
 
 use work::ReadAccess;
 use workload::{Job, Workload};
@@ -23,24 +25,36 @@ use std::{
 
 use fontbe::{
     avar::create_avar_work,
+    cff::create_cff_work,
     cmap::create_cmap_work,
     features::FeatureWork,
     font::create_font_work,
     fvar::create_fvar_work,
+    gdef::create_gdef_work,
     glyphs::{create_glyf_loca_work, create_glyf_work},
     gvar::create_gvar_work,
     head::create_head_work,
+    hvar::create_hvar_work,
+    kern::create_kerning_work,
+    marks::create_mark_work,
     maxp::create_maxp_work,
     metrics_and_limits::create_metric_and_limit_work,
+    mvar::create_mvar_work,
     name::create_name_work,
     orchestration::{AnyWorkId, WorkId as BeWorkIdentifier},
     os2::create_os2_work,
     post::create_post_work,
+    stat::create_stat_work,
+    vvar::create_vvar_work,
+    woff::woff,
+    woff2::woff2,
 };
 
 use fontdrasil::{orchestration::Access, types::GlyphName};
 use fontir::{
     glyph::create_finalize_static_metadata_work,
+    instance::InstanceIrSource,
+    ir::NamedInstance,
     orchestration::{Context as FeContext, WorkId as FeWorkIdentifier},
     source::DeleteWork,
 };
@@ -64,7 +78,10 @@ pub fn require_dir(dir: &Path) -> Result<PathBuf, io::Error> {
 
 pub fn init_paths(args: &Args) -> Result<(IrPaths, BePaths), Error> {
     let ir_paths = IrPaths::new(&args.build_dir);
-    let be_paths = BePaths::new(&args.build_dir);
+    let mut be_paths = BePaths::new(&args.build_dir);
+    if args.cff {
+        be_paths = be_paths.with_cff_outlines();
+    }
 
     require_dir(ir_paths.build_dir())?;
     if args.emit_ir {
@@ -89,9 +106,62 @@ pub fn write_font_file(args: &Args, be_context: &BeContext) -> Result<(), Error>
     } else if !font_file.exists() {
         return Err(Error::FileExpected(font_file));
     }
+    if args.woff {
+        let woff = woff(be_context.get_font().get())?;
+        fs::write(be_context.paths.woff_file(), woff).map_err(Error::IoError)?;
+    }
+    if args.woff2 {
+        let woff2 = woff2(be_context.get_font().get())?;
+        fs::write(be_context.paths.woff2_file(), woff2).map_err(Error::IoError)?;
+    }
     Ok(())
 }
 
+/// Build a static font for instance from the IR of the variable font in variable.
+///
+/// Each instance gets a build directory of its own under the one in args so
+/// instances whose inputs haven't changed since the last build are skipped.
+pub fn compile_instance(
+    args: &Args,
+    variable: &FeContext,
+    instance: &NamedInstance,
+) -> Result<(), Error> {
+    let instances_dir = require_dir(&args.build_dir.join("instances"))?;
+    let dir_name = instance
+        .postscript_name
+        .clone()
+        .unwrap_or_else(|| instance.name.replace(' ', ""));
+    let args = Args {
+        build_dir: instances_dir.join(dir_name),
+        instances: false,
+        ..args.clone()
+    };
+    debug!("Build {} in {:?}", instance.name, args.build_dir);
+
+    let (ir_paths, be_paths) = init_paths(&args)?;
+    let config = Config::new(args)?;
+    let prev_inputs = config.init()?;
+
+    let ir_source = Box::new(InstanceIrSource::new(variable, instance.clone()));
+    let mut change_detector =
+        ChangeDetector::with_ir_source(config.clone(), ir_paths.clone(), prev_inputs, ir_source)?;
+    let workload = create_workload(&mut change_detector)?;
+
+    let fe_root = FeContext::new_root(
+        config.args.flags(),
+        ir_paths,
+        change_detector.current_inputs().clone(),
+    )
+    .with_max_conversion_error(config.args.max_conversion_error());
+    let be_root = BeContext::new_root(config.args.flags(), be_paths, &fe_root)
+        .with_iup_tolerance(config.args.iup_tolerance);
+    workload.exec(&fe_root, &be_root)?;
+
+    change_detector.finish_successfully()?;
+
+    write_font_file(&config.args, &be_root)
+}
+
 fn add_init_static_metadata_ir_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
@@ -125,9 +195,13 @@ fn add_finalize_static_metadata_ir_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
 ) -> Result<(), Error> {
-    if change_detector.final_static_metadata_ir_change() {
-        let mut dependencies: HashSet<_> = change_detector
-            .glyphs_changed()
+    let glyphs_changed = change_detector.glyphs_changed();
+
+    // Glyphs that changed need the same processing, such as overlap removal, as in a full build
+    if change_detector.final_static_metadata_ir_change() || !glyphs_changed.is_empty() {
+        // Unless static metadata changed only new glyphs, such as split alternates, need BE work
+        workload.rebuild_all_glyphs = change_detector.final_static_metadata_ir_change();
+        let mut dependencies: HashSet<_> = glyphs_changed
             .iter()
             .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
             .collect();
@@ -219,11 +293,42 @@ fn add_feature_ir_job(
     Ok(())
 }
 
+fn add_kerning_ir_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    if change_detector.kerning_ir_change() {
+        let id: AnyWorkId = FeWorkIdentifier::Kerning.into();
+        let write_access = Access::one(id.clone());
+        workload.insert(
+            id,
+            Job {
+                work: change_detector
+                    .ir_source()
+                    .create_kerning_ir_work(change_detector.current_inputs())?
+                    .into(),
+                dependencies: HashSet::from([FeWorkIdentifier::InitStaticMetadata.into()]),
+                read_access: ReadAccess::Dependencies,
+                write_access,
+            },
+        );
+    } else {
+        workload.mark_success(FeWorkIdentifier::Kerning);
+    }
+    Ok(())
+}
+
 fn add_feature_be_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
 ) -> Result<(), Error> {
-    if change_detector.feature_be_change() && change_detector.glyph_name_filter().is_none() {
+    // Glyphs contribute anchors, and kerning pairs, to the features we generate
+    let glyphs_changed = !change_detector.glyphs_changed().is_empty();
+    if (change_detector.feature_be_change()
+        || change_detector.kerning_be_change()
+        || glyphs_changed)
+        && change_detector.glyph_name_filter().is_none()
+    {
         let id: AnyWorkId = BeWorkIdentifier::Features.into();
         let write_access = Access::one(id.clone());
         workload.insert(
@@ -233,6 +338,8 @@ fn add_feature_be_job(
                 dependencies: HashSet::from([
                     FeWorkIdentifier::FinalizeStaticMetadata.into(),
                     FeWorkIdentifier::Features.into(),
+                    BeWorkIdentifier::Kerning.into(),
+                    BeWorkIdentifier::Marks.into(),
                 ]),
                 read_access: ReadAccess::Dependencies,
                 write_access,
@@ -248,6 +355,109 @@ fn add_feature_be_job(
     Ok(())
 }
 
+fn add_gdef_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    // GDEF builds on the GDEF from features so it has to run whenever they do
+    let glyphs_changed = change_detector.glyphs_changed();
+    if (change_detector.feature_be_change()
+        || change_detector.kerning_be_change()
+        || !glyphs_changed.is_empty())
+        && change_detector.glyph_name_filter().is_none()
+    {
+        let mut dependencies: HashSet<_> = glyphs_changed
+            .iter()
+            .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
+            .collect();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
+        dependencies.insert(BeWorkIdentifier::Features.into());
+
+        let id: AnyWorkId = BeWorkIdentifier::Gdef.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_gdef_work().into(),
+                dependencies,
+                // We need to read all glyph IR, even unchanged ones, plus static metadata and features
+                read_access: ReadAccess::custom(|id| {
+                    matches!(
+                        id,
+                        AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))
+                            | AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata)
+                            | AnyWorkId::Be(BeWorkIdentifier::Features)
+                    )
+                }),
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Gdef);
+    }
+    Ok(())
+}
+
+fn add_kerning_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    if change_detector.kerning_be_change() && change_detector.glyph_name_filter().is_none() {
+        let id: AnyWorkId = BeWorkIdentifier::Kerning.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_kerning_work().into(),
+                dependencies: HashSet::from([
+                    FeWorkIdentifier::FinalizeStaticMetadata.into(),
+                    FeWorkIdentifier::Kerning.into(),
+                ]),
+                read_access: ReadAccess::Dependencies,
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Kerning);
+    }
+    Ok(())
+}
+
+fn add_marks_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    let glyphs_changed = change_detector.glyphs_changed();
+
+    // Anchors live on glyphs, if none changed neither did marks
+    if !glyphs_changed.is_empty() && change_detector.glyph_name_filter().is_none() {
+        let mut dependencies: HashSet<_> = glyphs_changed
+            .iter()
+            .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
+            .collect();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
+
+        let id: AnyWorkId = BeWorkIdentifier::Marks.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_mark_work().into(),
+                dependencies,
+                // We need to read all glyph IR, even unchanged ones, plus static metadata
+                read_access: ReadAccess::custom(|id| {
+                    matches!(
+                        id,
+                        AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))
+                            | AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata)
+                    )
+                }),
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Marks);
+    }
+    Ok(())
+}
+
 fn add_glyph_ir_jobs(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
@@ -299,8 +509,9 @@ fn add_glyf_loca_be_job(
 ) -> Result<(), Error> {
     let glyphs_changed = change_detector.glyphs_changed();
 
-    // If no glyph has changed there isn't a lot of merging to do
-    if !glyphs_changed.is_empty() {
+    // If no glyph has changed there isn't a lot of merging to do. Finalizing static metadata
+    // adds every glyph it doesn't see being rebuilt so we have to merge then too.
+    if !glyphs_changed.is_empty() || change_detector.final_static_metadata_ir_change() {
         let mut dependencies: HashSet<_> = glyphs_changed
             .iter()
             .map(|gn| BeWorkIdentifier::GlyfFragment(gn.clone()).into())
@@ -397,8 +608,9 @@ fn add_gvar_be_job(
 ) -> Result<(), Error> {
     let glyphs_changed = change_detector.glyphs_changed();
 
-    // If no glyph has changed there isn't a lot of merging to do
-    if !glyphs_changed.is_empty() {
+    // If no glyph has changed there isn't a lot of merging to do. Finalizing static metadata
+    // adds every glyph it doesn't see being rebuilt so we have to merge then too.
+    if !glyphs_changed.is_empty() || change_detector.final_static_metadata_ir_change() {
         let mut dependencies: HashSet<_> = glyphs_changed
             .iter()
             .map(|gn| BeWorkIdentifier::GvarFragment(gn.clone()).into())
@@ -481,6 +693,30 @@ fn add_post_be_job(
     Ok(())
 }
 
+fn add_stat_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    if change_detector.stat_be_change() {
+        let mut dependencies = HashSet::new();
+        dependencies.insert(FeWorkIdentifier::InitStaticMetadata.into());
+
+        let id: AnyWorkId = BeWorkIdentifier::Stat.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_stat_work().into(),
+                dependencies,
+                read_access: ReadAccess::Dependencies,
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Stat);
+    }
+    Ok(())
+}
+
 fn add_head_be_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
@@ -536,7 +772,7 @@ fn add_name_be_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
 ) -> Result<(), Error> {
-    if change_detector.init_static_metadata_ir_change() {
+    if change_detector.name_be_change() {
         let mut dependencies = HashSet::new();
         dependencies.insert(FeWorkIdentifier::InitStaticMetadata.into());
 
@@ -560,10 +796,16 @@ fn add_os2_be_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
 ) -> Result<(), Error> {
-    if change_detector.init_static_metadata_ir_change() {
-        let mut dependencies = HashSet::new();
-        dependencies.insert(FeWorkIdentifier::InitStaticMetadata.into());
+    // Unicode ranges and the average width come from glyphs, max context from features
+    let glyphs_changed = change_detector.glyphs_changed();
+    if change_detector.os2_be_change() || !glyphs_changed.is_empty() {
+        let mut dependencies: HashSet<_> = glyphs_changed
+            .iter()
+            .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
+            .collect();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
         dependencies.insert(FeWorkIdentifier::GlobalMetrics.into());
+        dependencies.insert(BeWorkIdentifier::Features.into());
 
         let id: AnyWorkId = BeWorkIdentifier::Os2.into();
         workload.insert(
@@ -571,7 +813,17 @@ fn add_os2_be_job(
             Job {
                 work: create_os2_work().into(),
                 dependencies,
-                read_access: ReadAccess::Dependencies,
+                // We need to read all glyph IR, even unchanged ones, plus metadata, metrics and features
+                read_access: ReadAccess::custom(|id| {
+                    matches!(
+                        id,
+                        AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))
+                            | AnyWorkId::Fe(FeWorkIdentifier::InitStaticMetadata)
+                            | AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata)
+                            | AnyWorkId::Fe(FeWorkIdentifier::GlobalMetrics)
+                            | AnyWorkId::Be(BeWorkIdentifier::Features)
+                    )
+                }),
                 write_access: Access::one(id),
             },
         );
@@ -587,8 +839,12 @@ fn add_metric_and_limits_job(
 ) -> Result<(), Error> {
     let glyphs_changed = change_detector.glyphs_changed();
 
-    // If no glyph has changed there isn't a lot to do
-    if !glyphs_changed.is_empty() {
+    // If no glyph has changed there isn't a lot to do, unless finalizing static metadata
+    // means every glyph is rebuilt or the master metrics behind hhea changed
+    if !glyphs_changed.is_empty()
+        || change_detector.final_static_metadata_ir_change()
+        || change_detector.global_metrics_ir_change()
+    {
         let mut dependencies: HashSet<_> = glyphs_changed
             .iter()
             .flat_map(|gn| {
@@ -622,6 +878,8 @@ fn add_metric_and_limits_job(
                         id,
                         AnyWorkId::Be(BeWorkIdentifier::Hmtx)
                             | AnyWorkId::Be(BeWorkIdentifier::Hhea)
+                            | AnyWorkId::Be(BeWorkIdentifier::Vmtx)
+                            | AnyWorkId::Be(BeWorkIdentifier::Vhea)
                             | AnyWorkId::Be(BeWorkIdentifier::Maxp)
                     )
                 }),
@@ -629,114 +887,297 @@ fn add_metric_and_limits_job(
         );
     } else {
         workload.mark_success(BeWorkIdentifier::Hmtx);
+        workload.mark_success(BeWorkIdentifier::Hhea);
+        workload.mark_success(BeWorkIdentifier::Vmtx);
+        workload.mark_success(BeWorkIdentifier::Vhea);
     }
     Ok(())
 }
 
-fn add_font_be_job(
+fn add_hvar_be_job(
     change_detector: &mut ChangeDetector,
     workload: &mut Workload,
 ) -> Result<(), Error> {
     let glyphs_changed = change_detector.glyphs_changed();
-
-    // If glyphs or features changed we better do the thing
-    if !glyphs_changed.is_empty() || change_detector.feature_be_change() {
-        let mut dependencies = HashSet::new();
+    if change_detector.hvar_be_change() || !glyphs_changed.is_empty() {
+        let mut dependencies: HashSet<_> = glyphs_changed
+            .iter()
+            .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
+            .collect();
         dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
-        dependencies.insert(BeWorkIdentifier::Features.into());
-        dependencies.insert(BeWorkIdentifier::Avar.into());
-        dependencies.insert(BeWorkIdentifier::Cmap.into());
-        dependencies.insert(BeWorkIdentifier::Fvar.into());
-        dependencies.insert(BeWorkIdentifier::Glyf.into());
-        dependencies.insert(BeWorkIdentifier::Gvar.into());
-        dependencies.insert(BeWorkIdentifier::Head.into());
-        dependencies.insert(BeWorkIdentifier::Hhea.into());
-        dependencies.insert(BeWorkIdentifier::Hmtx.into());
-        dependencies.insert(BeWorkIdentifier::Loca.into());
-        dependencies.insert(BeWorkIdentifier::LocaFormat.into());
-        dependencies.insert(BeWorkIdentifier::Maxp.into());
-        dependencies.insert(BeWorkIdentifier::Name.into());
-        dependencies.insert(BeWorkIdentifier::Os2.into());
-        dependencies.insert(BeWorkIdentifier::Post.into());
 
-        let id: AnyWorkId = BeWorkIdentifier::Font.into();
+        let id: AnyWorkId = BeWorkIdentifier::Hvar.into();
         workload.insert(
             id.clone(),
             Job {
-                work: create_font_work().into(),
+                work: create_hvar_work().into(),
                 dependencies,
-                read_access: ReadAccess::Dependencies,
+                // We need to read all glyph IR, even unchanged ones, plus static metadata
+                read_access: ReadAccess::custom(|id| {
+                    matches!(
+                        id,
+                        AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))
+                            | AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata)
+                    )
+                }),
                 write_access: Access::one(id),
             },
         );
     } else {
-        workload.mark_success(BeWorkIdentifier::Font);
+        workload.mark_success(BeWorkIdentifier::Hvar);
     }
     Ok(())
 }
 
-fn add_glyph_be_job(workload: &mut Workload, fe_root: &FeContext, glyph_name: GlyphName) {
-    let glyph_ir = fe_root.get_glyph_ir(&glyph_name);
-
-    // To build a glyph we need it's components, plus static metadata
-    let mut dependencies: HashSet<_> = glyph_ir
-        .sources()
-        .values()
-        .flat_map(|s| &s.components)
-        .map(|c| AnyWorkId::Fe(FeWorkIdentifier::Glyph(c.base.clone())))
-        .collect();
-    dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
-
-    let id = AnyWorkId::Be(BeWorkIdentifier::GlyfFragment(glyph_name.clone()));
-    let gvar_id = AnyWorkId::Be(BeWorkIdentifier::GvarFragment(glyph_name.clone()));
+fn add_vvar_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    let glyphs_changed = change_detector.glyphs_changed();
+    if change_detector.vvar_be_change() || !glyphs_changed.is_empty() {
+        let mut dependencies: HashSet<_> = glyphs_changed
+            .iter()
+            .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
+            .collect();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
+        dependencies.insert(FeWorkIdentifier::GlobalMetrics.into());
 
-    // this job should already be a dependency of glyf, gvar, and hmtx; if not terrible things will happen
-    if !workload.is_dependency(&BeWorkIdentifier::Glyf.into(), &id) {
-        panic!("BE glyph '{glyph_name}' is being built but not participating in glyf",);
-    }
-    if !workload.is_dependency(&BeWorkIdentifier::Gvar.into(), &gvar_id) {
-        panic!("BE glyph '{glyph_name}' is being built but not participating in gvar",);
-    }
-    if !workload.is_dependency(&BeWorkIdentifier::Hmtx.into(), &id) {
-        panic!("BE glyph '{glyph_name}' is being built but not participating in hmtx",);
+        let id: AnyWorkId = BeWorkIdentifier::Vvar.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_vvar_work().into(),
+                dependencies,
+                // We need to read all glyph IR, even unchanged ones, plus static metadata and metrics
+                read_access: ReadAccess::custom(|id| {
+                    matches!(
+                        id,
+                        AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))
+                            | AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata)
+                            | AnyWorkId::Fe(FeWorkIdentifier::GlobalMetrics)
+                    )
+                }),
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Vvar);
     }
-
-    let write_access = Access::Set(HashSet::from([id.clone(), gvar_id]));
-    workload.insert(
-        id,
-        Job {
-            work: create_glyf_work(glyph_name).into(),
-            dependencies,
-            read_access: ReadAccess::Dependencies,
-            write_access,
-        },
-    );
+    Ok(())
 }
 
-//FIXME: I should be a method on ChangeDetector
-pub fn create_workload(change_detector: &mut ChangeDetector) -> Result<Workload, Error> {
-    let mut workload = Workload::new();
+fn add_cff_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    let glyphs_changed = change_detector.glyphs_changed();
+    if change_detector.cff_outlines()
+        && (change_detector.cff_be_change() || !glyphs_changed.is_empty())
+    {
+        let mut dependencies: HashSet<_> = glyphs_changed
+            .iter()
+            .map(|gn| FeWorkIdentifier::Glyph(gn.clone()).into())
+            .collect();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
 
-    // FE: f(source) => IR
-    add_init_static_metadata_ir_job(change_detector, &mut workload)?;
-    add_global_metric_ir_job(change_detector, &mut workload)?;
-    add_feature_ir_job(change_detector, &mut workload)?;
-    add_glyph_ir_jobs(change_detector, &mut workload)?;
+        // We write CFF and CFF2, one of them empty
+        let write_access = Access::custom(|id| {
+            matches!(
+                id,
+                AnyWorkId::Be(BeWorkIdentifier::Cff) | AnyWorkId::Be(BeWorkIdentifier::Cff2)
+            )
+        });
+        let id: AnyWorkId = BeWorkIdentifier::Cff.into();
+        workload.insert(
+            id,
+            Job {
+                work: create_cff_work().into(),
+                dependencies,
+                // We need to read all glyph IR, even unchanged ones, plus static metadata
+                read_access: ReadAccess::custom(|id| {
+                    matches!(
+                        id,
+                        AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))
+                            | AnyWorkId::Fe(FeWorkIdentifier::InitStaticMetadata)
+                            | AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata)
+                    )
+                }),
+                write_access,
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Cff);
+        workload.mark_success(BeWorkIdentifier::Cff2);
+    }
+    Ok(())
+}
+
+fn add_mvar_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    if change_detector.mvar_be_change() {
+        let mut dependencies = HashSet::new();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
+        dependencies.insert(FeWorkIdentifier::GlobalMetrics.into());
+
+        let id: AnyWorkId = BeWorkIdentifier::Mvar.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_mvar_work().into(),
+                dependencies,
+                read_access: ReadAccess::Dependencies,
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Mvar);
+    }
+    Ok(())
+}
+
+fn add_font_be_job(
+    change_detector: &mut ChangeDetector,
+    workload: &mut Workload,
+) -> Result<(), Error> {
+    let glyphs_changed = change_detector.glyphs_changed();
+
+    // If glyphs, features, kerning, names or tables built from global metrics changed we better do the thing
+    if !glyphs_changed.is_empty()
+        || change_detector.feature_be_change()
+        || change_detector.kerning_be_change()
+        || change_detector.os2_be_change()
+        || change_detector.mvar_be_change()
+        || change_detector.vvar_be_change()
+        || change_detector.name_be_change()
+    {
+        let mut dependencies = HashSet::new();
+        dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
+        dependencies.insert(BeWorkIdentifier::Features.into());
+        dependencies.insert(BeWorkIdentifier::Avar.into());
+        dependencies.insert(BeWorkIdentifier::Cff.into());
+        dependencies.insert(BeWorkIdentifier::Cff2.into());
+        dependencies.insert(BeWorkIdentifier::Cmap.into());
+        dependencies.insert(BeWorkIdentifier::Fvar.into());
+        dependencies.insert(BeWorkIdentifier::Gdef.into());
+        dependencies.insert(BeWorkIdentifier::Glyf.into());
+        dependencies.insert(BeWorkIdentifier::Gvar.into());
+        dependencies.insert(BeWorkIdentifier::Head.into());
+        dependencies.insert(BeWorkIdentifier::Hhea.into());
+        dependencies.insert(BeWorkIdentifier::Hmtx.into());
+        dependencies.insert(BeWorkIdentifier::Hvar.into());
+        dependencies.insert(BeWorkIdentifier::Kerning.into());
+        dependencies.insert(BeWorkIdentifier::Loca.into());
+        dependencies.insert(BeWorkIdentifier::LocaFormat.into());
+        dependencies.insert(BeWorkIdentifier::Marks.into());
+        dependencies.insert(BeWorkIdentifier::Maxp.into());
+        dependencies.insert(BeWorkIdentifier::Mvar.into());
+        dependencies.insert(BeWorkIdentifier::Name.into());
+        dependencies.insert(BeWorkIdentifier::Os2.into());
+        dependencies.insert(BeWorkIdentifier::Post.into());
+        dependencies.insert(BeWorkIdentifier::Stat.into());
+        dependencies.insert(BeWorkIdentifier::Vhea.into());
+        dependencies.insert(BeWorkIdentifier::Vmtx.into());
+        dependencies.insert(BeWorkIdentifier::Vvar.into());
+
+        let id: AnyWorkId = BeWorkIdentifier::Font.into();
+        workload.insert(
+            id.clone(),
+            Job {
+                work: create_font_work().into(),
+                dependencies,
+                read_access: ReadAccess::Dependencies,
+                write_access: Access::one(id),
+            },
+        );
+    } else {
+        workload.mark_success(BeWorkIdentifier::Font);
+    }
+    Ok(())
+}
+
+fn add_glyph_be_job(workload: &mut Workload, fe_root: &FeContext, glyph_name: GlyphName) {
+    let glyph_ir = fe_root.get_glyph_ir(&glyph_name);
+
+    // To build a glyph we need it's components, plus static metadata and, for the vertical
+    // phantom points, global metrics
+    let mut dependencies: HashSet<_> = glyph_ir
+        .sources()
+        .values()
+        .flat_map(|s| &s.components)
+        .map(|c| AnyWorkId::Fe(FeWorkIdentifier::Glyph(c.base.clone())))
+        .collect();
+    dependencies.insert(FeWorkIdentifier::FinalizeStaticMetadata.into());
+    dependencies.insert(FeWorkIdentifier::GlobalMetrics.into());
+
+    let id = AnyWorkId::Be(BeWorkIdentifier::GlyfFragment(glyph_name.clone()));
+    let gvar_id = AnyWorkId::Be(BeWorkIdentifier::GvarFragment(glyph_name.clone()));
+
+    // this job should already be a dependency of glyf, gvar, and hmtx; if not terrible things will happen
+    if !workload.is_dependency(&BeWorkIdentifier::Glyf.into(), &id) {
+        panic!("BE glyph '{glyph_name}' is being built but not participating in glyf",);
+    }
+    if !workload.is_dependency(&BeWorkIdentifier::Gvar.into(), &gvar_id) {
+        panic!("BE glyph '{glyph_name}' is being built but not participating in gvar",);
+    }
+    if !workload.is_dependency(&BeWorkIdentifier::Hmtx.into(), &id) {
+        panic!("BE glyph '{glyph_name}' is being built but not participating in hmtx",);
+    }
+
+    // Overlap detection looks through nested components, which may be any glyph. All glyph IR
+    // is complete before static metadata is final, which we depend on.
+    let read_access = {
+        let dependencies = dependencies.clone();
+        ReadAccess::custom(move |id| {
+            matches!(id, AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))) || dependencies.contains(id)
+        })
+    };
+    let write_access = Access::Set(HashSet::from([id.clone(), gvar_id]));
+    workload.insert(
+        id,
+        Job {
+            work: create_glyf_work(glyph_name).into(),
+            dependencies,
+            read_access,
+            write_access,
+        },
+    );
+}
+
+//FIXME: I should be a method on ChangeDetector
+pub fn create_workload(change_detector: &mut ChangeDetector) -> Result<Workload, Error> {
+    let mut workload = Workload::new();
+
+    // FE: f(source) => IR
+    add_init_static_metadata_ir_job(change_detector, &mut workload)?;
+    add_global_metric_ir_job(change_detector, &mut workload)?;
+    add_feature_ir_job(change_detector, &mut workload)?;
+    add_kerning_ir_job(change_detector, &mut workload)?;
+    add_glyph_ir_jobs(change_detector, &mut workload)?;
     add_finalize_static_metadata_ir_job(change_detector, &mut workload)?;
 
     // BE: f(IR) => binary
+    add_kerning_be_job(change_detector, &mut workload)?;
+    add_marks_be_job(change_detector, &mut workload)?;
     add_feature_be_job(change_detector, &mut workload)?;
+    add_gdef_be_job(change_detector, &mut workload)?;
     add_glyf_loca_be_job(change_detector, &mut workload)?;
     add_avar_be_job(change_detector, &mut workload)?;
+    add_cff_be_job(change_detector, &mut workload)?;
     add_cmap_be_job(change_detector, &mut workload)?;
     add_fvar_be_job(change_detector, &mut workload)?;
     add_gvar_be_job(change_detector, &mut workload)?;
     add_head_be_job(change_detector, &mut workload)?;
     add_metric_and_limits_job(change_detector, &mut workload)?;
+    add_hvar_be_job(change_detector, &mut workload)?;
     add_name_be_job(change_detector, &mut workload)?;
     add_maxp_be_job(change_detector, &mut workload)?;
+    add_mvar_be_job(change_detector, &mut workload)?;
     add_os2_be_job(change_detector, &mut workload)?;
     add_post_be_job(change_detector, &mut workload)?;
+    add_stat_be_job(change_detector, &mut workload)?;
+    add_vvar_be_job(change_detector, &mut workload)?;
 
     // Make a damn font
     add_font_be_job(change_detector, &mut workload)?;
@@ -753,6 +1194,7 @@ mod tests {
         io::Read,
         path::{Path, PathBuf},
         str::FromStr,
+        sync::Arc,
     };
 
     use fontbe::orchestration::{
@@ -761,6 +1203,7 @@ mod tests {
     };
     use fontdrasil::types::GlyphName;
     use fontir::{
+        coords::UserCoord,
         ir,
         orchestration::{Context as FeContext, WorkId as FeWorkIdentifier},
     };
@@ -778,14 +1221,24 @@ mod tests {
                 glyf::{self, CompositeGlyph, CurvePoint, Glyf, SimpleGlyph},
                 hmtx::Hmtx,
                 loca::Loca,
+                stat::AxisValue,
+                variations::{DeltaSetIndex, ItemVariationStore},
             },
-            types::F2Dot14,
+            types::{F2Dot14, NameId, Version16Dot16},
             FontData, FontRead, FontReadWithArgs, FontRef, TableProvider,
         },
         GlyphId, Tag,
     };
     use tempfile::{tempdir, TempDir};
-    use write_fonts::dump_table;
+    use write_fonts::{
+        dump_table,
+        from_obj::ToOwnedTable,
+        tables::{
+            gpos::{Gpos, PairPos, PositionLookup},
+            gsub::{Gsub, SingleSubst, SubstitutionLookup},
+            layout::ClassDef,
+        },
+    };
 
     use super::*;
 
@@ -876,19 +1329,28 @@ mod tests {
         add_finalize_static_metadata_ir_job(&mut change_detector, &mut workload).unwrap();
         add_glyph_ir_jobs(&mut change_detector, &mut workload).unwrap();
         add_feature_ir_job(&mut change_detector, &mut workload).unwrap();
+        add_kerning_ir_job(&mut change_detector, &mut workload).unwrap();
+        add_kerning_be_job(&mut change_detector, &mut workload).unwrap();
+        add_marks_be_job(&mut change_detector, &mut workload).unwrap();
         add_feature_be_job(&mut change_detector, &mut workload).unwrap();
+        add_gdef_be_job(&mut change_detector, &mut workload).unwrap();
 
         add_glyf_loca_be_job(&mut change_detector, &mut workload).unwrap();
         add_avar_be_job(&mut change_detector, &mut workload).unwrap();
+        add_cff_be_job(&mut change_detector, &mut workload).unwrap();
         add_cmap_be_job(&mut change_detector, &mut workload).unwrap();
         add_fvar_be_job(&mut change_detector, &mut workload).unwrap();
         add_gvar_be_job(&mut change_detector, &mut workload).unwrap();
         add_head_be_job(&mut change_detector, &mut workload).unwrap();
         add_metric_and_limits_job(&mut change_detector, &mut workload).unwrap();
+        add_hvar_be_job(&mut change_detector, &mut workload).unwrap();
         add_maxp_be_job(&mut change_detector, &mut workload).unwrap();
+        add_mvar_be_job(&mut change_detector, &mut workload).unwrap();
         add_name_be_job(&mut change_detector, &mut workload).unwrap();
         add_os2_be_job(&mut change_detector, &mut workload).unwrap();
         add_post_be_job(&mut change_detector, &mut workload).unwrap();
+        add_stat_be_job(&mut change_detector, &mut workload).unwrap();
+        add_vvar_be_job(&mut change_detector, &mut workload).unwrap();
 
         add_font_be_job(&mut change_detector, &mut workload).unwrap();
 
@@ -900,8 +1362,10 @@ mod tests {
             config.args.flags(),
             ir_paths,
             change_detector.current_inputs().clone(),
-        );
-        let be_root = BeContext::new_root(config.args.flags(), be_paths, &fe_root.read_only());
+        )
+        .with_max_conversion_error(config.args.max_conversion_error());
+        let be_root = BeContext::new_root(config.args.flags(), be_paths, &fe_root.read_only())
+            .with_iup_tolerance(config.args.iup_tolerance);
         let mut result = TestCompile::new(
             &change_detector,
             fe_root.read_only(),
@@ -933,10 +1397,12 @@ mod tests {
                 FeWorkIdentifier::Glyph("plus".into()).into(),
                 FeWorkIdentifier::FinalizeStaticMetadata.into(),
                 FeWorkIdentifier::Features.into(),
+                FeWorkIdentifier::Kerning.into(),
                 BeWorkIdentifier::Features.into(),
                 BeWorkIdentifier::Avar.into(),
                 BeWorkIdentifier::Cmap.into(),
                 BeWorkIdentifier::Fvar.into(),
+                BeWorkIdentifier::Gdef.into(),
                 BeWorkIdentifier::Glyf.into(),
                 BeWorkIdentifier::GlyfFragment("bar".into()).into(),
                 BeWorkIdentifier::GlyfFragment("plus".into()).into(),
@@ -946,12 +1412,20 @@ mod tests {
                 BeWorkIdentifier::Head.into(),
                 BeWorkIdentifier::Hhea.into(),
                 BeWorkIdentifier::Hmtx.into(),
+                BeWorkIdentifier::Hvar.into(),
+                BeWorkIdentifier::Kerning.into(),
                 BeWorkIdentifier::Loca.into(),
                 BeWorkIdentifier::LocaFormat.into(),
+                BeWorkIdentifier::Marks.into(),
                 BeWorkIdentifier::Maxp.into(),
+                BeWorkIdentifier::Mvar.into(),
                 BeWorkIdentifier::Name.into(),
                 BeWorkIdentifier::Os2.into(),
                 BeWorkIdentifier::Post.into(),
+                BeWorkIdentifier::Stat.into(),
+                BeWorkIdentifier::Vhea.into(),
+                BeWorkIdentifier::Vmtx.into(),
+                BeWorkIdentifier::Vvar.into(),
                 BeWorkIdentifier::Font.into(),
             ],
             completed
@@ -993,16 +1467,25 @@ mod tests {
         assert_eq!(
             vec![
                 AnyWorkId::Fe(FeWorkIdentifier::Glyph("bar".into())),
+                FeWorkIdentifier::FinalizeStaticMetadata.into(),
+                BeWorkIdentifier::Features.into(),
                 BeWorkIdentifier::Cmap.into(),
+                BeWorkIdentifier::Gdef.into(),
                 BeWorkIdentifier::Glyf.into(),
                 BeWorkIdentifier::GlyfFragment("bar".into()).into(),
                 BeWorkIdentifier::Gvar.into(),
                 BeWorkIdentifier::GvarFragment("bar".into()).into(),
                 BeWorkIdentifier::Hhea.into(),
                 BeWorkIdentifier::Hmtx.into(),
+                BeWorkIdentifier::Hvar.into(),
                 BeWorkIdentifier::Loca.into(),
                 BeWorkIdentifier::LocaFormat.into(),
+                BeWorkIdentifier::Marks.into(),
                 BeWorkIdentifier::Maxp.into(),
+                BeWorkIdentifier::Os2.into(),
+                BeWorkIdentifier::Vhea.into(),
+                BeWorkIdentifier::Vmtx.into(),
+                BeWorkIdentifier::Vvar.into(),
                 BeWorkIdentifier::Font.into(),
             ],
             completed
@@ -1175,6 +1658,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn looser_conversion_error_means_fewer_points() {
+        let num_points = |conversion_error_units: f64| {
+            let temp_dir = tempdir().unwrap();
+            let build_dir = temp_dir.path();
+            let mut args = Args::for_test(build_dir, "glyphs3/Oswald-O.glyphs");
+            args.conversion_error_units = Some(conversion_error_units);
+            let result = compile(args);
+
+            let glyph_data = result.glyphs();
+            let glyphs = glyph_data.read();
+            let glyf::Glyph::Simple(glyph) = &glyphs[result.get_glyph_index("O") as usize] else {
+                panic!("Expected 'O' to be a simple glyph");
+            };
+            glyph.num_points()
+        };
+
+        assert!(num_points(10.0) < num_points(0.1));
+    }
+
+    #[test]
+    fn font_wide_quadratics_split_shared_curves_alike() {
+        let num_points = |font_wide_quadratics: bool| {
+            let temp_dir = tempdir().unwrap();
+            let build_dir = temp_dir.path();
+            let mut args = Args::for_test(build_dir, "glyphs3/WghtVar_SharedCurve.glyphs");
+            args.font_wide_quadratics = font_wide_quadratics;
+            let result = compile(args);
+
+            let glyph_data = result.glyphs();
+            let glyphs = glyph_data.read();
+            ["bowl", "bowl.big"].map(|name| {
+                let glyf::Glyph::Simple(glyph) = &glyphs[result.get_glyph_index(name) as usize]
+                else {
+                    panic!("Expected '{name}' to be a simple glyph");
+                };
+                glyph.num_points()
+            })
+        };
+
+        // bowl.big is bowl scaled up in bold, decomposed because its 2x2 varies, so the
+        // same curve at the default is split separately to fit each glyph
+        let [bowl, big] = num_points(false);
+        assert!(big > bowl, "{big} should exceed {bowl}");
+
+        // Split together the curve takes as many splits as the most demanding copy needs
+        let [bowl, big] = num_points(true);
+        assert_eq!(bowl, big);
+    }
+
+    #[test]
+    fn rebuild_with_font_wide_quadratics_matches_full_build() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "glyphs3/WghtVar_SharedCurve.glyphs");
+        let source = temp_dir.path().join("WghtVar_SharedCurve.glyphs");
+        let glyphs = fs::read_to_string(&args.source).unwrap();
+        fs::write(
+            &source,
+            glyphs.replace("scale = (3,3);", "scale = (1.5,1.5);"),
+        )
+        .unwrap();
+        args.source = source.clone();
+        args.font_wide_quadratics = true;
+        compile(args.clone());
+        let bowl_before = glyph_glyf_bytes(&build_dir, "bowl");
+
+        // Only bowl.big changes, but bowl shares its curve so has to split to suit it
+        fs::write(&source, glyphs).unwrap();
+        compile(args.clone());
+        let bowl = glyph_glyf_bytes(&build_dir, "bowl");
+        assert_ne!(bowl_before, bowl);
+
+        let fresh_dir = temp_dir.path().join("fresh");
+        args.build_dir = fresh_dir.clone();
+        compile(args);
+        for name in ["bowl", "bowl.big"] {
+            assert_eq!(
+                glyph_glyf_bytes(&fresh_dir, name),
+                glyph_glyf_bytes(&build_dir, name),
+                "{name}"
+            );
+        }
+    }
+
     #[test]
     fn compile_composite_glyphs_has_expected_glyph_types() {
         let temp_dir = tempdir().unwrap();
@@ -1393,7 +1961,10 @@ mod tests {
 
         assert_eq!(
             vec![
+                Tag::new(b"HVAR"),
+                Tag::new(b"MVAR"),
                 Tag::new(b"OS/2"),
+                Tag::new(b"STAT"),
                 Tag::new(b"avar"),
                 Tag::new(b"cmap"),
                 Tag::new(b"fvar"),
@@ -1416,98 +1987,903 @@ mod tests {
     }
 
     #[test]
-    fn compile_mov_xy_and_move_around() {
+    fn advances_vary_in_hvar() {
         let temp_dir = tempdir().unwrap();
         let build_dir = temp_dir.path();
-        compile(Args::for_test(build_dir, "mov_xy.designspace"));
+        let result = compile(Args::for_test(build_dir, "wght_var.designspace"));
 
-        let font_file = build_dir.join("font.ttf");
-        assert!(font_file.exists());
-        let buf = fs::read(font_file).unwrap();
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
         let font = FontRef::new(&buf).unwrap();
+        let hvar = font.hvar().unwrap();
 
-        // Confirm movx then movy
+        // Bold is 34 and 15 units wider than Regular
         assert_eq!(
-            vec!["movx", "movy"],
-            font.fvar()
-                .unwrap()
-                .axes()
-                .unwrap()
+            vec![34.0, 15.0],
+            ["bar", "plus"]
                 .iter()
-                .map(|a| a.axis_tag.get().to_string())
+                .map(|name| {
+                    let gid = GlyphId::new(result.get_glyph_index(name) as u16);
+                    hvar.advance_width_delta(gid, &[F2Dot14::from_f32(1.0)])
+                        .unwrap()
+                        .to_f64()
+                })
                 .collect::<Vec<_>>()
         );
+    }
 
-        // Confirm movement on axes has the intended effect
+    #[test]
+    fn vertical_metrics() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let result = compile(Args::for_test(build_dir, "glyphs3/WghtVar_Vertical.glyphs"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let gid = GlyphId::new(result.get_glyph_index("exclam") as u16);
+
+        let vhea = font.vhea().unwrap();
         assert_eq!(
-            vec![
-                Rect::new(50.0, 50.0, 150.0, 150.0),   // default
-                Rect::new(850.0, 50.0, 950.0, 150.0),  // movx max
-                Rect::new(50.0, 850.0, 150.0, 950.0),  // movy max
-                Rect::new(850.0, 850.0, 950.0, 950.0), // movx and movy max
-            ],
-            vec![
-                cbox_of_char(0x2e, &font, vec![0.0, 0.0]),
-                cbox_of_char(0x2e, &font, vec![1.0, 0.0]),
-                cbox_of_char(0x2e, &font, vec![0.0, 1.0]),
-                cbox_of_char(0x2e, &font, vec![1.0, 1.0]),
-            ]
-        )
+            (500, -500, 0),
+            (
+                vhea.ascender().to_i16(),
+                vhea.descender().to_i16(),
+                vhea.line_gap().to_i16()
+            )
+        );
+        let vmtx = font.vmtx().unwrap();
+        assert_eq!(1000, vmtx.v_metrics()[gid.to_u16() as usize].advance());
+
+        // Bold is 100 units taller than Regular
+        let vvar = font.vvar().unwrap();
+        assert_eq!(
+            100.0,
+            vvar.advance_height_delta(gid, &[F2Dot14::from_f32(1.0)])
+                .unwrap()
+                .to_f64()
+        );
     }
 
-    fn cbox_of_char(ch: u32, font: &FontRef, coords: Vec<f32>) -> Rect {
-        let gid = Charmap::new(font).map(ch).unwrap();
+    #[test]
+    fn ascender_varies_in_mvar() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "wght_var.designspace"));
 
-        let mut bp = CboxPen::new();
-        let mut cx = skrifa::scale::Context::new();
-        cx.new_scaler()
-            .size(Size::new(1000.0))
-            .normalized_coords(
-                coords
-                    .into_iter()
-                    .map(F2Dot14::from_f32)
-                    .collect::<Vec<_>>(),
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let mvar = font.table_data(Tag::new(b"MVAR")).unwrap();
+        let mvar = mvar.as_ref();
+
+        // Only the ascender differs between masters, 799 => 801; the typo ascender
+        // follows it and so does the typo line gap derived from it
+        let u16_at = |pos: usize| u16::from_be_bytes([mvar[pos], mvar[pos + 1]]);
+        assert_eq!(2, u16_at(8), "value record count");
+        assert_eq!(b"hasc", &mvar[12..16]);
+        let store = ItemVariationStore::read(FontData::new(&mvar[u16_at(10) as usize..])).unwrap();
+        let delta = store
+            .compute_delta(
+                DeltaSetIndex {
+                    outer: u16_at(16),
+                    inner: u16_at(18),
+                },
+                &[F2Dot14::from_f32(1.0)],
             )
-            .build(font)
-            .outline(gid, &mut bp)
             .unwrap();
-
-        bp.cbox().unwrap()
+        assert_eq!(2, delta);
     }
 
-    struct CboxPen {
-        last_move: Point,
-        bounds: Option<Rect>,
+    fn table_tags(font: &FontRef) -> Vec<Tag> {
+        font.table_directory
+            .table_records()
+            .iter()
+            .map(|tr| tr.tag())
+            .collect()
     }
 
-    impl CboxPen {
-        fn new() -> Self {
-            Self {
-                last_move: Default::default(),
-                bounds: None,
-            }
-        }
+    #[test]
+    fn variable_cff_outlines_are_cff2() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let mut args = Args::for_test(build_dir, "wght_var.designspace");
+        args.cff = true;
+        compile(args);
 
-        fn cbox(self) -> Option<Rect> {
-            self.bounds
-        }
+        let buf = fs::read(build_dir.join("font.otf")).unwrap();
+        assert_eq!(b"OTTO", &buf[..4]);
+        let font = FontRef::new(&buf).unwrap();
 
-        fn update_bounds(&mut self, x: f32, y: f32) {
-            let pt = Point::new(x as f64, y as f64);
-            self.bounds = Some(match self.bounds {
-                None => Rect::from_points(pt, pt),
-                Some(bounds) => bounds.union_pt(pt),
-            });
+        let tags = table_tags(&font);
+        assert!(tags.contains(&Tag::new(b"CFF2")), "{tags:?}");
+        for tag in [b"CFF ", b"glyf", b"gvar", b"loca"] {
+            assert!(!tags.contains(&Tag::new(tag)), "{tags:?}");
         }
+        assert_eq!(Version16Dot16::VERSION_0_5, font.maxp().unwrap().version());
+
+        // major, minor, header size
+        let cff2 = font.table_data(Tag::new(b"CFF2")).unwrap();
+        assert_eq!(&[2, 0, 5], &cff2.as_ref()[..3]);
     }
 
-    impl skrifa::scale::Pen for CboxPen {
-        fn move_to(&mut self, x: f32, y: f32) {
-            self.last_move = Point::new(x as f64, y as f64);
-            self.update_bounds(x, y);
+    #[test]
+    fn static_cff_outlines_are_cff() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let mut args = Args::for_test(build_dir, "static.designspace");
+        args.cff = true;
+        compile(args);
+
+        let buf = fs::read(build_dir.join("font.otf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+
+        let tags = table_tags(&font);
+        assert!(tags.contains(&Tag::new(b"CFF ")), "{tags:?}");
+        for tag in [b"CFF2", b"glyf", b"loca"] {
+            assert!(!tags.contains(&Tag::new(tag)), "{tags:?}");
         }
 
-        fn line_to(&mut self, x: f32, y: f32) {
+        // major, minor, header size, offset size
+        let cff = font.table_data(Tag::new(b"CFF ")).unwrap();
+        assert_eq!(&[1, 0, 4, 4], &cff.as_ref()[..4]);
+    }
+
+    #[test]
+    fn anchors_become_gpos() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "glyphs3/WghtVar_Anchors.glyphs"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+
+        let gpos = font.gpos().unwrap();
+        assert_eq!(
+            vec![Tag::new(b"mark"), Tag::new(b"mkmk")],
+            gpos.feature_list()
+                .unwrap()
+                .feature_records()
+                .iter()
+                .map(|fr| fr.feature_tag())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, gpos.lookup_list().unwrap().lookup_count());
+
+        // The anchors move so GDEF has to hold the deltas
+        let gdef = font.gdef().unwrap();
+        assert!(gdef.item_var_store().is_some());
+    }
+
+    #[test]
+    fn fvar_has_named_instances() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "wght_var.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let fvar = font.fvar().unwrap();
+        let name = font.name().unwrap();
+        let name_string = |name_id| {
+            name.name_record()
+                .iter()
+                .find(|record| record.name_id() == name_id)
+                .map(|record| {
+                    record
+                        .string(name.string_data())
+                        .unwrap()
+                        .chars()
+                        .collect::<String>()
+                })
+                .unwrap_or_else(|| format!("{name_id:?}"))
+        };
+
+        assert_eq!(
+            vec![
+                ("Regular".to_string(), vec![400.0], "65535".to_string()),
+                ("Bold".to_string(), vec![700.0], "WghtVar-Bold".to_string()),
+            ],
+            fvar.instances()
+                .unwrap()
+                .iter()
+                .map(|instance| {
+                    let instance = instance.unwrap();
+                    (
+                        name_string(instance.subfamily_name_id),
+                        instance
+                            .coordinates
+                            .iter()
+                            .map(|c| c.get().to_f64())
+                            .collect::<Vec<_>>(),
+                        name_string(instance.post_script_name_id.unwrap()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compile_woff_and_woff2() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let mut args = Args::for_test(build_dir, "wght_var.designspace");
+        args.woff = true;
+        args.woff2 = true;
+        compile(args);
+
+        let font = fs::read(build_dir.join("font.ttf")).unwrap();
+        let num_tables = FontRef::new(&font)
+            .unwrap()
+            .table_directory
+            .table_records()
+            .len() as u16;
+        for (file, signature) in [("font.woff", b"wOFF"), ("font.woff2", b"wOF2")] {
+            let buf = fs::read(build_dir.join(file)).unwrap();
+            assert_eq!(signature, &buf[0..4], "{file}");
+            assert_eq!(&font[0..4], &buf[4..8], "{file} flavor");
+            assert_eq!(
+                buf.len() as u32,
+                u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+                "{file} length"
+            );
+            assert_eq!(
+                num_tables,
+                u16::from_be_bytes(buf[12..14].try_into().unwrap()),
+                "{file} numTables"
+            );
+            assert!(buf.len() < font.len(), "{file} isn't compressed");
+        }
+    }
+
+    #[test]
+    fn compile_static_instances() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let args = Args::for_test(build_dir, "wght_var.designspace");
+        let result = compile(args.clone());
+        let static_metadata = result.fe_context.get_final_static_metadata();
+        let bar = result.get_glyph_index("bar");
+
+        let bold = static_metadata
+            .named_instances()
+            .iter()
+            .find(|instance| instance.name == "Bold")
+            .unwrap();
+        let semibold = ir::NamedInstance {
+            name: "SemiBold".to_string(),
+            postscript_name: None,
+            location: [("Weight".to_string(), UserCoord::new(450.0))]
+                .into_iter()
+                .collect(),
+        };
+        compile_instance(&args, &result.fe_context, bold).unwrap();
+        compile_instance(&args, &result.fe_context, &semibold).unwrap();
+
+        let instance = |dir: &str| {
+            let buf = fs::read(build_dir.join("instances").join(dir).join("font.ttf")).unwrap();
+            let font = FontRef::new(&buf).unwrap();
+            let name = font.name().unwrap();
+            let names = [
+                NameId::FAMILY_NAME,
+                NameId::SUBFAMILY_NAME,
+                NameId::POSTSCRIPT_NAME,
+            ]
+            .into_iter()
+            .map(|name_id| {
+                name.name_record()
+                    .iter()
+                    .find(|record| record.name_id() == name_id)
+                    .map(|record| {
+                        record
+                            .string(name.string_data())
+                            .unwrap()
+                            .chars()
+                            .collect::<String>()
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+            let advance = font.hmtx().unwrap().h_metrics()[bar as usize].advance.get();
+            (
+                font.fvar().is_ok(),
+                names,
+                font.os2().unwrap().us_weight_class(),
+                advance,
+            )
+        };
+
+        // bar is 517 at 400 and 540 at 600 so a quarter of the way is 522.75
+        assert_eq!(
+            vec![
+                (
+                    false,
+                    vec![
+                        "New Font".to_string(),
+                        "Bold".to_string(),
+                        "WghtVar-Bold".to_string()
+                    ],
+                    700,
+                    551
+                ),
+                (
+                    false,
+                    vec![
+                        "New Font SemiBold".to_string(),
+                        "Regular".to_string(),
+                        "NewFont-SemiBold".to_string()
+                    ],
+                    450,
+                    523
+                ),
+            ],
+            vec![instance("WghtVar-Bold"), instance("SemiBold")]
+        );
+    }
+
+    #[test]
+    fn unchanged_static_instance_is_not_rebuilt() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let args = Args::for_test(build_dir, "wght_var.designspace");
+        let result = compile(args.clone());
+        let static_metadata = result.fe_context.get_final_static_metadata();
+        let bold = static_metadata
+            .named_instances()
+            .iter()
+            .find(|instance| instance.name == "Bold")
+            .unwrap();
+
+        let font_file = build_dir.join("instances/WghtVar-Bold/font.ttf");
+        compile_instance(&args, &result.fe_context, bold).unwrap();
+        let modified = fs::metadata(&font_file).unwrap().modified().unwrap();
+
+        let result = compile(args.clone());
+        assert!(result.work_completed.is_empty());
+        compile_instance(&args, &result.fe_context, bold).unwrap();
+        assert_eq!(
+            modified,
+            fs::metadata(&font_file).unwrap().modified().unwrap()
+        );
+    }
+
+    #[test]
+    fn stat_from_designspace_labels() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "stat.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let stat = font.stat().unwrap();
+        let name = font.name().unwrap();
+        let name_string = |name_id| {
+            name.name_record()
+                .iter()
+                .find(|record| record.name_id() == name_id)
+                .map(|record| {
+                    record
+                        .string(name.string_data())
+                        .unwrap()
+                        .chars()
+                        .collect::<String>()
+                })
+                .unwrap()
+        };
+
+        assert_eq!(
+            vec![Tag::new(b"wght")],
+            stat.design_axes()
+                .unwrap()
+                .iter()
+                .map(|axis| axis.axis_tag())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "Regular",
+            name_string(stat.elided_fallback_name_id().unwrap())
+        );
+        assert_eq!(
+            vec!["Regular", "Medium", "Bold", "Semibold"],
+            stat.offset_to_axis_values()
+                .unwrap()
+                .axis_values()
+                .map(|value| name_string(match value.unwrap() {
+                    AxisValue::Format1(value) => value.value_name_id(),
+                    AxisValue::Format2(value) => value.value_name_id(),
+                    AxisValue::Format3(value) => value.value_name_id(),
+                    AxisValue::Format4(value) => value.value_name_id(),
+                }))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn no_stat_for_static_font() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "static.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        assert!(font.stat().is_err());
+    }
+
+    fn assert_gdef_classes_and_carets(source: &str, base: &str) {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let result = compile(Args::for_test(build_dir, source));
+        let static_metadata = result.fe_context.get_final_static_metadata();
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let gdef = font.gdef().unwrap();
+
+        // The base is classified by its anchors, the others by the source
+        let class_def: ClassDef = gdef.glyph_class_def().unwrap().unwrap().to_owned_table();
+        let gid = |name: &str| GlyphId::new(static_metadata.glyph_id(&name.into()).unwrap() as u16);
+        assert_eq!(
+            vec![1, 3, 2],
+            [base, "acutecomb", "f_i"]
+                .iter()
+                .map(|name| class_def.get(gid(name)))
+                .collect::<Vec<_>>()
+        );
+
+        let lig_caret_list = gdef.lig_caret_list().unwrap().unwrap();
+        assert_eq!(
+            vec![gid("f_i")],
+            lig_caret_list
+                .coverage()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        );
+
+        // The caret moves so GDEF has to hold the deltas
+        assert!(gdef.item_var_store().is_some());
+    }
+
+    #[test]
+    fn gdef_from_ufo() {
+        assert_gdef_classes_and_carets("gdef.designspace", "a");
+    }
+
+    #[test]
+    fn gdef_from_glyphs() {
+        assert_gdef_classes_and_carets("glyphs3/WghtVar_Gdef.glyphs", "A");
+    }
+
+    #[test]
+    fn kerning_becomes_gpos() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "kern.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+
+        let gpos: Gpos = font.gpos().unwrap().to_owned_table();
+        assert_eq!(
+            vec![Tag::new(b"kern")],
+            gpos.feature_list
+                .feature_records
+                .iter()
+                .map(|fr| fr.feature_tag)
+                .collect::<Vec<_>>()
+        );
+        // plus/bar is glyph to glyph, bars/pluses group to group
+        let PositionLookup::Pair(lookup) = &*gpos.lookup_list.lookups[0] else {
+            panic!("kern should be pair positioning");
+        };
+        assert!(matches!(
+            lookup.subtables.iter().map(|st| &**st).collect::<Vec<_>>()[..],
+            [PairPos::Format1(..), PairPos::Format2(..)]
+        ));
+
+        // The bars/pluses kern varies so GDEF has to hold the deltas
+        let gdef = font.gdef().unwrap();
+        assert!(gdef.item_var_store().is_some());
+    }
+
+    /// The kern value of bar, plus and whether it has a device
+    fn bar_plus_kern(gpos: &Gpos) -> (Option<i16>, bool) {
+        let PositionLookup::Pair(lookup) = &*gpos.lookup_list.lookups[0] else {
+            panic!("kern should be pair positioning");
+        };
+        let bar = GlyphId::new(0);
+        let pair_pos = lookup
+            .subtables
+            .iter()
+            .find_map(|subtable| match &**subtable {
+                PairPos::Format1(pair_pos) if pair_pos.coverage.iter().any(|gid| gid == bar) => {
+                    Some(pair_pos)
+                }
+                _ => None,
+            })
+            .expect("bar plus should be glyph to glyph");
+        let record = &pair_pos.pair_sets[0].pair_value_records[0];
+        (
+            record.value_record1.x_advance,
+            record.value_record1.x_advance_device.is_some()
+                && record.value_record2.format().is_empty(),
+        )
+    }
+
+    #[test]
+    fn variable_fea_values_vary() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "variable_fea.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let gpos: Gpos = font.gpos().unwrap().to_owned_table();
+        assert_eq!((Some(-10), true), bar_plus_kern(&gpos));
+        assert!(font.gdef().unwrap().item_var_store().is_some());
+    }
+
+    #[test]
+    fn fea_per_master_varies() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "per_master_fea.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let gpos: Gpos = font.gpos().unwrap().to_owned_table();
+        assert_eq!((Some(-10), true), bar_plus_kern(&gpos));
+        assert!(font.gdef().unwrap().item_var_store().is_some());
+    }
+
+    #[test]
+    fn rules_become_feature_variations() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "rules.designspace"));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let gsub: Gsub = font.gsub().unwrap().to_owned_table();
+        assert_eq!(
+            vec![Tag::new(b"rclt")],
+            gsub.feature_list
+                .feature_records
+                .iter()
+                .map(|fr| fr.feature_tag)
+                .collect::<Vec<_>>()
+        );
+
+        // Weight 600..700 is .667..1 normalized, there the rule lookup is added to rclt
+        let records = &gsub
+            .feature_variations
+            .as_ref()
+            .unwrap()
+            .feature_variation_records;
+        assert_eq!(
+            vec![(vec![(0, 0.666687, 1.0)], vec![(0, vec![0])])],
+            records
+                .iter()
+                .map(|r| (
+                    r.condition_set
+                        .conditions
+                        .iter()
+                        .map(|c| (
+                            c.axis_index,
+                            c.filter_range_min_value.to_f32(),
+                            c.filter_range_max_value.to_f32()
+                        ))
+                        .collect::<Vec<_>>(),
+                    r.feature_table_substitution
+                        .substitutions
+                        .iter()
+                        .map(|s| (
+                            s.feature_index,
+                            s.alternate_feature.lookup_list_indices.clone()
+                        ))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn bracket_layers_become_feature_variations() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let result = compile(Args::for_test(build_dir, "glyphs3/WghtVar_Bracket.glyphs"));
+        let exclam = result.get_glyph_index("exclam");
+        let bracket_exclam = result.get_glyph_index("exclam.BRACKET.varAlt01");
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let gsub: Gsub = font.gsub().unwrap().to_owned_table();
+        assert_eq!(
+            vec![Tag::new(b"rvrn")],
+            gsub.feature_list
+                .feature_records
+                .iter()
+                .map(|fr| fr.feature_tag)
+                .collect::<Vec<_>>()
+        );
+
+        let SubstitutionLookup::Single(lookup) = &*gsub.lookup_list.lookups[0] else {
+            panic!("Bracket layers should substitute single glyphs");
+        };
+        let SingleSubst::Format2(subst) = &*lookup.subtables[0] else {
+            panic!("Expected a format 2 single substitution");
+        };
+        assert_eq!(
+            vec![(exclam as u16, bracket_exclam as u16)],
+            subst
+                .coverage
+                .iter()
+                .zip(subst.substitute_glyph_ids.iter())
+                .map(|(from, to)| (from.to_u16(), to.to_u16()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            1,
+            gsub.feature_variations
+                .as_ref()
+                .unwrap()
+                .feature_variation_records
+                .len()
+        );
+    }
+
+    #[test]
+    fn bracket_range_edit_reruns_finalize() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "glyphs3/WghtVar_Bracket.glyphs");
+        let source = temp_dir.path().join("WghtVar_Bracket.glyphs");
+        fs::copy(&args.source, &source).unwrap();
+        args.source = source.clone();
+        compile(args.clone());
+
+        // Moving where the alternate kicks in changes feature variations, which finalize builds
+        let glyphs = fs::read_to_string(&source).unwrap();
+        fs::write(&source, glyphs.replace("min = 600;", "min = 650;")).unwrap();
+        let result = compile(args);
+        assert!(result
+            .work_completed
+            .contains(&FeWorkIdentifier::FinalizeStaticMetadata.into()));
+    }
+
+    #[test]
+    fn brace_layer_adds_intermediate_region() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let result = compile(Args::for_test(build_dir, "glyphs3/WghtVar_Brace.glyphs"));
+
+        let peaks = |name: &str| {
+            let mut peaks: Vec<_> = result
+                .be_context
+                .get_gvar_fragment(&name.into())
+                .deltas
+                .iter()
+                .filter(|(region, _)| !region.is_default())
+                .flat_map(|(region, _)| region.iter().map(|(_, tent)| tent.peak.to_f32()))
+                .collect();
+            peaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            peaks
+        };
+        // Only the glyph with the brace layer varies at the intermediate location
+        assert_eq!(vec![0.5, 1.0], peaks("hyphen"));
+        assert_eq!(vec![1.0], peaks("exclam"));
+    }
+
+    #[test]
+    fn sparse_glyph_uses_its_own_model() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let result = compile(Args::for_test(build_dir, "wght_var.designspace"));
+
+        let tents = |name: &str| {
+            let mut tents: Vec<_> = result
+                .be_context
+                .get_gvar_fragment(&name.into())
+                .deltas
+                .iter()
+                .filter(|(region, _)| !region.is_default())
+                .flat_map(|(region, _)| {
+                    region
+                        .iter()
+                        .map(|(_, tent)| (tent.min.to_f32(), tent.peak.to_f32(), tent.max.to_f32()))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            tents.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            tents
+        };
+        // bar has an intermediate master, plus does not so its bold delta covers the whole axis
+        assert_eq!(
+            vec![(0.0, 0.6666667, 1.0), (0.6666667, 1.0, 1.0)],
+            tents("bar")
+        );
+        assert_eq!(vec![(0.0, 1.0, 1.0)], tents("plus"));
+    }
+
+    #[test]
+    fn gvar_omits_deltas_iup_can_infer() {
+        let explicit_points = |optimize_gvar: bool| {
+            let temp_dir = tempdir().unwrap();
+            let build_dir = temp_dir.path();
+            let mut args = Args::for_test(build_dir, "wght_var.designspace");
+            args.optimize_gvar = optimize_gvar;
+            let result = compile(args);
+
+            let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+            let font = FontRef::new(&buf).unwrap();
+            let gid = GlyphId::new(result.get_glyph_index("bar") as u16);
+            let data = font.gvar().unwrap().glyph_variation_data(gid).unwrap();
+            data.tuples()
+                .map(|tuple| tuple.deltas().count())
+                .collect::<Vec<_>>()
+        };
+
+        // bar is a rectangle, 4 points plus 4 phantoms
+        let all = explicit_points(false);
+        assert!(!all.is_empty());
+        assert!(all.iter().all(|n| *n == 8), "{all:?}");
+
+        // Points that stay put or move in step with their neighbours can be interpolated
+        let optimized = explicit_points(true);
+        assert!(!optimized.is_empty());
+        assert!(optimized.iter().all(|n| *n < 8), "{optimized:?}");
+    }
+
+    #[test]
+    fn overlap_flag_on_all_glyphs() {
+        let overlap_flags = |overlap_flag_on_all_glyphs: bool| {
+            let temp_dir = tempdir().unwrap();
+            let build_dir = temp_dir.path();
+            let mut args = Args::for_test(build_dir, "wght_var.designspace");
+            args.overlap_flag_on_all_glyphs = overlap_flag_on_all_glyphs;
+            let result = compile(args);
+
+            let glyph_data = result.glyphs();
+            let glyphs = glyph_data.read();
+            ["bar", "plus"]
+                .into_iter()
+                .map(|glyph_name| {
+                    let glyf::Glyph::Simple(glyph) =
+                        &glyphs[result.get_glyph_index(glyph_name) as usize]
+                    else {
+                        panic!("Expected '{glyph_name}' to be a simple glyph");
+                    };
+                    glyf::SimpleGlyphFlags::from_bits_truncate(glyph.glyph_data()[0])
+                        .contains(glyf::SimpleGlyphFlags::OVERLAP_SIMPLE)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Neither glyph overlaps itself
+        assert_eq!(vec![false, false], overlap_flags(false));
+        assert_eq!(vec![true, true], overlap_flags(true));
+    }
+
+    #[test]
+    fn overlapping_glyphs_are_flagged() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        let result = compile(Args::for_test(build_dir, "glyphs3/WghtVar_Overlap.glyphs"));
+
+        let glyph_data = result.glyphs();
+        let glyphs = glyph_data.read();
+        let overlap_flag =
+            |glyph_name: &str| match &glyphs[result.get_glyph_index(glyph_name) as usize] {
+                glyf::Glyph::Simple(glyph) => {
+                    glyf::SimpleGlyphFlags::from_bits_truncate(glyph.glyph_data()[0])
+                        .contains(glyf::SimpleGlyphFlags::OVERLAP_SIMPLE)
+                }
+                glyf::Glyph::Composite(glyph) => glyph
+                    .components()
+                    .next()
+                    .unwrap()
+                    .flags
+                    .contains(glyf::CompositeGlyphFlags::OVERLAP_COMPOUND),
+            };
+
+        // Contours that overlap
+        assert!(overlap_flag("cross"));
+        assert!(!overlap_flag("bar"));
+        // Components that overlap
+        assert!(overlap_flag("crossbars"));
+        assert!(!overlap_flag("bars"));
+    }
+
+    #[test]
+    fn compile_mov_xy_and_move_around() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path();
+        compile(Args::for_test(build_dir, "mov_xy.designspace"));
+
+        let font_file = build_dir.join("font.ttf");
+        assert!(font_file.exists());
+        let buf = fs::read(font_file).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+
+        // Confirm movx then movy
+        assert_eq!(
+            vec!["movx", "movy"],
+            font.fvar()
+                .unwrap()
+                .axes()
+                .unwrap()
+                .iter()
+                .map(|a| a.axis_tag.get().to_string())
+                .collect::<Vec<_>>()
+        );
+
+        // Confirm movement on axes has the intended effect
+        assert_eq!(
+            vec![
+                Rect::new(50.0, 50.0, 150.0, 150.0),   // default
+                Rect::new(850.0, 50.0, 950.0, 150.0),  // movx max
+                Rect::new(50.0, 850.0, 150.0, 950.0),  // movy max
+                Rect::new(850.0, 850.0, 950.0, 950.0), // movx and movy max
+            ],
+            vec![
+                cbox_of_char(0x2e, &font, vec![0.0, 0.0]),
+                cbox_of_char(0x2e, &font, vec![1.0, 0.0]),
+                cbox_of_char(0x2e, &font, vec![0.0, 1.0]),
+                cbox_of_char(0x2e, &font, vec![1.0, 1.0]),
+            ]
+        )
+    }
+
+    fn cbox_of_char(ch: u32, font: &FontRef, coords: Vec<f32>) -> Rect {
+        let gid = Charmap::new(font).map(ch).unwrap();
+
+        let mut bp = CboxPen::new();
+        let mut cx = skrifa::scale::Context::new();
+        cx.new_scaler()
+            .size(Size::new(1000.0))
+            .normalized_coords(
+                coords
+                    .into_iter()
+                    .map(F2Dot14::from_f32)
+                    .collect::<Vec<_>>(),
+            )
+            .build(font)
+            .outline(gid, &mut bp)
+            .unwrap();
+
+        bp.cbox().unwrap()
+    }
+
+    struct CboxPen {
+        last_move: Point,
+        bounds: Option<Rect>,
+    }
+
+    impl CboxPen {
+        fn new() -> Self {
+            Self {
+                last_move: Default::default(),
+                bounds: None,
+            }
+        }
+
+        fn cbox(self) -> Option<Rect> {
+            self.bounds
+        }
+
+        fn update_bounds(&mut self, x: f32, y: f32) {
+            let pt = Point::new(x as f64, y as f64);
+            self.bounds = Some(match self.bounds {
+                None => Rect::from_points(pt, pt),
+                Some(bounds) => bounds.union_pt(pt),
+            });
+        }
+    }
+
+    impl skrifa::scale::Pen for CboxPen {
+        fn move_to(&mut self, x: f32, y: f32) {
+            self.last_move = Point::new(x as f64, y as f64);
+            self.update_bounds(x, y);
+        }
+
+        fn line_to(&mut self, x: f32, y: f32) {
             self.update_bounds(x, y);
         }
 
@@ -1632,4 +3008,358 @@ mod tests {
             .collect::<Vec<_>>();
         assert_eq!(vec![Path::new("font.ttf")], outputs);
     }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let path = entry.unwrap().path();
+            let dest = to.join(path.file_name().unwrap());
+            if path.is_dir() {
+                copy_dir(&path, &dest);
+            } else {
+                fs::copy(&path, &dest).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn rebuild_in_memory_redoes_only_what_changed() {
+        let temp_dir = tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "wght_var.designspace");
+        let testdata = args.source.parent().unwrap().to_path_buf();
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::copy(&args.source, source_dir.join("wght_var.designspace")).unwrap();
+        for ufo in ["WghtVar-Regular.ufo", "WghtVar-Bold.ufo"] {
+            copy_dir(&testdata.join(ufo), &source_dir.join(ufo));
+        }
+        args.source = source_dir.join("wght_var.designspace");
+        args.emit_ir = false;
+
+        let (ir_paths, be_paths) = init_paths(&args).unwrap();
+        let mut build =
+            IncrementalBuild::new(Config::new(args).unwrap(), ir_paths, be_paths).unwrap();
+        let bar_glif = source_dir.join("WghtVar-Regular.ufo/glyphs/bar.glif");
+        assert!(build.input_files().contains(&bar_glif));
+        assert!(!build.rebuild().unwrap(), "Nothing changed, nothing to do");
+
+        let plus = build.fe_context().get_glyph_ir(&"plus".into());
+        let bar = build.fe_context().get_glyph_ir(&"bar".into());
+        let glif = fs::read_to_string(&bar_glif).unwrap();
+        fs::write(&bar_glif, glif.replace(r#"width="517""#, r#"width="1517""#)).unwrap();
+        assert!(build.rebuild().unwrap());
+
+        // Only the glyph that changed is recomputed, the rest comes from memory
+        assert!(Arc::ptr_eq(
+            &plus,
+            &build.fe_context().get_glyph_ir(&"plus".into())
+        ));
+        assert!(!Arc::ptr_eq(
+            &bar,
+            &build.fe_context().get_glyph_ir(&"bar".into())
+        ));
+
+        let gid = build
+            .fe_context()
+            .get_final_static_metadata()
+            .glyph_id(&"bar".into())
+            .unwrap();
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        assert_eq!(
+            1517,
+            font.hmtx().unwrap().h_metrics()[gid as usize].advance.get()
+        );
+    }
+
+    #[test]
+    fn rebuild_checks_interpolation_of_edited_glyph() {
+        let temp_dir = tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "wght_var.designspace");
+        let testdata = args.source.parent().unwrap().to_path_buf();
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::copy(&args.source, source_dir.join("wght_var.designspace")).unwrap();
+        for ufo in ["WghtVar-Regular.ufo", "WghtVar-Bold.ufo"] {
+            copy_dir(&testdata.join(ufo), &source_dir.join(ufo));
+        }
+        args.source = source_dir.join("wght_var.designspace");
+        args.emit_ir = false;
+        args.check_interpolation = true;
+
+        let (ir_paths, be_paths) = init_paths(&args).unwrap();
+        let mut build =
+            IncrementalBuild::new(Config::new(args).unwrap(), ir_paths, be_paths).unwrap();
+
+        // Drop a point from bold plus, nothing but that glyph changes
+        let plus_glif = source_dir.join("WghtVar-Bold.ufo/glyphs/plus.glif");
+        let glif = fs::read_to_string(&plus_glif).unwrap();
+        fs::write(
+            &plus_glif,
+            glif.replace(r#"<point x="43" y="299" type="line"/>"#, ""),
+        )
+        .unwrap();
+        let Err(Error::TasksFailed(failures)) = build.rebuild() else {
+            panic!("bold plus should not interpolate");
+        };
+        assert!(
+            failures.iter().any(|(id, message)| {
+                *id == FeWorkIdentifier::Glyph("plus".into()).into()
+                    && message.contains("not interpolation compatible")
+            }),
+            "{failures:?}"
+        );
+    }
+
+    #[test]
+    fn rebuild_composite_with_unchanged_components() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "glyphs3/WghtVar.glyphs");
+        let source = temp_dir.path().join("WghtVar.glyphs");
+        let glyphs = fs::read_to_string(&args.source).unwrap();
+        fs::write(&source, &glyphs).unwrap();
+        args.source = source.clone();
+        compile(args.clone());
+        let before = glyph_glyf_bytes(&build_dir, "manual-component");
+
+        // Move a component of manual-component; hyphen, the glyph it uses, is untouched
+        fs::write(&source, glyphs.replace("pos = (0,100);", "pos = (0,120);")).unwrap();
+        let result = compile(args);
+
+        assert_eq!(
+            IndexSet::from(["manual-component".into()]),
+            result.glyphs_changed
+        );
+        assert!(result
+            .work_completed
+            .contains(&BeWorkIdentifier::GlyfFragment("manual-component".into()).into()));
+        assert_ne!(before, glyph_glyf_bytes(&build_dir, "manual-component"));
+    }
+
+    #[test]
+    fn rebuild_removes_overlaps_from_edited_glyph() {
+        let temp_dir = tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "static.designspace");
+        let testdata = args.source.parent().unwrap().to_path_buf();
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::copy(&args.source, source_dir.join("static.designspace")).unwrap();
+        copy_dir(
+            &testdata.join("Static-Regular.ufo"),
+            &source_dir.join("Static-Regular.ufo"),
+        );
+        args.source = source_dir.join("static.designspace");
+        args.remove_overlaps = true;
+        compile(args.clone());
+
+        // Cross bar with a contour that overlaps it
+        let bar_glif = source_dir.join("Static-Regular.ufo/glyphs/bar.glif");
+        let glif = fs::read_to_string(&bar_glif).unwrap();
+        fs::write(
+            &bar_glif,
+            glif.replace(
+                "</outline>",
+                r#"  <contour>
+      <point x="100" y="200" type="line"/>
+      <point x="417" y="200" type="line"/>
+      <point x="417" y="273" type="line"/>
+      <point x="100" y="273" type="line"/>
+    </contour>
+  </outline>"#,
+            ),
+        )
+        .unwrap();
+        let result = compile(args);
+        assert_eq!(IndexSet::from(["bar".into()]), result.glyphs_changed);
+
+        let bar = result.fe_context.get_glyph_ir(&"bar".into());
+        assert_eq!(1, bar.default_instance().contours.len(), "{bar:?}");
+        let raw_glyph = glyph_glyf_bytes(&build_dir, "bar");
+        let glyph = SimpleGlyph::read(FontData::new(&raw_glyph)).unwrap();
+        assert_eq!(1, glyph.number_of_contours());
+        assert_eq!(
+            [100, -241, 417, 760],
+            [glyph.x_min(), glyph.y_min(), glyph.x_max(), glyph.y_max()]
+        );
+    }
+
+    #[test]
+    fn names_only_edit_leaves_glyphs_alone() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "glyphs2/MixedContourComponent.glyphs");
+        let source = temp_dir.path().join("MixedContourComponent.glyphs");
+        fs::copy(&args.source, &source).unwrap();
+        args.source = source.clone();
+        args.prefer_simple_glyphs = false;
+
+        let result = compile(args.clone());
+        assert!(result
+            .fe_context
+            .get_final_static_metadata()
+            .glyph_order
+            .contains(&GlyphName::from("contour_and_component.0")));
+
+        let glyphs = fs::read_to_string(&source).unwrap();
+        fs::write(
+            &source,
+            glyphs.replace(
+                r#"familyName = "New Font";"#,
+                r#"familyName = "Renamed Font";"#,
+            ),
+        )
+        .unwrap();
+        let result = compile(args);
+
+        // Names change, glyphs don't
+        assert_eq!(IndexSet::new(), result.glyphs_changed);
+        for id in [
+            AnyWorkId::Fe(FeWorkIdentifier::InitStaticMetadata),
+            BeWorkIdentifier::Name.into(),
+            BeWorkIdentifier::Fvar.into(),
+            BeWorkIdentifier::Stat.into(),
+            BeWorkIdentifier::Font.into(),
+        ] {
+            assert!(result.work_completed.contains(&id), "{id:?} didn't run");
+        }
+        let glyph_ir_rebuilt: Vec<_> = result
+            .work_completed
+            .iter()
+            .filter(|id| matches!(id, AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))))
+            .collect();
+        assert!(glyph_ir_rebuilt.is_empty(), "{glyph_ir_rebuilt:?}");
+
+        // Nor does anything that doesn't use names. This font is static so has no avar, which
+        // therefore reruns regardless; names_only_edit_leaves_variations_alone covers it.
+        for id in [
+            AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata),
+            FeWorkIdentifier::Features.into(),
+            FeWorkIdentifier::Kerning.into(),
+            BeWorkIdentifier::Features.into(),
+            BeWorkIdentifier::Kerning.into(),
+            BeWorkIdentifier::Gdef.into(),
+            BeWorkIdentifier::Os2.into(),
+            BeWorkIdentifier::Hvar.into(),
+            BeWorkIdentifier::Post.into(),
+            BeWorkIdentifier::Cff.into(),
+        ] {
+            assert!(!result.work_completed.contains(&id), "{id:?} ran");
+        }
+
+        // Glyphs split by the first build stay split
+        assert!(result
+            .fe_context
+            .get_final_static_metadata()
+            .glyph_order
+            .contains(&GlyphName::from("contour_and_component.0")));
+
+        let buf = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&buf).unwrap();
+        let name = font.name().unwrap();
+        let family_names: Vec<_> = name
+            .name_record()
+            .iter()
+            .filter(|record| record.name_id() == NameId::FAMILY_NAME)
+            .map(|record| {
+                record
+                    .string(name.string_data())
+                    .unwrap()
+                    .chars()
+                    .collect::<String>()
+            })
+            .collect();
+        assert_eq!(vec!["Renamed Font".to_string()], family_names);
+    }
+
+    #[test]
+    fn names_only_edit_leaves_variations_alone() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "glyphs3/WghtVar.glyphs");
+        let source = temp_dir.path().join("WghtVar.glyphs");
+        fs::copy(&args.source, &source).unwrap();
+        args.source = source.clone();
+        compile(args.clone());
+
+        let glyphs = fs::read_to_string(&source).unwrap();
+        fs::write(
+            &source,
+            glyphs.replace("my/font/license", "my/renamed/license"),
+        )
+        .unwrap();
+        let result = compile(args.clone());
+
+        for id in [
+            AnyWorkId::Fe(FeWorkIdentifier::InitStaticMetadata),
+            BeWorkIdentifier::Name.into(),
+            BeWorkIdentifier::Font.into(),
+        ] {
+            assert!(result.work_completed.contains(&id), "{id:?} didn't run");
+        }
+        for id in [
+            AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata),
+            FeWorkIdentifier::Features.into(),
+            FeWorkIdentifier::Kerning.into(),
+            BeWorkIdentifier::Features.into(),
+            BeWorkIdentifier::Kerning.into(),
+            BeWorkIdentifier::Gdef.into(),
+            BeWorkIdentifier::Os2.into(),
+            BeWorkIdentifier::Hvar.into(),
+            BeWorkIdentifier::Avar.into(),
+            BeWorkIdentifier::Gvar.into(),
+            BeWorkIdentifier::Post.into(),
+        ] {
+            assert!(!result.work_completed.contains(&id), "{id:?} ran");
+        }
+
+        let fresh_dir = temp_dir.path().join("fresh");
+        args.build_dir = fresh_dir.clone();
+        compile(args);
+        assert_eq!(
+            read_file(&build_dir.join("font.ttf")),
+            read_file(&fresh_dir.join("font.ttf"))
+        );
+    }
+
+    #[test]
+    fn master_metric_edit_rebuilds_hhea_and_font() {
+        let temp_dir = tempdir().unwrap();
+        let build_dir = temp_dir.path().join("build");
+        let mut args = Args::for_test(&build_dir, "glyphs3/WghtVar.glyphs");
+        let source = temp_dir.path().join("WghtVar.glyphs");
+        fs::copy(&args.source, &source).unwrap();
+        args.source = source.clone();
+        compile(args.clone());
+
+        // Master metrics are global metrics, not static metadata
+        let glyphs = fs::read_to_string(&source).unwrap();
+        fs::write(&source, glyphs.replacen("pos = 737;", "pos = 777;", 1)).unwrap();
+        let result = compile(args.clone());
+        for id in [
+            AnyWorkId::Fe(FeWorkIdentifier::GlobalMetrics),
+            BeWorkIdentifier::Hhea.into(),
+            BeWorkIdentifier::Os2.into(),
+            BeWorkIdentifier::Font.into(),
+        ] {
+            assert!(result.work_completed.contains(&id), "{id:?} didn't run");
+        }
+
+        let incremental = fs::read(build_dir.join("font.ttf")).unwrap();
+        let font = FontRef::new(&incremental).unwrap();
+        assert_eq!(777, font.hhea().unwrap().ascender().to_i16());
+
+        let fresh_dir = temp_dir.path().join("fresh");
+        args.build_dir = fresh_dir.clone();
+        compile(args);
+        assert_eq!(
+            read_file(&build_dir.join("hhea.table")),
+            read_file(&fresh_dir.join("hhea.table"))
+        );
+        // Tables the edit didn't touch, such as HVAR, are reused exactly as built
+        assert_eq!(incremental, read_file(&fresh_dir.join("font.ttf")));
+    }
 }
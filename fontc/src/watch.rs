@@ -0,0 +1,220 @@
+//! Rebuilding as the source changes, keeping everything built so far in memory
+
+use std::{collections::HashSet, path::PathBuf};
+
+use fontbe::{orchestration::Context as BeContext, paths::Paths as BePaths};
+use fontir::{
+    orchestration::Context as FeContext, paths::Paths as IrPaths, source::Input,
+    stateset::StateIdentifier,
+};
+
+use crate::{compile_instance, create_workload, write_font_file, ChangeDetector, Config, Error};
+
+/// A build whose IR and binary results stay alive between runs.
+///
+/// A rebuild only redoes the work whose inputs changed, everything else is
+/// taken from the contexts of the previous run rather than read back from disk.
+pub struct IncrementalBuild {
+    config: Config,
+    ir_paths: IrPaths,
+    inputs: Input,
+    fe_root: FeContext,
+    be_root: BeContext,
+}
+
+impl IncrementalBuild {
+    /// Build the font, reusing whatever a previous process left in the build dir.
+    pub fn new(
+        config: Config,
+        ir_paths: IrPaths,
+        be_paths: BePaths,
+    ) -> Result<IncrementalBuild, Error> {
+        let prev_inputs = config.init()?;
+        let mut change_detector =
+            ChangeDetector::new(config.clone(), ir_paths.clone(), prev_inputs)?;
+        let workload = create_workload(&mut change_detector)?;
+
+        let fe_root = FeContext::new_root(
+            config.args.flags(),
+            ir_paths.clone(),
+            change_detector.current_inputs().clone(),
+        )
+        .with_max_conversion_error(config.args.max_conversion_error());
+        let be_root = BeContext::new_root(config.args.flags(), be_paths, &fe_root)
+            .with_iup_tolerance(config.args.iup_tolerance);
+        workload.exec(&fe_root, &be_root)?;
+
+        let inputs = change_detector.current_inputs().clone();
+        change_detector.finish_successfully()?;
+
+        let build = IncrementalBuild {
+            config,
+            ir_paths,
+            inputs,
+            fe_root,
+            be_root,
+        };
+        build.write()?;
+        Ok(build)
+    }
+
+    /// Redo the work that changes to the source since the last successful build call for.
+    ///
+    /// Returns false, having done nothing, if the source is unchanged. If the
+    /// rebuild fails the next one picks up every change since the last success.
+    pub fn rebuild(&mut self) -> Result<bool, Error> {
+        let mut change_detector = ChangeDetector::new(
+            self.config.clone(),
+            self.ir_paths.clone(),
+            self.inputs.clone(),
+        )?
+        .with_outputs_in_memory();
+        if *change_detector.current_inputs() == self.inputs {
+            return Ok(false);
+        }
+        let workload = create_workload(&mut change_detector)?;
+
+        let fe_root = self
+            .fe_root
+            .with_input(change_detector.current_inputs().clone());
+        let be_root = self.be_root.with_ir(&fe_root);
+        workload.exec(&fe_root, &be_root)?;
+
+        self.inputs = change_detector.current_inputs().clone();
+        change_detector.finish_successfully()?;
+        self.fe_root = fe_root;
+        self.be_root = be_root;
+
+        self.write()?;
+        Ok(true)
+    }
+
+    /// Write the font, and any static instances asked for, to the build dir.
+    fn write(&self) -> Result<(), Error> {
+        write_font_file(&self.config.args, &self.be_root)?;
+        if self.config.args.instances {
+            for instance in self.fe_root.get_init_static_metadata().named_instances() {
+                compile_instance(&self.config.args, &self.fe_root, instance)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fe_context(&self) -> &FeContext {
+        &self.fe_root
+    }
+
+    pub fn be_context(&self) -> &BeContext {
+        &self.be_root
+    }
+
+    /// The files the last successful build read.
+    pub fn input_files(&self) -> HashSet<PathBuf> {
+        let inputs = &self.inputs;
+        [
+            &inputs.static_metadata,
+            &inputs.global_metrics,
+            &inputs.features,
+            &inputs.kerning,
+        ]
+        .into_iter()
+        .chain(inputs.glyphs.values())
+        .flat_map(|state| state.keys())
+        .filter_map(|key| match key {
+            StateIdentifier::File(path) => Some(path.clone()),
+            StateIdentifier::Memory(..) => None,
+        })
+        .collect()
+    }
+}
+
+/// Rebuild whenever a file the last build read changes, until killed.
+///
+/// Failed rebuilds are reported and we keep watching, the source may well be
+/// saved mid-edit.
+#[cfg(target_os = "linux")]
+pub fn watch(mut build: IncrementalBuild) -> Result<(), Error> {
+    use std::{
+        collections::HashMap,
+        io::ErrorKind,
+        path::Path,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use inotify::{EventMask, Inotify, WatchMask};
+    use log::{debug, error, info};
+
+    // Editors tend to save in several steps, give them a moment to finish
+    const SETTLE_TIME: Duration = Duration::from_millis(50);
+
+    // Watch directories rather than files so saves that replace a file are seen
+    let mask = WatchMask::CLOSE_WRITE
+        | WatchMask::MOVED_TO
+        | WatchMask::MOVED_FROM
+        | WatchMask::CREATE
+        | WatchMask::DELETE;
+    let mut inotify = Inotify::init()?;
+    let mut dirs = HashMap::new();
+    let mut buffer = [0; 4096];
+
+    loop {
+        let input_files = build.input_files();
+        for file in input_files.iter() {
+            let dir = file.parent().unwrap_or(Path::new(""));
+            if !dirs.values().any(|watched| watched == dir) {
+                let to_watch = if dir == Path::new("") {
+                    Path::new(".")
+                } else {
+                    dir
+                };
+                let wd = inotify.watches().add(to_watch, mask)?;
+                dirs.insert(wd, dir.to_path_buf());
+            }
+        }
+        info!("Watching {} files for changes", input_files.len());
+
+        // Wait for something to happen to a file we read
+        'wait: loop {
+            for event in inotify.read_events_blocking(&mut buffer)? {
+                if event.mask.contains(EventMask::IGNORED) {
+                    dirs.remove(&event.wd);
+                    continue;
+                }
+                let (Some(dir), Some(name)) = (dirs.get(&event.wd), event.name) else {
+                    continue;
+                };
+                let path = dir.join(name);
+                if input_files.contains(&path) {
+                    debug!("{path:?} {:?}", event.mask);
+                    break 'wait;
+                }
+            }
+        }
+        loop {
+            thread::sleep(SETTLE_TIME);
+            match inotify.read_events(&mut buffer) {
+                Ok(mut events) => {
+                    if events.next().is_none() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let start = Instant::now();
+        match build.rebuild() {
+            Ok(true) => info!("Rebuilt in {:.2?}", start.elapsed()),
+            Ok(false) => debug!("No changes to rebuild"),
+            Err(e) => error!("Rebuild failed: {e} {e:?}"),
+        }
+    }
+}
+
+/// Watching relies on inotify.
+#[cfg(not(target_os = "linux"))]
+pub fn watch(_: IncrementalBuild) -> Result<(), Error> {
+    Err(Error::WatchUnsupported)
+}
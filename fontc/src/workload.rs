@@ -21,6 +21,8 @@ pub struct Workload {
     success: HashSet<AnyWorkId>,
     error: Vec<(AnyWorkId, String)>,
     pub(crate) jobs_pending: HashMap<AnyWorkId, Job>,
+    // Whether finalizing static metadata means every glyph needs BE work, or just new ones
+    pub(crate) rebuild_all_glyphs: bool,
 }
 
 /// A unit of executable work plus the identifiers of work that it depends on
@@ -74,12 +76,15 @@ impl Workload {
             // When static metadata finalizes, add BE work for any new glyphs
             // If anything progresses before we've done this we have a graph bug
             AnyWorkId::Fe(FeWorkIdentifier::FinalizeStaticMetadata) => {
+                let init_glyph_order = fe_root.get_init_static_metadata().glyph_order.clone();
                 for glyph_name in fe_root.get_final_static_metadata().glyph_order.iter() {
                     let glyph_work_id =
                         AnyWorkId::Be(BeWorkIdentifier::GlyfFragment(glyph_name.clone()));
                     let gvar_work_id =
                         AnyWorkId::Be(BeWorkIdentifier::GvarFragment(glyph_name.clone()));
-                    if self.jobs_pending.contains_key(&glyph_work_id) {
+                    if self.jobs_pending.contains_key(&glyph_work_id)
+                        || (!self.rebuild_all_glyphs && init_glyph_order.contains(glyph_name))
+                    {
                         continue;
                     }
 
@@ -101,6 +106,15 @@ impl Workload {
 
                     super::add_glyph_be_job(self, fe_root, glyph_name.clone());
                 }
+
+                // All glyph IR is final by now, including that of components that didn't change
+                // and so have no job to wait on
+                for (id, job) in self.jobs_pending.iter_mut() {
+                    if matches!(id, AnyWorkId::Be(BeWorkIdentifier::GlyfFragment(..))) {
+                        job.dependencies
+                            .retain(|id| !matches!(id, AnyWorkId::Fe(FeWorkIdentifier::Glyph(..))));
+                    }
+                }
             }
 
             // GlyfFragment carries GvarFragment along for the ride
@@ -114,9 +128,16 @@ impl Workload {
                 self.mark_success(AnyWorkId::Be(BeWorkIdentifier::LocaFormat));
             }
 
-            // Hmtx carries hhea along for the ride
+            // Cff carries Cff2 along for the ride
+            AnyWorkId::Be(BeWorkIdentifier::Cff) => {
+                self.mark_success(AnyWorkId::Be(BeWorkIdentifier::Cff2));
+            }
+
+            // Hmtx carries hhea, vmtx and vhea along for the ride
             AnyWorkId::Be(BeWorkIdentifier::Hmtx) => {
                 self.mark_success(AnyWorkId::Be(BeWorkIdentifier::Hhea));
+                self.mark_success(AnyWorkId::Be(BeWorkIdentifier::Vmtx));
+                self.mark_success(AnyWorkId::Be(BeWorkIdentifier::Vhea));
             }
             _ => (),
         }
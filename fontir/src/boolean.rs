@@ -0,0 +1,459 @@
+//! Boolean union of contours, to remove overlaps.
+//!
+//! Segments are cut wherever they cross one another. A piece with fill on one side only is
+//! part of the outline of the union; a piece with fill, or no fill, on both sides is not.
+//! The pieces we keep are joined back up into contours. Curves stay curves, cut at the
+//! crossings, and every contour is drawn with the fill on its left so outer contours run
+//! counter-clockwise, as in PostScript sources.
+
+use std::{collections::HashMap, ops::Range};
+
+use kurbo::{
+    BezPath, CubicBez, Line, ParamCurve, ParamCurveExtrema, PathEl, PathSeg, Point, QuadBez, Shape,
+    Vec2,
+};
+
+use crate::overlap::rects_intersect;
+
+/// Points closer than this, in font units, are the same point
+const POINT_TOLERANCE: f64 = 1e-3;
+
+/// Curves are cut in half until they are this small, in font units, to find where they cross
+const CURVE_TOLERANCE: f64 = 1e-4;
+
+/// How many times we may cut a pair of curves before giving up, e.g. because they coincide
+const MAX_SUBDIVISIONS: usize = 10_000;
+
+/// How far, in font units, either side of a piece we look for fill
+const SIDE_OFFSET: f64 = 1e-2;
+
+/// Part of a segment, running between two vertices.
+struct Piece {
+    seg: PathSeg,
+    start: usize,
+    end: usize,
+}
+
+/// Every distinct point where pieces start or end.
+#[derive(Default)]
+struct Vertices(Vec<Point>);
+
+impl Vertices {
+    fn id(&mut self, p: Point) -> usize {
+        if let Some(id) = self.0.iter().position(|v| v.distance(p) < POINT_TOLERANCE) {
+            return id;
+        }
+        self.0.push(p);
+        self.0.len() - 1
+    }
+}
+
+/// The union of the area filled, nonzero, by contours.
+///
+/// None if we can't find a union we trust, such as when curves coincide or a contour is open.
+pub fn union(contours: &[BezPath]) -> Option<Vec<BezPath>> {
+    let closed = contours.iter().all(|contour| {
+        contour
+            .elements()
+            .iter()
+            .filter(|el| matches!(el, PathEl::MoveTo(..)))
+            .count()
+            == contour
+                .elements()
+                .iter()
+                .filter(|el| matches!(el, PathEl::ClosePath))
+                .count()
+    });
+    if !closed {
+        return None;
+    }
+    let segments: Vec<_> = contours.iter().flat_map(|c| c.segments()).collect();
+
+    // Find where segments cross
+    let mut cuts: Vec<Vec<(f64, Point)>> = vec![Vec::new(); segments.len()];
+    let bboxes: Vec<_> = segments
+        .iter()
+        .map(|seg| ParamCurveExtrema::bounding_box(seg).inflate(POINT_TOLERANCE, POINT_TOLERANCE))
+        .collect();
+    for i in 0..segments.len() {
+        for j in i + 1..segments.len() {
+            if !rects_intersect(bboxes[i], bboxes[j]) {
+                continue;
+            }
+            for (t1, t2) in intersections(&segments[i], &segments[j])? {
+                let p = segments[i].eval(t1).midpoint(segments[j].eval(t2));
+                cuts[i].push((t1, p));
+                cuts[j].push((t2, p));
+            }
+        }
+    }
+
+    // Cut segments into pieces that meet at vertices
+    let mut vertices = Vertices::default();
+    let mut pieces = Vec::new();
+    for (seg, mut cuts) in segments.iter().zip(cuts) {
+        // Cuts at the ends of the segment are vertices already
+        cuts.retain(|(t, p)| {
+            *t > 0.0
+                && *t < 1.0
+                && p.distance(seg.start()) >= POINT_TOLERANCE
+                && p.distance(seg.end()) >= POINT_TOLERANCE
+        });
+        cuts.sort_by(|(t1, _), (t2, _)| t1.total_cmp(t2));
+        cuts.push((1.0, seg.end()));
+
+        let (mut t0, mut p0) = (0.0, seg.start());
+        for (t1, p1) in cuts {
+            if p0.distance(p1) < POINT_TOLERANCE && t1 < 1.0 {
+                continue;
+            }
+            let (start, end) = (vertices.id(p0), vertices.id(p1));
+            let piece = with_ends(seg.subsegment(t0..t1), vertices.0[start], vertices.0[end]);
+            pieces.push(Piece {
+                seg: piece,
+                start,
+                end,
+            });
+            (t0, p0) = (t1, p1);
+        }
+    }
+
+    // Keep pieces with fill on one side, turned so the fill is on their left
+    let filled = |p: Point| contours.iter().map(|c| c.winding(p)).sum::<i32>() != 0;
+    let mut kept: Vec<Piece> = Vec::new();
+    for piece in pieces {
+        let bbox = ParamCurveExtrema::bounding_box(&piece.seg);
+        if bbox.width().max(bbox.height()) < POINT_TOLERANCE {
+            continue;
+        }
+        let direction = tangent(&piece.seg, 0.5);
+        if direction.hypot() == 0.0 {
+            continue;
+        }
+        let normal = Vec2::new(-direction.y, direction.x).normalize() * SIDE_OFFSET;
+        let mid = piece.seg.eval(0.5);
+        let piece = match (filled(mid + normal), filled(mid - normal)) {
+            (true, false) => piece,
+            (false, true) => Piece {
+                seg: piece.seg.reverse(),
+                start: piece.end,
+                end: piece.start,
+            },
+            _ => continue,
+        };
+        // The same outline drawn twice need only be kept once
+        if !kept.iter().any(|k| same_piece(k, &piece)) {
+            kept.push(piece);
+        }
+    }
+
+    // Join pieces up into contours
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, piece) in kept.iter().enumerate() {
+        outgoing.entry(piece.start).or_default().push(idx);
+    }
+    let mut used = vec![false; kept.len()];
+    let mut result = Vec::new();
+    for first in 0..kept.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let mut contour = vec![kept[first].seg];
+        let mut current = first;
+        while kept[current].end != kept[first].start {
+            // Where contours touch prefer the leftmost turn, keeping to the edge of the fill
+            let incoming = tangent(&kept[current].seg, 1.0);
+            let next = outgoing
+                .get(&kept[current].end)?
+                .iter()
+                .copied()
+                .filter(|idx| !used[*idx])
+                .max_by(|a, b| {
+                    let turn_a = turn(incoming, tangent(&kept[*a].seg, 0.0));
+                    let turn_b = turn(incoming, tangent(&kept[*b].seg, 0.0));
+                    turn_a.total_cmp(&turn_b)
+                })?;
+            used[next] = true;
+            contour.push(kept[next].seg);
+            current = next;
+        }
+        result.push(to_path(merge_lines(contour)));
+    }
+    Some(result)
+}
+
+/// The parameters at which two segments meet
+fn intersections(seg1: &PathSeg, seg2: &PathSeg) -> Option<Vec<(f64, f64)>> {
+    let mut found = Vec::new();
+    match (seg1, seg2) {
+        (PathSeg::Line(line1), PathSeg::Line(line2)) => found.extend(line_line(*line1, *line2)),
+        (PathSeg::Line(line), seg) => found.extend(
+            seg.intersect_line(*line)
+                .iter()
+                .map(|i| (i.line_t, i.segment_t)),
+        ),
+        (seg, PathSeg::Line(line)) => found.extend(
+            seg.intersect_line(*line)
+                .iter()
+                .map(|i| (i.segment_t, i.line_t)),
+        ),
+        _ => {
+            let mut budget = MAX_SUBDIVISIONS;
+            curve_curve(seg1, 0.0..1.0, seg2, 0.0..1.0, &mut budget, &mut found)?;
+        }
+    }
+    Some(found)
+}
+
+fn line_line(line1: Line, line2: Line) -> Vec<(f64, f64)> {
+    let (d1, d2) = (line1.p1 - line1.p0, line2.p1 - line2.p0);
+    let offset = line2.p0 - line1.p0;
+    let denom = d1.cross(d2);
+    if denom.abs() <= f64::EPSILON * d1.hypot2().max(d2.hypot2()) {
+        // Parallel. If they run along one another each is cut where the other ends.
+        if offset.cross(d1).abs() > POINT_TOLERANCE * d1.hypot() {
+            return Vec::new();
+        }
+        let along = |p: Point, line: Line, d: Vec2| (p - line.p0).dot(d) / d.hypot2();
+        let mut found = Vec::new();
+        for (p, t2) in [(line2.p0, 0.0), (line2.p1, 1.0)] {
+            found.push((along(p, line1, d1), t2));
+        }
+        for (p, t1) in [(line1.p0, 0.0), (line1.p1, 1.0)] {
+            found.push((t1, along(p, line2, d2)));
+        }
+        found.retain(|(t1, t2)| (0.0..=1.0).contains(t1) && (0.0..=1.0).contains(t2));
+        return found;
+    }
+    let t1 = offset.cross(d2) / denom;
+    let t2 = offset.cross(d1) / denom;
+    if (0.0..=1.0).contains(&t1) && (0.0..=1.0).contains(&t2) {
+        vec![(t1, t2)]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Cut both curves in half until the parts that might meet are small enough to treat as lines.
+fn curve_curve(
+    seg1: &PathSeg,
+    range1: Range<f64>,
+    seg2: &PathSeg,
+    range2: Range<f64>,
+    budget: &mut usize,
+    found: &mut Vec<(f64, f64)>,
+) -> Option<()> {
+    *budget = budget.checked_sub(1)?;
+    let (part1, part2) = (
+        seg1.subsegment(range1.clone()),
+        seg2.subsegment(range2.clone()),
+    );
+    let (bbox1, bbox2) = (
+        ParamCurveExtrema::bounding_box(&part1),
+        ParamCurveExtrema::bounding_box(&part2),
+    );
+    if !rects_intersect(bbox1, bbox2) {
+        return Some(());
+    }
+    let small = |bbox: kurbo::Rect| bbox.width().max(bbox.height()) < CURVE_TOLERANCE;
+    if small(bbox1) && small(bbox2) {
+        let chord1 = Line::new(part1.start(), part1.end());
+        let chord2 = Line::new(part2.start(), part2.end());
+        for (t1, t2) in line_line(chord1, chord2) {
+            found.push((
+                range1.start + t1 * (range1.end - range1.start),
+                range2.start + t2 * (range2.end - range2.start),
+            ));
+        }
+        return Some(());
+    }
+    let mid1 = (range1.start + range1.end) / 2.0;
+    let mid2 = (range2.start + range2.end) / 2.0;
+    for sub1 in [range1.start..mid1, mid1..range1.end] {
+        for sub2 in [range2.start..mid2, mid2..range2.end] {
+            curve_curve(seg1, sub1.clone(), seg2, sub2, budget, found)?;
+        }
+    }
+    Some(())
+}
+
+/// The segment, moved to start and end exactly at the given points
+fn with_ends(seg: PathSeg, start: Point, end: Point) -> PathSeg {
+    match seg {
+        PathSeg::Line(..) => Line::new(start, end).into(),
+        PathSeg::Quad(quad) => QuadBez::new(start, quad.p1, end).into(),
+        PathSeg::Cubic(cubic) => CubicBez::new(start, cubic.p1, cubic.p2, end).into(),
+    }
+}
+
+/// The direction of the segment at t, estimated so a control point on top of an end doesn't matter
+fn tangent(seg: &PathSeg, t: f64) -> Vec2 {
+    const DT: f64 = 1e-4;
+    seg.eval((t + DT).min(1.0)) - seg.eval((t - DT).max(0.0))
+}
+
+/// How far, in radians, we turn going from one direction to the other. Left is positive.
+fn turn(from: Vec2, to: Vec2) -> f64 {
+    from.cross(to).atan2(from.dot(to))
+}
+
+fn same_piece(piece1: &Piece, piece2: &Piece) -> bool {
+    let close = |p1: Point, p2: Point| p1.distance(p2) < POINT_TOLERANCE;
+    piece1.start == piece2.start
+        && piece1.end == piece2.end
+        && match (piece1.seg, piece2.seg) {
+            (PathSeg::Line(..), PathSeg::Line(..)) => true,
+            (PathSeg::Quad(q1), PathSeg::Quad(q2)) => close(q1.p1, q2.p1),
+            (PathSeg::Cubic(c1), PathSeg::Cubic(c2)) => close(c1.p1, c2.p1) && close(c1.p2, c2.p2),
+            _ => false,
+        }
+}
+
+/// Join consecutive lines that run straight on, such as the two halves of a line that was cut
+fn merge_lines(segs: Vec<PathSeg>) -> Vec<PathSeg> {
+    let straight_on = |line1: Line, line2: Line| {
+        let (d1, d2) = (line1.p1 - line1.p0, line2.p1 - line2.p0);
+        d1.dot(d2) > 0.0 && d1.cross(line2.p1 - line1.p0).abs() < POINT_TOLERANCE * d1.hypot()
+    };
+    let mut merged: Vec<PathSeg> = Vec::with_capacity(segs.len());
+    for seg in segs {
+        if let (Some(PathSeg::Line(prev)), PathSeg::Line(line)) = (merged.last(), seg) {
+            if straight_on(*prev, line) {
+                *merged.last_mut().unwrap() = Line::new(prev.p0, line.p1).into();
+                continue;
+            }
+        }
+        merged.push(seg);
+    }
+    // The contour may have started part way along a line
+    if merged.len() > 2 {
+        if let (PathSeg::Line(last), PathSeg::Line(first)) = (merged[merged.len() - 1], merged[0]) {
+            if straight_on(last, first) {
+                merged[0] = Line::new(last.p0, first.p1).into();
+                merged.pop();
+            }
+        }
+    }
+    merged
+}
+
+fn to_path(segs: Vec<PathSeg>) -> BezPath {
+    let mut path = BezPath::new();
+    path.move_to(segs[0].start());
+    let last = segs.len() - 1;
+    for (idx, seg) in segs.into_iter().enumerate() {
+        match seg {
+            // Closing the path draws the last line
+            PathSeg::Line(..) if idx == last => (),
+            PathSeg::Line(line) => path.line_to(line.p1),
+            PathSeg::Quad(quad) => path.quad_to(quad.p1, quad.p2),
+            PathSeg::Cubic(cubic) => path.curve_to(cubic.p1, cubic.p2, cubic.p3),
+        }
+    }
+    path.close_path();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use kurbo::{Affine, BezPath, Circle, PathEl, Rect, Shape};
+
+    use super::union;
+
+    /// Counter-clockwise
+    fn square(x: f64, y: f64, size: f64) -> BezPath {
+        Rect::new(x, y, x + size, y + size).to_path(0.1)
+    }
+
+    fn clockwise_square(x: f64, y: f64, size: f64) -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to((x, y));
+        path.line_to((x, y + size));
+        path.line_to((x + size, y + size));
+        path.line_to((x + size, y));
+        path.close_path();
+        path
+    }
+
+    fn area(contours: &[BezPath]) -> f64 {
+        contours.iter().map(|c| c.area()).sum()
+    }
+
+    fn num_segments(contours: &[BezPath]) -> usize {
+        contours.iter().map(|c| c.segments().count()).sum()
+    }
+
+    #[test]
+    fn crossing_squares() {
+        let result = union(&[square(0.0, 0.0, 100.0), square(50.0, 50.0, 100.0)]).unwrap();
+        assert_eq!(1, result.len(), "{result:?}");
+        assert_eq!(8, num_segments(&result), "{result:?}");
+        assert!((17500.0 - area(&result)).abs() < 1e-6, "{}", area(&result));
+    }
+
+    #[test]
+    fn touching_squares_merge() {
+        let result = union(&[square(0.0, 0.0, 100.0), square(100.0, 0.0, 100.0)]).unwrap();
+        assert_eq!(1, result.len(), "{result:?}");
+        assert_eq!(4, num_segments(&result), "{result:?}");
+        assert!((20000.0 - area(&result)).abs() < 1e-6, "{}", area(&result));
+    }
+
+    #[test]
+    fn counter_is_kept() {
+        let outer = square(0.0, 0.0, 100.0);
+        let inner = clockwise_square(25.0, 25.0, 50.0);
+        let result = union(&[outer, inner]).unwrap();
+        assert_eq!(2, result.len(), "{result:?}");
+        assert!((7500.0 - area(&result)).abs() < 1e-6, "{}", area(&result));
+    }
+
+    #[test]
+    fn clockwise_becomes_counter_clockwise() {
+        let result = union(&[
+            clockwise_square(0.0, 0.0, 100.0),
+            clockwise_square(50.0, 50.0, 100.0),
+        ])
+        .unwrap();
+        assert_eq!(1, result.len(), "{result:?}");
+        assert!((17500.0 - area(&result)).abs() < 1e-6, "{}", area(&result));
+    }
+
+    #[test]
+    fn duplicate_is_dropped() {
+        let result = union(&[square(0.0, 0.0, 100.0), square(0.0, 0.0, 100.0)]).unwrap();
+        assert!((10000.0 - area(&result)).abs() < 1e-6, "{}", area(&result));
+        assert_eq!(4, num_segments(&result), "{result:?}");
+    }
+
+    #[test]
+    fn crossing_circles_stay_cubic() {
+        let radius = 100.0;
+        let distance = 150.0;
+        let circle = Circle::new((0.0, 0.0), radius).to_path(0.1);
+        let other = Affine::translate((distance, 0.0)) * circle.clone();
+        let result = union(&[circle.clone(), other]).unwrap();
+        assert_eq!(1, result.len(), "{result:?}");
+        assert!(result[0]
+            .elements()
+            .iter()
+            .all(|el| !matches!(el, PathEl::QuadTo(..))));
+        assert!(result[0]
+            .elements()
+            .iter()
+            .any(|el| matches!(el, PathEl::CurveTo(..))));
+
+        // The union is both circles less the lens where they overlap
+        let lens = 2.0 * radius * radius * (distance / (2.0 * radius)).acos()
+            - distance / 2.0 * (4.0 * radius * radius - distance * distance).sqrt();
+        let expected = 2.0 * PI * radius * radius - lens;
+        assert!(
+            (expected - area(&result)).abs() < expected * 1e-3,
+            "{expected} {}",
+            area(&result)
+        );
+    }
+}
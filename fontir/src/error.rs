@@ -5,7 +5,11 @@ use fontdrasil::types::GlyphName;
 use kurbo::Point;
 use thiserror::Error;
 
-use crate::coords::{DesignCoord, NormalizedCoord, NormalizedLocation, UserCoord, UserLocation};
+use crate::{
+    coords::{DesignCoord, NormalizedCoord, NormalizedLocation, UserCoord, UserLocation},
+    interpolatable::{report, GlyphIncompatibility},
+    variations::DeltaError,
+};
 
 // TODO: eliminate dyn Error and collapse Error/WorkError
 
@@ -77,8 +81,6 @@ pub enum WorkError {
     NoGlyphForName(GlyphName),
     #[error("File expected: {0:?}")]
     FileExpected(PathBuf),
-    #[error("Expected to match: {0:?}, {1:?}")]
-    FileMismatch(PathBuf, PathBuf),
     #[error("Metadata access expected: {0:?}")]
     MetadataFailed(PathBuf),
     #[error("Unable to parse {0:?}: {1}")]
@@ -114,6 +116,10 @@ pub enum WorkError {
     PathConversionError(#[from] PathConversionError),
     #[error("Variation model error")]
     VariationModelError(#[from] VariationModelError),
+    #[error("Unable to interpolate {what}: {error}")]
+    InterpolationError { what: String, error: DeltaError },
+    #[error("Sources are not interpolation compatible:\n{}", report(.0))]
+    InterpolationIncompatible(Vec<GlyphIncompatibility>),
     #[error("Contour reversal error {0}")]
     ContourReversalError(String),
     #[error("Unable to determine units per em")]
@@ -141,6 +147,16 @@ pub enum PathConversionError {
         num_offcurve: usize,
         points: Vec<Point>,
     },
+    #[error("{glyph_name} paths can't be converted together: {reason}")]
+    IncompatiblePaths {
+        glyph_name: GlyphName,
+        reason: String,
+    },
+    #[error("{glyph_name} has no quadratic approximation within {max_error} units")]
+    NoQuadraticApproximation {
+        glyph_name: GlyphName,
+        max_error: f64,
+    },
 }
 
 #[derive(Debug, Error)]
@@ -7,16 +7,18 @@ use std::collections::{HashSet, VecDeque};
 
 use fontdrasil::{orchestration::Work, types::GlyphName};
 use indexmap::IndexSet;
-use kurbo::Affine;
-use log::{debug, log_enabled, trace};
+use kurbo::{Affine, BezPath};
+use log::{debug, log_enabled, trace, warn};
 use ordered_float::OrderedFloat;
 use write_fonts::pens::{write_to_pen, BezPathPen, ReverseContourPen};
 
 use crate::{
+    boolean,
     coords::NormalizedLocation,
     error::WorkError,
-    ir::{Component, Glyph, GlyphBuilder},
+    ir::{Component, FeatureVariations, Glyph, GlyphBuilder, SubstitutionRule},
     orchestration::{Context, Flags, IrWork},
+    overlap, quadratic,
 };
 
 pub fn create_finalize_static_metadata_work() -> Box<IrWork> {
@@ -76,6 +78,76 @@ fn name_for_derivative(base_name: &GlyphName, names_in_use: &IndexSet<GlyphName>
     }
 }
 
+/// The derivatives, named as by [name_for_derivative], that glyph uses as components but that aren't in the glyph order.
+///
+/// Finalizing glyph IR that was finalized by a prior build, and hasn't changed since,
+/// finds glyphs that were already split so their derivatives have to be picked back up.
+fn derivatives_in_use<'a>(
+    glyph: &'a Glyph,
+    names_in_use: &'a IndexSet<GlyphName>,
+) -> impl Iterator<Item = &'a GlyphName> {
+    let prefix = format!("{}.", glyph.name);
+    glyph
+        .default_instance()
+        .components
+        .iter()
+        .map(|component| &component.base)
+        .filter(move |base| {
+            base.as_str()
+                .strip_prefix(&prefix)
+                .is_some_and(|suffix| suffix.parse::<u32>().is_ok())
+                && !names_in_use.contains(*base)
+        })
+}
+
+/// Alternates are named as glyphsLib names bracket glyphs, e.g. dollar.BRACKET.varAlt01
+fn name_for_alternate(base_name: &GlyphName, names_in_use: &IndexSet<GlyphName>) -> GlyphName {
+    let mut i = 1;
+    let base_name = base_name.as_str();
+    loop {
+        let new_name: GlyphName = format!("{base_name}.BRACKET.varAlt{i:02}").into();
+        if !names_in_use.contains(&new_name) {
+            return new_name;
+        }
+        i += 1;
+    }
+}
+
+/// Make a glyph of each alternate and a rule to substitute it in where its conditions are met.
+///
+/// Alternates that share conditions share a rule.
+fn split_alternates(
+    context: &Context,
+    glyph_order: &mut IndexSet<GlyphName>,
+    feature_variations: &mut FeatureVariations,
+    glyph: &Glyph,
+) -> Result<(), WorkError> {
+    for alternate in glyph.alternates.iter() {
+        let alt_name = name_for_alternate(&glyph.name, glyph_order);
+        let alt_glyph = Glyph::new(alt_name.clone(), HashSet::new(), alternate.sources.clone())?;
+        debug!("Split alternate {alt_name} from {}", glyph.name);
+
+        let condition_sets = vec![alternate.condition_set.clone()];
+        let substitution = (glyph.name.clone(), alt_name.clone());
+        match feature_variations
+            .rules
+            .iter_mut()
+            .find(|r| r.condition_sets == condition_sets)
+        {
+            Some(rule) => rule.substitutions.push(substitution),
+            None => feature_variations.rules.push(SubstitutionRule {
+                name: None,
+                condition_sets,
+                substitutions: vec![substitution],
+            }),
+        }
+
+        glyph_order.insert(alt_name);
+        context.set_glyph_ir(alt_glyph);
+    }
+    Ok(())
+}
+
 /// Returns a tuple of (simple glyph, composite glyph).
 ///
 /// The former contains all the contours, the latter contains all the components.
@@ -84,9 +156,12 @@ fn split_glyph(
     original: &Glyph,
 ) -> Result<(Glyph, Glyph), WorkError> {
     // Make a simple glyph by erasing the components from it
+    // Anchors stay with the original glyph, the derivative is never positioned directly
     let mut simple_glyph: GlyphBuilder = original.into();
+    simple_glyph.alternates.clear();
     simple_glyph.sources.iter_mut().for_each(|(_, inst)| {
         inst.components.clear();
+        inst.anchors.clear();
     });
 
     // Find a free name for the contour glyph
@@ -355,6 +430,109 @@ fn flatten_glyph(context: &Context, glyph: &Glyph) -> Result<(), WorkError> {
     Ok(())
 }
 
+/// The default outline of a component, with any nested components resolved to contours.
+pub fn component_contours(context: &Context, component: &Component) -> Vec<BezPath> {
+    let mut contours = Vec::new();
+    let mut frontier = VecDeque::from([(component.base.clone(), component.transform)]);
+    while let Some((glyph_name, transform)) = frontier.pop_front() {
+        let glyph = context.get_glyph_ir(&glyph_name);
+        let inst = glyph.default_instance();
+        contours.extend(
+            inst.contours
+                .iter()
+                .map(|contour| transform * contour.clone()),
+        );
+        frontier.extend(
+            inst.components
+                .iter()
+                .map(|c| (c.base.clone(), transform * c.transform)),
+        );
+    }
+    contours
+}
+
+/// Whether the default outlines of any two components of a glyph overlap
+pub fn components_overlap(context: &Context, glyph: &Glyph) -> bool {
+    let components = &glyph.default_instance().components;
+    if components.len() < 2 {
+        return false;
+    }
+    let outlines: Vec<_> = components
+        .iter()
+        .map(|c| component_contours(context, c))
+        .collect();
+    overlap::groups_overlap(&outlines)
+}
+
+/// Replace overlapping contours with their union.
+///
+/// A glyph whose components overlap one another is converted to contours first.
+fn remove_overlaps(context: &Context, glyph_name: &GlyphName) -> Result<(), WorkError> {
+    let glyph = context.get_glyph_ir(glyph_name);
+    if components_overlap(context, &glyph) {
+        debug!("Coalescing '{glyph_name}' into a simple glyph because its components overlap");
+        convert_components_to_contours(context, &glyph)?;
+    }
+
+    let glyph = context.get_glyph_ir(glyph_name);
+    if !glyph
+        .sources()
+        .values()
+        .any(|inst| overlap::contours_overlap(&inst.contours))
+    {
+        return Ok(());
+    }
+    trace!("Remove overlaps from '{glyph_name}'");
+    let mut glyph = (*glyph).clone();
+    for (loc, inst) in glyph.sources_mut() {
+        match boolean::union(&inst.contours) {
+            Some(contours) => inst.contours = contours,
+            None => warn!("Unable to remove overlaps from '{glyph_name}' at {loc:?}, keeping them"),
+        }
+    }
+    context.set_glyph_ir(glyph);
+    Ok(())
+}
+
+/// Convert the cubics of every glyph to quadratics, splitting the same curve the same way throughout.
+///
+/// Glyphs converted one at a time may split the same curve differently, e.g. a decomposed
+/// component and the glyph it came from, or two glyphs drawn from the same parts.
+fn convert_to_quadratic_font_wide(
+    context: &Context,
+    glyph_order: &IndexSet<GlyphName>,
+    default_location: &NormalizedLocation,
+    max_error: f64,
+) -> Result<(), WorkError> {
+    let glyphs: Vec<_> = glyph_order
+        .iter()
+        .map(|glyph_name| context.get_glyph_ir(glyph_name))
+        .filter(|glyph| quadratic::has_cubics(glyph.sources().values().flat_map(|i| &i.contours)))
+        .collect();
+    let contours: Vec<_> = glyphs
+        .iter()
+        .map(|glyph| {
+            let contours = glyph
+                .sources()
+                .iter()
+                .map(|(loc, inst)| (loc.clone(), inst.contours.clone()))
+                .collect();
+            (glyph.name.clone(), contours)
+        })
+        .collect();
+    trace!("Convert {} glyphs to quadratic font-wide", glyphs.len());
+
+    let converted = quadratic::to_quadratic_font_wide(&contours, default_location, max_error)?;
+    for (glyph, mut contours) in glyphs.into_iter().zip(converted) {
+        let mut glyph = (*glyph).clone();
+        for (loc, inst) in glyph.sources_mut() {
+            inst.contours = contours.remove(loc).unwrap();
+        }
+        context.set_glyph_ir(glyph);
+    }
+    Ok(())
+}
+
 impl Work<Context, WorkError> for FinalizeStaticMetadataWork {
     fn exec(&self, context: &Context) -> Result<(), WorkError> {
         // We should now have access to *all* the glyph IR
@@ -362,13 +540,27 @@ impl Work<Context, WorkError> for FinalizeStaticMetadataWork {
         // In particular, glyphs with both paths and components need to push the path into a component
         let current_metadata = context.get_init_static_metadata();
         let mut new_glyph_order = current_metadata.glyph_order.clone();
+        let mut feature_variations = current_metadata.feature_variations.clone();
+
+        // Alternates, such as Glyphs bracket layers, become glyphs in their own right
+        for glyph_name in current_metadata.glyph_order.iter() {
+            let glyph = context.get_glyph_ir(glyph_name);
+            if !glyph.alternates.is_empty() {
+                split_alternates(
+                    context,
+                    &mut new_glyph_order,
+                    &mut feature_variations,
+                    &glyph,
+                )?;
+            }
+        }
 
         // Glyphs with paths and components, and glyphs whose component 2x2 transforms vary over designspace
         // are not directly supported in fonts. To resolve we must do one of:
         // 1) need to push their paths to a new glyph that is a component
         // 2) collapse such glyphs into a simple (contour-only) glyph
         // fontmake (Python) prefers option 2.
-        for glyph_name in current_metadata.glyph_order.iter() {
+        for glyph_name in new_glyph_order.clone().iter() {
             let glyph = context.get_glyph_ir(glyph_name);
             let inconsistent_components = !has_consistent_2x2_transforms(&glyph);
             if inconsistent_components || has_components_and_contours(&glyph) {
@@ -387,6 +579,11 @@ impl Work<Context, WorkError> for FinalizeStaticMetadataWork {
                 } else {
                     move_contours_to_new_component(context, &mut new_glyph_order, &glyph)?;
                 }
+            } else {
+                let derivatives: Vec<_> = derivatives_in_use(&glyph, &new_glyph_order)
+                    .cloned()
+                    .collect();
+                new_glyph_order.extend(derivatives);
             }
         }
 
@@ -397,9 +594,33 @@ impl Work<Context, WorkError> for FinalizeStaticMetadataWork {
             }
         }
 
+        // Only static fonts; the union of each master need not interpolate
+        if context.flags.contains(Flags::REMOVE_OVERLAPS)
+            && current_metadata.variable_axes.is_empty()
+        {
+            for glyph_name in new_glyph_order.iter() {
+                remove_overlaps(context, glyph_name)?;
+            }
+        }
+
+        if context.flags.contains(Flags::FONT_WIDE_QUADRATICS)
+            && !context.flags.contains(Flags::CFF_OUTLINES)
+        {
+            convert_to_quadratic_font_wide(
+                context,
+                &new_glyph_order,
+                current_metadata.default_location(),
+                context
+                    .max_conversion_error
+                    .in_units(current_metadata.units_per_em),
+            )?;
+        }
+
         // We now have the final static metadata
-        // If the glyph order changed try not to forget about it
-        if current_metadata.glyph_order != new_glyph_order {
+        // If the glyph order or rules changed try not to forget about it
+        if current_metadata.glyph_order != new_glyph_order
+            || current_metadata.feature_variations != feature_variations
+        {
             if log_enabled!(log::Level::Trace) {
                 let mut new_glyphs: Vec<_> = new_glyph_order
                     .difference(&current_metadata.glyph_order)
@@ -412,6 +633,7 @@ impl Work<Context, WorkError> for FinalizeStaticMetadataWork {
             }
             let mut updated_metadata = (*current_metadata).clone();
             updated_metadata.glyph_order = new_glyph_order;
+            updated_metadata.feature_variations = feature_variations;
             context.set_final_static_metadata(updated_metadata);
         } else {
             trace!("No new glyphs; final static metadata is unchanged");
@@ -433,15 +655,19 @@ mod tests {
     use crate::{
         coords::{NormalizedCoord, NormalizedLocation},
         glyph::has_consistent_2x2_transforms,
-        ir::{Component, Glyph, GlyphBuilder, GlyphInstance},
+        ir::{
+            Component, Condition, FeatureVariations, Glyph, GlyphAlternate, GlyphBuilder,
+            GlyphInstance, SubstitutionRule,
+        },
         orchestration::{Context, Flags},
         paths::Paths,
         source::Input,
     };
 
     use super::{
-        convert_components_to_contours, flatten_glyph, has_components_and_contours,
-        name_for_derivative, split_glyph,
+        convert_components_to_contours, derivatives_in_use, flatten_glyph,
+        has_components_and_contours, name_for_derivative, remove_overlaps, split_alternates,
+        split_glyph,
     };
 
     fn norm_loc(positions: &[(&str, f32)]) -> NormalizedLocation {
@@ -637,6 +863,67 @@ mod tests {
             .all(|gi| !gi.components.is_empty()));
     }
 
+    #[test]
+    fn derivatives_of_a_split_glyph_are_found() {
+        let split_me = contour_and_component_weight_glyph("glyphname");
+        let (simple, composite) = split_glyph(&IndexSet::new(), &split_me).unwrap();
+
+        let glyph_order = IndexSet::from([composite.name.clone(), "component".into()]);
+        assert_eq!(
+            vec![&simple.name],
+            derivatives_in_use(&composite, &glyph_order).collect::<Vec<_>>()
+        );
+
+        let glyph_order = IndexSet::from([composite.name.clone(), simple.name.clone()]);
+        assert_eq!(
+            Vec::<&GlyphName>::new(),
+            derivatives_in_use(&composite, &glyph_order).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn split_alternates_share_rules() {
+        let context = test_context();
+        let condition_set = vec![Condition {
+            axis: "W".to_string(),
+            min: NormalizedCoord::new(0.5),
+            max: NormalizedCoord::new(1.0),
+        }];
+        let mut glyph_order = IndexSet::new();
+        let mut feature_variations = FeatureVariations::default();
+        for name in ["dollar", "cent"] {
+            let mut glyph = contour_glyph(name);
+            glyph.alternates.push(GlyphAlternate {
+                condition_set: condition_set.clone(),
+                sources: contour_glyph(name).sources().clone(),
+            });
+            glyph_order.insert(glyph.name.clone());
+            split_alternates(&context, &mut glyph_order, &mut feature_variations, &glyph).unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                "dollar",
+                "dollar.BRACKET.varAlt01",
+                "cent",
+                "cent.BRACKET.varAlt01"
+            ],
+            glyph_order.iter().map(|n| n.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![SubstitutionRule {
+                name: None,
+                condition_sets: vec![condition_set],
+                substitutions: vec![
+                    ("dollar".into(), "dollar.BRACKET.varAlt01".into()),
+                    ("cent".into(), "cent.BRACKET.varAlt01".into()),
+                ],
+            }],
+            feature_variations.rules
+        );
+        assert_simple(&context.get_glyph_ir(&"cent.BRACKET.varAlt01".into()));
+    }
+
     #[test]
     fn components_to_contours_shallow() {
         let coalesce_me = contour_and_component_weight_glyph("coalesce_me");
@@ -866,4 +1153,35 @@ mod tests {
         flatten_glyph(&context, &test_data.deep_component).unwrap();
         assert_is_flattened_component(&context, test_data.deep_component.name);
     }
+
+    #[test]
+    fn remove_overlaps_decomposes_overlapping_components() {
+        let context = test_context();
+        context.set_glyph_ir(contour_glyph("shape"));
+        let twice = GlyphInstance {
+            components: vec![
+                Component {
+                    base: "shape".into(),
+                    transform: Affine::IDENTITY,
+                },
+                Component {
+                    base: "shape".into(),
+                    transform: Affine::translate((0.5, 0.0)),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut glyph = GlyphBuilder::new("twice".into());
+        glyph
+            .try_add_source(&norm_loc(&[("W", 0.0)]), twice)
+            .unwrap();
+        context.set_glyph_ir(glyph.try_into().unwrap());
+
+        remove_overlaps(&context, &"twice".into()).unwrap();
+
+        let glyph = context.get_glyph_ir(&"twice".into());
+        let inst = glyph.default_instance();
+        assert!(inst.components.is_empty(), "{inst:?}");
+        assert_eq!(1, inst.contours.len(), "{inst:?}");
+    }
 }
@@ -0,0 +1,597 @@
+//! Static instances of a variable font.
+//!
+//! Treats the IR of a variable font as a source. Everything is interpolated at the
+//! location of a named instance, producing IR for a static font that the backend
+//! compiles as it would any other.
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem::discriminant,
+    path::Path,
+};
+
+use font_types::NameId;
+use fontdrasil::{orchestration::Work, types::GlyphName};
+use indexmap::IndexSet;
+use kurbo::{Affine, BezPath, PathEl, Point};
+use log::{debug, trace, warn};
+use ordered_float::OrderedFloat;
+
+use crate::{
+    coords::{NormalizedCoord, NormalizedLocation},
+    error::{Error, WorkError},
+    ir::{
+        normalize_for_postscript, Anchor, Component, Features, GlobalMetrics, Glyph, GlyphInstance,
+        Kerning, NameBuilder, NamedInstance, StaticMetadata,
+    },
+    orchestration::{Context, IrWork, WorkId},
+    source::{Input, Source},
+    stateset::StateSet,
+};
+
+/// Style names that fit in the four-member family of name ids 1 and 2.
+const RIBBI_STYLES: [&str; 4] = ["Regular", "Italic", "Bold", "Bold Italic"];
+
+/// A [Source] that instantiates the IR of a variable font at a named instance.
+///
+/// The variable font must already have been compiled to IR.
+pub struct InstanceIrSource {
+    variable: Context,
+    instance: NamedInstance,
+}
+
+impl InstanceIrSource {
+    pub fn new(variable: &Context, instance: NamedInstance) -> InstanceIrSource {
+        InstanceIrSource {
+            variable: variable.read_only(),
+            instance,
+        }
+    }
+
+    fn location(&self) -> NormalizedLocation {
+        let static_metadata = self.variable.get_final_static_metadata();
+        let mut location = NormalizedLocation::new();
+        for axis in static_metadata.axes.iter() {
+            let pos = self
+                .instance
+                .location
+                .get(&axis.name)
+                .unwrap_or(axis.default);
+            location.set_pos(axis.name.clone(), pos.to_normalized(&axis.converter));
+        }
+        location
+    }
+
+    /// Track the variable IR file for id, along with the instance we are building.
+    fn ir_state(&self, id: &WorkId) -> Result<StateSet, Error> {
+        let mut state = StateSet::new();
+        track_if_present(&mut state, &self.variable.paths().target_file(id))?;
+        state.track_memory("/instance".to_string(), &self.instance)?;
+        Ok(state)
+    }
+}
+
+/// Variable IR isn't written to disk if emit IR is off, in which case nothing is incremental.
+fn track_if_present(state: &mut StateSet, file: &Path) -> Result<(), Error> {
+    if file.is_file() {
+        state.track_file(file)?;
+    }
+    Ok(())
+}
+
+/// The glyph whose design glyph_name takes on at location, if a substitution rule applies there.
+fn substitute(
+    static_metadata: &StaticMetadata,
+    location: &NormalizedLocation,
+    glyph_name: &GlyphName,
+) -> Option<GlyphName> {
+    static_metadata
+        .feature_variations
+        .rules
+        .iter()
+        .filter(|rule| {
+            rule.condition_sets.iter().any(|condition_set| {
+                condition_set.iter().all(|condition| {
+                    let pos = location
+                        .get(&condition.axis)
+                        .unwrap_or(NormalizedCoord::new(0.0));
+                    condition.min <= pos && pos <= condition.max
+                })
+            })
+        })
+        .flat_map(|rule| rule.substitutions.iter())
+        .find(|(from, _)| from == glyph_name)
+        .map(|(_, to)| to.clone())
+}
+
+impl Source for InstanceIrSource {
+    fn inputs(&mut self) -> Result<Input, Error> {
+        let static_metadata = self.variable.get_final_static_metadata();
+        let location = self.location();
+
+        // Names and custom parameters are current only in init static metadata
+        let mut static_metadata_state = self.ir_state(&WorkId::FinalizeStaticMetadata)?;
+        track_if_present(
+            &mut static_metadata_state,
+            &self
+                .variable
+                .paths()
+                .target_file(&WorkId::InitStaticMetadata),
+        )?;
+
+        let mut glyphs = HashMap::new();
+        for glyph_name in static_metadata.glyph_order.iter() {
+            let mut state = StateSet::new();
+            let paths = self.variable.paths();
+            track_if_present(
+                &mut state,
+                &paths.target_file(&WorkId::Glyph(glyph_name.clone())),
+            )?;
+            if let Some(substitute) = substitute(&static_metadata, &location, glyph_name) {
+                track_if_present(&mut state, &paths.target_file(&WorkId::Glyph(substitute)))?;
+            }
+            glyphs.insert(glyph_name.clone(), state);
+        }
+
+        // The fea files themselves don't pass through IR so changes to them must be tracked directly
+        let mut features = self.variable.input.features.clone();
+        track_if_present(
+            &mut features,
+            &self.variable.paths().target_file(&WorkId::Features),
+        )?;
+        features.track_memory("/instance".to_string(), &self.instance)?;
+
+        Ok(Input {
+            static_metadata: static_metadata_state,
+            global_metrics: self.ir_state(&WorkId::GlobalMetrics)?,
+            glyphs,
+            features,
+            kerning: self.ir_state(&WorkId::Kerning)?,
+        })
+    }
+
+    fn create_static_metadata_work(&self, _input: &Input) -> Result<Box<IrWork>, Error> {
+        Ok(Box::new(StaticMetadataWork {
+            variable: self.variable.read_only(),
+            instance: self.instance.clone(),
+        }))
+    }
+
+    fn create_global_metric_work(&self, _input: &Input) -> Result<Box<IrWork>, Error> {
+        Ok(Box::new(GlobalMetricsWork {
+            variable: self.variable.read_only(),
+            location: self.location(),
+        }))
+    }
+
+    fn create_glyph_ir_work(
+        &self,
+        glyph_names: &IndexSet<GlyphName>,
+        _input: &Input,
+    ) -> Result<Vec<Box<IrWork>>, Error> {
+        let location = self.location();
+        Ok(glyph_names
+            .iter()
+            .map(|glyph_name| -> Box<IrWork> {
+                Box::new(GlyphWork {
+                    variable: self.variable.read_only(),
+                    glyph_name: glyph_name.clone(),
+                    location: location.clone(),
+                })
+            })
+            .collect())
+    }
+
+    fn create_feature_ir_work(&self, _input: &Input) -> Result<Box<IrWork>, Error> {
+        Ok(Box::new(FeatureWork {
+            variable: self.variable.read_only(),
+            location: self.location(),
+        }))
+    }
+
+    fn create_kerning_ir_work(&self, _input: &Input) -> Result<Box<IrWork>, Error> {
+        Ok(Box::new(KerningWork {
+            variable: self.variable.read_only(),
+            location: self.location(),
+        }))
+    }
+}
+
+struct StaticMetadataWork {
+    variable: Context,
+    instance: NamedInstance,
+}
+
+struct GlobalMetricsWork {
+    variable: Context,
+    location: NormalizedLocation,
+}
+
+struct GlyphWork {
+    variable: Context,
+    glyph_name: GlyphName,
+    location: NormalizedLocation,
+}
+
+struct FeatureWork {
+    variable: Context,
+    location: NormalizedLocation,
+}
+
+struct KerningWork {
+    variable: Context,
+    location: NormalizedLocation,
+}
+
+/// Names for the instance, as fontTools instancer updateNameTable would produce.
+///
+/// Family-wide names carry over from the variable font, style names are replaced.
+fn instance_names(variable: &StaticMetadata, instance: &NamedInstance) -> NameBuilder {
+    let variable_name = |name_id: NameId| {
+        variable
+            .names
+            .iter()
+            .find(|(key, _)| key.name_id == name_id)
+            .map(|(_, value)| value.clone())
+    };
+    let family = variable_name(NameId::TYPOGRAPHIC_FAMILY_NAME)
+        .or_else(|| variable_name(NameId::FAMILY_NAME))
+        .unwrap_or_default();
+    let style = instance.name.as_str();
+
+    // Font-specific names belong to axes, STAT and fvar, none of which a static font has
+    let replaced = [
+        NameId::FAMILY_NAME,
+        NameId::SUBFAMILY_NAME,
+        NameId::UNIQUE_ID,
+        NameId::FULL_NAME,
+        NameId::POSTSCRIPT_NAME,
+        NameId::TYPOGRAPHIC_FAMILY_NAME,
+        NameId::TYPOGRAPHIC_SUBFAMILY_NAME,
+        NameId::VARIATIONS_POSTSCRIPT_NAME_PREFIX,
+    ];
+    let mut names = NameBuilder::default();
+    for (key, value) in variable.names.iter() {
+        if key.name_id.to_u16() <= 255 && !replaced.contains(&key.name_id) {
+            names.add(key.name_id, value.clone());
+        }
+    }
+
+    if RIBBI_STYLES.contains(&style) {
+        names.add(NameId::FAMILY_NAME, family.clone());
+        names.add(NameId::SUBFAMILY_NAME, style.to_string());
+    } else {
+        names.add(NameId::FAMILY_NAME, format!("{family} {style}"));
+        names.add(NameId::SUBFAMILY_NAME, "Regular".to_string());
+        names.add(NameId::TYPOGRAPHIC_FAMILY_NAME, family.clone());
+        names.add(NameId::TYPOGRAPHIC_SUBFAMILY_NAME, style.to_string());
+    }
+    names.add(NameId::FULL_NAME, format!("{family} {style}"));
+
+    let postscript_name = instance.postscript_name.clone().unwrap_or_else(|| {
+        let mut postscript_name = format!("{family}-{style}");
+        normalize_for_postscript(&mut postscript_name, false);
+        postscript_name
+    });
+    names.add(NameId::POSTSCRIPT_NAME, postscript_name);
+
+    names.apply_default_fallbacks();
+    names
+}
+
+impl Work<Context, WorkError> for StaticMetadataWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Static metadata for instance {}", self.instance.name);
+        // Finalized static metadata isn't rebuilt when only names or custom parameters change
+        let init_variable = self.variable.get_init_static_metadata();
+        let variable = self.variable.get_final_static_metadata();
+
+        let mut static_metadata = StaticMetadata::new(
+            variable.units_per_em,
+            instance_names(&init_variable, &self.instance).into_inner(),
+            Vec::new(),
+            variable.glyph_order.clone(),
+            HashSet::from([NormalizedLocation::new()]),
+        )?;
+        static_metadata.vendor_id = init_variable.vendor_id;
+        static_metadata.gdef_categories = variable.gdef_categories.clone();
+        static_metadata.os2 = init_variable.os2.clone();
+
+        if let Some(weight) = variable
+            .axes
+            .iter()
+            .find(|axis| axis.tag == font_types::Tag::new(b"wght"))
+            .map(|axis| {
+                self.instance
+                    .location
+                    .get(&axis.name)
+                    .unwrap_or(axis.default)
+            })
+        {
+            static_metadata.os2.weight_class =
+                Some(weight.into_inner().into_inner().round().clamp(1.0, 1000.0) as u16);
+        }
+
+        // Bold and italic bits describe the style, not the family
+        let mut selection_flags = static_metadata.os2.selection_flags & !(1 | 1 << 5);
+        match self.instance.name.as_str() {
+            "Italic" => selection_flags |= 1,
+            "Bold" => selection_flags |= 1 << 5,
+            "Bold Italic" => selection_flags |= 1 | 1 << 5,
+            _ => (),
+        }
+        static_metadata.os2.selection_flags = selection_flags;
+
+        context.set_init_static_metadata(static_metadata);
+        Ok(())
+    }
+}
+
+impl Work<Context, WorkError> for GlobalMetricsWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Global metrics at {:?}", self.location);
+        let static_metadata = self.variable.get_final_static_metadata();
+        let variable = self.variable.get_global_metrics();
+
+        let mut metrics =
+            GlobalMetrics::new(NormalizedLocation::new(), static_metadata.units_per_em);
+        for (metric, values) in variable.0.iter() {
+            let model = static_metadata.variation_model_for(values.keys())?;
+            let point_seqs = values
+                .iter()
+                .map(|(loc, value)| (loc.clone(), vec![value.into_inner() as f64]))
+                .collect();
+            let value = model
+                .interpolate(&point_seqs, &self.location)
+                .map_err(|error| WorkError::InterpolationError {
+                    what: format!("{metric:?}"),
+                    error,
+                })?[0];
+            metrics.set(*metric, NormalizedLocation::new(), value as f32);
+        }
+
+        context.set_global_metrics(metrics);
+        Ok(())
+    }
+}
+
+/// The positions in a glyph instance, in an order that matches every compatible instance.
+///
+/// Contour points, then each component transform as 3 points, then anchors in the order
+/// of template, then the advance and the vertical origin.
+fn glyph_points(template: &GlyphInstance, instance: &GlyphInstance) -> Result<Vec<Point>, String> {
+    let mut points = Vec::new();
+
+    if template.contours.len() != instance.contours.len() {
+        return Err(format!(
+            "{} contours, expected {}",
+            instance.contours.len(),
+            template.contours.len()
+        ));
+    }
+    for (idx, (expected, contour)) in template
+        .contours
+        .iter()
+        .zip(instance.contours.iter())
+        .enumerate()
+    {
+        let compatible = expected.elements().len() == contour.elements().len()
+            && expected
+                .elements()
+                .iter()
+                .zip(contour.elements())
+                .all(|(e1, e2)| discriminant(e1) == discriminant(e2));
+        if !compatible {
+            return Err(format!("contour {idx} has different segments"));
+        }
+        for el in contour.elements() {
+            match el {
+                PathEl::MoveTo(p) | PathEl::LineTo(p) => points.push(*p),
+                PathEl::QuadTo(p0, p1) => points.extend([*p0, *p1]),
+                PathEl::CurveTo(p0, p1, p2) => points.extend([*p0, *p1, *p2]),
+                PathEl::ClosePath => (),
+            }
+        }
+    }
+
+    let bases: Vec<_> = instance.components.iter().map(|c| &c.base).collect();
+    if template
+        .components
+        .iter()
+        .map(|c| &c.base)
+        .ne(bases.iter().copied())
+    {
+        return Err(format!("components {bases:?} differ"));
+    }
+    for component in instance.components.iter() {
+        let [xx, xy, yx, yy, dx, dy] = component.transform.as_coeffs();
+        points.extend([Point::new(xx, xy), Point::new(yx, yy), Point::new(dx, dy)]);
+    }
+
+    for expected in template.anchors.iter() {
+        let Some(anchor) = instance.anchors.iter().find(|a| a.name == expected.name) else {
+            return Err(format!("no anchor {}", expected.name));
+        };
+        points.push(anchor.pos);
+    }
+
+    points.push(Point::new(
+        instance.width,
+        instance.height.unwrap_or_default(),
+    ));
+    points.push(Point::new(
+        0.0,
+        instance.vertical_origin.unwrap_or_default(),
+    ));
+    Ok(points)
+}
+
+/// Rebuild template with the positions [glyph_points] would produce for it replaced by points.
+fn from_glyph_points(template: &GlyphInstance, points: Vec<Point>) -> GlyphInstance {
+    let mut points = points.into_iter();
+    let mut next = || points.next().unwrap();
+
+    let contours = template
+        .contours
+        .iter()
+        .map(|contour| {
+            contour
+                .elements()
+                .iter()
+                .map(|el| match el {
+                    PathEl::MoveTo(_) => PathEl::MoveTo(next()),
+                    PathEl::LineTo(_) => PathEl::LineTo(next()),
+                    PathEl::QuadTo(..) => PathEl::QuadTo(next(), next()),
+                    PathEl::CurveTo(..) => PathEl::CurveTo(next(), next(), next()),
+                    PathEl::ClosePath => PathEl::ClosePath,
+                })
+                .collect::<BezPath>()
+        })
+        .collect();
+    let components = template
+        .components
+        .iter()
+        .map(|component| {
+            let (p0, p1, p2) = (next(), next(), next());
+            Component {
+                base: component.base.clone(),
+                transform: Affine::new([p0.x, p0.y, p1.x, p1.y, p2.x, p2.y]),
+            }
+        })
+        .collect();
+    let anchors = template
+        .anchors
+        .iter()
+        .map(|anchor| Anchor {
+            name: anchor.name.clone(),
+            pos: next(),
+        })
+        .collect();
+    let advance = next();
+    let vertical_origin = next();
+
+    GlyphInstance {
+        width: advance.x,
+        height: template.height.map(|_| advance.y),
+        vertical_origin: template.vertical_origin.map(|_| vertical_origin.y),
+        contours,
+        components,
+        anchors,
+    }
+}
+
+impl Work<Context, WorkError> for GlyphWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        trace!("Instantiate '{}' at {:?}", self.glyph_name, self.location);
+        let static_metadata = self.variable.get_final_static_metadata();
+        let original = self.variable.get_glyph_ir(&self.glyph_name);
+
+        // Where a substitution rule applies the instance takes on the design it substitutes in
+        let glyph = match substitute(&static_metadata, &self.location, &self.glyph_name) {
+            Some(substitute) => {
+                debug!(
+                    "'{}' takes on the design of '{substitute}' at {:?}",
+                    self.glyph_name, self.location
+                );
+                self.variable.get_glyph_ir(&substitute)
+            }
+            None => original.clone(),
+        };
+
+        let incompatible = |message| WorkError::InvalidSourceGlyph {
+            glyph_name: self.glyph_name.clone(),
+            message,
+        };
+        let template = glyph.default_instance();
+        let point_seqs = glyph
+            .sources()
+            .iter()
+            .map(|(loc, instance)| {
+                glyph_points(template, instance)
+                    .map(|points| (loc.clone(), points))
+                    .map_err(|e| incompatible(format!("incompatible at {loc:?}: {e}")))
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        let model = static_metadata.variation_model_for(glyph.sources().keys())?;
+        let points = model
+            .interpolate(&point_seqs, &self.location)
+            .map_err(|e| incompatible(format!("unable to interpolate: {e}")))?;
+
+        context.set_glyph_ir(Glyph::new(
+            self.glyph_name.clone(),
+            original.codepoints.clone(),
+            HashMap::from([(
+                NormalizedLocation::new(),
+                from_glyph_points(template, points),
+            )]),
+        )?);
+        Ok(())
+    }
+}
+
+impl Work<Context, WorkError> for FeatureWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Features at {:?}", self.location);
+        let features = match &*self.variable.get_features() {
+            Features::Empty => Features::Empty,
+            Features::File(file) => Features::File(file.clone()),
+            Features::Memory(fea) => Features::Memory(fea.clone()),
+            Features::PerMaster(fea_files) => {
+                // Only the master at the instance, if there is one, has the right values
+                let static_metadata = self.variable.get_final_static_metadata();
+                let at_instance = fea_files.iter().find(|(loc, _)| *loc == self.location);
+                let default = fea_files
+                    .iter()
+                    .find(|(loc, _)| loc == static_metadata.default_location());
+                match at_instance.or(default).or(fea_files.first()) {
+                    Some((loc, file)) => {
+                        if *loc != self.location {
+                            warn!(
+                                "No master at {:?}, using the features of the master at {loc:?}",
+                                self.location
+                            );
+                        }
+                        Features::File(file.clone())
+                    }
+                    None => Features::Empty,
+                }
+            }
+        };
+        context.set_features(features);
+        Ok(())
+    }
+}
+
+impl Work<Context, WorkError> for KerningWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Kerning at {:?}", self.location);
+        let static_metadata = self.variable.get_final_static_metadata();
+        let variable = self.variable.get_kerning();
+
+        let mut kerning = Kerning {
+            groups: variable.groups.clone(),
+            kerns: Default::default(),
+        };
+        for (pair, values) in variable.kerns.iter() {
+            let model = static_metadata.variation_model_for(values.keys())?;
+            let point_seqs = values
+                .iter()
+                .map(|(loc, value)| (loc.clone(), vec![value.into_inner() as f64]))
+                .collect();
+            let value = model
+                .interpolate(&point_seqs, &self.location)
+                .map_err(|error| WorkError::InterpolationError {
+                    what: format!("kerning of {pair:?}"),
+                    error,
+                })?[0];
+            kerning.kerns.insert(
+                pair.clone(),
+                [(NormalizedLocation::new(), OrderedFloat(value as f32))].into(),
+            );
+        }
+
+        context.set_kerning(kerning);
+        Ok(())
+    }
+}
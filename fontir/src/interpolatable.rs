@@ -0,0 +1,848 @@
+//! Checks that the sources of a glyph can interpolate.
+//!
+//! Compares every source to the default and describes what differs, in the spirit of
+//! fontTools [varLib.interpolatable](https://github.com/fonttools/fonttools/blob/main/Lib/fontTools/varLib/interpolatable.py).
+//! Meant to tell the designer what to fix before incompatible sources cause an unhelpful
+//! failure deep in the backend.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    mem::discriminant,
+};
+
+use fontdrasil::types::GlyphName;
+use kurbo::{BezPath, PathEl, Point, Shape, Vec2};
+use log::{info, warn};
+
+use crate::{
+    coords::NormalizedLocation,
+    error::WorkError,
+    ir::{Glyph, GlyphInstance},
+};
+
+/// How much better a start point or contour order must fit than the one the source has to be reported.
+const BETTER_FIT: f64 = 0.5;
+
+/// A way in which a source of a glyph differs from the default source.
+///
+/// Contours and components are identified by their index in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incompatibility {
+    ContourCount {
+        expected: usize,
+        actual: usize,
+    },
+    NodeCount {
+        contour: usize,
+        expected: usize,
+        actual: usize,
+    },
+    NodeTypes {
+        contour: usize,
+    },
+    /// The contour starts shift nodes further along than the default
+    StartPoint {
+        contour: usize,
+        shift: usize,
+    },
+    Direction {
+        contour: usize,
+    },
+    /// order[i] is the contour in the source that matches contour i of the default
+    ContourOrder {
+        order: Vec<usize>,
+    },
+    ComponentOrder {
+        expected: Vec<GlyphName>,
+        actual: Vec<GlyphName>,
+    },
+    Components {
+        expected: Vec<GlyphName>,
+        actual: Vec<GlyphName>,
+    },
+    /// The component is mirrored relative to the default
+    ComponentTransform {
+        component: usize,
+        base: GlyphName,
+    },
+    Anchors {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+}
+
+impl Display for Incompatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Incompatibility::ContourCount { expected, actual } => {
+                write!(f, "has {actual} contours, the default has {expected}")
+            }
+            Incompatibility::NodeCount {
+                contour,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "contour {contour} has {actual} nodes, the default has {expected}"
+            ),
+            Incompatibility::NodeTypes { contour } => write!(
+                f,
+                "contour {contour} has different node types than the default"
+            ),
+            Incompatibility::StartPoint { contour, shift } => write!(
+                f,
+                "contour {contour} starts {shift} nodes further along than the default"
+            ),
+            Incompatibility::Direction { contour } => {
+                write!(
+                    f,
+                    "contour {contour} runs in the opposite direction to the default"
+                )
+            }
+            Incompatibility::ContourOrder { order } => {
+                write!(
+                    f,
+                    "contours are in a different order, {order:?} would match the default"
+                )
+            }
+            Incompatibility::ComponentOrder { expected, actual } => write!(
+                f,
+                "components {actual:?} are in a different order than the default {expected:?}"
+            ),
+            Incompatibility::Components { expected, actual } => {
+                write!(f, "has components {actual:?}, the default has {expected:?}")
+            }
+            Incompatibility::ComponentTransform { component, base } => write!(
+                f,
+                "component {component} ({base}) is flipped relative to the default"
+            ),
+            Incompatibility::Anchors { missing, extra } => write!(
+                f,
+                "anchors differ from the default, missing {missing:?}, extra {extra:?}"
+            ),
+        }
+    }
+}
+
+/// A source of a glyph that can't interpolate with the default source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphIncompatibility {
+    pub glyph_name: GlyphName,
+    pub location: NormalizedLocation,
+    pub problem: Incompatibility,
+}
+
+impl Display for GlyphIncompatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' at {:?} {}",
+            self.glyph_name, self.location, self.problem
+        )
+    }
+}
+
+/// One incompatibility per line.
+pub fn report(incompatibilities: &[GlyphIncompatibility]) -> String {
+    incompatibilities
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Every way the sources of glyph, or those of one of its alternates, can't interpolate.
+///
+/// Alternates become glyphs of their own so they are checked against their own default.
+pub fn check_glyph(glyph: &Glyph) -> Vec<GlyphIncompatibility> {
+    let mut result = check_sources(&glyph.name, glyph.sources());
+    for alternate in glyph.alternates.iter() {
+        result.extend(check_sources(&glyph.name, &alternate.sources));
+    }
+    result
+}
+
+/// Report every way the sources of glyph can't interpolate, failing if there are any.
+pub fn check_interpolation(glyph: &Glyph) -> Result<(), WorkError> {
+    let incompatibilities = check_glyph(glyph);
+    if incompatibilities.is_empty() {
+        return Ok(());
+    }
+    for incompatibility in incompatibilities.iter() {
+        warn!("{incompatibility}");
+    }
+    Err(WorkError::InterpolationIncompatible(incompatibilities))
+}
+
+fn check_sources(
+    glyph_name: &GlyphName,
+    sources: &HashMap<NormalizedLocation, GlyphInstance>,
+) -> Vec<GlyphIncompatibility> {
+    let Some(default) = default_source(sources) else {
+        return Vec::new();
+    };
+    let mut sources: Vec<_> = sources.iter().collect();
+    sources.sort_by_key(|(loc, _)| *loc);
+
+    let mut result = Vec::new();
+    for (location, instance) in sources.iter() {
+        result.extend(
+            check_instance(default, instance)
+                .into_iter()
+                .map(|problem| GlyphIncompatibility {
+                    glyph_name: glyph_name.clone(),
+                    location: (*location).clone(),
+                    problem,
+                }),
+        );
+    }
+    result
+}
+
+/// The source at the default location, or failing that the first one.
+fn default_source(sources: &HashMap<NormalizedLocation, GlyphInstance>) -> Option<&GlyphInstance> {
+    sources
+        .iter()
+        .find(|(loc, _)| !loc.has_any_non_zero())
+        .or_else(|| sources.iter().min_by_key(|(loc, _)| *loc))
+        .map(|(_, source)| source)
+}
+
+/// Reorder contours and rotate start points in the sources of glyph, and of its alternates,
+/// where that makes them fit their default much better.
+///
+/// Logs and returns each fix.
+pub fn match_contours(glyph: &mut Glyph) -> Vec<GlyphIncompatibility> {
+    let glyph_name = glyph.name.clone();
+    let mut fixes = Vec::new();
+
+    let default = glyph.default_instance().clone();
+    for (location, instance) in glyph.sources_mut() {
+        fixes.extend(fixes_at(
+            &glyph_name,
+            location,
+            match_instance(&default, instance),
+        ));
+    }
+    for alternate in glyph.alternates.iter_mut() {
+        let Some(default) = default_source(&alternate.sources).cloned() else {
+            continue;
+        };
+        for (location, instance) in alternate.sources.iter_mut() {
+            fixes.extend(fixes_at(
+                &glyph_name,
+                location,
+                match_instance(&default, instance),
+            ));
+        }
+    }
+
+    fixes.sort_by_key(|fix| fix.location.clone());
+    for fix in fixes.iter() {
+        info!("Fixed {fix}");
+    }
+    fixes
+}
+
+fn fixes_at<'a>(
+    glyph_name: &GlyphName,
+    location: &'a NormalizedLocation,
+    fixes: Vec<Incompatibility>,
+) -> impl Iterator<Item = GlyphIncompatibility> + 'a {
+    let glyph_name = glyph_name.clone();
+    fixes.into_iter().map(move |problem| GlyphIncompatibility {
+        glyph_name: glyph_name.clone(),
+        location: location.clone(),
+        problem,
+    })
+}
+
+/// Reorder the contours and rotate the start points of instance to best fit default.
+///
+/// Returns the problems fixed.
+fn match_instance(default: &GlyphInstance, instance: &mut GlyphInstance) -> Vec<Incompatibility> {
+    if default.contours.len() != instance.contours.len() {
+        return Vec::new();
+    }
+    let mut fixes = Vec::new();
+    let expected: Vec<_> = default.contours.iter().map(Contour::new).collect();
+
+    let actual: Vec<_> = instance.contours.iter().map(Contour::new).collect();
+    if let Some(order) = better_contour_order(&expected, &actual) {
+        instance.contours = order
+            .iter()
+            .map(|i| instance.contours[*i].clone())
+            .collect();
+        fixes.push(Incompatibility::ContourOrder { order });
+    }
+
+    for (contour, (expected, path)) in expected
+        .iter()
+        .zip(instance.contours.iter_mut())
+        .enumerate()
+    {
+        let actual = Contour::new(path);
+        if expected.num_nodes() != actual.num_nodes() {
+            continue;
+        }
+        if let Some(shift) = expected.better_start(&actual) {
+            *path = actual.restarted(actual.segments.len() - shift);
+            fixes.push(Incompatibility::StartPoint {
+                contour,
+                shift: expected.nodes_before(shift),
+            });
+        }
+    }
+    fixes
+}
+
+/// Every way instance differs from default such that they can't interpolate.
+pub fn check_instance(default: &GlyphInstance, instance: &GlyphInstance) -> Vec<Incompatibility> {
+    let mut result = Vec::new();
+    check_contours(default, instance, &mut result);
+    check_components(default, instance, &mut result);
+    check_anchors(default, instance, &mut result);
+    result
+}
+
+fn check_contours(
+    default: &GlyphInstance,
+    instance: &GlyphInstance,
+    result: &mut Vec<Incompatibility>,
+) {
+    if default.contours.len() != instance.contours.len() {
+        result.push(Incompatibility::ContourCount {
+            expected: default.contours.len(),
+            actual: instance.contours.len(),
+        });
+        return;
+    }
+    let expected: Vec<_> = default.contours.iter().map(Contour::new).collect();
+    let actual: Vec<_> = instance.contours.iter().map(Contour::new).collect();
+
+    // Differences in the contours themselves are noise if they are just in the wrong order
+    if let Some(order) = better_contour_order(&expected, &actual) {
+        result.push(Incompatibility::ContourOrder { order });
+        return;
+    }
+
+    for (contour, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+        if expected.num_nodes() != actual.num_nodes() {
+            result.push(Incompatibility::NodeCount {
+                contour,
+                expected: expected.num_nodes(),
+                actual: actual.num_nodes(),
+            });
+            continue;
+        }
+        let same_types = expected.same_types(actual, 0);
+        match expected.better_start(actual) {
+            Some(shift) => result.push(Incompatibility::StartPoint {
+                contour,
+                shift: expected.nodes_before(shift),
+            }),
+            None if !same_types => result.push(Incompatibility::NodeTypes { contour }),
+            None => (),
+        }
+        if expected.area * actual.area < 0.0 {
+            result.push(Incompatibility::Direction { contour });
+        }
+    }
+}
+
+fn check_components(
+    default: &GlyphInstance,
+    instance: &GlyphInstance,
+    result: &mut Vec<Incompatibility>,
+) {
+    let expected: Vec<_> = default.components.iter().map(|c| c.base.clone()).collect();
+    let actual: Vec<_> = instance.components.iter().map(|c| c.base.clone()).collect();
+    if expected != actual {
+        let mut sorted_expected = expected.clone();
+        let mut sorted_actual = actual.clone();
+        sorted_expected.sort();
+        sorted_actual.sort();
+        if sorted_expected == sorted_actual {
+            result.push(Incompatibility::ComponentOrder { expected, actual });
+        } else {
+            result.push(Incompatibility::Components { expected, actual });
+        }
+        return;
+    }
+
+    // Components whose 2x2 varies are converted to contours, a mirrored one can't interpolate sensibly
+    for (component, (expected, actual)) in default
+        .components
+        .iter()
+        .zip(instance.components.iter())
+        .enumerate()
+    {
+        if expected.transform.determinant() * actual.transform.determinant() < 0.0 {
+            result.push(Incompatibility::ComponentTransform {
+                component,
+                base: actual.base.clone(),
+            });
+        }
+    }
+}
+
+fn check_anchors(
+    default: &GlyphInstance,
+    instance: &GlyphInstance,
+    result: &mut Vec<Incompatibility>,
+) {
+    let expected: BTreeSet<_> = default.anchors.iter().map(|a| &a.name).collect();
+    let actual: BTreeSet<_> = instance.anchors.iter().map(|a| &a.name).collect();
+    if expected != actual {
+        result.push(Incompatibility::Anchors {
+            missing: expected
+                .difference(&actual)
+                .map(|s| s.to_string())
+                .collect(),
+            extra: actual
+                .difference(&expected)
+                .map(|s| s.to_string())
+                .collect(),
+        });
+    }
+}
+
+/// A contour as a cycle of segments, so we can try starting it at different nodes.
+struct Contour {
+    /// Each segment is its off-curve points followed by its end point
+    segments: Vec<Vec<Point>>,
+    /// The node each segment starts from is the end of the previous one
+    types: Vec<PathEl>,
+    closed: bool,
+    centroid: Point,
+    area: f64,
+}
+
+impl Contour {
+    fn new(path: &BezPath) -> Contour {
+        let mut start = Point::ZERO;
+        let mut segments = Vec::new();
+        let mut types = Vec::new();
+        let mut closed = false;
+        for el in path.elements() {
+            match el {
+                PathEl::MoveTo(p) => {
+                    start = *p;
+                    continue;
+                }
+                PathEl::LineTo(p) => segments.push(vec![*p]),
+                PathEl::QuadTo(p0, p1) => segments.push(vec![*p0, *p1]),
+                PathEl::CurveTo(p0, p1, p2) => segments.push(vec![*p0, *p1, *p2]),
+                PathEl::ClosePath => {
+                    closed = true;
+                    continue;
+                }
+            }
+            types.push(*el);
+        }
+        // Make the closing line explicit so every node starts a segment
+        if closed && segments.last().and_then(|s| s.last()) != Some(&start) {
+            segments.push(vec![start]);
+            types.push(PathEl::LineTo(start));
+        }
+        if !closed {
+            segments.insert(0, vec![start]);
+            types.insert(0, PathEl::MoveTo(start));
+        }
+
+        let points: Vec<_> = segments.iter().flatten().collect();
+        let centroid = if points.is_empty() {
+            Point::ZERO
+        } else {
+            (points.iter().fold(Vec2::ZERO, |acc, p| acc + p.to_vec2()) / points.len() as f64)
+                .to_point()
+        };
+
+        Contour {
+            segments,
+            types,
+            closed,
+            centroid,
+            area: path.area(),
+        }
+    }
+
+    fn num_nodes(&self) -> usize {
+        self.segments.iter().map(|s| s.len()).sum()
+    }
+
+    /// Whether the node types match if other starts shift segments later.
+    fn same_types(&self, other: &Contour, shift: usize) -> bool {
+        let n = self.types.len();
+        n == other.types.len()
+            && (0..n).all(|i| {
+                discriminant(&self.types[(i + shift) % n]) == discriminant(&other.types[i])
+            })
+    }
+
+    /// Sum of squared distances between our points, started shift segments later, and those of other.
+    ///
+    /// Both contours are centered on the origin so the cost reflects shape, not position.
+    fn cost(&self, other: &Contour, shift: usize) -> f64 {
+        let n = self.segments.len();
+        (0..n)
+            .flat_map(|i| {
+                self.segments[(i + shift) % n]
+                    .iter()
+                    .zip(other.segments[i].iter())
+            })
+            .map(|(p0, p1)| ((*p0 - self.centroid) - (*p1 - other.centroid)).hypot2())
+            .sum()
+    }
+
+    /// If other fits much better started at a different node, how many segments later.
+    fn better_start(&self, other: &Contour) -> Option<usize> {
+        if !self.closed || self.segments.len() != other.segments.len() {
+            return None;
+        }
+        let identity = self.same_types(other, 0).then(|| self.cost(other, 0));
+        let (shift, cost) = (1..self.segments.len())
+            .filter(|shift| self.same_types(other, *shift))
+            .map(|shift| (shift, self.cost(other, shift)))
+            .min_by(|(_, c0), (_, c1)| c0.total_cmp(c1))?;
+        match identity {
+            Some(identity) if cost >= identity * BETTER_FIT => None,
+            _ => Some(shift),
+        }
+    }
+
+    /// The number of nodes in the first segments.
+    fn nodes_before(&self, segments: usize) -> usize {
+        self.segments[..segments].iter().map(|s| s.len()).sum()
+    }
+
+    /// The path of a closed contour started segments later.
+    fn restarted(&self, segments: usize) -> BezPath {
+        let n = self.types.len();
+        let mut path = BezPath::new();
+        if let Some(start) = self.segments[(segments + n - 1) % n].last() {
+            path.move_to(*start);
+        }
+        for i in 0..n {
+            path.push(self.types[(i + segments) % n]);
+        }
+        path.close_path();
+        path
+    }
+}
+
+/// If matching contours by position and size gives a different order that fits much better, that order.
+fn better_contour_order(expected: &[Contour], actual: &[Contour]) -> Option<Vec<usize>> {
+    if expected.len() < 2 {
+        return None;
+    }
+    let cost = |e: &Contour, a: &Contour| {
+        (e.centroid - a.centroid).hypot2() + (e.area.abs().sqrt() - a.area.abs().sqrt()).powi(2)
+    };
+    let order: Vec<usize> = expected
+        .iter()
+        .map(|e| {
+            (0..actual.len())
+                .min_by(|i, j| cost(e, &actual[*i]).total_cmp(&cost(e, &actual[*j])))
+                .unwrap()
+        })
+        .collect();
+
+    let is_permutation = order.iter().collect::<BTreeSet<_>>().len() == order.len();
+    if !is_permutation || order.iter().enumerate().all(|(i, j)| i == *j) {
+        return None;
+    }
+    let identity_cost: f64 = expected.iter().zip(actual).map(|(e, a)| cost(e, a)).sum();
+    let order_cost: f64 = order
+        .iter()
+        .enumerate()
+        .map(|(i, j)| cost(&expected[i], &actual[*j]))
+        .sum();
+    (order_cost < identity_cost * BETTER_FIT).then_some(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Affine, BezPath, Point, Rect, Shape};
+
+    use crate::{
+        coords::{NormalizedCoord, NormalizedLocation},
+        error::WorkError,
+        ir::{Anchor, Component, GlyphBuilder, GlyphInstance},
+    };
+
+    use super::{
+        check_instance, check_interpolation, match_instance, GlyphIncompatibility, Incompatibility,
+    };
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64) -> BezPath {
+        Rect::new(x0, y0, x1, y1).to_path(0.0)
+    }
+
+    fn contours(contours: Vec<BezPath>) -> GlyphInstance {
+        GlyphInstance {
+            contours,
+            ..Default::default()
+        }
+    }
+
+    fn polygon(points: &[Point]) -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to(points[0]);
+        for point in points.iter().skip(1) {
+            path.line_to(*point);
+        }
+        path.close_path();
+        path
+    }
+
+    fn rect_points(x0: f64, y0: f64, x1: f64, y1: f64) -> Vec<Point> {
+        vec![
+            Point::new(x0, y0),
+            Point::new(x1, y0),
+            Point::new(x1, y1),
+            Point::new(x0, y1),
+        ]
+    }
+
+    #[test]
+    fn compatible_instances() {
+        let default = contours(vec![rect(0.0, 0.0, 100.0, 100.0)]);
+        let bold = contours(vec![rect(0.0, 0.0, 150.0, 110.0)]);
+        assert_eq!(
+            Vec::<Incompatibility>::new(),
+            check_instance(&default, &bold)
+        );
+    }
+
+    #[test]
+    fn contour_count() {
+        let default = contours(vec![rect(0.0, 0.0, 100.0, 100.0)]);
+        let bold = contours(vec![
+            rect(0.0, 0.0, 100.0, 100.0),
+            rect(200.0, 0.0, 300.0, 100.0),
+        ]);
+        assert_eq!(
+            vec![Incompatibility::ContourCount {
+                expected: 1,
+                actual: 2
+            }],
+            check_instance(&default, &bold)
+        );
+    }
+
+    #[test]
+    fn node_count() {
+        let default = contours(vec![rect(0.0, 0.0, 100.0, 100.0)]);
+        let mut triangle = BezPath::new();
+        triangle.move_to((0.0, 0.0));
+        triangle.line_to((100.0, 0.0));
+        triangle.line_to((50.0, 100.0));
+        triangle.close_path();
+        assert_eq!(
+            vec![Incompatibility::NodeCount {
+                contour: 0,
+                expected: 4,
+                actual: 3
+            }],
+            check_instance(&default, &contours(vec![triangle]))
+        );
+    }
+
+    #[test]
+    fn start_point() {
+        let mut wide = rect_points(0.0, 0.0, 300.0, 100.0);
+        let default = contours(vec![polygon(&wide)]);
+        wide.rotate_left(1);
+        let bold = contours(vec![polygon(&wide)]);
+        assert_eq!(
+            vec![Incompatibility::StartPoint {
+                contour: 0,
+                shift: 1
+            }],
+            check_instance(&default, &bold)
+        );
+    }
+
+    #[test]
+    fn direction() {
+        let mut square = rect_points(0.0, 0.0, 100.0, 100.0);
+        let default = contours(vec![polygon(&square)]);
+        square.reverse();
+        let bold = contours(vec![polygon(&square)]);
+        assert!(
+            check_instance(&default, &bold).contains(&Incompatibility::Direction { contour: 0 }),
+            "{:?}",
+            check_instance(&default, &bold)
+        );
+    }
+
+    #[test]
+    fn contour_order() {
+        let small = rect(0.0, 0.0, 10.0, 10.0);
+        let big = rect(200.0, 0.0, 400.0, 200.0);
+        let default = contours(vec![small.clone(), big.clone()]);
+        let bold = contours(vec![big, small]);
+        assert_eq!(
+            vec![Incompatibility::ContourOrder { order: vec![1, 0] }],
+            check_instance(&default, &bold)
+        );
+    }
+
+    #[test]
+    fn components() {
+        let component = |base: &str, transform| Component {
+            base: base.into(),
+            transform,
+        };
+        let default = GlyphInstance {
+            components: vec![
+                component("a", Affine::IDENTITY),
+                component("b", Affine::IDENTITY),
+            ],
+            ..Default::default()
+        };
+        let swapped = GlyphInstance {
+            components: vec![
+                component("b", Affine::IDENTITY),
+                component("a", Affine::IDENTITY),
+            ],
+            ..Default::default()
+        };
+        let flipped = GlyphInstance {
+            components: vec![
+                component("a", Affine::FLIP_X),
+                component("b", Affine::IDENTITY),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                vec![Incompatibility::ComponentOrder {
+                    expected: vec!["a".into(), "b".into()],
+                    actual: vec!["b".into(), "a".into()],
+                }],
+                vec![Incompatibility::ComponentTransform {
+                    component: 0,
+                    base: "a".into()
+                }],
+            ],
+            vec![
+                check_instance(&default, &swapped),
+                check_instance(&default, &flipped)
+            ]
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        let anchor = |name: &str| Anchor {
+            name: name.to_string(),
+            pos: Point::ZERO,
+        };
+        let default = GlyphInstance {
+            anchors: vec![anchor("top"), anchor("bottom")],
+            ..Default::default()
+        };
+        let bold = GlyphInstance {
+            anchors: vec![anchor("top"), anchor("_top")],
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![Incompatibility::Anchors {
+                missing: vec!["bottom".to_string()],
+                extra: vec!["_top".to_string()],
+            }],
+            check_instance(&default, &bold)
+        );
+    }
+
+    #[test]
+    fn match_start_point() {
+        // Contours from the IR builders end with an explicit line back to the start
+        let mut wide = rect_points(0.0, 0.0, 300.0, 100.0);
+        wide.push(wide[0]);
+        let default = contours(vec![polygon(&wide)]);
+        let mut rotated = wide[..4].to_vec();
+        rotated.rotate_left(1);
+        rotated.push(rotated[0]);
+        let mut bold = contours(vec![polygon(&rotated)]);
+        assert_eq!(
+            vec![Incompatibility::StartPoint {
+                contour: 0,
+                shift: 1
+            }],
+            match_instance(&default, &mut bold)
+        );
+        assert_eq!(
+            Vec::<Incompatibility>::new(),
+            check_instance(&default, &bold)
+        );
+        assert_eq!(default.contours, bold.contours);
+    }
+
+    #[test]
+    fn match_contour_order() {
+        let small = polygon(&rect_points(0.0, 0.0, 10.0, 10.0));
+        let big = polygon(&rect_points(200.0, 0.0, 400.0, 200.0));
+        let default = contours(vec![small.clone(), big.clone()]);
+        let mut bold = contours(vec![big, small]);
+        assert_eq!(
+            vec![Incompatibility::ContourOrder { order: vec![1, 0] }],
+            match_instance(&default, &mut bold)
+        );
+        assert_eq!(default.contours, bold.contours);
+    }
+
+    #[test]
+    fn match_leaves_compatible_instance_alone() {
+        let default = contours(vec![polygon(&rect_points(0.0, 0.0, 100.0, 100.0))]);
+        let mut bold = contours(vec![polygon(&rect_points(0.0, 0.0, 150.0, 110.0))]);
+        let before = bold.clone();
+        assert_eq!(
+            Vec::<Incompatibility>::new(),
+            match_instance(&default, &mut bold)
+        );
+        assert_eq!(before, bold);
+    }
+
+    #[test]
+    fn check_interpolation_reports_incompatible_sources() {
+        let weight = |value: f32| -> NormalizedLocation {
+            [("W".to_string(), NormalizedCoord::new(value))]
+                .into_iter()
+                .collect()
+        };
+        let mut glyph = GlyphBuilder::new("bad".into());
+        glyph
+            .try_add_source(&weight(0.0), contours(vec![rect(0.0, 0.0, 100.0, 100.0)]))
+            .unwrap();
+        glyph
+            .try_add_source(
+                &weight(1.0),
+                contours(vec![
+                    rect(0.0, 0.0, 100.0, 100.0),
+                    rect(200.0, 0.0, 300.0, 100.0),
+                ]),
+            )
+            .unwrap();
+
+        let Err(WorkError::InterpolationIncompatible(incompatibilities)) =
+            check_interpolation(&glyph.try_into().unwrap())
+        else {
+            panic!("'bad' should be incompatible");
+        };
+        assert_eq!(
+            vec![GlyphIncompatibility {
+                glyph_name: "bad".into(),
+                location: weight(1.0),
+                problem: Incompatibility::ContourCount {
+                    expected: 1,
+                    actual: 2
+                },
+            }],
+            incompatibilities
+        );
+    }
+}
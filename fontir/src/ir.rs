@@ -1,13 +1,13 @@
 //! Font IR types.
 
 use crate::{
-    coords::{CoordConverter, NormalizedCoord, NormalizedLocation, UserCoord},
+    coords::{CoordConverter, NormalizedCoord, NormalizedLocation, UserCoord, UserLocation},
     error::{PathConversionError, VariationModelError, WorkError},
     serde::{
         deserialize_name_id, deserialize_tag, serialize_name_id, serialize_tag,
-        GlobalMetricsSerdeRepr, GlyphSerdeRepr, StaticMetadataSerdeRepr,
+        GlobalMetricsSerdeRepr, GlyphSerdeRepr, KerningSerdeRepr, StaticMetadataSerdeRepr,
     },
-    variations::VariationModel,
+    variations::{SubModels, VariationModel},
 };
 use font_types::NameId;
 use font_types::Tag;
@@ -18,9 +18,10 @@ use log::{trace, warn};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Debug,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 const DEFAULT_VENDOR_ID: Tag = Tag::new(b"NONE");
@@ -58,8 +59,27 @@ pub struct StaticMetadata {
     ///
     /// This copy includes all locations used in the entire font. That is, every
     /// location any glyph has an instance. Use of a location not in the global model
-    /// is an error.
+    /// is an error. Things defined at only some locations, such as sparse glyphs,
+    /// should use [StaticMetadata::variation_model_for].
     pub variation_model: VariationModel,
+    sub_models: SubModels,
+
+    /// Glyph categories the source states explicitly, for GDEF.
+    ///
+    /// Glyphs not listed here are classified by their anchors.
+    pub gdef_categories: BTreeMap<GlyphName, GdefCategory>,
+
+    /// Style names for STAT, see [StaticMetadata::set_stat_labels].
+    pub(crate) stat_labels: StatLabels,
+
+    /// Named instances for fvar, see [StaticMetadata::set_named_instances].
+    pub(crate) named_instances: Vec<NamedInstance>,
+
+    /// OS/2 values that don't vary across the designspace.
+    pub os2: Os2Metadata,
+
+    /// Glyph substitutions that only apply in part of the designspace.
+    pub feature_variations: FeatureVariations,
 
     axes_default: NormalizedLocation,
     variable_axes_default: NormalizedLocation,
@@ -115,6 +135,12 @@ impl StaticMetadata {
             variable_axes,
             glyph_order,
             variation_model,
+            sub_models: Default::default(),
+            gdef_categories: Default::default(),
+            stat_labels: Default::default(),
+            named_instances: Default::default(),
+            os2: Default::default(),
+            feature_variations: Default::default(),
             axes_default,
             variable_axes_default,
         })
@@ -124,6 +150,45 @@ impl StaticMetadata {
         self.glyph_order.get_index_of(name).map(|i| i as u32)
     }
 
+    /// The variation model for something defined at the given locations, such as a glyph.
+    ///
+    /// The global model if given all of its locations, otherwise a model of just the
+    /// given locations. Models are built once per set of locations.
+    pub fn variation_model_for<'a>(
+        &self,
+        locations: impl IntoIterator<Item = &'a NormalizedLocation>,
+    ) -> Result<Arc<VariationModel>, VariationModelError> {
+        // Match the locations of the global model, which are on all variable axes and only those
+        let locations: BTreeSet<_> = locations
+            .into_iter()
+            .map(|loc| {
+                let mut loc = loc.clone();
+                loc.retain(|axis_name, _| self.variable_axes.iter().any(|a| a.name == *axis_name));
+                for axis in self.variable_axes.iter() {
+                    if !loc.has(&axis.name) {
+                        loc.set_pos(axis.name.clone(), NormalizedCoord::new(0.0));
+                    }
+                }
+                loc
+            })
+            .collect();
+        self.sub_models.get_or_create(locations, |locations| {
+            if locations.len() == self.variation_model.locations().count()
+                && self
+                    .variation_model
+                    .locations()
+                    .all(|loc| locations.contains(loc))
+            {
+                return Ok(self.variation_model.clone());
+            }
+            trace!("Create a sub model for {locations:?}");
+            VariationModel::new(
+                locations.iter().cloned().collect(),
+                self.variable_axes.clone(),
+            )
+        })
+    }
+
     /// The default on all known axes.
     pub fn default_location(&self) -> &NormalizedLocation {
         &self.axes_default
@@ -133,6 +198,190 @@ impl StaticMetadata {
     pub fn variable_axes_default(&self) -> &NormalizedLocation {
         &self.variable_axes_default
     }
+
+    pub fn stat_labels(&self) -> &StatLabels {
+        &self.stat_labels
+    }
+
+    /// Set the STAT labels, adding every name they use to [StaticMetadata::names].
+    pub fn set_stat_labels(&mut self, stat_labels: StatLabels) {
+        let label_names = stat_labels
+            .axis_labels
+            .values()
+            .flat_map(|labels| labels.iter().map(|l| &l.name))
+            .chain(stat_labels.location_labels.iter().map(|l| &l.name))
+            .chain(stat_labels.elided_fallback_name.iter());
+        for name in label_names {
+            self.claim_name_id(name);
+        }
+        self.stat_labels = stat_labels;
+    }
+
+    pub fn named_instances(&self) -> &[NamedInstance] {
+        &self.named_instances
+    }
+
+    /// Set the named instances, adding every name they use to [StaticMetadata::names].
+    pub fn set_named_instances(&mut self, named_instances: Vec<NamedInstance>) {
+        for instance in named_instances.iter() {
+            self.claim_name_id(&instance.name);
+            if let Some(postscript_name) = &instance.postscript_name {
+                self.claim_name_id(postscript_name);
+            }
+        }
+        self.named_instances = named_instances;
+    }
+
+    /// Make sure name has a font-specific (> 255) name id, allocating the next free one if not.
+    fn claim_name_id(&mut self, name: &str) {
+        if self.font_specific_name_id(name).is_some() {
+            return;
+        }
+        let name_id = self
+            .names
+            .keys()
+            .map(|key| key.name_id.to_u16())
+            .max()
+            .unwrap_or_default()
+            .max(255)
+            + 1;
+        self.names
+            .insert(NameKey::new(name_id.into(), name), name.to_string());
+    }
+
+    /// The lowest font-specific (> 255) name id whose value is name.
+    pub fn font_specific_name_id(&self, name: &str) -> Option<NameId> {
+        self.names
+            .iter()
+            .filter(|(key, value)| key.name_id.to_u16() > 255 && value.as_str() == name)
+            .map(|(key, _)| key.name_id)
+            .min()
+    }
+}
+
+/// The class of a glyph in the GDEF
+/// [GlyphClassDef](https://learn.microsoft.com/en-us/typography/opentype/spec/gdef#glyph-class-definition-table).
+///
+/// Named as for UFO public.openTypeCategories.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GdefCategory {
+    Base,
+    Ligature,
+    Mark,
+    Component,
+}
+
+/// A named value on one axis, for STAT.
+///
+/// See <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#label-element-axis>
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AxisLabel {
+    pub name: String,
+    pub value: UserCoord,
+    /// Set if the label applies to a range of values
+    pub min: Option<UserCoord>,
+    pub max: Option<UserCoord>,
+    /// The value of the style linked to this one, e.g. Bold for Regular
+    pub linked_value: Option<UserCoord>,
+    pub elidable: bool,
+    pub older_sibling: bool,
+}
+
+/// A named location spanning several axes, for STAT.
+///
+/// See <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#label-element>
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LocationLabel {
+    pub name: String,
+    pub location: UserLocation,
+    pub elidable: bool,
+    pub older_sibling: bool,
+}
+
+/// The style names STAT describes the font with.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct StatLabels {
+    /// Labels by axis name
+    pub axis_labels: BTreeMap<String, Vec<AxisLabel>>,
+    pub location_labels: Vec<LocationLabel>,
+    /// The name of the style whose labels are all elided, name id 2 if None
+    pub elided_fallback_name: Option<String>,
+}
+
+/// A named instance of a variable font, see
+/// <https://learn.microsoft.com/en-us/typography/opentype/spec/fvar#instancerecord>.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NamedInstance {
+    /// The subfamily name, such as Bold
+    pub name: String,
+    pub postscript_name: Option<String>,
+    pub location: UserLocation,
+}
+
+/// A range on one axis, in normalized coordinates, that a location must be within.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub axis: String,
+    pub min: NormalizedCoord,
+    pub max: NormalizedCoord,
+}
+
+/// Substitutions that apply wherever any of the condition sets is met.
+///
+/// A condition set is met when the location satisfies every condition in it.
+/// See <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#rule-element>
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubstitutionRule {
+    pub name: Option<String>,
+    pub condition_sets: Vec<Vec<Condition>>,
+    /// (from, to) glyph pairs
+    pub substitutions: Vec<(GlyphName, GlyphName)>,
+}
+
+/// Rules that swap glyphs depending on location, for GSUB
+/// [FeatureVariations](https://learn.microsoft.com/en-us/typography/opentype/spec/chapter2#featurevariations-table).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeatureVariations {
+    /// The feature the substitutions apply under, rvrn unless the source says otherwise
+    #[serde(serialize_with = "serialize_tag")]
+    #[serde(deserialize_with = "deserialize_tag")]
+    pub feature_tag: Tag,
+    pub rules: Vec<SubstitutionRule>,
+}
+
+impl Default for FeatureVariations {
+    fn default() -> Self {
+        FeatureVariations {
+            feature_tag: Tag::new(b"rvrn"),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl FeatureVariations {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// [OS/2](https://learn.microsoft.com/en-us/typography/opentype/spec/os2) values that cannot vary.
+///
+/// None means the source didn't say; the backend falls back to a default or
+/// computes the value from the font.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Os2Metadata {
+    pub weight_class: Option<u16>,
+    pub width_class: Option<u16>,
+    /// Embedding permissions, fsType
+    pub fs_type: Option<u16>,
+    /// fsSelection bits the source sets, such as ITALIC or USE_TYPO_METRICS
+    pub selection_flags: u16,
+    pub family_class: Option<i16>,
+    pub panose: Option<[u8; 10]>,
+    /// Bit numbers for ulUnicodeRange1-4
+    pub unicode_range_bits: Option<BTreeSet<u32>>,
+    /// Bit numbers for ulCodePageRange1-2
+    pub codepage_range_bits: Option<BTreeSet<u32>>,
 }
 
 /// Global metrics. Ascender/descender, cap height, etc.
@@ -151,6 +400,25 @@ pub enum GlobalMetric {
     Descender,
     CapHeight,
     XHeight,
+    Os2TypoAscender,
+    Os2TypoDescender,
+    Os2TypoLineGap,
+    Os2WinAscent,
+    Os2WinDescent,
+    SubscriptXSize,
+    SubscriptYSize,
+    SubscriptXOffset,
+    SubscriptYOffset,
+    SuperscriptXSize,
+    SuperscriptYSize,
+    SuperscriptXOffset,
+    SuperscriptYOffset,
+    StrikeoutSize,
+    StrikeoutPosition,
+    /// The vhea metrics are only present if the font has vertical metrics
+    VheaAscender,
+    VheaDescender,
+    VheaLineGap,
 }
 
 impl GlobalMetrics {
@@ -170,6 +438,74 @@ impl GlobalMetrics {
         metrics
     }
 
+    /// Fills in every metric the source didn't set at pos.
+    ///
+    /// Call once all explicit values, at every location, are set; fallbacks for the OS/2
+    /// metrics are derived from the ascender, descender and x-height at pos. The vhea
+    /// metrics are only filled in if some location has them.
+    pub fn populate_defaults(&mut self, pos: &NormalizedLocation, units_per_em: u16) {
+        let upem = units_per_em as f32;
+        // There is no ufo2ft fallback for vhea, a font either has vertical metrics or it
+        // doesn't. A master that is missing them gets an em box centered on the baseline.
+        let vertical = [
+            GlobalMetric::VheaAscender,
+            GlobalMetric::VheaDescender,
+            GlobalMetric::VheaLineGap,
+        ]
+        .iter()
+        .any(|metric| self.0.contains_key(metric));
+        let mut fill = |metric, fallback: &dyn Fn(&GlobalMetrics) -> f32| {
+            if !self.values_mut(metric).contains_key(pos) {
+                let value = fallback(self);
+                self.set(metric, pos.clone(), value);
+            }
+        };
+        let at = |metrics: &GlobalMetrics, metric| metrics.get(metric, pos).into_inner();
+
+        // https://github.com/googlefonts/ufo2ft/blob/fca66fe3ea1ea88ffb36f8264b21ce042d3afd05/Lib/ufo2ft/fontInfoData.py#L38-L55
+        fill(GlobalMetric::Ascender, &|_| 0.8 * upem);
+        fill(GlobalMetric::Descender, &|_| -0.2 * upem);
+        fill(GlobalMetric::CapHeight, &|_| 0.7 * upem);
+        fill(GlobalMetric::XHeight, &|_| 0.5 * upem);
+
+        // https://github.com/googlefonts/ufo2ft/blob/fca66fe3ea1ea88ffb36f8264b21ce042d3afd05/Lib/ufo2ft/fontInfoData.py
+        fill(GlobalMetric::Os2TypoAscender, &|m| {
+            at(m, GlobalMetric::Ascender)
+        });
+        fill(GlobalMetric::Os2TypoDescender, &|m| {
+            at(m, GlobalMetric::Descender)
+        });
+        fill(GlobalMetric::Os2TypoLineGap, &|m| {
+            (1.2 * upem - at(m, GlobalMetric::Ascender) + at(m, GlobalMetric::Descender)).max(0.0)
+        });
+        // ufo2ft uses the font bounding box when it has glyphs, we only have metrics
+        fill(GlobalMetric::Os2WinAscent, &|m| {
+            at(m, GlobalMetric::Ascender) + at(m, GlobalMetric::Os2TypoLineGap)
+        });
+        fill(GlobalMetric::Os2WinDescent, &|m| {
+            at(m, GlobalMetric::Descender).abs()
+        });
+
+        fill(GlobalMetric::SubscriptXSize, &|_| 0.65 * upem);
+        fill(GlobalMetric::SubscriptYSize, &|_| 0.6 * upem);
+        fill(GlobalMetric::SubscriptXOffset, &|_| 0.0);
+        fill(GlobalMetric::SubscriptYOffset, &|_| 0.075 * upem);
+        fill(GlobalMetric::SuperscriptXSize, &|_| 0.65 * upem);
+        fill(GlobalMetric::SuperscriptYSize, &|_| 0.6 * upem);
+        fill(GlobalMetric::SuperscriptXOffset, &|_| 0.0);
+        fill(GlobalMetric::SuperscriptYOffset, &|_| 0.35 * upem);
+        fill(GlobalMetric::StrikeoutSize, &|_| 0.05 * upem);
+        fill(GlobalMetric::StrikeoutPosition, &|m| {
+            0.6 * at(m, GlobalMetric::XHeight)
+        });
+
+        if vertical {
+            fill(GlobalMetric::VheaAscender, &|_| 0.5 * upem);
+            fill(GlobalMetric::VheaDescender, &|_| -0.5 * upem);
+            fill(GlobalMetric::VheaLineGap, &|_| 0.0);
+        }
+    }
+
     fn values(&self, metric: GlobalMetric) -> &HashMap<NormalizedLocation, OrderedFloat<f32>> {
         // We presume that ctor initializes for every GlobalMetric
         self.0.get(&metric).unwrap()
@@ -189,6 +525,18 @@ impl GlobalMetrics {
         *value
     }
 
+    /// The value of metric at pos, None if it isn't set there
+    fn get_if_present(
+        &self,
+        metric: GlobalMetric,
+        pos: &NormalizedLocation,
+    ) -> Option<OrderedFloat<f32>> {
+        self.0
+            .get(&metric)
+            .and_then(|values| values.get(pos))
+            .copied()
+    }
+
     pub fn set(
         &mut self,
         metric: GlobalMetric,
@@ -216,6 +564,24 @@ impl GlobalMetrics {
             descender: self.get(GlobalMetric::Descender, pos),
             cap_height: self.get(GlobalMetric::CapHeight, pos),
             x_height: self.get(GlobalMetric::XHeight, pos),
+            os2_typo_ascender: self.get(GlobalMetric::Os2TypoAscender, pos),
+            os2_typo_descender: self.get(GlobalMetric::Os2TypoDescender, pos),
+            os2_typo_line_gap: self.get(GlobalMetric::Os2TypoLineGap, pos),
+            os2_win_ascent: self.get(GlobalMetric::Os2WinAscent, pos),
+            os2_win_descent: self.get(GlobalMetric::Os2WinDescent, pos),
+            subscript_x_size: self.get(GlobalMetric::SubscriptXSize, pos),
+            subscript_y_size: self.get(GlobalMetric::SubscriptYSize, pos),
+            subscript_x_offset: self.get(GlobalMetric::SubscriptXOffset, pos),
+            subscript_y_offset: self.get(GlobalMetric::SubscriptYOffset, pos),
+            superscript_x_size: self.get(GlobalMetric::SuperscriptXSize, pos),
+            superscript_y_size: self.get(GlobalMetric::SuperscriptYSize, pos),
+            superscript_x_offset: self.get(GlobalMetric::SuperscriptXOffset, pos),
+            superscript_y_offset: self.get(GlobalMetric::SuperscriptYOffset, pos),
+            strikeout_size: self.get(GlobalMetric::StrikeoutSize, pos),
+            strikeout_position: self.get(GlobalMetric::StrikeoutPosition, pos),
+            vhea_ascender: self.get_if_present(GlobalMetric::VheaAscender, pos),
+            vhea_descender: self.get_if_present(GlobalMetric::VheaDescender, pos),
+            vhea_line_gap: self.get_if_present(GlobalMetric::VheaLineGap, pos),
         }
     }
 
@@ -234,6 +600,32 @@ pub struct GlobalMetricsInstance {
     pub descender: OrderedFloat<f32>,
     pub cap_height: OrderedFloat<f32>,
     pub x_height: OrderedFloat<f32>,
+    pub os2_typo_ascender: OrderedFloat<f32>,
+    pub os2_typo_descender: OrderedFloat<f32>,
+    pub os2_typo_line_gap: OrderedFloat<f32>,
+    pub os2_win_ascent: OrderedFloat<f32>,
+    pub os2_win_descent: OrderedFloat<f32>,
+    pub subscript_x_size: OrderedFloat<f32>,
+    pub subscript_y_size: OrderedFloat<f32>,
+    pub subscript_x_offset: OrderedFloat<f32>,
+    pub subscript_y_offset: OrderedFloat<f32>,
+    pub superscript_x_size: OrderedFloat<f32>,
+    pub superscript_y_size: OrderedFloat<f32>,
+    pub superscript_x_offset: OrderedFloat<f32>,
+    pub superscript_y_offset: OrderedFloat<f32>,
+    pub strikeout_size: OrderedFloat<f32>,
+    pub strikeout_position: OrderedFloat<f32>,
+    /// None if the font has no vertical metrics
+    pub vhea_ascender: Option<OrderedFloat<f32>>,
+    pub vhea_descender: Option<OrderedFloat<f32>>,
+    pub vhea_line_gap: Option<OrderedFloat<f32>>,
+}
+
+impl GlobalMetricsInstance {
+    /// Whether the font has vertical metrics, in which case we build vhea and vmtx.
+    pub fn is_vertical(&self) -> bool {
+        self.vhea_ascender.is_some()
+    }
 }
 
 /// Helps accumulate 'name' values.
@@ -361,7 +753,7 @@ impl NameBuilder {
 }
 
 /// <https://github.com/googlefonts/ufo2ft/blob/fca66fe3ea1ea88ffb36f8264b21ce042d3afd05/Lib/ufo2ft/fontInfoData.py#L263>
-fn normalize_for_postscript(value: &mut String, allow_spaces: bool) {
+pub(crate) fn normalize_for_postscript(value: &mut String, allow_spaces: bool) {
     value.retain(|c| {
         if !allow_spaces && c.is_ascii_whitespace() {
             return false;
@@ -453,13 +845,16 @@ impl Axis {
 
 /// Features (Adobe fea).
 ///
-/// In time will split gpos/gsub, have different features for different
-/// locations, etc.
+/// In time will split gpos/gsub.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum Features {
     Empty,
     File(PathBuf),
     Memory(String),
+    /// A fea file per master, for sources whose masters don't share one.
+    ///
+    /// Each is compiled at its location; they must differ only in values.
+    PerMaster(Vec<(NormalizedLocation, PathBuf)>),
 }
 
 impl Features {
@@ -472,6 +867,39 @@ impl Features {
     pub fn from_string(fea_content: String) -> Features {
         Features::Memory(fea_content)
     }
+    pub fn per_master(fea_files: Vec<(NormalizedLocation, PathBuf)>) -> Features {
+        Features::PerMaster(fea_files)
+    }
+}
+
+/// A glyph or a group of glyphs, as one side of a kerning pair.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KernParticipant {
+    Glyph(GlyphName),
+    Group(String),
+}
+
+/// The first and second participant in a kerning pair.
+pub type KernPair = (KernParticipant, KernParticipant);
+
+/// Kerning groups and pair adjustments.
+///
+/// Groups follow UFO naming, public.kern1.* groups may only be used as the first
+/// member of a pair and public.kern2.* groups as the second.
+///
+/// Every pair has a value at every location that defines kerning; sources that
+/// don't mention a pair contribute 0.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(from = "KerningSerdeRepr", into = "KerningSerdeRepr")]
+pub struct Kerning {
+    pub groups: BTreeMap<String, BTreeSet<GlyphName>>,
+    pub kerns: BTreeMap<KernPair, BTreeMap<NormalizedLocation, OrderedFloat<f32>>>,
+}
+
+impl Kerning {
+    pub fn is_empty(&self) -> bool {
+        self.kerns.is_empty()
+    }
 }
 
 /// A variable definition of a single glyph.
@@ -489,6 +917,17 @@ pub struct Glyph {
     pub codepoints: HashSet<u32>, // single unicodes that each point to this glyph. Typically 0 or 1.
     default_location: NormalizedLocation,
     sources: HashMap<NormalizedLocation, GlyphInstance>,
+    /// Designs that replace this one in parts of the designspace
+    ///
+    /// Split out into glyphs of their own when static metadata is finalized.
+    pub alternates: Vec<GlyphAlternate>,
+}
+
+/// An alternate design of a glyph, such as a Glyphs bracket layer, that applies where the conditions are met.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GlyphAlternate {
+    pub condition_set: Vec<Condition>,
+    pub sources: HashMap<NormalizedLocation, GlyphInstance>,
 }
 
 impl Glyph {
@@ -519,6 +958,7 @@ impl Glyph {
             codepoints,
             default_location,
             sources,
+            alternates: Vec::new(),
         })
     }
 
@@ -550,6 +990,7 @@ pub struct GlyphBuilder {
     pub name: GlyphName,
     pub codepoints: HashSet<u32>, // single unicodes that each point to this glyph. Typically 0 or 1.
     pub sources: HashMap<NormalizedLocation, GlyphInstance>,
+    pub alternates: Vec<GlyphAlternate>,
 }
 
 impl GlyphBuilder {
@@ -558,6 +999,7 @@ impl GlyphBuilder {
             name,
             codepoints: HashSet::new(),
             sources: HashMap::new(),
+            alternates: Vec::new(),
         }
     }
 
@@ -581,7 +1023,9 @@ impl TryInto<Glyph> for GlyphBuilder {
     type Error = WorkError;
 
     fn try_into(self) -> Result<Glyph, Self::Error> {
-        Glyph::new(self.name, self.codepoints, self.sources)
+        let mut glyph = Glyph::new(self.name, self.codepoints, self.sources)?;
+        glyph.alternates = self.alternates;
+        Ok(glyph)
     }
 }
 
@@ -591,6 +1035,7 @@ impl From<&Glyph> for GlyphBuilder {
             name: value.name.clone(),
             codepoints: value.codepoints.clone(),
             sources: value.sources.clone(),
+            alternates: value.alternates.clone(),
         }
     }
 }
@@ -601,6 +1046,7 @@ impl From<Glyph> for GlyphBuilder {
             name: value.name,
             codepoints: value.codepoints,
             sources: value.sources,
+            alternates: value.alternates,
         }
     }
 }
@@ -612,10 +1058,39 @@ pub struct GlyphInstance {
     pub width: f64,
     /// Advance height; if None, assumed to equal font's ascender - descender.
     pub height: Option<f64>,
+    /// Y coordinate of the origin for vertical layout; if None, assumed to be the font's ascender.
+    pub vertical_origin: Option<f64>,
     /// List of glyph contours.
     pub contours: Vec<BezPath>,
     /// List of glyph components.
     pub components: Vec<Component>,
+    /// List of glyph anchors.
+    pub anchors: Vec<Anchor>,
+}
+
+impl GlyphInstance {
+    /// The advance height, falling back to the ascender - descender of metrics.
+    pub fn advance_height(&self, metrics: &GlobalMetricsInstance) -> f64 {
+        self.height.unwrap_or_else(|| {
+            metrics.ascender.into_inner() as f64 - metrics.descender.into_inner() as f64
+        })
+    }
+
+    /// The y coordinate of the vertical origin, falling back to the ascender of metrics.
+    pub fn vertical_origin_y(&self, metrics: &GlobalMetricsInstance) -> f64 {
+        self.vertical_origin
+            .unwrap_or_else(|| metrics.ascender.into_inner() as f64)
+    }
+}
+
+/// A named position used to attach marks and cursive connections.
+///
+/// Anchors whose name starts with an underscore, such as `_top`, identify
+/// the glyph as a mark that attaches to `top` anchors on other glyphs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Anchor {
+    pub name: String,
+    pub pos: Point,
 }
 
 /// A single glyph component, reference to another glyph.
@@ -773,16 +1248,19 @@ impl GlyphPathBuilder {
 #[cfg(test)]
 mod tests {
 
-    use std::str::FromStr;
+    use std::{collections::HashMap, str::FromStr, sync::Arc};
 
     use font_types::Tag;
 
     use crate::{
-        coords::{CoordConverter, UserCoord},
+        coords::{CoordConverter, NormalizedCoord, NormalizedLocation, UserCoord},
         ir::Axis,
     };
 
-    use super::GlyphPathBuilder;
+    use super::{
+        AxisLabel, GdefCategory, GlobalMetric, GlobalMetrics, GlyphPathBuilder, KernParticipant,
+        Kerning, StatLabels, StaticMetadata,
+    };
 
     fn test_axis() -> Axis {
         let min = UserCoord::new(100.0);
@@ -814,6 +1292,202 @@ mod tests {
         assert_eq!(test_axis, bincode::deserialize(&bin).unwrap());
     }
 
+    #[test]
+    fn kerning_yaml() {
+        let mut kerning = Kerning::default();
+        kerning.groups.insert(
+            "public.kern1.O".to_string(),
+            ["O".into(), "D".into()].into(),
+        );
+        let mut bold = NormalizedLocation::new();
+        bold.set_pos("Weight", NormalizedCoord::new(1.0));
+        kerning.kerns.insert(
+            (
+                KernParticipant::Group("public.kern1.O".to_string()),
+                KernParticipant::Glyph("V".into()),
+            ),
+            [
+                (NormalizedLocation::new(), (-40.0).into()),
+                (bold, (-60.0).into()),
+            ]
+            .into(),
+        );
+        let yml = serde_yaml::to_string(&kerning).unwrap();
+        assert_eq!(kerning, serde_yaml::from_str(&yml).unwrap());
+    }
+
+    #[test]
+    fn static_metadata_yaml_keeps_gdef_categories() {
+        let mut default = NormalizedLocation::new();
+        default.set_pos("Weight", NormalizedCoord::new(0.0));
+        let mut static_metadata = StaticMetadata::new(
+            1000,
+            HashMap::new(),
+            vec![test_axis()],
+            ["a".into(), "acutecomb".into()].into(),
+            [default].into(),
+        )
+        .unwrap();
+        static_metadata
+            .gdef_categories
+            .insert("acutecomb".into(), GdefCategory::Mark);
+        let yml = serde_yaml::to_string(&static_metadata).unwrap();
+        assert_eq!(static_metadata, serde_yaml::from_str(&yml).unwrap());
+    }
+
+    #[test]
+    fn static_metadata_yaml_keeps_os2() {
+        let mut default = NormalizedLocation::new();
+        default.set_pos("Weight", NormalizedCoord::new(0.0));
+        let mut static_metadata = StaticMetadata::new(
+            1000,
+            HashMap::new(),
+            vec![test_axis()],
+            ["a".into()].into(),
+            [default].into(),
+        )
+        .unwrap();
+        static_metadata.vendor_id = Tag::new(b"RODS");
+        static_metadata.os2.weight_class = Some(300);
+        static_metadata.os2.panose = Some([2, 0, 5, 3, 0, 0, 0, 0, 0, 0]);
+        static_metadata.os2.unicode_range_bits = Some([0, 1].into());
+        let yml = serde_yaml::to_string(&static_metadata).unwrap();
+        assert_eq!(static_metadata, serde_yaml::from_str(&yml).unwrap());
+    }
+
+    #[test]
+    fn variation_model_for_sparse_locations() {
+        let loc = |weight: f32| {
+            let mut loc = NormalizedLocation::new();
+            loc.set_pos("Weight", NormalizedCoord::new(weight));
+            loc
+        };
+        let static_metadata = StaticMetadata::new(
+            1000,
+            HashMap::new(),
+            vec![test_axis()],
+            ["a".into()].into(),
+            [loc(0.0), loc(0.5), loc(1.0)].into(),
+        )
+        .unwrap();
+
+        let all = static_metadata
+            .variation_model_for([loc(0.0), loc(0.5), loc(1.0)].iter())
+            .unwrap();
+        assert_eq!(static_metadata.variation_model, *all);
+
+        let sparse = static_metadata
+            .variation_model_for([loc(0.0), loc(1.0)].iter())
+            .unwrap();
+        assert_eq!(
+            vec![&loc(0.0), &loc(1.0)],
+            sparse.locations().collect::<Vec<_>>()
+        );
+        let again = static_metadata
+            .variation_model_for([loc(1.0), loc(0.0)].iter())
+            .unwrap();
+        assert!(Arc::ptr_eq(&sparse, &again));
+    }
+
+    #[test]
+    fn populate_defaults_derives_from_explicit_values() {
+        let pos = NormalizedLocation::new();
+        let mut metrics = GlobalMetrics::new(pos.clone(), 1000);
+        metrics.set(GlobalMetric::Ascender, pos.clone(), 750.0);
+        metrics.set(GlobalMetric::Descender, pos.clone(), -250.0);
+        metrics.set(GlobalMetric::Os2WinDescent, pos.clone(), 300.0);
+        metrics.populate_defaults(&pos, 1000);
+
+        let metrics = metrics.at(&pos);
+        assert_eq!(
+            (750.0, -250.0, 200.0, 950.0, 300.0, 300.0),
+            (
+                metrics.os2_typo_ascender.into_inner(),
+                metrics.os2_typo_descender.into_inner(),
+                metrics.os2_typo_line_gap.into_inner(),
+                metrics.os2_win_ascent.into_inner(),
+                metrics.os2_win_descent.into_inner(),
+                metrics.strikeout_position.into_inner(),
+            )
+        );
+        assert!(!metrics.is_vertical());
+    }
+
+    #[test]
+    fn populate_defaults_fills_vhea_only_if_some_master_has_it() {
+        let regular = NormalizedLocation::new();
+        let mut bold = NormalizedLocation::new();
+        bold.set_pos("wght", NormalizedCoord::new(1.0));
+        let mut metrics = GlobalMetrics::new(regular.clone(), 1000);
+        metrics.set(GlobalMetric::VheaAscender, bold.clone(), 600.0);
+        metrics.populate_defaults(&regular, 1000);
+        metrics.populate_defaults(&bold, 1000);
+
+        let (regular, bold) = (metrics.at(&regular), metrics.at(&bold));
+        assert!(regular.is_vertical());
+        assert_eq!(
+            vec![
+                (Some(500.0), Some(-500.0), Some(0.0)),
+                (Some(600.0), Some(-500.0), Some(0.0))
+            ],
+            [regular, bold]
+                .iter()
+                .map(|m| (
+                    m.vhea_ascender.map(|v| v.into_inner()),
+                    m.vhea_descender.map(|v| v.into_inner()),
+                    m.vhea_line_gap.map(|v| v.into_inner()),
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stat_labels_claim_names() {
+        let mut static_metadata = StaticMetadata::new(
+            1000,
+            HashMap::new(),
+            vec![test_axis()],
+            Default::default(),
+            Default::default(),
+        )
+        .unwrap();
+        let label = |name: &str, value: f32| AxisLabel {
+            name: name.to_string(),
+            value: UserCoord::new(value),
+            min: None,
+            max: None,
+            linked_value: None,
+            elidable: false,
+            older_sibling: false,
+        };
+        static_metadata.set_stat_labels(StatLabels {
+            axis_labels: [(
+                "Weight".to_string(),
+                vec![label("Regular", 400.0), label("Bold", 700.0)],
+            )]
+            .into(),
+            location_labels: Vec::new(),
+            elided_fallback_name: Some("Regular".to_string()),
+        });
+
+        // 256 is the axis name, Regular is only added once
+        assert_eq!(
+            vec![(256, "Weight"), (257, "Regular"), (258, "Bold")],
+            ["Weight", "Regular", "Bold"]
+                .iter()
+                .map(|name| (
+                    static_metadata
+                        .font_specific_name_id(name)
+                        .unwrap()
+                        .to_u16(),
+                    *name
+                ))
+                .collect::<Vec<_>>()
+        );
+        let yml = serde_yaml::to_string(&static_metadata).unwrap();
+        assert_eq!(static_metadata, serde_yaml::from_str(&yml).unwrap());
+    }
+
     #[test]
     fn a_qcurve_with_no_offcurve_is_a_line() {
         let mut builder = GlyphPathBuilder::new("test".into());
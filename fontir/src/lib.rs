@@ -1,10 +1,15 @@
+pub mod boolean;
 pub mod coords;
 pub mod error;
 pub mod glyph;
+pub mod instance;
+pub mod interpolatable;
 pub mod ir;
 pub mod orchestration;
+pub mod overlap;
 pub mod paths;
 pub mod piecewise_linear_map;
+pub mod quadratic;
 pub(crate) mod serde;
 pub mod source;
 pub mod stateset;
@@ -17,7 +17,7 @@ use fontdrasil::{
 use parking_lot::RwLock;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{error::WorkError, ir, paths::Paths, source::Input};
+use crate::{error::WorkError, ir, paths::Paths, quadratic::MaxConversionError, source::Input};
 
 bitflags! {
     #[derive(Clone, Copy, Debug)]
@@ -31,6 +31,22 @@ bitflags! {
         // If set, a composite that references another composite will replace that composite with the
         // glyph(s) it references until only simple (contour) glyphs are referenced
         const FLATTEN_COMPONENTS = 0b00001000;
+        // If set, emit CFF (static) or CFF2 (variable) outlines instead of glyf and gvar
+        const CFF_OUTLINES = 0b00010000;
+        // If set, glyphs whose sources can't interpolate are reported when their IR is built
+        const CHECK_INTERPOLATION = 0b00100000;
+        // If set, contour order and start points in non-default sources are matched to the default
+        const MATCH_CONTOURS = 0b01000000;
+        // If set, gvar omits deltas that can be interpolated from those of neighbouring points
+        const OPTIMIZE_GVAR = 0b10000000;
+        // If set, cubics are converted to quadratics across all glyphs at once so the same curve is
+        // split the same way wherever it occurs, rather than glyph by glyph
+        const FONT_WIDE_QUADRATICS = 0b100000000;
+        // If set, variable fonts flag every glyph as overlapping rather than only those whose default
+        // master overlaps
+        const OVERLAP_FLAG_ON_ALL_GLYPHS = 0b1000000000;
+        // If set, static fonts replace overlapping contours with their union
+        const REMOVE_OVERLAPS = 0b10000000000;
     }
 }
 
@@ -74,7 +90,7 @@ macro_rules! context_accessors {
 impl Default for Flags {
     /// Match the way gftools configures fontmake by default
     fn default() -> Self {
-        Flags::EMIT_IR | Flags::PREFER_SIMPLE_GLYPHS
+        Flags::EMIT_IR | Flags::PREFER_SIMPLE_GLYPHS | Flags::OPTIMIZE_GVAR
     }
 }
 
@@ -94,6 +110,7 @@ pub enum WorkId {
     /// BE glyphs so the glyph order may change.
     FinalizeStaticMetadata,
     Features,
+    Kerning,
 }
 
 pub type IrWork = dyn Work<Context, WorkError> + Send;
@@ -106,6 +123,9 @@ pub type IrWork = dyn Work<Context, WorkError> + Send;
 pub struct Context {
     pub flags: Flags,
 
+    /// How far quadratics converted from cubics may stray from the original
+    pub max_conversion_error: MaxConversionError,
+
     paths: Arc<Paths>,
 
     // The input we're working on. Note that change detection may mean we only process
@@ -121,6 +141,7 @@ pub struct Context {
     global_metrics: ContextItem<ir::GlobalMetrics>,
     glyph_ir: Arc<RwLock<HashMap<GlyphName, Arc<ir::Glyph>>>>,
     feature_ir: ContextItem<ir::Features>,
+    kerning: ContextItem<ir::Kerning>,
 }
 
 pub fn set_cached<T>(lock: &Arc<RwLock<Option<Arc<T>>>>, value: T) {
@@ -132,6 +153,7 @@ impl Context {
     fn copy(&self, acl: AccessControlList<WorkId>) -> Context {
         Context {
             flags: self.flags,
+            max_conversion_error: self.max_conversion_error,
             paths: self.paths.clone(),
             input: self.input.clone(),
             acl,
@@ -140,12 +162,14 @@ impl Context {
             global_metrics: self.global_metrics.clone(),
             glyph_ir: self.glyph_ir.clone(),
             feature_ir: self.feature_ir.clone(),
+            kerning: self.kerning.clone(),
         }
     }
 
     pub fn new_root(flags: Flags, paths: Paths, input: Input) -> Context {
         Context {
             flags,
+            max_conversion_error: Default::default(),
             paths: Arc::from(paths),
             input: Arc::from(input),
             acl: AccessControlList::read_only(),
@@ -154,9 +178,27 @@ impl Context {
             global_metrics: Arc::from(RwLock::new(None)),
             glyph_ir: Arc::from(RwLock::new(HashMap::new())),
             feature_ir: Arc::from(RwLock::new(None)),
+            kerning: Arc::from(RwLock::new(None)),
         }
     }
 
+    pub fn with_max_conversion_error(
+        mut self,
+        max_conversion_error: MaxConversionError,
+    ) -> Context {
+        self.max_conversion_error = max_conversion_error;
+        self
+    }
+
+    /// A root context for input that keeps everything computed so far.
+    ///
+    /// Lets a long-running process redo only the work whose input changed.
+    pub fn with_input(&self, input: Input) -> Context {
+        let mut context = self.read_only();
+        context.input = Arc::from(input);
+        context
+    }
+
     pub fn copy_for_work(
         &self,
         read_access: Access<WorkId>,
@@ -169,6 +211,10 @@ impl Context {
         self.copy(AccessControlList::read_only())
     }
 
+    pub(crate) fn paths(&self) -> &Paths {
+        &self.paths
+    }
+
     fn maybe_persist<V>(&self, file: &Path, content: &V)
     where
         V: ?Sized + Serialize + Debug,
@@ -222,6 +268,7 @@ impl Context {
     context_accessors! { get_final_static_metadata, set_final_static_metadata, final_static_metadata, ir::StaticMetadata, WorkId::FinalizeStaticMetadata, restore, nop }
     context_accessors! { get_global_metrics, set_global_metrics, global_metrics, ir::GlobalMetrics, WorkId::GlobalMetrics, restore, nop }
     context_accessors! { get_features, set_features, feature_ir, ir::Features, WorkId::Features, restore, nop }
+    context_accessors! { get_kerning, set_kerning, kerning, ir::Kerning, WorkId::Kerning, restore, nop }
 }
 
 fn nop<T>(v: &T) -> &T {
@@ -0,0 +1,249 @@
+//! Detects overlapping outlines.
+//!
+//! Overlaps render fine with a nonzero fill but some rasterizers, notably on macOS, only
+//! handle them in variable fonts if the glyph is flagged as overlapping. See
+//! OVERLAP_SIMPLE and OVERLAP_COMPOUND in <https://learn.microsoft.com/en-us/typography/opentype/spec/glyf>.
+
+use kurbo::{BezPath, PathEl, Point, Rect};
+
+/// How closely, in font units, flattened curves follow the original
+const FLATTEN_TOLERANCE: f64 = 0.1;
+
+/// A closed contour approximated by straight lines.
+struct Polygon {
+    points: Vec<Point>,
+    bbox: Rect,
+    /// Positive if counter-clockwise
+    signed_area: f64,
+}
+
+impl Polygon {
+    fn new(points: Vec<Point>) -> Option<Polygon> {
+        if points.len() < 3 {
+            return None;
+        }
+        let bbox = points
+            .iter()
+            .skip(1)
+            .fold(Rect::from_points(points[0], points[0]), |bbox, p| {
+                bbox.union_pt(*p)
+            });
+        let signed_area = edges(&points)
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum::<f64>()
+            / 2.0;
+        Some(Polygon {
+            points,
+            bbox,
+            signed_area,
+        })
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        edges(&self.points)
+    }
+
+    /// Nonzero winding number of the polygon around p
+    fn winding(&self, p: Point) -> i32 {
+        self.edges()
+            .map(|(a, b)| {
+                if a.y <= p.y {
+                    (b.y > p.y && cross(a, b, p) > 0.0) as i32
+                } else {
+                    -((b.y <= p.y && cross(a, b, p) < 0.0) as i32)
+                }
+            })
+            .sum()
+    }
+
+    /// Whether the polygon crosses itself
+    fn self_intersects(&self) -> bool {
+        let edges: Vec<_> = self.edges().collect();
+        edges.iter().enumerate().any(|(i, e1)| {
+            edges[i + 1..]
+                .iter()
+                .any(|e2| segments_cross(e1.0, e1.1, e2.0, e2.1))
+        })
+    }
+
+    /// Whether the area one polygon fills is also filled by the other.
+    ///
+    /// Polygons that cross overlap. One inside the other overlaps if both wind the same
+    /// way; winding opposite ways it is a counter, such as the inside of an o.
+    fn overlaps(&self, other: &Polygon) -> bool {
+        if !rects_intersect(self.bbox, other.bbox) {
+            return false;
+        }
+        let crosses = self
+            .edges()
+            .any(|(a, b)| other.edges().any(|(c, d)| segments_cross(a, b, c, d)));
+        if crosses {
+            return true;
+        }
+        let same_direction = (self.signed_area > 0.0) == (other.signed_area > 0.0);
+        same_direction && (self.winding(other.points[0]) != 0 || other.winding(self.points[0]) != 0)
+    }
+}
+
+fn edges(points: &[Point]) -> impl Iterator<Item = (Point, Point)> + '_ {
+    points
+        .iter()
+        .copied()
+        .zip(points.iter().copied().cycle().skip(1))
+}
+
+/// Positive if p is left of the line through a and b
+fn cross(a: Point, b: Point, p: Point) -> f64 {
+    (b - a).cross(p - a)
+}
+
+/// Whether ab and cd cross at a point inside both.
+///
+/// Segments that merely touch, or run along one another, don't cross.
+fn segments_cross(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+pub(crate) fn rects_intersect(r1: Rect, r2: Rect) -> bool {
+    r1.x0 <= r2.x1 && r2.x0 <= r1.x1 && r1.y0 <= r2.y1 && r2.y0 <= r1.y1
+}
+
+fn polygons<'a>(contours: impl IntoIterator<Item = &'a BezPath>) -> Vec<Polygon> {
+    let mut polygons = Vec::new();
+    let mut points = Vec::new();
+    for contour in contours {
+        contour.flatten(FLATTEN_TOLERANCE, |el| match el {
+            PathEl::MoveTo(p) => {
+                polygons.extend(Polygon::new(std::mem::take(&mut points)));
+                points.push(p);
+            }
+            PathEl::LineTo(p) => {
+                if points.last() != Some(&p) {
+                    points.push(p);
+                }
+            }
+            PathEl::ClosePath => (),
+            PathEl::QuadTo(..) | PathEl::CurveTo(..) => unreachable!("flatten only emits lines"),
+        });
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        polygons.extend(Polygon::new(std::mem::take(&mut points)));
+    }
+    polygons
+}
+
+/// Whether any contour crosses itself or overlaps another.
+pub fn contours_overlap(contours: &[BezPath]) -> bool {
+    let polygons = polygons(contours);
+    polygons.iter().enumerate().any(|(i, polygon)| {
+        polygon.self_intersects()
+            || polygons[i + 1..]
+                .iter()
+                .any(|other| polygon.overlaps(other))
+    })
+}
+
+/// Whether any contour of one group, such as the outline of a component, overlaps a
+/// contour of another.
+pub fn groups_overlap(groups: &[Vec<BezPath>]) -> bool {
+    let groups: Vec<_> = groups.iter().map(polygons).collect();
+    groups.iter().enumerate().any(|(i, group)| {
+        groups[i + 1..].iter().any(|other| {
+            group
+                .iter()
+                .any(|polygon| other.iter().any(|o| polygon.overlaps(o)))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Affine, BezPath, Circle, ParamCurve, Shape};
+
+    use super::{contours_overlap, groups_overlap};
+
+    fn square(x: f64, y: f64, size: f64) -> BezPath {
+        BezPath::from_svg(&format!(
+            "M{x},{y} L{},{y} L{},{} L{x},{} Z",
+            x + size,
+            x + size,
+            y + size,
+            y + size
+        ))
+        .unwrap()
+    }
+
+    fn reversed(path: &BezPath) -> BezPath {
+        let mut points: Vec<_> = path.segments().map(|s| s.start()).collect();
+        points.reverse();
+        let mut reversed = BezPath::new();
+        reversed.move_to(points[0]);
+        points[1..].iter().for_each(|p| reversed.line_to(*p));
+        reversed.close_path();
+        reversed
+    }
+
+    #[test]
+    fn apart_is_not_overlap() {
+        assert!(!contours_overlap(&[
+            square(0.0, 0.0, 100.0),
+            square(200.0, 0.0, 100.0)
+        ]));
+    }
+
+    #[test]
+    fn touching_is_not_overlap() {
+        assert!(!contours_overlap(&[
+            square(0.0, 0.0, 100.0),
+            square(100.0, 0.0, 100.0)
+        ]));
+    }
+
+    #[test]
+    fn crossing_is_overlap() {
+        assert!(contours_overlap(&[
+            square(0.0, 0.0, 100.0),
+            square(50.0, 50.0, 100.0)
+        ]));
+    }
+
+    #[test]
+    fn curves_crossing_is_overlap() {
+        let circle = Circle::new((0.0, 0.0), 100.0).to_path(0.1);
+        let mut other = circle.clone();
+        other.apply_affine(Affine::translate((150.0, 0.0)));
+        assert!(contours_overlap(&[circle, other]));
+    }
+
+    #[test]
+    fn counter_is_not_overlap() {
+        let inner = reversed(&square(25.0, 25.0, 50.0));
+        assert!(!contours_overlap(&[square(0.0, 0.0, 100.0), inner]));
+    }
+
+    #[test]
+    fn same_direction_inside_is_overlap() {
+        assert!(contours_overlap(&[
+            square(0.0, 0.0, 100.0),
+            square(25.0, 25.0, 50.0)
+        ]));
+    }
+
+    #[test]
+    fn self_intersection_is_overlap() {
+        let bowtie = BezPath::from_svg("M0,0 L100,100 L100,0 L0,100 Z").unwrap();
+        assert!(contours_overlap(&[bowtie]));
+    }
+
+    #[test]
+    fn groups_only_overlap_one_another() {
+        // Each group overlaps itself, but not the other
+        let group1 = vec![square(0.0, 0.0, 100.0), square(50.0, 50.0, 100.0)];
+        let group2 = vec![square(500.0, 0.0, 100.0), square(550.0, 50.0, 100.0)];
+        assert!(!groups_overlap(&[group1.clone(), group2]));
+        assert!(groups_overlap(&[group1, vec![square(125.0, 125.0, 100.0)]]));
+    }
+}
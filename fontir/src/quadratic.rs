@@ -0,0 +1,409 @@
+//! Conversion of cubic curves to the quadratic curves TrueType outlines require.
+//!
+//! Like [cu2qu](https://github.com/fonttools/fonttools/tree/main/Lib/fontTools/cu2qu) the
+//! same segment is converted at every location at once, splitting it into the same number
+//! of quadratics everywhere, so the result interpolates.
+
+use std::collections::HashMap;
+
+use fontdrasil::types::GlyphName;
+use kurbo::{cubics_to_quadratic_splines, BezPath, CubicBez, PathEl, Point, QuadSpline};
+
+use crate::{coords::NormalizedLocation, error::PathConversionError};
+
+/// How far a quadratic approximation may stray from the cubic it replaces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaxConversionError {
+    /// In font units
+    Units(f64),
+    /// Relative to units per em
+    Em(f64),
+}
+
+impl Default for MaxConversionError {
+    /// Match ufo2ft, 1 unit at 1000 units per em
+    fn default() -> Self {
+        MaxConversionError::Em(0.001)
+    }
+}
+
+impl MaxConversionError {
+    pub fn in_units(&self, units_per_em: u16) -> f64 {
+        match self {
+            MaxConversionError::Units(units) => *units,
+            MaxConversionError::Em(em) => em * units_per_em as f64,
+        }
+    }
+}
+
+/// Whether there's anything to convert
+pub fn has_cubics<'a>(contours: impl IntoIterator<Item = &'a BezPath>) -> bool {
+    contours
+        .into_iter()
+        .flat_map(|path| path.elements())
+        .any(|el| matches!(el, PathEl::CurveTo(..)))
+}
+
+/// Convert the cubics in the contours of a glyph to quadratics.
+///
+/// Contours that are already quadratic, or have no curves at all, are kept as they are.
+pub fn to_quadratic(
+    glyph_name: &GlyphName,
+    contours: &HashMap<NormalizedLocation, Vec<BezPath>>,
+    max_error: f64,
+) -> Result<HashMap<NormalizedLocation, Vec<BezPath>>, PathConversionError> {
+    if !has_cubics(contours.values().flatten()) {
+        return Ok(contours.clone());
+    }
+    let segments = Segments::new(glyph_name, contours)?;
+    let mut splines = HashMap::new();
+    for (idx, cubics) in segments.cubics() {
+        splines.insert(idx, approximate(glyph_name, &cubics, max_error)?);
+    }
+    Ok(segments.rebuild(&mut splines))
+}
+
+/// Convert the cubics in the contours of many glyphs to quadratics.
+///
+/// Cubics that are the same, allowing for translation, at the default location are
+/// split into the same number of quadratics wherever they occur. Contours copied from
+/// a component, and glyphs drawn from the same parts, keep matching one another.
+pub fn to_quadratic_font_wide(
+    glyphs: &[(GlyphName, HashMap<NormalizedLocation, Vec<BezPath>>)],
+    default_location: &NormalizedLocation,
+    max_error: f64,
+) -> Result<Vec<HashMap<NormalizedLocation, Vec<BezPath>>>, PathConversionError> {
+    let segments = glyphs
+        .iter()
+        .map(|(glyph_name, contours)| Segments::new(glyph_name, contours))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Group the same cubic, wherever it occurs
+    let mut groups: HashMap<CubicKey, Vec<(usize, usize, Vec<CubicBez>)>> = HashMap::new();
+    let mut ungrouped = Vec::new();
+    for (glyph_idx, glyph_segments) in segments.iter().enumerate() {
+        for (idx, cubics) in glyph_segments.cubics() {
+            match glyph_segments.key(idx, default_location) {
+                Some(key) => groups
+                    .entry(key)
+                    .or_default()
+                    .push((glyph_idx, idx, cubics)),
+                None => ungrouped.push(vec![(glyph_idx, idx, cubics)]),
+            }
+        }
+    }
+
+    let mut splines: Vec<HashMap<usize, Vec<QuadSpline>>> = vec![HashMap::new(); glyphs.len()];
+    for group in groups.into_values().chain(ungrouped) {
+        let all_cubics: Vec<_> = group
+            .iter()
+            .flat_map(|(_, _, cubics)| cubics.iter().copied())
+            .collect();
+        let mut all_splines =
+            approximate(&glyphs[group[0].0].0, &all_cubics, max_error)?.into_iter();
+        for (glyph_idx, idx, cubics) in group {
+            splines[glyph_idx].insert(idx, all_splines.by_ref().take(cubics.len()).collect());
+        }
+    }
+
+    Ok(segments
+        .into_iter()
+        .zip(splines.iter_mut())
+        .map(|(segments, splines)| segments.rebuild(splines))
+        .collect())
+}
+
+/// Quadratic splines for each cubic, each split the same way
+fn approximate(
+    glyph_name: &GlyphName,
+    cubics: &[CubicBez],
+    max_error: f64,
+) -> Result<Vec<QuadSpline>, PathConversionError> {
+    cubics_to_quadratic_splines(cubics, max_error)
+        .filter(|splines| splines.len() == cubics.len())
+        .ok_or_else(|| PathConversionError::NoQuadraticApproximation {
+            glyph_name: glyph_name.clone(),
+            max_error,
+        })
+}
+
+/// A cubic moved to start at the origin, rounded so float noise doesn't split groups
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct CubicKey([(i64, i64); 3]);
+
+impl CubicKey {
+    fn new(cubic: &CubicBez) -> CubicKey {
+        let key = |p: Point| {
+            let v = p - cubic.p0;
+            ((v.x * 1000.0).round() as i64, (v.y * 1000.0).round() as i64)
+        };
+        CubicKey([key(cubic.p1), key(cubic.p2), key(cubic.p3)])
+    }
+}
+
+/// The contours of a glyph at every location, lined up segment by segment.
+struct Segments {
+    locations: Vec<NormalizedLocation>,
+    /// The number of elements in each contour, the same at every location
+    contour_lens: Vec<usize>,
+    /// For each element, the element at every location
+    elements: Vec<Vec<PathEl>>,
+    /// For each element, the point it starts from at every location
+    starts: Vec<Vec<Point>>,
+}
+
+impl Segments {
+    fn new(
+        glyph_name: &GlyphName,
+        contours: &HashMap<NormalizedLocation, Vec<BezPath>>,
+    ) -> Result<Segments, PathConversionError> {
+        let incompatible = |reason: String| PathConversionError::IncompatiblePaths {
+            glyph_name: glyph_name.clone(),
+            reason,
+        };
+
+        let mut locations: Vec<_> = contours.keys().cloned().collect();
+        locations.sort();
+        let contour_lens: Vec<_> = locations
+            .first()
+            .map(|loc| contours[loc].iter().map(|c| c.elements().len()).collect())
+            .unwrap_or_default();
+        for loc in locations.iter() {
+            let lens: Vec<_> = contours[loc].iter().map(|c| c.elements().len()).collect();
+            if lens != contour_lens {
+                return Err(incompatible(format!(
+                    "contour lengths {lens:?} at {loc:?} but {contour_lens:?} at {:?}",
+                    locations[0]
+                )));
+            }
+        }
+
+        let n = contour_lens.iter().sum();
+        let mut elements = vec![Vec::with_capacity(locations.len()); n];
+        let mut starts = vec![Vec::with_capacity(locations.len()); n];
+        for loc in locations.iter() {
+            let mut subpath_start = Point::ZERO;
+            let mut current = Point::ZERO;
+            let loc_elements = contours[loc].iter().flat_map(|c| c.elements().iter());
+            for (idx, el) in loc_elements.enumerate() {
+                if idx == 0 && !matches!(el, PathEl::MoveTo(..)) {
+                    return Err(incompatible(format!(
+                        "illegal start of path {el:?} at {loc:?}"
+                    )));
+                }
+                if let Some(first) = elements[idx].first() {
+                    if std::mem::discriminant(first) != std::mem::discriminant(el) {
+                        return Err(incompatible(format!(
+                            "element {idx} is {first:?} at {:?} but {el:?} at {loc:?}",
+                            locations[0]
+                        )));
+                    }
+                }
+                elements[idx].push(*el);
+                starts[idx].push(current);
+                current = match el {
+                    PathEl::MoveTo(p) => {
+                        subpath_start = *p;
+                        *p
+                    }
+                    PathEl::LineTo(p) | PathEl::QuadTo(_, p) | PathEl::CurveTo(_, _, p) => *p,
+                    PathEl::ClosePath => subpath_start,
+                };
+            }
+        }
+
+        Ok(Segments {
+            locations,
+            contour_lens,
+            elements,
+            starts,
+        })
+    }
+
+    /// The index and cubic at every location of each cubic segment
+    fn cubics(&self) -> impl Iterator<Item = (usize, Vec<CubicBez>)> + '_ {
+        self.elements
+            .iter()
+            .zip(self.starts.iter())
+            .enumerate()
+            .filter(|(_, (elements, _))| matches!(elements.first(), Some(PathEl::CurveTo(..))))
+            .map(|(idx, (elements, starts))| {
+                let cubics = elements
+                    .iter()
+                    .zip(starts)
+                    .map(|(el, p0)| match el {
+                        PathEl::CurveTo(p1, p2, p3) => CubicBez::new(*p0, *p1, *p2, *p3),
+                        _ => unreachable!("Segments::new checked every location has a cubic"),
+                    })
+                    .collect();
+                (idx, cubics)
+            })
+    }
+
+    fn key(&self, idx: usize, default_location: &NormalizedLocation) -> Option<CubicKey> {
+        let loc_idx = self
+            .locations
+            .iter()
+            .position(|loc| loc == default_location)?;
+        match self.elements[idx][loc_idx] {
+            PathEl::CurveTo(p1, p2, p3) => Some(CubicKey::new(&CubicBez::new(
+                self.starts[idx][loc_idx],
+                p1,
+                p2,
+                p3,
+            ))),
+            _ => None,
+        }
+    }
+
+    /// Contours with the splines, by segment index, in place of the cubics.
+    fn rebuild(
+        self,
+        splines: &mut HashMap<usize, Vec<QuadSpline>>,
+    ) -> HashMap<NormalizedLocation, Vec<BezPath>> {
+        let mut result: HashMap<_, _> = self
+            .locations
+            .iter()
+            .map(|loc| (loc.clone(), Vec::with_capacity(self.contour_lens.len())))
+            .collect();
+        let mut idx = 0;
+        for len in self.contour_lens.iter() {
+            let mut paths = vec![BezPath::new(); self.locations.len()];
+            for _ in 0..*len {
+                match splines.remove(&idx) {
+                    Some(splines) => {
+                        for (path, spline) in paths.iter_mut().zip(splines) {
+                            for quad in spline.to_quads() {
+                                path.quad_to(quad.p1, quad.p2);
+                            }
+                        }
+                    }
+                    None => {
+                        for (path, el) in paths.iter_mut().zip(self.elements[idx].iter()) {
+                            path.push(*el);
+                        }
+                    }
+                }
+                idx += 1;
+            }
+            for (loc, path) in self.locations.iter().zip(paths) {
+                result.get_mut(loc).unwrap().push(path);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Affine, Shape};
+
+    use crate::coords::NormalizedCoord;
+
+    use super::*;
+
+    fn loc(wght: f32) -> NormalizedLocation {
+        let mut loc = NormalizedLocation::new();
+        loc.set_pos("wght", NormalizedCoord::new(wght));
+        loc
+    }
+
+    fn quads(paths: &[BezPath]) -> usize {
+        paths
+            .iter()
+            .flat_map(|p| p.elements())
+            .filter(|el| matches!(el, PathEl::QuadTo(..)))
+            .count()
+    }
+
+    fn circle(radius: f64) -> BezPath {
+        kurbo::Circle::new((0.0, 0.0), radius).to_path(0.1)
+    }
+
+    #[test]
+    fn max_error_relative_to_upem() {
+        assert_eq!(1.0, MaxConversionError::default().in_units(1000));
+        assert_eq!(2.048, MaxConversionError::Em(0.001).in_units(2048));
+        assert_eq!(0.5, MaxConversionError::Units(0.5).in_units(2048));
+    }
+
+    #[test]
+    fn quadratics_are_kept_as_they_are() {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.quad_to((50.0, 100.0), (100.0, 0.0));
+        path.close_path();
+        let contours = HashMap::from([(loc(0.0), vec![path])]);
+
+        assert_eq!(contours, to_quadratic(&"q".into(), &contours, 1.0).unwrap());
+    }
+
+    #[test]
+    fn every_location_gets_the_same_splits() {
+        let contours = HashMap::from([
+            (loc(0.0), vec![circle(50.0)]),
+            (loc(1.0), vec![circle(500.0)]),
+        ]);
+        let converted = to_quadratic(&"o".into(), &contours, 1.0).unwrap();
+        assert!(!has_cubics(converted.values().flatten()));
+
+        // The big circle needs more splits, the small one must follow
+        let small = quads(&converted[&loc(0.0)]);
+        let big = quads(&converted[&loc(1.0)]);
+        assert_eq!(small, big);
+        let small_alone = to_quadratic(
+            &"o".into(),
+            &HashMap::from([(loc(0.0), vec![circle(50.0)])]),
+            1.0,
+        )
+        .unwrap();
+        assert!(quads(&small_alone[&loc(0.0)]) < small);
+    }
+
+    #[test]
+    fn incompatible_paths_are_an_error() {
+        let mut line = BezPath::new();
+        line.move_to((0.0, 0.0));
+        line.line_to((50.0, 100.0));
+        line.line_to((100.0, 0.0));
+        line.line_to((0.0, 0.0));
+        let mut curve = BezPath::new();
+        curve.move_to((0.0, 0.0));
+        curve.curve_to((0.0, 50.0), (50.0, 100.0), (100.0, 0.0));
+        curve.line_to((100.0, 0.0));
+        curve.line_to((0.0, 0.0));
+        let contours = HashMap::from([(loc(0.0), vec![line]), (loc(1.0), vec![curve])]);
+
+        let result = to_quadratic(&"bad".into(), &contours, 1.0);
+        assert!(
+            matches!(result, Err(PathConversionError::IncompatiblePaths { .. })),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn font_wide_splits_copies_the_same_way() {
+        let name: GlyphName = "o".into();
+        let o = HashMap::from([
+            (loc(0.0), vec![circle(50.0)]),
+            (loc(1.0), vec![circle(500.0)]),
+        ]);
+        // A copy of o's default moved elsewhere, as if decomposed from a component
+        let mut copy = circle(50.0);
+        copy.apply_affine(Affine::translate((300.0, 20.0)));
+        let copy = HashMap::from([(loc(0.0), vec![copy])]);
+
+        let glyph_wise = to_quadratic(&"copy".into(), &copy, 1.0).unwrap();
+        let font_wide = to_quadratic_font_wide(
+            &[(name.clone(), o.clone()), ("copy".into(), copy)],
+            &loc(0.0),
+            1.0,
+        )
+        .unwrap();
+
+        let o_quads = quads(&to_quadratic(&name, &o, 1.0).unwrap()[&loc(0.0)]);
+        assert_eq!(o_quads, quads(&font_wide[0][&loc(0.0)]));
+        assert_eq!(o_quads, quads(&font_wide[1][&loc(0.0)]));
+        assert!(quads(&glyph_wise[&loc(0.0)]) < o_quads);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     path::PathBuf,
     str::FromStr,
 };
@@ -7,14 +7,16 @@ use std::{
 use filetime::FileTime;
 use font_types::NameId;
 use font_types::Tag;
+use fontdrasil::types::GlyphName;
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     coords::{CoordConverter, DesignCoord, NormalizedLocation, UserCoord},
     ir::{
-        Axis, GlobalMetric, GlobalMetrics, Glyph, GlyphBuilder, GlyphInstance, NameKey,
-        StaticMetadata,
+        Axis, Condition, FeatureVariations, GdefCategory, GlobalMetric, GlobalMetrics, Glyph,
+        GlyphAlternate, GlyphBuilder, GlyphInstance, KernParticipant, Kerning, NameKey,
+        NamedInstance, Os2Metadata, StatLabels, StaticMetadata,
     },
     stateset::{FileState, MemoryState, State, StateIdentifier, StateSet},
 };
@@ -122,22 +124,37 @@ impl From<CoordConverter> for CoordConverterSerdeRepr {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StaticMetadataSerdeRepr {
     pub units_per_em: u16,
+    #[serde(serialize_with = "serialize_tag")]
+    #[serde(deserialize_with = "deserialize_tag")]
+    pub vendor_id: Tag,
     pub axes: Vec<Axis>,
     pub glyph_locations: Vec<NormalizedLocation>,
     pub names: HashMap<NameKey, String>,
     pub glyph_order: Vec<String>,
+    pub gdef_categories: BTreeMap<GlyphName, GdefCategory>,
+    pub stat_labels: StatLabels,
+    pub named_instances: Vec<NamedInstance>,
+    pub os2: Os2Metadata,
+    pub feature_variations: FeatureVariations,
 }
 
 impl From<StaticMetadataSerdeRepr> for StaticMetadata {
     fn from(from: StaticMetadataSerdeRepr) -> Self {
-        StaticMetadata::new(
+        let mut static_metadata = StaticMetadata::new(
             from.units_per_em,
             from.names,
             from.axes,
             from.glyph_order.into_iter().map(|s| s.into()).collect(),
             from.glyph_locations.into_iter().collect(),
         )
-        .unwrap()
+        .unwrap();
+        static_metadata.vendor_id = from.vendor_id;
+        static_metadata.gdef_categories = from.gdef_categories;
+        static_metadata.set_stat_labels(from.stat_labels);
+        static_metadata.set_named_instances(from.named_instances);
+        static_metadata.os2 = from.os2;
+        static_metadata.feature_variations = from.feature_variations;
+        static_metadata
     }
 }
 
@@ -146,6 +163,7 @@ impl From<StaticMetadata> for StaticMetadataSerdeRepr {
         let glyph_locations = from.variation_model.locations().cloned().collect();
         StaticMetadataSerdeRepr {
             units_per_em: from.units_per_em,
+            vendor_id: from.vendor_id,
             axes: from.axes,
             glyph_locations,
             names: from.names,
@@ -154,6 +172,11 @@ impl From<StaticMetadata> for StaticMetadataSerdeRepr {
                 .into_iter()
                 .map(|n| n.as_str().to_string())
                 .collect(),
+            gdef_categories: from.gdef_categories,
+            stat_labels: from.stat_labels,
+            named_instances: from.named_instances,
+            os2: from.os2,
+            feature_variations: from.feature_variations,
         }
     }
 }
@@ -189,6 +212,49 @@ impl From<GlobalMetrics> for GlobalMetricsSerdeRepr {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KernSerdeRepr {
+    side1: KernParticipant,
+    side2: KernParticipant,
+    values: Vec<(NormalizedLocation, OrderedFloat<f32>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KerningSerdeRepr {
+    groups: BTreeMap<String, BTreeSet<GlyphName>>,
+    kerns: Vec<KernSerdeRepr>,
+}
+
+impl From<KerningSerdeRepr> for Kerning {
+    fn from(from: KerningSerdeRepr) -> Self {
+        Kerning {
+            groups: from.groups,
+            kerns: from
+                .kerns
+                .into_iter()
+                .map(|k| ((k.side1, k.side2), k.values.into_iter().collect()))
+                .collect(),
+        }
+    }
+}
+
+impl From<Kerning> for KerningSerdeRepr {
+    fn from(from: Kerning) -> Self {
+        KerningSerdeRepr {
+            groups: from.groups,
+            kerns: from
+                .kerns
+                .into_iter()
+                .map(|((side1, side2), values)| KernSerdeRepr {
+                    side1,
+                    side2,
+                    values: values.into_iter().collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
 // The HashMap<NormalizedLocation, GlyphInstance> seems to throw serde for a loop sometimes
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GlyphInstanceSerdeRepr {
@@ -196,11 +262,39 @@ pub struct GlyphInstanceSerdeRepr {
     instance: GlyphInstance,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GlyphAlternateSerdeRepr {
+    condition_set: Vec<Condition>,
+    instances: Vec<GlyphInstanceSerdeRepr>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GlyphSerdeRepr {
     pub name: String,
     pub codepoints: HashSet<u32>,
     pub instances: Vec<GlyphInstanceSerdeRepr>,
+    pub alternates: Vec<GlyphAlternateSerdeRepr>,
+}
+
+fn to_instance_reprs(
+    sources: HashMap<NormalizedLocation, GlyphInstance>,
+) -> Vec<GlyphInstanceSerdeRepr> {
+    sources
+        .into_iter()
+        .map(|(loc, inst)| GlyphInstanceSerdeRepr {
+            location: loc,
+            instance: inst,
+        })
+        .collect()
+}
+
+fn from_instance_reprs(
+    instances: Vec<GlyphInstanceSerdeRepr>,
+) -> HashMap<NormalizedLocation, GlyphInstance> {
+    instances
+        .into_iter()
+        .map(|g| (g.location, g.instance))
+        .collect()
 }
 
 impl From<GlyphSerdeRepr> for Glyph {
@@ -208,10 +302,14 @@ impl From<GlyphSerdeRepr> for Glyph {
         GlyphBuilder {
             name: from.name.into(),
             codepoints: from.codepoints,
-            sources: from
-                .instances
+            sources: from_instance_reprs(from.instances),
+            alternates: from
+                .alternates
                 .into_iter()
-                .map(|g| (g.location, g.instance))
+                .map(|a| GlyphAlternate {
+                    condition_set: a.condition_set,
+                    sources: from_instance_reprs(a.instances),
+                })
                 .collect(),
         }
         .try_into()
@@ -225,12 +323,13 @@ impl From<Glyph> for GlyphSerdeRepr {
         GlyphSerdeRepr {
             name: from.name.as_str().to_string(),
             codepoints: from.codepoints,
-            instances: from
-                .sources
+            instances: to_instance_reprs(from.sources),
+            alternates: from
+                .alternates
                 .into_iter()
-                .map(|(loc, inst)| GlyphInstanceSerdeRepr {
-                    location: loc,
-                    instance: inst,
+                .map(|a| GlyphAlternateSerdeRepr {
+                    condition_set: a.condition_set,
+                    instances: to_instance_reprs(a.sources),
                 })
                 .collect(),
         }
@@ -243,31 +342,34 @@ pub(crate) struct StateSetSerdeRepr {
     slices: Vec<SliceStateSerdeRepr>,
 }
 
-impl From<StateSetSerdeRepr> for StateSet {
-    fn from(from: StateSetSerdeRepr) -> Self {
-        let entries: HashMap<StateIdentifier, State> = from
+impl TryFrom<StateSetSerdeRepr> for StateSet {
+    type Error = blake3::HexError;
+
+    fn try_from(from: StateSetSerdeRepr) -> Result<Self, Self::Error> {
+        let entries = from
             .files
             .into_iter()
             .map(|serde_repr| {
-                (
+                Ok((
                     StateIdentifier::File(PathBuf::from(&serde_repr.path)),
                     State::File(FileState {
                         mtime: FileTime::from_unix_time(serde_repr.unix_seconds, serde_repr.nanos),
                         size: serde_repr.size,
+                        hash: serde_repr.hash.map(blake3::Hash::from_hex).transpose()?,
                     }),
-                )
+                ))
             })
             .chain(from.slices.into_iter().map(|serde_repr| {
-                (
+                Ok((
                     StateIdentifier::Memory(serde_repr.identifier),
                     State::Memory(MemoryState {
-                        hash: blake3::Hash::from_hex(serde_repr.hash).unwrap(),
+                        hash: blake3::Hash::from_hex(serde_repr.hash)?,
                         size: serde_repr.size,
                     }),
-                )
+                ))
             }))
-            .collect();
-        StateSet { entries }
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(StateSet { entries })
     }
 }
 
@@ -287,6 +389,7 @@ impl From<StateSet> for StateSetSerdeRepr {
                         unix_seconds: state.mtime.unix_seconds(),
                         nanos: state.mtime.nanoseconds(),
                         size: state.size,
+                        hash: state.hash.map(|hash| hash.to_hex().to_string()),
                     });
                 }
                 State::Memory(state) => {
@@ -317,6 +420,8 @@ struct FileStateSerdeRepr {
     unix_seconds: i64,
     nanos: u32,
     size: u64,
+    #[serde(default)]
+    hash: Option<String>,
 }
 
 /// The serde-friendly representation of a [MemoryState].
@@ -1,6 +1,6 @@
 //! Generic model of font sources.
 
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{collections::HashMap, fs, io, path::PathBuf};
 
 use indexmap::IndexSet;
 use log::debug;
@@ -69,6 +69,11 @@ pub trait Source {
     ///
     /// When run work should update [Context] with [crate::ir::Features].
     fn create_feature_ir_work(&self, input: &Input) -> Result<Box<IrWork>, Error>;
+
+    /// Create a function that could be called to generate kerning.
+    ///
+    /// When run work should update [Context] with [crate::ir::Kerning].
+    fn create_kerning_ir_work(&self, input: &Input) -> Result<Box<IrWork>, Error>;
 }
 
 /// The files (in future non-file sources?) that drive various parts of IR
@@ -87,12 +92,28 @@ pub struct Input {
 
     /// The input(s) that inform feature IR construction
     pub features: StateSet,
+
+    /// The input(s) that inform kerning IR construction
+    pub kerning: StateSet,
 }
 
 impl Input {
     pub fn new() -> Input {
         Default::default()
     }
+
+    /// Record content hashes for every tracked file, see [StateSet::hash_files].
+    pub fn hash_files(&mut self, prev: &Input) -> Result<(), io::Error> {
+        self.static_metadata.hash_files(&prev.static_metadata)?;
+        self.global_metrics.hash_files(&prev.global_metrics)?;
+        self.features.hash_files(&prev.features)?;
+        self.kerning.hash_files(&prev.kerning)?;
+        let no_state = StateSet::new();
+        for (glyph_name, state) in self.glyphs.iter_mut() {
+            state.hash_files(prev.glyphs.get(glyph_name).unwrap_or(&no_state))?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -137,11 +158,17 @@ mod tests {
             .track_file(&write(temp_dir, Path::new("features.fea"), "blah"))
             .unwrap();
 
+        let mut kerning = StateSet::new();
+        kerning
+            .track_file(&write(temp_dir, Path::new("kerning.plist"), "blah"))
+            .unwrap();
+
         Input {
             static_metadata: font_info.clone(),
             global_metrics: font_info,
             glyphs,
             features,
+            kerning,
         }
     }
 
@@ -13,7 +13,7 @@ use std::{
 
 /// Helps to identify changes in a set of stateful things.
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
-#[serde(from = "StateSetSerdeRepr", into = "StateSetSerdeRepr")]
+#[serde(try_from = "StateSetSerdeRepr", into = "StateSetSerdeRepr")]
 pub struct StateSet {
     pub(crate) entries: HashMap<StateIdentifier, State>,
 }
@@ -32,10 +32,30 @@ pub(crate) enum State {
     Memory(MemoryState),
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone)]
 pub(crate) struct FileState {
     pub(crate) mtime: FileTime,
     pub(crate) size: u64,
+    /// Hash of the content, only present if asked for by [StateSet::hash_files]
+    pub(crate) hash: Option<blake3::Hash>,
+}
+
+/// Files are unchanged if size and content hash match or, if neither was hashed, size and mtime do.
+///
+/// Trusting the hash over the mtime means that a git checkout or a restore from cache
+/// that touches files without changing them doesn't look like a change. The mtime
+/// only decides whether a file needs to be hashed again, see [FileState::content_hash].
+impl PartialEq for FileState {
+    fn eq(&self, other: &Self) -> bool {
+        if self.size != other.size {
+            return false;
+        }
+        match (self.hash, other.hash) {
+            (Some(hash), Some(other_hash)) => hash == other_hash,
+            (None, None) => self.mtime == other.mtime,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -50,8 +70,30 @@ impl FileState {
         Ok(FileState {
             mtime: FileTime::from_system_time(metadata.modified()?),
             size: metadata.len(),
+            hash: None,
         })
     }
+
+    /// The hash of the content of path, reused from prev if mtime and size say it can be.
+    fn content_hash(
+        &self,
+        path: &Path,
+        prev: Option<&FileState>,
+    ) -> Result<blake3::Hash, io::Error> {
+        if let Some(FileState {
+            mtime,
+            size,
+            hash: Some(hash),
+        }) = prev
+        {
+            if *mtime == self.mtime && *size == self.size {
+                return Ok(*hash);
+            }
+        }
+        let mut hasher = blake3::Hasher::new();
+        io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+        Ok(hasher.finalize())
+    }
 }
 
 impl MemoryState {
@@ -109,6 +151,17 @@ impl StateSet {
             .contains_key(&StateIdentifier::File(path.to_path_buf()))
     }
 
+    /// The state of just the identified things; those that aren't tracked are skipped.
+    pub fn subset<'a>(&self, keys: impl IntoIterator<Item = &'a StateIdentifier>) -> StateSet {
+        StateSet {
+            entries: keys
+                .into_iter()
+                .filter_map(|key| self.entries.get_key_value(key))
+                .map(|(key, state)| (key.clone(), state.clone()))
+                .collect(),
+        }
+    }
+
     /// Pay attention to path, we'd like to know if it changes.
     pub fn track_file(&mut self, path: &Path) -> Result<(), io::Error> {
         self.entries.insert(
@@ -145,6 +198,27 @@ impl StateSet {
         Ok(())
     }
 
+    /// Record the content hash of every tracked file so an mtime change alone isn't a change.
+    ///
+    /// Files are only read if their mtime or size differ from those in prev, otherwise
+    /// the hash prev has for them is reused. Directories don't have content to hash.
+    pub fn hash_files(&mut self, prev: &StateSet) -> Result<(), io::Error> {
+        for (key, state) in self.entries.iter_mut() {
+            let (StateIdentifier::File(path), State::File(state)) = (key, state) else {
+                continue;
+            };
+            if path.is_dir() {
+                continue;
+            }
+            let prev = match prev.entries.get(key) {
+                Some(State::File(prev)) => Some(prev),
+                _ => None,
+            };
+            state.hash = Some(state.content_hash(path, prev)?);
+        }
+        Ok(())
+    }
+
     fn new_files(&self, dirs_visited: &mut HashSet<PathBuf>, dir: &Path) -> Vec<PathBuf> {
         assert!(dir.is_dir());
         if dirs_visited.contains(dir) {
@@ -204,7 +278,11 @@ impl StateSet {
     pub fn set_file_state(&mut self, path: &Path, mtime: FileTime, size: u64) {
         self.entries.insert(
             StateIdentifier::File(path.to_path_buf()),
-            State::File(FileState { mtime, size }),
+            State::File(FileState {
+                mtime,
+                size,
+                hash: None,
+            }),
         );
     }
 }
@@ -248,10 +326,10 @@ mod tests {
         time::Duration,
     };
 
-    use filetime::set_file_mtime;
+    use filetime::{set_file_mtime, FileTime};
     use tempfile::{tempdir, TempDir};
 
-    use super::{StateDiff, StateIdentifier, StateSet};
+    use super::{FileState, State, StateDiff, StateIdentifier, StateSet};
 
     fn assert_no_file_changes(fs: &StateSet) {
         assert_eq!(
@@ -282,6 +360,25 @@ mod tests {
         )
     }
 
+    #[test]
+    fn subset_only_sees_its_keys() {
+        let names = StateIdentifier::Memory("/names".to_string());
+        let axes = StateIdentifier::Memory("/axes".to_string());
+        let missing = StateIdentifier::Memory("/nope".to_string());
+
+        let mut s1 = StateSet::new();
+        s1.track_memory("/names".to_string(), "Family").unwrap();
+        s1.track_memory("/axes".to_string(), "wght").unwrap();
+
+        let mut s2 = s1.clone();
+        s2.track_memory("/names".to_string(), "Other Family")
+            .unwrap();
+
+        assert_eq!(s1.subset([&axes, &missing]), s2.subset([&axes, &missing]));
+        assert_ne!(s1.subset([&names, &axes]), s2.subset([&names, &axes]));
+        assert!(s1.subset([&missing]).is_empty());
+    }
+
     #[test]
     fn detect_file_change() {
         let temp_dir = tempdir().unwrap();
@@ -327,6 +424,43 @@ mod tests {
         assert_no_file_changes(&updated);
     }
 
+    #[test]
+    fn hashed_file_only_changes_with_content() {
+        let temp_dir = tempdir().unwrap();
+
+        let file = temp_dir.path().join("a");
+        fs::write(&file, "eh").unwrap();
+
+        let mut fs = StateSet::new();
+        fs.track_file(&file).unwrap();
+        fs.hash_files(&StateSet::new()).unwrap();
+
+        // A new mtime alone isn't a change
+        let new_mtime = file
+            .metadata()
+            .unwrap()
+            .modified()
+            .unwrap()
+            .add(Duration::from_secs(1));
+        set_file_mtime(&file, new_mtime.into()).unwrap();
+        let mut touched = update_file_entries(&fs).unwrap();
+        assert_ne!(touched, fs, "Without hashes mtime should count");
+        touched.hash_files(&fs).unwrap();
+        assert_eq!(StateDiff::new(), touched.diff(&fs).unwrap());
+
+        // New content of the same size is
+        fs::write(&file, "ah").unwrap();
+        let mut updated = update_file_entries(&touched).unwrap();
+        updated.hash_files(&touched).unwrap();
+        assert_eq!(
+            StateDiff {
+                updated: HashSet::from([StateIdentifier::File(file)]),
+                ..Default::default()
+            },
+            updated.diff(&touched).unwrap()
+        );
+    }
+
     #[test]
     fn detect_dir_change() {
         let temp_dir = tempdir().unwrap();
@@ -425,6 +559,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hashed_and_unhashed_files_are_not_equal() {
+        let file_state = |hash: Option<&str>| FileState {
+            mtime: FileTime::from_unix_time(1, 0),
+            size: 2,
+            hash: hash.map(|h| blake3::hash(h.as_bytes())),
+        };
+        let unhashed = file_state(None);
+        let eh = file_state(Some("eh"));
+        let ah = file_state(Some("ah"));
+
+        // Were the mtime to decide when one side lacks a hash eh == unhashed == ah != eh
+        assert_ne!(eh, ah);
+        assert_ne!(eh, unhashed);
+        assert_ne!(unhashed, ah);
+        assert_eq!(eh, file_state(Some("eh")));
+        assert_eq!(unhashed, file_state(None));
+    }
+
+    #[test]
+    fn bad_hash_fails_to_deserialize() {
+        let mut fs = StateSet::new();
+        fs.track_memory("/glyph/glyph_name".to_string(), "Hi World!")
+            .unwrap();
+        let Some(State::Memory(state)) = fs.entries.values().next() else {
+            panic!("Should have one memory state");
+        };
+
+        let yml = serde_yaml::to_string(&fs).unwrap();
+        let yml = yml.replace(&state.hash.to_hex().to_string(), "not a hash");
+        assert!(serde_yaml::from_str::<StateSet>(&yml).is_err(), "{yml}");
+    }
+
     #[test]
     fn read_write_yaml() {
         let temp_dir = tempdir().unwrap();
@@ -445,6 +612,7 @@ mod tests {
         let (_, _, mut fs) = one_changed_file_one_not(&temp_dir);
         fs.track_memory("/glyph/glyph_name".to_string(), "Hi World!")
             .unwrap();
+        fs.hash_files(&StateSet::new()).unwrap();
 
         let bc = bincode::serialize(&fs).unwrap();
         let restored: StateSet = bincode::deserialize(&bc).unwrap();
@@ -1,13 +1,15 @@
 //! Helps manipulate variation data.
 use std::{
     cmp::Ordering,
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::{Debug, Display},
-    ops::{Mul, Sub},
+    ops::{Add, Mul, Sub},
+    sync::Arc,
 };
 
 use log::{log_enabled, trace};
 use ordered_float::OrderedFloat;
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -150,6 +152,23 @@ impl VariationModel {
         &self,
         point_seqs: &HashMap<NormalizedLocation, Vec<P>>,
     ) -> Result<Vec<(VariationRegion, Vec<V>)>, DeltaError>
+    where
+        P: Copy + Default + Sub<P, Output = V>,
+        V: Copy + Mul<f64, Output = V> + Sub<V, Output = V>,
+    {
+        self.rounded_deltas(point_seqs, |v| v)
+    }
+
+    /// As [VariationModel::deltas] but each delta is rounded as soon as it's computed.
+    ///
+    /// Deltas computed later are relative to the rounded deltas that influence them, so
+    /// rounding errors are compensated for rather than accumulated. Like the round argument
+    /// to fonttools getDeltas.
+    pub fn rounded_deltas<P, V>(
+        &self,
+        point_seqs: &HashMap<NormalizedLocation, Vec<P>>,
+        round: impl Fn(V) -> V,
+    ) -> Result<Vec<(VariationRegion, Vec<V>)>, DeltaError>
     where
         P: Copy + Default + Sub<P, Output = V>,
         V: Copy + Mul<f64, Output = V> + Sub<V, Output = V>,
@@ -194,7 +213,7 @@ impl VariationModel {
 
             for (idx, point) in points.iter().enumerate() {
                 let initial_vector: V = *point - Default::default();
-                deltas.push(
+                deltas.push(round(
                     // Find other masters that are active (have influence)
                     // Any master with influence on us was processed already so we can get that masters
                     // deltas from the results so far. If we subtract away all such influences what's
@@ -216,7 +235,7 @@ impl VariationModel {
                         .fold(initial_vector, |acc, (other, other_weight)| {
                             acc - *other * other_weight.into()
                         }),
-                );
+                ));
             }
             model_idx_to_result_idx.insert(model_idx, result.len());
             result.push((region.clone(), deltas));
@@ -224,6 +243,34 @@ impl VariationModel {
 
         Ok(result)
     }
+
+    /// Compute the position of every point at location from the positions at master locations.
+    ///
+    /// Point sequences must meet the same requirements as for [VariationModel::deltas]. Like
+    /// fonttools interpolateFromMasters we sum the deltas of every region, scaled by
+    /// how much influence the region has at location.
+    pub fn interpolate<P, V>(
+        &self,
+        point_seqs: &HashMap<NormalizedLocation, Vec<P>>,
+        location: &NormalizedLocation,
+    ) -> Result<Vec<P>, DeltaError>
+    where
+        P: Copy + Default + Sub<P, Output = V> + Add<V, Output = P>,
+        V: Copy + Mul<f64, Output = V> + Sub<V, Output = V>,
+    {
+        let deltas = self.deltas::<P, V>(point_seqs)?;
+        let mut points = vec![P::default(); deltas.first().map(|(_, d)| d.len()).unwrap_or(0)];
+        for (region, deltas) in deltas.iter() {
+            let scalar = region.scalar_at(location).into_inner() as f64;
+            if scalar == 0.0 {
+                continue;
+            }
+            for (point, delta) in points.iter_mut().zip(deltas) {
+                *point = *point + *delta * scalar;
+            }
+        }
+        Ok(points)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -236,6 +283,48 @@ pub enum DeltaError {
     UnknownLocation(NormalizedLocation),
 }
 
+/// Models for subsets of the locations of a model, built on first use and shared by clones.
+///
+/// Something defined at only some of the locations of a model, such as a glyph
+/// that lacks an intermediate master, interpolates incorrectly in the full model
+/// because the regions of the locations it does have are shaped by the ones it doesn't.
+#[derive(Default, Clone)]
+pub(crate) struct SubModels(
+    Arc<RwLock<HashMap<BTreeSet<NormalizedLocation>, Arc<VariationModel>>>>,
+);
+
+impl SubModels {
+    pub(crate) fn get_or_create(
+        &self,
+        locations: BTreeSet<NormalizedLocation>,
+        create: impl FnOnce(
+            &BTreeSet<NormalizedLocation>,
+        ) -> Result<VariationModel, VariationModelError>,
+    ) -> Result<Arc<VariationModel>, VariationModelError> {
+        if let Some(model) = self.0.read().get(&locations) {
+            return Ok(model.clone());
+        }
+        let model = Arc::new(create(&locations)?);
+        self.0.write().insert(locations, model.clone());
+        Ok(model)
+    }
+}
+
+/// A cache, not part of what makes one set of metadata differ from another
+impl PartialEq for SubModels {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SubModels {}
+
+impl Debug for SubModels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SubModels({})", self.0.read().len())
+    }
+}
+
 /// Gryffindor!
 ///
 /// Sorts locations, and thus the resulting regions, based on the intuition that from most
@@ -413,7 +502,7 @@ impl VariationRegion {
     ///
     /// In Python, supportScalar. We only implement the ot=True, extrapolate=False paths.
     /// <https://github.com/fonttools/fonttools/blob/2f1f5e5e7be331d960a0e30d537c2b4c70d89285/Lib/fontTools/varLib/models.py#L123>.
-    fn scalar_at(&self, location: &NormalizedLocation) -> OrderedFloat<f32> {
+    pub fn scalar_at(&self, location: &NormalizedLocation) -> OrderedFloat<f32> {
         let scalar = self.axis_tents.iter().filter(|(_, ar)| ar.validate()).fold(
             ONE,
             |scalar, (axis_name, tent)| {
@@ -1232,6 +1321,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rounded_deltas_compensate_for_rounding() {
+        let origin = norm_loc(&[("Weight", 0.0), ("Width", 0.0)]);
+        let max_wght = norm_loc(&[("Weight", 1.0), ("Width", 0.0)]);
+        let max_wdth = norm_loc(&[("Weight", 0.0), ("Width", 1.0)]);
+        let max_wght_wdth = norm_loc(&[("Weight", 1.0), ("Width", 1.0)]);
+        let locations = HashSet::from([
+            origin.clone(),
+            max_wght.clone(),
+            max_wdth.clone(),
+            max_wght_wdth.clone(),
+        ]);
+        let axes = vec![axis("Weight"), axis("Width")];
+        let model = VariationModel::new(locations, axes).unwrap();
+
+        let point_seqs = HashMap::from([
+            (origin, vec![0.0]),
+            (max_wght, vec![0.4]),
+            (max_wdth, vec![0.4]),
+            (max_wght_wdth, vec![1.0]),
+        ]);
+
+        // Rounding each delta on its own would make the corner delta round(1.0 - 0.4 - 0.4) = 0,
+        // leaving the corner 1.0 off. Relative to the rounded deltas it's round(1.0 - 0 - 0) = 1.
+        assert_eq!(
+            vec![
+                (
+                    region(&[("Weight", 0.0, 0.0, 0.0), ("Width", 0.0, 0.0, 0.0)]),
+                    vec![0.0]
+                ),
+                (
+                    region(&[("Weight", 0.0, 1.0, 1.0), ("Width", 0.0, 0.0, 0.0)]),
+                    vec![0.0]
+                ),
+                (
+                    region(&[("Weight", 0.0, 0.0, 0.0), ("Width", 0.0, 1.0, 1.0)]),
+                    vec![0.0]
+                ),
+                (
+                    region(&[("Weight", 0.0, 1.0, 1.0), ("Width", 0.0, 1.0, 1.0)]),
+                    vec![1.0]
+                ),
+            ],
+            model
+                .rounded_deltas(&point_seqs, |v: f64| v.round())
+                .unwrap()
+        );
+    }
+
     #[test]
     fn compute_1d_deltas() {
         let origin = norm_loc(&[("Weight", 0.0)]);
@@ -1256,4 +1394,33 @@ mod tests {
             model.deltas(&point_seqs).unwrap()
         );
     }
+
+    #[test]
+    fn interpolate_1d() {
+        let origin = norm_loc(&[("Weight", 0.0)]);
+        let max_wght = norm_loc(&[("Weight", 1.0)]);
+        let min_wght = norm_loc(&[("Weight", -1.0)]);
+        let locations = HashSet::from([origin.clone(), max_wght.clone(), min_wght.clone()]);
+        let axes = vec![axis("Weight")];
+        let model = VariationModel::new(locations, axes).unwrap();
+
+        let point_seqs = HashMap::from([
+            (origin, vec![10.0]),
+            (max_wght, vec![12.0]),
+            (min_wght, vec![5.0]),
+        ]);
+
+        assert_eq!(
+            vec![11.0],
+            model
+                .interpolate(&point_seqs, &norm_loc(&[("Weight", 0.5)]))
+                .unwrap()
+        );
+        assert_eq!(
+            vec![5.0],
+            model
+                .interpolate(&point_seqs, &norm_loc(&[("Weight", -1.0)]))
+                .unwrap()
+        );
+    }
 }
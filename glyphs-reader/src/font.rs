@@ -59,6 +59,19 @@ pub struct Font {
     pub instances: Vec<Instance>,
     pub version_major: i32,
     pub version_minor: u32,
+    /// Left to right kerning by master id, then (first, second) member of the pair.
+    ///
+    /// Members are glyph names or @MMK_L_/@MMK_R_ prefixed kerning group names.
+    pub kerning_ltr: BTreeMap<String, BTreeMap<(String, String), OrderedFloat<f64>>>,
+    /// OS/2 values from font custom parameters
+    pub fs_type: Option<u16>,
+    pub use_typo_metrics: bool,
+    pub has_wws_names: bool,
+    pub panose: Option<Vec<i64>>,
+    pub unicode_range_bits: Option<BTreeSet<u32>>,
+    pub codepage_range_bits: Option<BTreeSet<u32>>,
+    /// The feature bracket layer substitutions apply under, from the "Feature for Feature Variations" custom parameter
+    pub feature_variations_tag: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -74,13 +87,59 @@ impl FeatureSnippet {
 pub struct Glyph {
     pub glyphname: String,
     pub layers: Vec<Layer>,
+    /// The kerning group for the left side of the glyph, @MMK_R_ in kerning.
+    pub left_kern: Option<String>,
+    /// The kerning group for the right side of the glyph, @MMK_L_ in kerning.
+    pub right_kern: Option<String>,
+    pub category: Option<String>,
+    pub sub_category: Option<String>,
+    /// Alternate layers that apply in part of the designspace, see [Layer::axis_rules]
+    pub bracket_layers: Vec<Layer>,
+    /// Intermediate layers, masters for just this glyph, see [Layer::coordinates]
+    pub brace_layers: Vec<Layer>,
 }
 
 #[derive(Debug, PartialEq, Hash)]
 pub struct Layer {
     pub layer_id: String,
+    pub associated_master_id: Option<String>,
     pub width: OrderedFloat<f64>,
+    /// Advance height for vertical layout
+    pub vert_width: Option<OrderedFloat<f64>>,
+    /// How far below the ascender the vertical origin is
+    pub vert_origin: Option<OrderedFloat<f64>>,
     pub shapes: Vec<Shape>,
+    pub anchors: Vec<Anchor>,
+    /// For bracket layers, the (min, max) design location on each axis where the layer applies
+    ///
+    /// In axis order, a missing bound means the axis is unbounded on that side.
+    /// <https://glyphsapp.com/learn/alternating-glyph-shapes>
+    pub axis_rules: Vec<AxisRule>,
+    /// For brace layers, the design location of the layer in axis order
+    ///
+    /// May have fewer values than there are axes, the rest are at the associated master's location.
+    /// <https://glyphsapp.com/learn/additional-masters-for-individual-glyphs-the-brace-trick>
+    pub coordinates: Vec<OrderedFloat<f64>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AxisRule {
+    pub min: Option<OrderedFloat<f64>>,
+    pub max: Option<OrderedFloat<f64>>,
+}
+
+impl Layer {
+    pub fn is_brace_layer(&self) -> bool {
+        self.associated_master_id.is_some() && !self.coordinates.is_empty()
+    }
+
+    pub fn is_bracket_layer(&self) -> bool {
+        self.associated_master_id.is_some()
+            && self
+                .axis_rules
+                .iter()
+                .any(|r| r.min.is_some() || r.max.is_some())
+    }
 }
 
 #[derive(Debug, PartialEq, Hash)]
@@ -158,6 +217,10 @@ pub struct Axis {
 pub struct RawGlyph {
     pub layers: Vec<RawLayer>,
     pub glyphname: String,
+    pub kern_left: Option<String>,
+    pub kern_right: Option<String>,
+    pub category: Option<String>,
+    pub sub_category: Option<String>,
     #[fromplist(rest)]
     pub other_stuff: BTreeMap<String, Plist>,
 }
@@ -167,10 +230,12 @@ pub struct RawLayer {
     pub layer_id: String,
     pub associated_master_id: Option<String>,
     pub width: OrderedFloat<f64>,
+    pub vert_width: Option<OrderedFloat<f64>>,
+    pub vert_origin: Option<OrderedFloat<f64>>,
     shapes: Option<Vec<RawShape>>,
     paths: Option<Vec<Path>>,
     components: Option<Vec<Component>>,
-    //pub anchors: Option<Vec<Anchor>>,
+    anchors: Option<Vec<Anchor>>,
     #[fromplist(rest)]
     pub other_stuff: BTreeMap<String, Plist>,
 }
@@ -257,10 +322,25 @@ pub enum NodeType {
     CurveSmooth,
 }
 
-#[derive(Clone, Debug, FromPlist, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Anchor {
     pub name: String,
-    pub position: Point,
+    pub pos: Point,
+}
+
+impl PartialEq for Anchor {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Into::<PointForEqAndHash>::into(self.pos) == other.pos.into()
+    }
+}
+
+impl Eq for Anchor {}
+
+impl Hash for Anchor {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        PointForEqAndHash::new(self.pos).hash(state);
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Hash)]
@@ -268,6 +348,7 @@ pub struct FontMaster {
     pub id: String,
     pub axes_values: Vec<OrderedFloat<f64>>,
     metric_values: BTreeMap<String, RawMetricValue>,
+    custom_numbers: BTreeMap<String, OrderedFloat<f64>>,
 }
 
 impl FontMaster {
@@ -292,6 +373,11 @@ impl FontMaster {
     pub fn cap_height(&self) -> Option<OrderedFloat<f64>> {
         self.read_metric("cap height")
     }
+
+    /// A numeric custom parameter of the master, such as typoAscender
+    pub fn custom_number(&self, name: &str) -> Option<OrderedFloat<f64>> {
+        self.custom_numbers.get(name).copied()
+    }
 }
 
 #[derive(Debug, Clone, FromPlist, PartialEq, Eq, Hash)]
@@ -330,6 +416,8 @@ pub struct Instance {
     // So named to let FromPlist populate it from a field called "type"
     pub type_: InstanceType,
     pub axis_mappings: BTreeMap<String, RawAxisUserToDesignMap>,
+    pub axes_values: Vec<OrderedFloat<f64>>,
+    pub postscript_name: Option<String>,
 }
 
 /// <https://github.com/googlefonts/glyphsLib/blob/6f243c1f732ea1092717918d0328f3b5303ffe56/Lib/glyphsLib/classes.py#L150>
@@ -357,6 +445,7 @@ struct RawInstance {
     pub type_: Option<String>,
     #[fromplist(default)]
     pub axes_values: Vec<OrderedFloat<f64>>,
+    pub properties: Option<Vec<RawName>>,
 
     #[fromplist(rest)]
     pub other_stuff: BTreeMap<String, Plist>,
@@ -490,6 +579,30 @@ impl FromPlist for Component {
     }
 }
 
+impl FromPlist for Anchor {
+    fn from_plist(plist: Plist) -> Self {
+        let Plist::Dictionary(mut dict) = plist else {
+            panic!("Anchor must be a dict: {plist:?}");
+        };
+        let name = dict
+            .remove("name")
+            .map(Plist::into_string)
+            .expect("Anchor must have a name");
+
+        // Glyphs 2 uses position = "{x, y}", Glyphs 3 uses pos = (x, y)
+        // An absent position means the origin.
+        let pos = match (dict.remove("pos"), dict.remove("position")) {
+            (Some(Plist::Array(coords)), _) => Point::new(
+                coords.first().and_then(Plist::as_f64).unwrap_or_default(),
+                coords.get(1).and_then(Plist::as_f64).unwrap_or_default(),
+            ),
+            (_, Some(position)) => Point::from_plist(position),
+            _ => Point::ZERO,
+        };
+        Anchor { name, pos }
+    }
+}
+
 impl FromPlist for Affine {
     fn from_plist(plist: Plist) -> Self {
         let raw = plist.as_str().unwrap();
@@ -668,6 +781,112 @@ fn custom_param<'a>(
     None
 }
 
+fn custom_param_value<'a>(
+    other_stuff: &'a BTreeMap<String, Plist>,
+    key: &str,
+) -> Option<&'a Plist> {
+    custom_param(other_stuff, key).and_then(|(_, param)| param.get("value"))
+}
+
+/// A custom parameter whose value is a list of numbers, such as fsType
+fn custom_param_ints(other_stuff: &BTreeMap<String, Plist>, key: &str) -> Option<Vec<i64>> {
+    let values = custom_param_value(other_stuff, key).and_then(Plist::as_array)?;
+    Some(
+        values
+            .iter()
+            .filter_map(|value| {
+                value
+                    .as_i64()
+                    .or_else(|| value.as_str().and_then(|v| v.trim().parse().ok()))
+            })
+            .collect(),
+    )
+}
+
+fn custom_param_bool(other_stuff: &BTreeMap<String, Plist>, key: &str) -> bool {
+    custom_param_value(other_stuff, key)
+        .and_then(Plist::as_i64)
+        .map(|v| v != 0)
+        .unwrap_or_default()
+}
+
+/// Numeric custom parameters, e.g. typoAscender on a master
+fn custom_param_numbers(
+    other_stuff: &BTreeMap<String, Plist>,
+) -> BTreeMap<String, OrderedFloat<f64>> {
+    custom_params(other_stuff)
+        .into_iter()
+        .flatten()
+        .filter_map(|param| {
+            let name = param.get("name").and_then(Plist::as_str)?;
+            let value = param.get("value").and_then(Plist::as_f64)?;
+            Some((name.to_string(), value.into()))
+        })
+        .collect()
+}
+
+/// Code pages are listed by number, or as "bit N" for those without one.
+///
+/// <https://github.com/googlefonts/glyphsLib/blob/main/Lib/glyphsLib/builder/custom_params.py>
+fn codepage_range_bits(other_stuff: &BTreeMap<String, Plist>) -> Option<BTreeSet<u32>> {
+    let values = custom_param_value(other_stuff, "codePageRanges").and_then(Plist::as_array)?;
+    Some(
+        values
+            .iter()
+            .filter_map(|value| {
+                let codepage = match value {
+                    Plist::Integer(codepage) => *codepage,
+                    Plist::String(codepage) => {
+                        if let Some(bit) = codepage.strip_prefix("bit ") {
+                            return bit.trim().parse().ok();
+                        }
+                        codepage.parse().ok()?
+                    }
+                    _ => return None,
+                };
+                let bit = match codepage {
+                    1252 => 0,
+                    1250 => 1,
+                    1251 => 2,
+                    1253 => 3,
+                    1254 => 4,
+                    1255 => 5,
+                    1256 => 6,
+                    1257 => 7,
+                    1258 => 8,
+                    874 => 16,
+                    932 => 17,
+                    936 => 18,
+                    949 => 19,
+                    950 => 20,
+                    1361 => 21,
+                    869 => 48,
+                    866 => 49,
+                    865 => 50,
+                    864 => 51,
+                    863 => 52,
+                    862 => 53,
+                    861 => 54,
+                    860 => 55,
+                    857 => 56,
+                    855 => 57,
+                    852 => 58,
+                    775 => 59,
+                    737 => 60,
+                    708 => 61,
+                    850 => 62,
+                    437 => 63,
+                    _ => {
+                        warn!("Unknown code page {codepage}");
+                        return None;
+                    }
+                };
+                Some(bit)
+            })
+            .collect(),
+    )
+}
+
 fn v2_to_v3_name(properties: &mut Vec<RawName>, v2_prop: &Option<String>, v3_name: &str) {
     // https://github.com/schriftgestalt/GlyphsSDK/blob/Glyphs3/GlyphsFileFormat/GlyphsFileFormatv3.md#properties
     // Keys ending with "s" are localizable that means the second key is values
@@ -936,6 +1155,36 @@ impl RawFont {
             // "Axis position related properties (e.g. weightValue, widthValue, customValue) have been replaced by the axesValues list which is indexed in parallel with the toplevel axes list."
             instance.axes_values =
                 v2_to_v3_axis_values(self.axes.as_ref().unwrap(), &mut instance.other_stuff)?;
+
+            // v2 names the instance for postscript with a custom parameter, v3 with a property
+            if let Some((_, Plist::Dictionary(param))) =
+                custom_param(&instance.other_stuff, "postscriptFontName")
+            {
+                if let Some(Plist::String(value)) = param.get("value") {
+                    let properties = instance.properties.get_or_insert_with(Default::default);
+                    v2_to_v3_name(properties, &Some(value.clone()), "postscriptFontName");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn v2_to_v3_kerning(&mut self) -> Result<(), Error> {
+        // v2 kerning is always left to right
+        if let Some(kerning) = self.other_stuff.remove("kerning") {
+            self.other_stuff.insert("kerningLTR".to_string(), kerning);
+        }
+
+        for glyph in self.glyphs.iter_mut() {
+            for (v2_name, v3_field) in [
+                ("leftKerningGroup", &mut glyph.kern_left),
+                ("rightKerningGroup", &mut glyph.kern_right),
+            ] {
+                if let Some(Plist::String(group)) = glyph.other_stuff.remove(v2_name) {
+                    *v3_field = Some(group);
+                }
+            }
         }
 
         Ok(())
@@ -948,10 +1197,35 @@ impl RawFont {
         self.v2_to_v3_metrics()?;
         self.v2_to_v3_names()?;
         self.v2_to_v3_instances()?;
+        self.v2_to_v3_kerning()?;
         Ok(())
     }
 }
 
+fn parse_kerning(
+    kerning: Option<Plist>,
+) -> BTreeMap<String, BTreeMap<(String, String), OrderedFloat<f64>>> {
+    let Some(Plist::Dictionary(kerning)) = kerning else {
+        return Default::default();
+    };
+    kerning
+        .into_iter()
+        .map(|(master_id, pairs)| {
+            let mut kerns = BTreeMap::new();
+            for (side1, seconds) in pairs.into_btreemap() {
+                for (side2, value) in seconds.into_btreemap() {
+                    let Some(value) = value.as_f64() else {
+                        warn!("Kerning {side1}/{side2} in {master_id} is not a number");
+                        continue;
+                    };
+                    kerns.insert((side1.clone(), side2), OrderedFloat(value));
+                }
+            }
+            (master_id, kerns)
+        })
+        .collect()
+}
+
 fn parse_glyph_order(raw_font: &RawFont) -> Vec<String> {
     let mut valid_names: HashSet<_> = raw_font.glyphs.iter().map(|g| &g.glyphname).collect();
     let mut glyph_order = Vec::new();
@@ -1237,10 +1511,90 @@ fn map_and_push_if_present<T, U>(dest: &mut Vec<T>, src: Option<Vec<U>>, map: fn
     src.into_iter().map(map).for_each(|v| dest.push(v));
 }
 
+/// Glyphs 3 has attr.axisRules, Glyphs 2 names bracket layers like "Bold [600]".
+///
+/// In Glyphs 2 the rule is always on the first axis; "]600]" means below 600 instead of above.
+fn axis_rules(from: &RawLayer) -> Vec<AxisRule> {
+    if let Some(rules) = from
+        .other_stuff
+        .get("attr")
+        .and_then(|attr| attr.get("axisRules"))
+        .and_then(Plist::as_array)
+    {
+        let bound = |rule: &Plist, key| rule.get(key).and_then(Plist::as_f64).map(OrderedFloat);
+        return rules
+            .iter()
+            .map(|rule| AxisRule {
+                min: bound(rule, "min"),
+                max: bound(rule, "max"),
+            })
+            .collect();
+    }
+    let Some(name) = from.other_stuff.get("name").and_then(Plist::as_str) else {
+        return Vec::new();
+    };
+    let re = Regex::new(r"([\[\]])\s*(-?[0-9.]+)\s*\]").unwrap();
+    let Some(captures) = re.captures(name) else {
+        return Vec::new();
+    };
+    let Ok(value) = captures[2].parse::<f64>() else {
+        warn!("Unable to parse bracket layer name {name:?}");
+        return Vec::new();
+    };
+    let value = Some(OrderedFloat(value));
+    if &captures[1] == "]" {
+        vec![AxisRule {
+            min: None,
+            max: value,
+        }]
+    } else {
+        vec![AxisRule {
+            min: value,
+            max: None,
+        }]
+    }
+}
+
+/// Glyphs 3 has attr.coordinates, Glyphs 2 names brace layers like "Light {550}" or "{550, 100}".
+fn brace_coordinates(from: &RawLayer) -> Vec<OrderedFloat<f64>> {
+    if let Some(coordinates) = from
+        .other_stuff
+        .get("attr")
+        .and_then(|attr| attr.get("coordinates"))
+        .and_then(Plist::as_array)
+    {
+        return coordinates
+            .iter()
+            .filter_map(Plist::as_f64)
+            .map(OrderedFloat)
+            .collect();
+    }
+    let Some(name) = from.other_stuff.get("name").and_then(Plist::as_str) else {
+        return Vec::new();
+    };
+    let re = Regex::new(r"\{\s*([-0-9., ]+?)\s*\}").unwrap();
+    let Some(captures) = re.captures(name) else {
+        return Vec::new();
+    };
+    let coordinates: Result<Vec<_>, _> = captures[1]
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map(OrderedFloat))
+        .collect();
+    coordinates.unwrap_or_else(|_| {
+        warn!("Unable to parse brace layer name {name:?}");
+        Vec::new()
+    })
+}
+
 impl TryFrom<RawLayer> for Layer {
     type Error = Error;
 
     fn try_from(from: RawLayer) -> Result<Self, Self::Error> {
+        let (axis_rules, coordinates) = if from.associated_master_id.is_some() {
+            (axis_rules(&from), brace_coordinates(&from))
+        } else {
+            (Vec::new(), Vec::new())
+        };
         let mut shapes = Vec::new();
 
         // Glyphs v2 uses paths and components
@@ -1254,8 +1608,14 @@ impl TryFrom<RawLayer> for Layer {
 
         Ok(Layer {
             layer_id: from.layer_id,
+            associated_master_id: from.associated_master_id,
             width: from.width,
+            vert_width: from.vert_width,
+            vert_origin: from.vert_origin,
             shapes,
+            anchors: from.anchors.unwrap_or_default(),
+            axis_rules,
+            coordinates,
         })
     }
 }
@@ -1265,10 +1625,18 @@ impl TryFrom<RawGlyph> for Glyph {
 
     fn try_from(from: RawGlyph) -> Result<Self, Self::Error> {
         let mut instances = Vec::new();
+        let mut bracket_layers = Vec::new();
+        let mut brace_layers = Vec::new();
         for layer in from.layers {
             // The presence of an associated master indicates this is not a simple instance
             // It's either a draft or a more complex usage, such as an alternate
             if layer.associated_master_id.is_some() {
+                let layer: Layer = layer.try_into()?;
+                if layer.is_brace_layer() {
+                    brace_layers.push(layer);
+                } else if layer.is_bracket_layer() {
+                    bracket_layers.push(layer);
+                }
                 continue;
             }
             instances.push(layer.try_into()?);
@@ -1276,6 +1644,12 @@ impl TryFrom<RawGlyph> for Glyph {
         Ok(Glyph {
             glyphname: from.glyphname,
             layers: instances,
+            left_kern: from.kern_left,
+            right_kern: from.kern_right,
+            category: from.category,
+            sub_category: from.sub_category,
+            bracket_layers,
+            brace_layers,
         })
     }
 }
@@ -1431,6 +1805,13 @@ impl Instance {
                 .map(|v| v.as_str().into())
                 .unwrap_or(InstanceType::Single),
             axis_mappings,
+            axes_values: value.axes_values.clone(),
+            postscript_name: value
+                .properties
+                .iter()
+                .flatten()
+                .find(|p| p.key == "postscriptFontName")
+                .and_then(|p| p.value.clone()),
         }
     }
 }
@@ -1449,6 +1830,7 @@ impl TryFrom<RawFont> for Font {
 
         let glyph_order = parse_glyph_order(&from);
         let glyph_to_codepoints = parse_codepoints(&mut from, radix);
+        let kerning_ltr = parse_kerning(from.other_stuff.remove("kerningLTR"));
 
         let axes = from.axes.clone().unwrap_or_default();
         let instances: Vec<_> = if let Some(raw_instances) = &from.instances {
@@ -1510,10 +1892,28 @@ impl TryFrom<RawFont> for Font {
             .filter_map(|(idx, metric)| metric.type_.map(|name| (idx, name)))
             .collect();
 
+        let fs_type = custom_param_ints(&from.other_stuff, "fsType").map(|bits| {
+            bits.into_iter()
+                .filter(|bit| (0..16).contains(bit))
+                .fold(0u16, |acc, bit| acc | 1 << bit)
+        });
+        let use_typo_metrics = custom_param_bool(&from.other_stuff, "Use Typo Metrics");
+        let has_wws_names = custom_param_bool(&from.other_stuff, "Has WWS Names");
+        let panose = custom_param_ints(&from.other_stuff, "panose")
+            .or_else(|| custom_param_ints(&from.other_stuff, "Panose"));
+        let unicode_range_bits = custom_param_ints(&from.other_stuff, "unicodeRanges")
+            .map(|bits| bits.into_iter().map(|bit| bit as u32).collect());
+        let codepage_range_bits = codepage_range_bits(&from.other_stuff);
+        let feature_variations_tag =
+            custom_param_value(&from.other_stuff, "Feature for Feature Variations")
+                .and_then(Plist::as_str)
+                .map(str::to_string);
+
         let masters = from
             .font_master
             .into_iter()
             .map(|m| FontMaster {
+                custom_numbers: custom_param_numbers(&m.other_stuff),
                 id: m.id,
                 axes_values: m.axes_values,
                 metric_values: m
@@ -1542,6 +1942,14 @@ impl TryFrom<RawFont> for Font {
             instances,
             version_major: from.versionMajor.unwrap_or_default() as i32,
             version_minor: from.versionMinor.unwrap_or_default() as u32,
+            kerning_ltr,
+            fs_type,
+            use_typo_metrics,
+            has_wws_names,
+            panose,
+            unicode_range_bits,
+            codepage_range_bits,
+            feature_variations_tag,
         })
     }
 }
@@ -1613,7 +2021,7 @@ impl From<Affine> for AffineForEqAndHash {
 mod tests {
     use crate::{
         font::{RawAxisUserToDesignMap, RawFeature, RawUserToDesignMapping},
-        Font, FromPlist, Node, Plist, Shape,
+        Anchor, AxisRule, Font, FromPlist, Node, Plist, Shape,
     };
     use std::{
         collections::{BTreeMap, BTreeSet},
@@ -1675,6 +2083,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_glyphs3_anchor() {
+        assert_eq!(
+            Anchor {
+                name: "top".into(),
+                pos: super::Point { x: 354.0, y: 700.0 }
+            },
+            Anchor::from_plist(Plist::parse("{name = top; pos = (354,700);}").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_glyphs3_anchor_at_origin() {
+        assert_eq!(
+            Anchor {
+                name: "_bottom".into(),
+                pos: super::Point::ZERO
+            },
+            Anchor::from_plist(Plist::parse("{name = _bottom;}").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_glyphs2_anchor() {
+        assert_eq!(
+            Anchor {
+                name: "top".into(),
+                pos: super::Point { x: 354.0, y: 700.0 }
+            },
+            Anchor::from_plist(Plist::parse("{name = top; position = \"{354, 700}\";}").unwrap())
+        );
+    }
+
     // unquoted infinity likes to parse as a float which is suboptimal for glyph names. Survive.
     // Observed on Work Sans and Lexend.
     #[test]
@@ -1726,6 +2167,20 @@ mod tests {
         assert_load_v2_matches_load_v3("WghtVar_Avar.glyphs");
     }
 
+    #[test]
+    fn read_instance_postscript_name() {
+        for dir in [glyphs2_dir(), glyphs3_dir()] {
+            let font = Font::load(&dir.join("WghtVar_Avar.glyphs")).unwrap();
+            assert_eq!(
+                vec![("Medium", Some("WghtVar-Medium"))],
+                font.instances
+                    .iter()
+                    .map(|i| (i.name.as_str(), i.postscript_name.as_deref()))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
     fn only_shape_in_only_layer<'a>(font: &'a Font, glyph_name: &str) -> &'a Shape {
         let glyph = font.glyphs.get(glyph_name).unwrap();
         assert_eq!(1, glyph.layers.len());
@@ -2030,6 +2485,54 @@ mod tests {
         );
     }
 
+    fn assert_kerning(glyphs_file: &Path) {
+        let font = Font::load(glyphs_file).unwrap();
+        assert_eq!(
+            vec![
+                ("A", Some("A"), Some("A")),
+                ("V", Some("V"), Some("V")),
+                ("period", None, None),
+            ],
+            font.glyphs
+                .values()
+                .map(|g| (
+                    g.glyphname.as_str(),
+                    g.left_kern.as_deref(),
+                    g.right_kern.as_deref()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("m01", "@MMK_L_A", "@MMK_R_V", -50.0),
+                ("m01", "@MMK_L_V", "@MMK_R_A", -45.0),
+                ("m01", "@MMK_L_V", "period", -80.0),
+                ("m02", "@MMK_L_A", "@MMK_R_V", -70.5),
+            ],
+            font.kerning_ltr
+                .iter()
+                .flat_map(|(master_id, kerns)| kerns
+                    .iter()
+                    .map(|((side1, side2), value)| (
+                        master_id.as_str(),
+                        side1.as_str(),
+                        side2.as_str(),
+                        value.into_inner()
+                    )))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn kerning_glyphs2() {
+        assert_kerning(&glyphs2_dir().join("WghtVar_Kerning.glyphs"));
+    }
+
+    #[test]
+    fn kerning_glyphs3() {
+        assert_kerning(&glyphs3_dir().join("WghtVar_Kerning.glyphs"));
+    }
+
     #[test]
     fn favor_regular_as_origin_glyphs2() {
         assert_wghtvar_avar_master_and_axes(&glyphs2_dir().join("WghtVar_Avar.glyphs"));
@@ -2039,4 +2542,125 @@ mod tests {
     fn favor_regular_as_origin_glyphs3() {
         assert_wghtvar_avar_master_and_axes(&glyphs3_dir().join("WghtVar_Avar.glyphs"));
     }
+
+    #[test]
+    fn read_os2_custom_parameters() {
+        let font = Font::load(&glyphs3_dir().join("WghtVar_OS2.glyphs")).unwrap();
+        assert_eq!(
+            (
+                Some(0),
+                true,
+                false,
+                Some(vec![2, 0, 5, 3, 0, 0, 0, 0, 0, 0]),
+                Some(BTreeSet::from([0, 1])),
+                Some(BTreeSet::from([0, 29])),
+            ),
+            (
+                font.fs_type,
+                font.use_typo_metrics,
+                font.has_wws_names,
+                font.panose,
+                font.unicode_range_bits,
+                font.codepage_range_bits,
+            )
+        );
+    }
+
+    #[test]
+    fn read_master_custom_numbers() {
+        let font = Font::load(&glyphs3_dir().join("WghtVar_OS2.glyphs")).unwrap();
+        let master = font.default_master();
+        assert_eq!(
+            (Some(950.0), Some(300.0), None),
+            (
+                master.custom_number("typoAscender").map(|v| v.into_inner()),
+                master.custom_number("winDescent").map(|v| v.into_inner()),
+                master
+                    .custom_number("Axis Location")
+                    .map(|v| v.into_inner()),
+            )
+        );
+    }
+
+    fn bracket_rules(glyphs_file: &Path) -> (Option<String>, Vec<(String, Vec<AxisRule>)>) {
+        let font = Font::load(glyphs_file).unwrap();
+        (
+            font.feature_variations_tag,
+            font.glyphs["exclam"]
+                .bracket_layers
+                .iter()
+                .map(|l| {
+                    (
+                        l.associated_master_id.clone().unwrap(),
+                        l.axis_rules.clone(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn read_bracket_layers_glyphs3() {
+        let min_600 = vec![AxisRule {
+            min: Some(OrderedFloat(600.0)),
+            max: None,
+        }];
+        assert_eq!(
+            (
+                None,
+                vec![
+                    ("m01".to_string(), min_600.clone()),
+                    ("E09E0C54-128D-4FEA-B209-1B70BEFE300B".to_string(), min_600),
+                ]
+            ),
+            bracket_rules(&glyphs3_dir().join("WghtVar_Bracket.glyphs"))
+        );
+    }
+
+    #[test]
+    fn read_bracket_layers_glyphs2() {
+        assert_eq!(
+            (
+                Some("rclt".to_string()),
+                vec![(
+                    "E09E0C54-128D-4FEA-B209-1B70BEFE300B".to_string(),
+                    vec![AxisRule {
+                        min: None,
+                        max: Some(OrderedFloat(600.0)),
+                    }]
+                )]
+            ),
+            bracket_rules(&glyphs2_dir().join("WghtVar_Bracket.glyphs"))
+        );
+    }
+
+    fn brace_coordinates(glyphs_file: &Path) -> Vec<(String, Vec<f64>)> {
+        let font = Font::load(glyphs_file).unwrap();
+        font.glyphs["hyphen"]
+            .brace_layers
+            .iter()
+            .map(|l| {
+                (
+                    l.associated_master_id.clone().unwrap(),
+                    l.coordinates.iter().map(|c| c.into_inner()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn read_brace_layers_glyphs3() {
+        assert_eq!(
+            vec![("m01".to_string(), vec![550.0])],
+            brace_coordinates(&glyphs3_dir().join("WghtVar_Brace.glyphs"))
+        );
+    }
+
+    #[test]
+    fn read_brace_layers_glyphs2() {
+        assert_eq!(
+            vec![("m01".to_string(), vec![550.0])],
+            brace_coordinates(&glyphs2_dir().join("WghtVar_Brace.glyphs"))
+        );
+    }
 }
@@ -1,23 +1,28 @@
 use font_types::{NameId, Tag};
 use fontdrasil::orchestration::Work;
 use fontdrasil::types::GlyphName;
-use fontir::coords::NormalizedCoord;
+use fontir::coords::{DesignCoord, NormalizedCoord, UserLocation};
 use fontir::error::{Error, WorkError};
+use fontir::interpolatable::{check_interpolation, match_contours};
 use fontir::ir::{
-    self, GlobalMetric, GlobalMetrics, GlyphInstance, NameBuilder, NameKey, StaticMetadata,
+    self, AxisLabel, GdefCategory, GlobalMetric, GlobalMetrics, GlyphAlternate, GlyphInstance,
+    KernParticipant, Kerning, LocationLabel, NameBuilder, NameKey, NamedInstance, Os2Metadata,
+    StatLabels, StaticMetadata,
 };
-use fontir::orchestration::{Context, IrWork};
+use fontir::orchestration::{Context, Flags, IrWork};
 use fontir::source::{Input, Source};
 use fontir::stateset::StateSet;
-use glyphs_reader::Font;
+use glyphs_reader::{AxisRule, Font, Instance, InstanceType, Layer};
 use indexmap::IndexSet;
 use log::{debug, trace, warn};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::{collections::HashMap, path::PathBuf};
 
-use crate::toir::{to_ir_contours_and_components, to_ir_features, FontInfo};
+use crate::toir::{
+    to_ir_anchors, to_ir_condition_set, to_ir_contours_and_components, to_ir_features, FontInfo,
+};
 
 pub struct GlyphsIrSource {
     glyphs_file: PathBuf,
@@ -35,6 +40,174 @@ impl Cache {
     }
 }
 
+/// Glyph categories as glyphsLib would write public.openTypeCategories.
+///
+/// Glyphs that would only be classified as base by virtue of their anchors are
+/// left out, they get the same treatment at GDEF construction.
+fn gdef_categories(font: &Font) -> BTreeMap<GlyphName, GdefCategory> {
+    font.glyphs
+        .values()
+        .filter_map(|glyph| {
+            let category = match (glyph.category.as_deref(), glyph.sub_category.as_deref()) {
+                (Some("Mark"), Some("Nonspacing" | "Spacing Combining")) => GdefCategory::Mark,
+                (_, Some("Ligature")) => GdefCategory::Ligature,
+                _ => return None,
+            };
+            Some((glyph.glyphname.as_str().into(), category))
+        })
+        .collect()
+}
+
+/// The user location of an instance, None if it doesn't have a value for every axis.
+fn instance_location(instance: &Instance, axes: &[ir::Axis]) -> Option<UserLocation> {
+    if instance.axes_values.len() != axes.len() {
+        return None;
+    }
+    Some(
+        axes.iter()
+            .zip(&instance.axes_values)
+            .map(|(axis, value)| {
+                let value = DesignCoord::new(value.into_inner() as f32);
+                (axis.name.clone(), value.to_user(&axis.converter))
+            })
+            .collect(),
+    )
+}
+
+/// Named instances for fvar from the active static instances.
+fn named_instances(font: &Font, axes: &[ir::Axis]) -> Vec<NamedInstance> {
+    font.instances
+        .iter()
+        .filter(|i| i.active && i.type_ == InstanceType::Single)
+        .filter_map(|instance| {
+            let Some(location) = instance_location(instance, axes) else {
+                warn!(
+                    "Instance {} has {} axis values for {} axes, not naming it",
+                    instance.name,
+                    instance.axes_values.len(),
+                    axes.len()
+                );
+                return None;
+            };
+            Some(NamedInstance {
+                name: instance.name.clone(),
+                postscript_name: instance.postscript_name.clone(),
+                location,
+            })
+        })
+        .collect()
+}
+
+/// STAT labels from the active static instances.
+///
+/// An instance off the default on a single axis labels that axis, one at the
+/// default labels every axis and is elidable if it's Regular. Instances off the
+/// default on several axes become location labels.
+fn stat_labels(font: &Font, axes: &[ir::Axis]) -> StatLabels {
+    let mut stat_labels = StatLabels::default();
+    let instances = font
+        .instances
+        .iter()
+        .filter(|i| i.active && i.type_ == InstanceType::Single);
+    for instance in instances {
+        let Some(location) = instance_location(instance, axes) else {
+            warn!(
+                "Instance {} has {} axis values for {} axes, not labelling it",
+                instance.name,
+                instance.axes_values.len(),
+                axes.len()
+            );
+            continue;
+        };
+        let off_default: Vec<_> = axes
+            .iter()
+            .filter(|axis| location.get(&axis.name) != Some(axis.default))
+            .collect();
+        let labelled_axes = match off_default.as_slice() {
+            [] => axes.iter().collect(),
+            [_] => off_default,
+            _ => {
+                stat_labels.location_labels.push(LocationLabel {
+                    name: instance.name.clone(),
+                    location,
+                    elidable: false,
+                    older_sibling: false,
+                });
+                continue;
+            }
+        };
+        for axis in labelled_axes {
+            let value = location.get(&axis.name).unwrap();
+            let labels = stat_labels
+                .axis_labels
+                .entry(axis.name.clone())
+                .or_default();
+            if labels.iter().any(|l| l.value == value) {
+                continue;
+            }
+            labels.push(AxisLabel {
+                name: instance.name.clone(),
+                value,
+                min: None,
+                max: None,
+                linked_value: None,
+                elidable: instance.name == "Regular" && value == axis.default,
+                older_sibling: false,
+            });
+        }
+    }
+    stat_labels
+}
+
+/// OS/2 values from font custom parameters.
+fn os2_metadata(font: &Font) -> Os2Metadata {
+    let mut selection_flags = 0;
+    if font.use_typo_metrics {
+        selection_flags |= 1 << 7;
+    }
+    if font.has_wws_names {
+        selection_flags |= 1 << 8;
+    }
+    let panose = font.panose.as_ref().and_then(|panose| {
+        let panose: Option<Vec<u8>> = panose.iter().map(|v| u8::try_from(*v).ok()).collect();
+        let panose = panose.and_then(|panose| panose.try_into().ok());
+        if panose.is_none() {
+            warn!("Ignoring panose {:?}, it must be 10 bytes", font.panose);
+        }
+        panose
+    });
+    Os2Metadata {
+        fs_type: font.fs_type,
+        selection_flags,
+        panose,
+        unicode_range_bits: font.unicode_range_bits.clone(),
+        codepage_range_bits: font.codepage_range_bits.clone(),
+        ..Default::default()
+    }
+}
+
+/// Master custom parameters that set global metrics
+const METRIC_PARAMETERS: [(&str, GlobalMetric); 18] = [
+    ("typoAscender", GlobalMetric::Os2TypoAscender),
+    ("typoDescender", GlobalMetric::Os2TypoDescender),
+    ("typoLineGap", GlobalMetric::Os2TypoLineGap),
+    ("winAscent", GlobalMetric::Os2WinAscent),
+    ("winDescent", GlobalMetric::Os2WinDescent),
+    ("subscriptXSize", GlobalMetric::SubscriptXSize),
+    ("subscriptYSize", GlobalMetric::SubscriptYSize),
+    ("subscriptXOffset", GlobalMetric::SubscriptXOffset),
+    ("subscriptYOffset", GlobalMetric::SubscriptYOffset),
+    ("superscriptXSize", GlobalMetric::SuperscriptXSize),
+    ("superscriptYSize", GlobalMetric::SuperscriptYSize),
+    ("superscriptXOffset", GlobalMetric::SuperscriptXOffset),
+    ("superscriptYOffset", GlobalMetric::SuperscriptYOffset),
+    ("strikeoutSize", GlobalMetric::StrikeoutSize),
+    ("strikeoutPosition", GlobalMetric::StrikeoutPosition),
+    ("vheaVertAscender", GlobalMetric::VheaAscender),
+    ("vheaVertDescender", GlobalMetric::VheaDescender),
+    ("vheaVertLineGap", GlobalMetric::VheaLineGap),
+];
+
 fn glyph_identifier(glyph_name: &str) -> String {
     format!("/glyph/{glyph_name}")
 }
@@ -64,51 +237,111 @@ impl GlyphsIrSource {
         Ok(state)
     }
 
+    fn kerning_inputs(&self, font: &Font) -> Result<StateSet, Error> {
+        let mut state = StateSet::new();
+        state.track_memory("/kerning".to_string(), &font.kerning_ltr)?;
+        let groups: Vec<_> = font
+            .glyphs
+            .values()
+            .map(|g| (&g.glyphname, &g.left_kern, &g.right_kern))
+            .collect();
+        state.track_memory("/kerning_groups".to_string(), &groups)?;
+        Ok(state)
+    }
+
     // When things like upem may have changed forget incremental and rebuild the whole thing
     fn static_metadata_inputs(&self, font: &Font) -> Result<StateSet, Error> {
         let mut state = StateSet::new();
-        // Wipe out glyph-related fields, track the rest
-        // Explicitly field by field so if we add more compiler will force us to update here
-        let font = Font {
-            units_per_em: font.units_per_em,
-            axes: font.axes.clone(),
-            masters: font.masters.clone(),
-            default_master_idx: font.default_master_idx,
-            glyphs: Default::default(),
-            glyph_order: Default::default(),
-            glyph_to_codepoints: Default::default(),
-            axis_mappings: font.axis_mappings.clone(),
-            features: Default::default(),
-            names: Default::default(),
-            instances: font.instances.clone(),
-            version_major: Default::default(),
-            version_minor: Default::default(),
-        };
-        state.track_memory("/font_master".to_string(), &font)?;
+        // Track each part separately, explicitly field by field so if we add
+        // more compiler will force us to decide where they belong
+        let Font {
+            units_per_em,
+            axes,
+            masters,
+            default_master_idx,
+            glyphs,
+            glyph_order,
+            glyph_to_codepoints: _,
+            axis_mappings,
+            features: _,
+            names,
+            instances,
+            version_major,
+            version_minor,
+            kerning_ltr: _,
+            fs_type,
+            use_typo_metrics,
+            has_wws_names,
+            panose,
+            unicode_range_bits,
+            codepage_range_bits,
+            feature_variations_tag,
+        } = font;
+        state.track_memory(
+            "/axes".to_string(),
+            &(units_per_em, axes, axis_mappings, default_master_idx),
+        )?;
+        // Only where the masters are, their metrics are global metrics
+        let master_locations: Vec<_> = masters.iter().map(|m| (&m.id, &m.axes_values)).collect();
+        state.track_memory("/masters".to_string(), &master_locations)?;
+        // The vendor id is kept with names in Glyphs but only OS/2 uses it
+        let vendor_id = names.get("vendorID");
+        let names: BTreeMap<_, _> = names.iter().filter(|(key, _)| *key != "vendorID").collect();
+        state.track_memory("/names".to_string(), &(names, version_major, version_minor))?;
+        state.track_memory("/instances".to_string(), instances)?;
+        state.track_memory("/glyph_order".to_string(), glyph_order)?;
+        state.track_memory(
+            "/custom_parameters".to_string(),
+            &(
+                fs_type,
+                use_typo_metrics,
+                has_wws_names,
+                panose,
+                unicode_range_bits,
+                codepage_range_bits,
+                vendor_id,
+            ),
+        )?;
+        // Categories live on glyphs but inform static metadata
+        state.track_memory("/gdef_categories".to_string(), &gdef_categories(font))?;
+        // Brace layers live on glyphs but add to the locations of the variation model
+        let brace_layers: Vec<_> = glyphs
+            .values()
+            .flat_map(|g| g.brace_layers.iter())
+            .map(|l| (&l.associated_master_id, &l.coordinates))
+            .collect();
+        state.track_memory("/brace_layers".to_string(), &brace_layers)?;
+        // Bracket layers live on glyphs but add alternate glyphs, to the glyph order and feature variations
+        // in the feature the tag names
+        let bracket_layers: Vec<_> = glyphs
+            .values()
+            .flat_map(|g| {
+                g.bracket_layers
+                    .iter()
+                    .map(move |l| (&g.glyphname, &l.layer_id, &l.axis_rules))
+            })
+            .collect();
+        state.track_memory(
+            "/bracket_layers".to_string(),
+            &(bracket_layers, feature_variations_tag),
+        )?;
         Ok(state)
     }
 
     // Things that could change global metrics.
     fn global_metric_inputs(&self, font: &Font) -> Result<StateSet, Error> {
         let mut state = StateSet::new();
-        // Wipe out fields that can't impact global metrics.
-        // Explicitly field by field so if we add more compiler will force us to update here
-        let font = Font {
-            units_per_em: font.units_per_em,
-            axes: font.axes.clone(),
-            masters: font.masters.clone(),
-            default_master_idx: font.default_master_idx,
-            glyphs: Default::default(),
-            glyph_order: Default::default(),
-            glyph_to_codepoints: Default::default(),
-            axis_mappings: Default::default(),
-            features: Default::default(),
-            names: Default::default(),
-            instances: font.instances.clone(),
-            version_major: Default::default(),
-            version_minor: Default::default(),
-        };
-        state.track_memory("/font_master".to_string(), &font)?;
+        // Metrics are per master, placed by the axes
+        state.track_memory(
+            "/axes".to_string(),
+            &(
+                font.units_per_em,
+                &font.axes,
+                &font.axis_mappings,
+                font.default_master_idx,
+            ),
+        )?;
+        state.track_memory("/metrics".to_string(), &font.masters)?;
         Ok(state)
     }
 
@@ -151,6 +384,7 @@ impl Source for GlyphsIrSource {
         let static_metadata = self.static_metadata_inputs(font)?;
         let global_metrics = self.global_metric_inputs(font)?;
         let features = self.feature_inputs(font)?;
+        let kerning = self.kerning_inputs(font)?;
         let glyphs = glyph_states(font)?;
 
         self.cache = Some(Cache {
@@ -163,6 +397,7 @@ impl Source for GlyphsIrSource {
             global_metrics,
             glyphs,
             features,
+            kerning,
         })
     }
 
@@ -211,6 +446,16 @@ impl Source for GlyphsIrSource {
             font_info: cache.font_info.clone(),
         }))
     }
+
+    fn create_kerning_ir_work(&self, input: &Input) -> Result<Box<IrWork>, Error> {
+        self.check_static_metadata(&input.static_metadata)?;
+
+        let cache = self.cache.as_ref().unwrap();
+
+        Ok(Box::new(KerningWork {
+            font_info: cache.font_info.clone(),
+        }))
+    }
 }
 
 fn try_name_id(name: &str) -> Option<NameId> {
@@ -268,7 +513,15 @@ impl Work<Context, WorkError> for StaticMetadataWork {
                 .unwrap_or("<nameless family>")
         );
         let axes = font_info.axes.clone();
-        let glyph_locations = font_info.master_locations.values().cloned().collect();
+        let mut glyph_locations: HashSet<_> =
+            font_info.master_locations.values().cloned().collect();
+        // Brace layers are intermediate masters for the glyphs that have them
+        glyph_locations.extend(
+            font.glyphs
+                .values()
+                .flat_map(|g| g.brace_layers.iter())
+                .filter_map(|l| font_info.brace_location(l)),
+        );
         let glyph_order = font
             .glyph_order
             .iter()
@@ -287,6 +540,14 @@ impl Work<Context, WorkError> for StaticMetadataWork {
         if let Some(vendor_id) = font.names.get("vendorID") {
             static_metadata.vendor_id = Tag::from_str(vendor_id).map_err(WorkError::InvalidTag)?;
         }
+        static_metadata.gdef_categories = gdef_categories(font);
+        static_metadata.set_stat_labels(stat_labels(font, &font_info.axes));
+        static_metadata.set_named_instances(named_instances(font, &font_info.axes));
+        static_metadata.os2 = os2_metadata(font);
+        if let Some(tag) = &font.feature_variations_tag {
+            static_metadata.feature_variations.feature_tag =
+                Tag::from_str(tag).map_err(WorkError::InvalidTag)?;
+        }
 
         context.set_init_static_metadata(static_metadata);
         Ok(())
@@ -321,6 +582,13 @@ impl Work<Context, WorkError> for GlobalMetricWork {
             metrics.set_if_some(GlobalMetric::Descender, pos.clone(), master.descender());
             metrics.set_if_some(GlobalMetric::CapHeight, pos.clone(), master.cap_height());
             metrics.set_if_some(GlobalMetric::XHeight, pos.clone(), master.x_height());
+            for (name, metric) in METRIC_PARAMETERS {
+                metrics.set_if_some(metric, pos.clone(), master.custom_number(name));
+            }
+        }
+        for master in font.masters.iter() {
+            let pos = font_info.master_locations.get(&master.id).unwrap();
+            metrics.populate_defaults(pos, static_metadata.units_per_em);
         }
 
         context.set_global_metrics(metrics);
@@ -343,6 +611,74 @@ impl Work<Context, WorkError> for FeatureWork {
     }
 }
 
+struct KerningWork {
+    font_info: Arc<FontInfo>,
+}
+
+/// Glyphs writes groups as @MMK_L_<right kern group> and @MMK_R_<left kern group>.
+///
+/// We name them the way a UFO would, public.kern1.* and public.kern2.*.
+fn kern_participant(name: &str) -> KernParticipant {
+    if let Some(group) = name.strip_prefix("@MMK_L_") {
+        KernParticipant::Group(format!("public.kern1.{group}"))
+    } else if let Some(group) = name.strip_prefix("@MMK_R_") {
+        KernParticipant::Group(format!("public.kern2.{group}"))
+    } else {
+        KernParticipant::Glyph(name.into())
+    }
+}
+
+impl Work<Context, WorkError> for KerningWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        trace!("Generate IR for kerning");
+        let font_info = self.font_info.as_ref();
+        let font = &font_info.font;
+
+        let mut kerning = Kerning::default();
+
+        // The right side of a glyph kerns against whatever follows it, hence kern1
+        for glyph in font.glyphs.values() {
+            for (prefix, group) in [
+                ("public.kern1.", &glyph.right_kern),
+                ("public.kern2.", &glyph.left_kern),
+            ] {
+                let Some(group) = group else {
+                    continue;
+                };
+                kerning
+                    .groups
+                    .entry(format!("{prefix}{group}"))
+                    .or_default()
+                    .insert(glyph.glyphname.as_str().into());
+            }
+        }
+
+        for (master_id, kerns) in font.kerning_ltr.iter() {
+            let Some(pos) = font_info.master_locations.get(master_id) else {
+                warn!("Kerning for unknown master {master_id}");
+                continue;
+            };
+            for ((side1, side2), value) in kerns.iter() {
+                kerning
+                    .kerns
+                    .entry((kern_participant(side1), kern_participant(side2)))
+                    .or_default()
+                    .insert(pos.clone(), (value.into_inner() as f32).into());
+            }
+        }
+
+        // A pair a master doesn't mention is 0 at that master
+        for values in kerning.kerns.values_mut() {
+            for pos in font_info.master_locations.values() {
+                values.entry(pos.clone()).or_insert(0.0.into());
+            }
+        }
+
+        context.set_kerning(kerning);
+        Ok(())
+    }
+}
+
 struct GlyphIrWork {
     glyph_name: GlyphName,
     font_info: Arc<FontInfo>,
@@ -364,6 +700,41 @@ fn check_pos(
     Ok(())
 }
 
+/// The ascender of the master a layer belongs to
+fn master_ascender(font_info: &FontInfo, layer: &Layer) -> f64 {
+    let master_id = layer
+        .associated_master_id
+        .as_deref()
+        .unwrap_or(&layer.layer_id);
+    font_info
+        .master_indices
+        .get(master_id)
+        .and_then(|idx| font_info.font.masters[*idx].ascender())
+        .map(|ascender| ascender.into_inner())
+        // The same fallback global metrics use
+        .unwrap_or(0.8 * font_info.font.units_per_em as f64)
+}
+
+fn to_glyph_instance(
+    glyph_name: &GlyphName,
+    font_info: &FontInfo,
+    layer: &Layer,
+) -> Result<GlyphInstance, WorkError> {
+    let (contours, components) = to_ir_contours_and_components(glyph_name.clone(), &layer.shapes)?;
+    // As in glyphsLib, vertOrigin is how far below the ascender the vertical origin is
+    let vertical_origin = layer
+        .vert_origin
+        .map(|offset| master_ascender(font_info, layer) - offset.into_inner());
+    Ok(GlyphInstance {
+        width: layer.width.into_inner(),
+        height: layer.vert_width.map(|height| height.into_inner()),
+        vertical_origin,
+        contours,
+        components,
+        anchors: to_ir_anchors(&layer.anchors),
+    })
+}
+
 impl Work<Context, WorkError> for GlyphIrWork {
     fn exec(&self, context: &Context) -> Result<(), WorkError> {
         trace!("Generate IR for '{}'", self.glyph_name.as_str());
@@ -405,18 +776,11 @@ impl Work<Context, WorkError> for GlyphIrWork {
                     .insert(*coord);
             }
 
-            // TODO populate width and height properly
-            let (contours, components) =
-                to_ir_contours_and_components(self.glyph_name.clone(), &instance.shapes)?;
-            let glyph_instance = GlyphInstance {
-                width: instance.width.into_inner(),
-                height: None,
-                contours,
-                components,
-            };
-
             ir_glyph
-                .try_add_source(location, glyph_instance)
+                .try_add_source(
+                    location,
+                    to_glyph_instance(&self.glyph_name, font_info, instance)?,
+                )
                 .map_err(|e| {
                     WorkError::AddGlyphSource(format!(
                         "Unable to add source to {:?} at {:?}: {}",
@@ -425,6 +789,83 @@ impl Work<Context, WorkError> for GlyphIrWork {
                 })?;
         }
 
+        // Brace layers are additional masters for just this glyph
+        for layer in glyph.brace_layers.iter() {
+            let Some(location) = font_info.brace_location(layer) else {
+                warn!(
+                    "{} brace layer {} has no master, ignoring it",
+                    self.glyph_name, layer.layer_id
+                );
+                continue;
+            };
+            if ir_glyph.sources.contains_key(&location) {
+                warn!(
+                    "{} brace layer {} is at {location:?}, which already has a source, ignoring it",
+                    self.glyph_name, layer.layer_id
+                );
+                continue;
+            }
+            ir_glyph.try_add_source(
+                &location,
+                to_glyph_instance(&self.glyph_name, font_info, layer)?,
+            )?;
+        }
+
+        // Bracket layers with the same rules form an alternate glyph
+        // Masters without a bracket layer of their own contribute their master layer, as in glyphsLib
+        let mut bracket_layers: Vec<(&[AxisRule], HashMap<&str, &Layer>)> = Vec::new();
+        for layer in glyph.bracket_layers.iter() {
+            let master_id = match layer.associated_master_id.as_deref() {
+                Some(master_id) if font_info.master_indices.contains_key(master_id) => master_id,
+                master_id => {
+                    return Err(WorkError::InvalidSourceGlyph {
+                        glyph_name: self.glyph_name.clone(),
+                        message: format!(
+                            "bracket layer {} is associated with unknown master {master_id:?}",
+                            layer.layer_id
+                        ),
+                    })
+                }
+            };
+            match bracket_layers
+                .iter_mut()
+                .find(|(rules, _)| *rules == layer.axis_rules.as_slice())
+            {
+                Some((_, layers)) => {
+                    layers.insert(master_id, layer);
+                }
+                None => {
+                    bracket_layers.push((&layer.axis_rules, HashMap::from([(master_id, layer)])))
+                }
+            }
+        }
+        for (axis_rules, layers) in bracket_layers {
+            let mut sources = HashMap::new();
+            for master_layer in glyph.layers.iter() {
+                let layer = layers
+                    .get(master_layer.layer_id.as_str())
+                    .copied()
+                    .unwrap_or(master_layer);
+                let Some(location) = font_info
+                    .master_locations
+                    .get(master_layer.layer_id.as_str())
+                else {
+                    return Err(WorkError::NoMasterForGlyph {
+                        master: master_layer.layer_id.clone(),
+                        glyph: self.glyph_name.clone(),
+                    });
+                };
+                sources.insert(
+                    location.clone(),
+                    to_glyph_instance(&self.glyph_name, font_info, layer)?,
+                );
+            }
+            ir_glyph.alternates.push(GlyphAlternate {
+                condition_set: to_ir_condition_set(axes, axis_rules),
+                sources,
+            });
+        }
+
         // It's helpful if glyphs are defined at min, default, and max (some of which may be cooincident)
         for axis in axes.iter() {
             let min = axis.min.to_normalized(&axis.converter);
@@ -438,7 +879,14 @@ impl Work<Context, WorkError> for GlyphIrWork {
             check_pos(&self.glyph_name, positions, axis, &max)?;
         }
 
-        context.set_glyph_ir(ir_glyph.try_into()?);
+        let mut ir_glyph: ir::Glyph = ir_glyph.try_into()?;
+        if context.flags.contains(Flags::MATCH_CONTOURS) {
+            match_contours(&mut ir_glyph);
+        }
+        if context.flags.contains(Flags::CHECK_INTERPOLATION) {
+            check_interpolation(&ir_glyph)?;
+        }
+        context.set_glyph_ir(ir_glyph);
         Ok(())
     }
 }
@@ -447,6 +895,7 @@ impl Work<Context, WorkError> for GlyphIrWork {
 mod tests {
     use std::{
         collections::{HashMap, HashSet},
+        fs,
         path::{Path, PathBuf},
         str::FromStr,
     };
@@ -460,7 +909,7 @@ mod tests {
             UserLocation,
         },
         error::WorkError,
-        ir::{self, GlobalMetricsInstance, NameKey},
+        ir::{self, GdefCategory, GlobalMetricsInstance, KernParticipant, NameKey, Os2Metadata},
         orchestration::{Context, Flags, WorkId},
         paths::Paths,
         source::Source,
@@ -528,6 +977,37 @@ mod tests {
         assert_eq!(HashSet::<GlyphName>::from(["hyphen".into()]), changed);
     }
 
+    #[test]
+    fn edits_only_change_the_state_they_affect() {
+        let glyphs_file = glyphs3_dir().join("WghtVar_Kerning.glyphs");
+        let source = GlyphsIrSource::new(glyphs_file.clone());
+        let mut font = Font::load(&glyphs_file).unwrap();
+        let static_metadata = source.static_metadata_inputs(&font).unwrap();
+        let global_metrics = source.global_metric_inputs(&font).unwrap();
+
+        // A kerning tweak is only a change to kerning
+        let kerning = source.kerning_inputs(&font).unwrap();
+        font.kerning_ltr
+            .values_mut()
+            .flat_map(|kerns| kerns.values_mut())
+            .for_each(|value| value.0 += 1.0);
+        assert_ne!(kerning, source.kerning_inputs(&font).unwrap());
+        assert_eq!(
+            static_metadata,
+            source.static_metadata_inputs(&font).unwrap()
+        );
+        assert_eq!(global_metrics, source.global_metric_inputs(&font).unwrap());
+
+        // A new name is a change to static metadata but not to global metrics
+        font.names
+            .insert("familyNames".to_string(), "Renamed".to_string());
+        assert_ne!(
+            static_metadata,
+            source.static_metadata_inputs(&font).unwrap()
+        );
+        assert_eq!(global_metrics, source.global_metric_inputs(&font).unwrap());
+    }
+
     fn context_for(glyphs_file: PathBuf) -> (impl Source, Context) {
         let mut source = GlyphsIrSource::new(glyphs_file);
         let input = source.inputs().unwrap();
@@ -665,6 +1145,20 @@ mod tests {
         (source, context)
     }
 
+    fn build_kerning(glyphs_file: PathBuf) -> (impl Source, Context) {
+        let (source, context) = build_static_metadata(glyphs_file);
+        let task_context = context.copy_for_work(
+            Access::one(WorkId::InitStaticMetadata),
+            Access::one(WorkId::Kerning),
+        );
+        source
+            .create_kerning_ir_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        (source, context)
+    }
+
     fn build_glyphs(
         source: &impl Source,
         context: &Context,
@@ -950,11 +1444,104 @@ mod tests {
                 descender: (-42.0).into(),
                 cap_height: 702.0.into(),
                 x_height: 501.0.into(),
+                os2_typo_ascender: 737.0.into(),
+                os2_typo_descender: (-42.0).into(),
+                os2_typo_line_gap: 421.0.into(),
+                os2_win_ascent: 1158.0.into(),
+                os2_win_descent: 42.0.into(),
+                subscript_x_size: 650.0.into(),
+                subscript_y_size: 600.0.into(),
+                subscript_x_offset: 0.0.into(),
+                subscript_y_offset: 75.0.into(),
+                superscript_x_size: 650.0.into(),
+                superscript_y_size: 600.0.into(),
+                superscript_x_offset: 0.0.into(),
+                superscript_y_offset: 350.0.into(),
+                strikeout_size: 50.0.into(),
+                strikeout_position: 300.6.into(),
+                vhea_ascender: None,
+                vhea_descender: None,
+                vhea_line_gap: None,
             },
             default_metrics
         );
     }
 
+    #[test]
+    fn captures_os2_custom_parameters() {
+        let (_, context) = build_global_metrics(glyphs3_dir().join("WghtVar_OS2.glyphs"));
+        let static_metadata = context.get_init_static_metadata();
+        assert_eq!(
+            Os2Metadata {
+                fs_type: Some(0),
+                selection_flags: 1 << 7,
+                panose: Some([2, 0, 5, 3, 0, 0, 0, 0, 0, 0]),
+                unicode_range_bits: Some([0, 1].into()),
+                codepage_range_bits: Some([0, 29].into()),
+                ..Default::default()
+            },
+            static_metadata.os2
+        );
+
+        let default_metrics = context
+            .get_global_metrics()
+            .at(static_metadata.default_location());
+        assert_eq!(
+            (950.0, -250.0, 0.0, 1100.0, 300.0, 320.0, 50.0),
+            (
+                default_metrics.os2_typo_ascender.into_inner(),
+                default_metrics.os2_typo_descender.into_inner(),
+                default_metrics.os2_typo_line_gap.into_inner(),
+                default_metrics.os2_win_ascent.into_inner(),
+                default_metrics.os2_win_descent.into_inner(),
+                default_metrics.strikeout_position.into_inner(),
+                default_metrics.strikeout_size.into_inner(),
+            )
+        );
+    }
+
+    #[test]
+    fn captures_vertical_metrics() {
+        let (source, context) = build_global_metrics(glyphs3_dir().join("WghtVar_Vertical.glyphs"));
+        let static_metadata = context.get_init_static_metadata();
+        let metrics = context.get_global_metrics();
+        let vhea = |pos: &NormalizedLocation| {
+            let metrics = metrics.at(pos);
+            (
+                metrics.vhea_ascender.map(|v| v.into_inner()),
+                metrics.vhea_descender.map(|v| v.into_inner()),
+                metrics.vhea_line_gap.map(|v| v.into_inner()),
+            )
+        };
+        let default_location = static_metadata.default_location();
+        let bold = static_metadata
+            .variation_model
+            .locations()
+            .find(|loc| *loc != default_location)
+            .unwrap();
+        assert_eq!(
+            vec![
+                (Some(500.0), Some(-500.0), Some(0.0)),
+                (Some(550.0), Some(-550.0), Some(0.0))
+            ],
+            vec![vhea(default_location), vhea(bold)]
+        );
+
+        // Layers know their vertical advance and origin, the origin is relative to the ascender
+        build_glyphs(&source, &context, &[&"exclam".into(), &"hyphen".into()]).unwrap();
+        let exclam = context.get_glyph_ir(&"exclam".into());
+        let exclam = exclam.default_instance();
+        let hyphen = context.get_glyph_ir(&"hyphen".into());
+        let hyphen = hyphen.default_instance();
+        assert_eq!(
+            vec![(Some(1000.0), Some(637.0)), (None, None)],
+            vec![
+                (exclam.height, exclam.vertical_origin),
+                (hyphen.height, hyphen.vertical_origin)
+            ]
+        );
+    }
+
     #[test]
     fn captures_vendor_id() {
         let (_, context) = build_static_metadata(glyphs3_dir().join("TheBestNames.glyphs"));
@@ -963,4 +1550,272 @@ mod tests {
             context.get_init_static_metadata().vendor_id
         );
     }
+
+    #[test]
+    fn captures_gdef_categories() {
+        let (_, context) = build_static_metadata(glyphs3_dir().join("WghtVar_Gdef.glyphs"));
+        assert_eq!(
+            vec![
+                ("acutecomb", GdefCategory::Mark),
+                ("f_i", GdefCategory::Ligature),
+            ],
+            context
+                .get_init_static_metadata()
+                .gdef_categories
+                .iter()
+                .map(|(name, category)| (name.as_str(), *category))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stat_labels_from_instances() {
+        let (_, context) = build_static_metadata(glyphs3_dir().join("WghtVar_Stat.glyphs"));
+        let static_metadata = context.get_init_static_metadata();
+        let stat_labels = static_metadata.stat_labels();
+
+        // Inactive and variable instances don't get a label
+        assert_eq!(
+            vec![
+                ("Regular", 400.0, true),
+                ("SemiBold", 600.0, false),
+                ("Bold", 700.0, false),
+            ],
+            stat_labels
+                .axis_labels
+                .get("Weight")
+                .unwrap()
+                .iter()
+                .map(|l| (
+                    l.name.as_str(),
+                    l.value.into_inner().into_inner(),
+                    l.elidable
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert!(stat_labels.location_labels.is_empty());
+        assert!(static_metadata.font_specific_name_id("SemiBold").is_some());
+    }
+
+    #[test]
+    fn named_instances_from_instances() {
+        let (_, context) = build_static_metadata(glyphs3_dir().join("WghtVar_Stat.glyphs"));
+        let static_metadata = context.get_init_static_metadata();
+
+        // Inactive and variable instances aren't named
+        assert_eq!(
+            vec![
+                ("Regular", None, 400.0),
+                ("SemiBold", Some("WghtVarStat-SemiBold"), 600.0),
+                ("Bold", None, 700.0),
+            ],
+            static_metadata
+                .named_instances()
+                .iter()
+                .map(|i| (
+                    i.name.as_str(),
+                    i.postscript_name.as_deref(),
+                    i.location
+                        .get(&"Weight".to_string())
+                        .unwrap()
+                        .into_inner()
+                        .into_inner()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert!(static_metadata
+            .font_specific_name_id("WghtVarStat-SemiBold")
+            .is_some());
+    }
+
+    #[test]
+    fn captures_kerning() {
+        let (_, context) = build_kerning(glyphs3_dir().join("WghtVar_Kerning.glyphs"));
+        let kerning = context.get_kerning();
+
+        assert_eq!(
+            vec![
+                ("public.kern1.A", vec!["A"]),
+                ("public.kern1.V", vec!["V"]),
+                ("public.kern2.A", vec!["A"]),
+                ("public.kern2.V", vec!["V"]),
+            ],
+            kerning
+                .groups
+                .iter()
+                .map(|(name, members)| (
+                    name.as_str(),
+                    members.iter().map(|m| m.as_str()).collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        // Only public.kern1.A/public.kern2.V is in Bold, the rest should be 0 there
+        assert_eq!(
+            vec![
+                (
+                    KernParticipant::Group("public.kern1.A".to_string()),
+                    KernParticipant::Group("public.kern2.V".to_string()),
+                    vec![(0.0, -50.0), (1.0, -70.5)]
+                ),
+                (
+                    KernParticipant::Group("public.kern1.V".to_string()),
+                    KernParticipant::Glyph("period".into()),
+                    vec![(0.0, -80.0), (1.0, 0.0)]
+                ),
+                (
+                    KernParticipant::Group("public.kern1.V".to_string()),
+                    KernParticipant::Group("public.kern2.A".to_string()),
+                    vec![(0.0, -45.0), (1.0, 0.0)]
+                ),
+            ],
+            kerning
+                .kerns
+                .iter()
+                .map(|((side1, side2), values)| (
+                    side1.clone(),
+                    side2.clone(),
+                    values
+                        .iter()
+                        .map(|(loc, value)| (
+                            loc.iter().next().unwrap().1.to_f32(),
+                            value.into_inner()
+                        ))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn bracket_layers_become_alternates() {
+        let glyph_name: GlyphName = "exclam".into();
+        let (source, context) = build_static_metadata(glyphs3_dir().join("WghtVar_Bracket.glyphs"));
+        build_glyphs(&source, &context, &[&glyph_name]).unwrap();
+        let glyph = context.get_glyph_ir(&glyph_name);
+
+        assert_eq!(1, glyph.alternates.len());
+        let alternate = &glyph.alternates[0];
+        assert_eq!(
+            vec![("Weight", 2.0 / 3.0, 1.0)],
+            alternate
+                .condition_set
+                .iter()
+                .map(|c| (c.axis.as_str(), c.min.to_f32(), c.max.to_f32()))
+                .collect::<Vec<_>>()
+        );
+        // Both masters have a bracket layer, each with a single contour
+        let mut contours: Vec<_> = alternate
+            .sources
+            .iter()
+            .map(|(loc, inst)| {
+                (
+                    loc.get(&"Weight".to_string()).unwrap().to_f32(),
+                    inst.contours.len(),
+                )
+            })
+            .collect();
+        contours.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![(0.0, 1), (1.0, 1)], contours);
+    }
+
+    #[test]
+    fn bracket_layers_fall_back_to_master_layers() {
+        let glyph_name: GlyphName = "exclam".into();
+        let (source, context) = build_static_metadata(glyphs2_dir().join("WghtVar_Bracket.glyphs"));
+        build_glyphs(&source, &context, &[&glyph_name]).unwrap();
+        let glyph = context.get_glyph_ir(&glyph_name);
+
+        assert_eq!(
+            Tag::new(b"rclt"),
+            context
+                .get_init_static_metadata()
+                .feature_variations
+                .feature_tag
+        );
+        assert_eq!(1, glyph.alternates.len());
+        let alternate = &glyph.alternates[0];
+        assert_eq!(
+            vec![("Weight", 0.0, 2.0 / 3.0)],
+            alternate
+                .condition_set
+                .iter()
+                .map(|c| (c.axis.as_str(), c.min.to_f32(), c.max.to_f32()))
+                .collect::<Vec<_>>()
+        );
+        // Only Bold has a bracket layer, Regular uses its master layer
+        let mut contours: Vec<_> = alternate
+            .sources
+            .iter()
+            .map(|(loc, inst)| {
+                (
+                    loc.get(&"Weight".to_string()).unwrap().to_f32(),
+                    inst.contours.len(),
+                )
+            })
+            .collect();
+        contours.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![(0.0, 2), (1.0, 1)], contours);
+    }
+
+    #[test]
+    fn bracket_layer_with_unknown_master_is_an_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let glyphs_file = temp_dir.path().join("WghtVar_Bracket.glyphs");
+        let glyphs = fs::read_to_string(glyphs3_dir().join("WghtVar_Bracket.glyphs")).unwrap();
+        fs::write(
+            &glyphs_file,
+            glyphs.replace(
+                r#"associatedMasterId = "E09E0C54-128D-4FEA-B209-1B70BEFE300B";"#,
+                r#"associatedMasterId = "no-such-master";"#,
+            ),
+        )
+        .unwrap();
+
+        let glyph_name: GlyphName = "exclam".into();
+        let (source, context) = build_static_metadata(glyphs_file);
+        let Err(WorkError::InvalidSourceGlyph {
+            glyph_name: bad_glyph,
+            message,
+        }) = build_glyphs(&source, &context, &[&glyph_name])
+        else {
+            panic!("Should fail, the bracket layer has no master");
+        };
+        assert_eq!(glyph_name, bad_glyph);
+        assert!(
+            message.contains("4E2D5F0E-8B8A-4F6C-9D2B-1D5C2B7A3E02"),
+            "{message}"
+        );
+    }
+
+    fn assert_brace_layer_is_a_master(glyphs_file: PathBuf) {
+        let glyph_name: GlyphName = "hyphen".into();
+        let (source, context) = build_static_metadata(glyphs_file);
+        build_glyphs(&source, &context, &[&glyph_name]).unwrap();
+
+        let weight = |loc: &NormalizedLocation| loc.get(&"Weight".to_string()).unwrap().to_f32();
+        let mut model_locations: Vec<_> = context
+            .get_init_static_metadata()
+            .variation_model
+            .locations()
+            .map(weight)
+            .collect();
+        model_locations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![0.0, 0.5, 1.0], model_locations);
+
+        let glyph = context.get_glyph_ir(&glyph_name);
+        let mut glyph_locations: Vec<_> = glyph.sources().keys().map(weight).collect();
+        glyph_locations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![0.0, 0.5, 1.0], glyph_locations);
+    }
+
+    #[test]
+    fn brace_layer_is_a_master_glyphs2() {
+        assert_brace_layer_is_a_master(glyphs2_dir().join("WghtVar_Brace.glyphs"));
+    }
+
+    #[test]
+    fn brace_layer_is_a_master_glyphs3() {
+        assert_brace_layer_is_a_master(glyphs3_dir().join("WghtVar_Brace.glyphs"));
+    }
 }
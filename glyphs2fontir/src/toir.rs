@@ -7,7 +7,9 @@ use fontir::{
     error::{Error, WorkError},
     ir::{self, GlyphPathBuilder},
 };
-use glyphs_reader::{Component, FeatureSnippet, Font, FontMaster, NodeType, Path, Shape};
+use glyphs_reader::{
+    Anchor, AxisRule, Component, FeatureSnippet, Font, FontMaster, Layer, NodeType, Path, Shape,
+};
 use kurbo::BezPath;
 use log::trace;
 use ordered_float::OrderedFloat;
@@ -31,6 +33,16 @@ pub(crate) fn to_ir_contours_and_components(
     Ok((contours, components))
 }
 
+pub(crate) fn to_ir_anchors(anchors: &[Anchor]) -> Vec<ir::Anchor> {
+    anchors
+        .iter()
+        .map(|anchor| ir::Anchor {
+            name: anchor.name.clone(),
+            pos: anchor.pos,
+        })
+        .collect()
+}
+
 fn to_ir_component(glyph_name: GlyphName, component: &Component) -> ir::Component {
     trace!(
         "{} reuses {} with transform {:?}",
@@ -140,6 +152,35 @@ fn find_by_design_coord(
         })
 }
 
+/// The region a bracket layer applies in.
+///
+/// Axis rules are in design units, in axis order. A missing bound means the axis min or max.
+pub(crate) fn to_ir_condition_set(
+    axes: &[ir::Axis],
+    axis_rules: &[AxisRule],
+) -> Vec<ir::Condition> {
+    axes.iter()
+        .zip(axis_rules)
+        .filter(|(_, rule)| rule.min.is_some() || rule.max.is_some())
+        .map(|(axis, rule)| {
+            let to_normalized = |value: OrderedFloat<f64>| {
+                DesignCoord::new(value.into_inner() as f32).to_normalized(&axis.converter)
+            };
+            ir::Condition {
+                axis: axis.name.clone(),
+                min: rule
+                    .min
+                    .map(to_normalized)
+                    .unwrap_or_else(|| axis.min.to_normalized(&axis.converter)),
+                max: rule
+                    .max
+                    .map(to_normalized)
+                    .unwrap_or_else(|| axis.max.to_normalized(&axis.converter)),
+            }
+        })
+        .collect()
+}
+
 /// Convert .glyphs axes to IR axes.
 ///
 ///  See <https://github.com/googlefonts/glyphsLib/blob/6f243c1f732ea1092717918d0328f3b5303ffe56/Lib/glyphsLib/builder/axes.py#L155>
@@ -231,6 +272,32 @@ pub struct FontInfo {
     pub axis_indices: HashMap<Tag, usize>,
 }
 
+impl FontInfo {
+    /// Where a brace layer is, axes it doesn't give a coordinate for are at its master's position.
+    ///
+    /// None if the layer isn't associated with a known master.
+    pub fn brace_location(&self, layer: &Layer) -> Option<NormalizedLocation> {
+        let master_idx = self
+            .master_indices
+            .get(layer.associated_master_id.as_deref()?)?;
+        let master = &self.font.masters[*master_idx];
+        let axes_by_name = self.axes.iter().map(|a| (&a.name, a)).collect();
+        let location: DesignLocation = self
+            .axes
+            .iter()
+            .enumerate()
+            .map(|(idx, axis)| {
+                let pos = layer
+                    .coordinates
+                    .get(idx)
+                    .unwrap_or(&master.axes_values[idx]);
+                (axis.name.clone(), DesignCoord::new(pos.into_inner() as f32))
+            })
+            .collect();
+        Some(location.to_normalized(&axes_by_name))
+    }
+}
+
 impl TryFrom<Font> for FontInfo {
     type Error = Error;
 
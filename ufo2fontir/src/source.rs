@@ -1,5 +1,6 @@
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
@@ -10,17 +11,27 @@ use fontdrasil::{orchestration::Work, types::GlyphName};
 use fontir::{
     coords::{DesignLocation, NormalizedLocation, UserCoord},
     error::{Error, WorkError},
-    ir::{Features, GlobalMetric, GlobalMetrics, NameBuilder, NameKey, StaticMetadata},
-    orchestration::{Context, IrWork},
+    interpolatable::{check_interpolation, match_contours},
+    ir::{
+        Features, GdefCategory, GlobalMetric, GlobalMetrics, KernParticipant, Kerning, NameBuilder,
+        NameKey, Os2Metadata, StaticMetadata,
+    },
+    orchestration::{Context, Flags, IrWork},
     source::{Input, Source},
     stateset::{StateIdentifier, StateSet},
 };
 use indexmap::IndexSet;
 use log::{debug, trace, warn};
-use norad::designspace::{self, DesignSpaceDocument};
+use norad::{
+    designspace::{self, DesignSpaceDocument},
+    fontinfo::StyleMapStyle,
+};
 use write_fonts::OtRound;
 
-use crate::toir::{master_locations, to_design_location, to_ir_axes, to_ir_glyph};
+use crate::toir::{
+    master_locations, to_design_location, to_ir_axes, to_ir_feature_variations, to_ir_glyph,
+    to_ir_named_instances, to_ir_stat_labels,
+};
 
 pub struct DesignSpaceIrSource {
     designspace_file: PathBuf,
@@ -34,7 +45,6 @@ struct Cache {
     locations: HashMap<PathBuf, Vec<DesignLocation>>,
     designspace_file: PathBuf,
     designspace: Arc<DesignSpaceDocument>,
-    fea_files: Arc<Vec<PathBuf>>,
 }
 
 impl Cache {
@@ -43,14 +53,12 @@ impl Cache {
         locations: HashMap<PathBuf, Vec<DesignLocation>>,
         designspace_file: PathBuf,
         designspace: DesignSpaceDocument,
-        feature_files: Vec<PathBuf>,
     ) -> Cache {
         Cache {
             static_metadata,
             locations,
             designspace_file,
             designspace: Arc::from(designspace),
-            fea_files: Arc::from(feature_files),
         }
     }
 
@@ -288,12 +296,29 @@ impl Source for DesignSpaceIrSource {
             features.track_file(fea_file)?;
         }
 
+        // Kerning comes from every master, groups only from the default master
+        let mut kerning = StateSet::new();
+        for (idx, source) in designspace.sources.iter().enumerate() {
+            if source.layer.is_some() {
+                continue;
+            }
+            let ufo_dir = ds_dir.join(&source.filename);
+            for filename in ["kerning.plist", "groups.plist"] {
+                if filename == "groups.plist" && idx != default_master_idx {
+                    continue;
+                }
+                let file = ufo_dir.join(filename);
+                if file.is_file() {
+                    kerning.track_file(&file)?;
+                }
+            }
+        }
+
         self.cache = Some(Cache::new(
             static_metadata.clone(),
             glif_locations,
             self.designspace_file.clone(),
             designspace,
-            fea_files,
         ));
 
         // fontinfo.plist spans static metadata and global metrics.
@@ -303,6 +328,7 @@ impl Source for DesignSpaceIrSource {
             global_metrics: static_metadata,
             glyphs,
             features,
+            kerning,
         })
     }
 
@@ -335,7 +361,17 @@ impl Source for DesignSpaceIrSource {
 
         Ok(Box::new(FeatureWork {
             designspace_file: cache.designspace_file.clone(),
-            fea_files: cache.fea_files.clone(),
+            designspace: cache.designspace.clone(),
+        }))
+    }
+
+    fn create_kerning_ir_work(&self, input: &Input) -> Result<Box<IrWork>, Error> {
+        self.check_static_metadata(&input.static_metadata)?;
+        let cache = self.cache.as_ref().unwrap();
+
+        Ok(Box::new(KerningWork {
+            designspace_file: cache.designspace_file.clone(),
+            designspace: cache.designspace.clone(),
         }))
     }
 
@@ -372,7 +408,12 @@ struct GlobalMetricsWork {
 
 struct FeatureWork {
     designspace_file: PathBuf,
-    fea_files: Arc<Vec<PathBuf>>,
+    designspace: Arc<DesignSpaceDocument>,
+}
+
+struct KerningWork {
+    designspace_file: PathBuf,
+    designspace: Arc<DesignSpaceDocument>,
 }
 
 fn default_master(designspace: &DesignSpaceDocument) -> Option<(usize, &designspace::Source)> {
@@ -449,6 +490,35 @@ fn glyph_order(
     Ok(glyph_order)
 }
 
+/// Glyph categories from the default master's public.openTypeCategories
+fn gdef_categories(
+    source: &norad::designspace::Source,
+    designspace_dir: &Path,
+) -> Result<BTreeMap<GlyphName, GdefCategory>, WorkError> {
+    let lib_plist = load_plist(&designspace_dir.join(&source.filename), "lib.plist")?;
+    let Some(plist::Value::Dictionary(categories)) = lib_plist.get("public.openTypeCategories")
+    else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(categories
+        .iter()
+        .filter_map(|(glyph_name, category)| {
+            let category = match category.as_string() {
+                Some("base") => GdefCategory::Base,
+                Some("ligature") => GdefCategory::Ligature,
+                Some("mark") => GdefCategory::Mark,
+                Some("component") => GdefCategory::Component,
+                Some("unassigned") => return None,
+                _ => {
+                    warn!("Unrecognized openTypeCategory for {glyph_name}: {category:?}");
+                    return None;
+                }
+            };
+            Some((glyph_name.into(), category))
+        })
+        .collect())
+}
+
 fn units_per_em<'a>(
     font_infos: impl Iterator<Item = &'a norad::FontInfo>,
 ) -> Result<u16, WorkError> {
@@ -486,7 +556,9 @@ fn files_identical(f1: &Path, f2: &Path) -> Result<bool, WorkError> {
     if m1.len() != m2.len() {
         return Ok(false);
     }
-    Ok(true)
+    let c1 = fs::read(f1).map_err(WorkError::IoError)?;
+    let c2 = fs::read(f2).map_err(WorkError::IoError)?;
+    Ok(c1 == c2)
 }
 
 /// Creates a map from UFO directory name => fontinfo.
@@ -579,6 +651,60 @@ fn names(font_info: &norad::FontInfo) -> HashMap<NameKey, String> {
     builder.into_inner()
 }
 
+fn bits(bitlist: &Option<Vec<u8>>) -> Option<BTreeSet<u32>> {
+    bitlist
+        .as_ref()
+        .map(|bits| bits.iter().map(|bit| *bit as u32).collect())
+}
+
+/// OS/2 values from the fontinfo of the default master.
+fn os2_metadata(font_info: &norad::FontInfo) -> Os2Metadata {
+    let mut selection_flags = match font_info.style_map_style_name {
+        Some(StyleMapStyle::Italic) => 1,
+        Some(StyleMapStyle::Bold) => 1 << 5,
+        Some(StyleMapStyle::BoldItalic) => 1 | 1 << 5,
+        _ => 0,
+    };
+    // The UFO spec limits openTypeOS2Selection to USE_TYPO_METRICS, WWS and OBLIQUE
+    for bit in font_info.open_type_os2_selection.iter().flatten() {
+        selection_flags |= 1 << bit;
+    }
+    Os2Metadata {
+        weight_class: font_info
+            .open_type_os2_weight_class
+            .map(|v| v.min(u16::MAX as u32) as u16),
+        width_class: font_info.open_type_os2_width_class.map(|v| v as u16),
+        fs_type: bits(&font_info.open_type_os2_type).map(|bits| {
+            bits.iter()
+                .filter(|bit| **bit < 16)
+                .map(|bit| 1 << bit)
+                .sum()
+        }),
+        selection_flags,
+        family_class: font_info
+            .open_type_os2_family_class
+            .as_ref()
+            .map(|c| ((c.class_id as i16) << 8) | c.subclass_id as i16),
+        panose: font_info.open_type_os2_panose.as_ref().map(|p| {
+            [
+                p.family_type,
+                p.serif_style,
+                p.weight,
+                p.proportion,
+                p.contrast,
+                p.stroke_variation,
+                p.arm_style,
+                p.letterform,
+                p.midline,
+                p.x_height,
+            ]
+            .map(|v| v as u8)
+        }),
+        unicode_range_bits: bits(&font_info.open_type_os2_unicode_ranges),
+        codepage_range_bits: bits(&font_info.open_type_os2_code_page_ranges),
+    }
+}
+
 impl Work<Context, WorkError> for StaticMetadataWork {
     fn exec(&self, context: &Context) -> Result<(), WorkError> {
         debug!("Static metadata for {:#?}", self.designspace_file);
@@ -604,6 +730,18 @@ impl Work<Context, WorkError> for StaticMetadataWork {
         if let Some(vendor_id) = &font_info_at_default.open_type_os2_vendor_id {
             static_metadata.vendor_id = Tag::from_str(vendor_id).map_err(WorkError::InvalidTag)?;
         }
+        static_metadata.gdef_categories = gdef_categories(default_master, designspace_dir)?;
+        static_metadata.set_stat_labels(to_ir_stat_labels(
+            &self.designspace_file,
+            &static_metadata.axes,
+        )?);
+        static_metadata.set_named_instances(to_ir_named_instances(
+            &self.designspace.instances,
+            &static_metadata.axes,
+        ));
+        static_metadata.os2 = os2_metadata(font_info_at_default);
+        static_metadata.feature_variations =
+            to_ir_feature_variations(&self.designspace_file, &static_metadata.axes)?;
 
         context.set_init_static_metadata(static_metadata);
         Ok(())
@@ -622,6 +760,7 @@ impl Work<Context, WorkError> for GlobalMetricsWork {
         let designspace_dir = self.designspace_file.parent().unwrap();
         let font_infos = font_infos(designspace_dir, &self.designspace)?;
         let master_locations = master_locations(&static_metadata.axes, &self.designspace.sources);
+        let mut metric_locations = Vec::new();
         for source in self.designspace.sources.iter() {
             let pos = master_locations.get(&source.name).unwrap();
 
@@ -642,6 +781,85 @@ impl Work<Context, WorkError> for GlobalMetricsWork {
             metrics.set_if_some(GlobalMetric::Descender, pos.clone(), font_info.descender);
             metrics.set_if_some(GlobalMetric::CapHeight, pos.clone(), font_info.cap_height);
             metrics.set_if_some(GlobalMetric::XHeight, pos.clone(), font_info.x_height);
+            let mut set_os2 = |metric, value: Option<i32>| {
+                metrics.set_if_some(metric, pos.clone(), value.map(f64::from))
+            };
+            set_os2(
+                GlobalMetric::Os2TypoAscender,
+                font_info.open_type_os2_typo_ascender,
+            );
+            set_os2(
+                GlobalMetric::Os2TypoDescender,
+                font_info.open_type_os2_typo_descender,
+            );
+            set_os2(
+                GlobalMetric::Os2TypoLineGap,
+                font_info.open_type_os2_typo_line_gap,
+            );
+            set_os2(
+                GlobalMetric::Os2WinAscent,
+                font_info.open_type_os2_win_ascent.map(|v| v as i32),
+            );
+            set_os2(
+                GlobalMetric::Os2WinDescent,
+                font_info.open_type_os2_win_descent.map(|v| v as i32),
+            );
+            set_os2(
+                GlobalMetric::SubscriptXSize,
+                font_info.open_type_os2_subscript_x_size,
+            );
+            set_os2(
+                GlobalMetric::SubscriptYSize,
+                font_info.open_type_os2_subscript_y_size,
+            );
+            set_os2(
+                GlobalMetric::SubscriptXOffset,
+                font_info.open_type_os2_subscript_x_offset,
+            );
+            set_os2(
+                GlobalMetric::SubscriptYOffset,
+                font_info.open_type_os2_subscript_y_offset,
+            );
+            set_os2(
+                GlobalMetric::SuperscriptXSize,
+                font_info.open_type_os2_superscript_x_size,
+            );
+            set_os2(
+                GlobalMetric::SuperscriptYSize,
+                font_info.open_type_os2_superscript_y_size,
+            );
+            set_os2(
+                GlobalMetric::SuperscriptXOffset,
+                font_info.open_type_os2_superscript_x_offset,
+            );
+            set_os2(
+                GlobalMetric::SuperscriptYOffset,
+                font_info.open_type_os2_superscript_y_offset,
+            );
+            set_os2(
+                GlobalMetric::StrikeoutSize,
+                font_info.open_type_os2_strikeout_size,
+            );
+            set_os2(
+                GlobalMetric::StrikeoutPosition,
+                font_info.open_type_os2_strikeout_position,
+            );
+            set_os2(
+                GlobalMetric::VheaAscender,
+                font_info.open_type_vhea_vert_typo_ascender,
+            );
+            set_os2(
+                GlobalMetric::VheaDescender,
+                font_info.open_type_vhea_vert_typo_descender,
+            );
+            set_os2(
+                GlobalMetric::VheaLineGap,
+                font_info.open_type_vhea_vert_typo_line_gap,
+            );
+            metric_locations.push(pos);
+        }
+        for pos in metric_locations {
+            metrics.populate_defaults(pos, static_metadata.units_per_em);
         }
 
         trace!("{:#?}", metrics);
@@ -654,24 +872,144 @@ impl Work<Context, WorkError> for FeatureWork {
     fn exec(&self, context: &Context) -> Result<(), WorkError> {
         debug!("Features for {:#?}", self.designspace_file);
 
-        // TODO: support feature files that aren't identical
-        let fea_files = self.fea_files.as_ref();
-        for fea_file in fea_files.iter().skip(1) {
-            if !files_identical(&fea_files[0], fea_file)? {
-                warn!("Bailing out due to non-identical feature files. This is an unnecessary limitation.");
-                return Err(WorkError::FileMismatch(
-                    fea_files[0].to_path_buf(),
-                    fea_file.to_path_buf(),
-                ));
+        let designspace_dir = self.designspace_file.parent().unwrap();
+        let axes = to_ir_axes(&self.designspace.axes)?;
+        let master_locations = master_locations(&axes, &self.designspace.sources);
+
+        // Sources that use layer= share the fea of the UFO they live in
+        let mut fea_files = Vec::new();
+        for source in self.designspace.sources.iter() {
+            if source.layer.is_some() {
+                continue;
+            }
+            let fea_file = designspace_dir.join(&source.filename).join("features.fea");
+            if fea_file.is_file() {
+                let pos = master_locations.get(&source.name).unwrap();
+                fea_files.push((pos.clone(), fea_file));
             }
         }
 
-        if !fea_files.is_empty() {
-            context.set_features(Features::from_file(&fea_files[0]));
-        } else {
+        let mut identical = true;
+        for (_, fea_file) in fea_files.iter().skip(1) {
+            if !files_identical(&fea_files[0].1, fea_file)? {
+                identical = false;
+                break;
+            }
+        }
+
+        if fea_files.is_empty() {
             context.set_features(Features::empty());
+        } else if identical {
+            context.set_features(Features::from_file(&fea_files[0].1));
+        } else {
+            debug!("Feature files differ, compiling each at its master location");
+            context.set_features(Features::per_master(fea_files));
+        }
+
+        Ok(())
+    }
+}
+
+fn kern_participant(groups: &BTreeMap<String, BTreeSet<GlyphName>>, name: &str) -> KernParticipant {
+    if groups.contains_key(name) {
+        KernParticipant::Group(name.to_string())
+    } else {
+        KernParticipant::Glyph(name.into())
+    }
+}
+
+/// The kerning groups of a UFO, public.kern1.* and public.kern2.*
+fn kerning_groups(ufo_dir: &Path) -> Result<BTreeMap<String, BTreeSet<GlyphName>>, WorkError> {
+    if !ufo_dir.join("groups.plist").is_file() {
+        return Ok(BTreeMap::new());
+    }
+    let groups = load_plist(ufo_dir, "groups.plist")?;
+    Ok(groups
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("public.kern1.") || name.starts_with("public.kern2."))
+        .map(|(name, members)| {
+            let members = members
+                .as_array()
+                .map(|members| {
+                    members
+                        .iter()
+                        .filter_map(|m| m.as_string())
+                        .map(GlyphName::from)
+                        .collect()
+                })
+                .unwrap_or_default();
+            (name, members)
+        })
+        .collect())
+}
+
+impl Work<Context, WorkError> for KerningWork {
+    fn exec(&self, context: &Context) -> Result<(), WorkError> {
+        debug!("Kerning for {:#?}", self.designspace_file);
+        let designspace_dir = self.designspace_file.parent().unwrap();
+        let static_metadata = context.get_init_static_metadata();
+        let master_locations = master_locations(&static_metadata.axes, &self.designspace.sources);
+        let Some((_, default_master)) = default_master(&self.designspace) else {
+            return Err(WorkError::NoDefaultMaster(self.designspace_file.clone()));
+        };
+
+        let mut kerning = Kerning {
+            groups: kerning_groups(&designspace_dir.join(&default_master.filename))?,
+            ..Default::default()
+        };
+
+        // Sources that use layer= contribute glyphs, not kerning
+        let mut kern_locations = Vec::new();
+        for source in self.designspace.sources.iter() {
+            if source.layer.is_some() {
+                continue;
+            }
+            let pos = master_locations.get(&source.name).unwrap();
+            kern_locations.push(pos);
+
+            let ufo_dir = designspace_dir.join(&source.filename);
+            if !ufo_dir.join("kerning.plist").is_file() {
+                continue;
+            }
+            for (side1, seconds) in load_plist(&ufo_dir, "kerning.plist")? {
+                let Some(seconds) = seconds.as_dictionary() else {
+                    warn!("{source:?} kerning for {side1} is not a dictionary");
+                    continue;
+                };
+                for (side2, value) in seconds.iter() {
+                    let Some(value) = value
+                        .as_real()
+                        .or_else(|| value.as_signed_integer().map(|v| v as f64))
+                    else {
+                        warn!("{source:?} kerning for {side1}/{side2} is not a number");
+                        continue;
+                    };
+                    let pair = (
+                        kern_participant(&kerning.groups, &side1),
+                        kern_participant(&kerning.groups, side2),
+                    );
+                    kerning
+                        .kerns
+                        .entry(pair)
+                        .or_default()
+                        .insert(pos.clone(), (value as f32).into());
+                }
+            }
         }
 
+        // A pair a master doesn't mention is 0 at that master
+        for values in kerning.kerns.values_mut() {
+            for pos in kern_locations.iter() {
+                values.entry((*pos).clone()).or_insert(0.0.into());
+            }
+        }
+
+        trace!(
+            "{} kerning groups, {} kerning pairs",
+            kerning.groups.len(),
+            kerning.kerns.len()
+        );
+        context.set_kerning(kerning);
         Ok(())
     }
 }
@@ -701,7 +1039,13 @@ impl Work<Context, WorkError> for GlyphIrWork {
             glif_files.insert(path, normalized_locations);
         }
 
-        let glyph_ir = to_ir_glyph(self.glyph_name.clone(), &glif_files)?;
+        let mut glyph_ir = to_ir_glyph(self.glyph_name.clone(), &glif_files)?;
+        if context.flags.contains(Flags::MATCH_CONTOURS) {
+            match_contours(&mut glyph_ir);
+        }
+        if context.flags.contains(Flags::CHECK_INTERPOLATION) {
+            check_interpolation(&glyph_ir)?;
+        }
         context.set_glyph_ir(glyph_ir);
         Ok(())
     }
@@ -718,7 +1062,7 @@ mod tests {
     use fontdrasil::{orchestration::Access, types::GlyphName};
     use fontir::{
         coords::{DesignCoord, DesignLocation, NormalizedCoord, NormalizedLocation, UserCoord},
-        ir::NameKey,
+        ir::{Features, GdefCategory, KernParticipant, NameKey, Os2Metadata},
         orchestration::{Context, Flags, WorkId},
         paths::Paths,
         source::{Input, Source},
@@ -820,6 +1164,31 @@ mod tests {
         (source, context)
     }
 
+    fn build_kerning(name: &str) -> (impl Source, Context) {
+        let (source, context) = build_static_metadata(name);
+        let task_context = context.copy_for_work(
+            Access::one(WorkId::InitStaticMetadata),
+            Access::one(WorkId::Kerning),
+        );
+        source
+            .create_kerning_ir_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        (source, context)
+    }
+
+    fn build_features(name: &str) -> (impl Source, Context) {
+        let (source, context) = build_static_metadata(name);
+        let task_context = context.copy_for_work(Access::none(), Access::one(WorkId::Features));
+        source
+            .create_feature_ir_work(&context.input)
+            .unwrap()
+            .exec(&task_context)
+            .unwrap();
+        (source, context)
+    }
+
     fn load_wght_var() -> (DesignSpaceIrSource, Input) {
         load_designspace("wght_var.designspace")
     }
@@ -1042,6 +1411,40 @@ mod tests {
             Tag::new(b"RODS"),
             context.get_init_static_metadata().vendor_id
         );
+        assert_eq!(
+            Os2Metadata {
+                weight_class: Some(300),
+                width_class: Some(4),
+                fs_type: Some(0),
+                // Italic from the style map, USE_TYPO_METRICS from openTypeOS2Selection
+                selection_flags: 1 | 1 << 7,
+                family_class: Some(0x0801),
+                panose: Some([2, 0, 5, 3, 0, 0, 0, 0, 0, 0]),
+                unicode_range_bits: Some([0, 1].into()),
+                codepage_range_bits: Some([0].into()),
+            },
+            context.get_init_static_metadata().os2
+        );
+    }
+
+    #[test]
+    fn captures_os2_metrics() {
+        let (_, context) = build_global_metrics("fontinfo.designspace");
+        let static_metadata = &context.get_init_static_metadata();
+        let default_metrics = context
+            .get_global_metrics()
+            .at(static_metadata.default_location());
+        // typo descender and win ascent aren't set and fall back
+        assert_eq!(
+            (950.0, -200.0, 300.0, 1000.0, 320.0),
+            (
+                default_metrics.os2_typo_ascender.into_inner(),
+                default_metrics.os2_typo_descender.into_inner(),
+                default_metrics.os2_win_descent.into_inner(),
+                default_metrics.os2_win_ascent.into_inner(),
+                default_metrics.strikeout_position.into_inner(),
+            ),
+        );
     }
 
     fn only_coord(loc: &NormalizedLocation) -> NormalizedCoord {
@@ -1094,4 +1497,238 @@ mod tests {
             metric_locations
         );
     }
+
+    #[test]
+    fn captures_gdef_categories() {
+        let (_, context) = build_static_metadata("gdef.designspace");
+        assert_eq!(
+            vec![
+                ("acutecomb", GdefCategory::Mark),
+                ("f_i", GdefCategory::Ligature),
+            ],
+            context
+                .get_init_static_metadata()
+                .gdef_categories
+                .iter()
+                .map(|(name, category)| (name.as_str(), *category))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn captures_named_instances() {
+        let (_, context) = build_static_metadata("wght_var.designspace");
+        let static_metadata = context.get_init_static_metadata();
+
+        assert_eq!(
+            vec![
+                ("Regular", None, 400.0),
+                ("Bold", Some("WghtVar-Bold"), 700.0),
+            ],
+            static_metadata
+                .named_instances()
+                .iter()
+                .map(|i| (
+                    i.name.as_str(),
+                    i.postscript_name.as_deref(),
+                    i.location
+                        .get(&"Weight".to_string())
+                        .unwrap()
+                        .into_inner()
+                        .into_inner()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert!(static_metadata.font_specific_name_id("Bold").is_some());
+    }
+
+    #[test]
+    fn captures_stat_labels() {
+        let (_, context) = build_static_metadata("stat.designspace");
+        let static_metadata = context.get_init_static_metadata();
+        let stat_labels = static_metadata.stat_labels();
+
+        let weight_labels = stat_labels.axis_labels.get("Weight").unwrap();
+        assert_eq!(
+            vec![
+                ("Regular", 400.0, true, Some(700.0)),
+                ("Medium", 500.0, false, None),
+                ("Bold", 700.0, false, None),
+            ],
+            weight_labels
+                .iter()
+                .map(|l| (
+                    l.name.as_str(),
+                    l.value.into_inner().into_inner(),
+                    l.elidable,
+                    l.linked_value.map(|v| v.into_inner().into_inner())
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            (Some(UserCoord::new(450.0)), Some(UserCoord::new(550.0))),
+            (weight_labels[1].min, weight_labels[1].max)
+        );
+        assert_eq!(
+            vec![("Semibold", vec![("Weight", UserCoord::new(600.0))])],
+            stat_labels
+                .location_labels
+                .iter()
+                .map(|l| (
+                    l.name.as_str(),
+                    l.location
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), *value))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("Regular"), stat_labels.elided_fallback_name.as_deref());
+
+        // Every label needs a name
+        for name in ["Regular", "Medium", "Bold", "Semibold"] {
+            assert!(
+                static_metadata.font_specific_name_id(name).is_some(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn captures_kerning() {
+        let (_, context) = build_kerning("kern.designspace");
+        let kerning = context.get_kerning();
+
+        assert_eq!(
+            vec![
+                ("public.kern1.bars", vec!["bar"]),
+                ("public.kern2.pluses", vec!["plus"]),
+            ],
+            kerning
+                .groups
+                .iter()
+                .map(|(name, members)| (
+                    name.as_str(),
+                    members.iter().map(|m| m.as_str()).collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        // plus/bar isn't in Bold so it should be 0 there
+        assert_eq!(
+            vec![
+                (
+                    KernParticipant::Glyph("plus".into()),
+                    KernParticipant::Glyph("bar".into()),
+                    vec![(0.0, -10.0), (1.0, 0.0)]
+                ),
+                (
+                    KernParticipant::Group("public.kern1.bars".to_string()),
+                    KernParticipant::Group("public.kern2.pluses".to_string()),
+                    vec![(0.0, -20.0), (1.0, -40.5)]
+                ),
+            ],
+            kerning
+                .kerns
+                .iter()
+                .map(|((side1, side2), values)| (
+                    side1.clone(),
+                    side2.clone(),
+                    values
+                        .iter()
+                        .map(|(loc, value)| (only_coord(loc).to_f32(), value.into_inner()))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn captures_rules() {
+        let (_, context) = build_static_metadata("rules.designspace");
+        let static_metadata = context.get_init_static_metadata();
+        let feature_variations = &static_metadata.feature_variations;
+
+        assert_eq!(Tag::new(b"rclt"), feature_variations.feature_tag);
+        assert_eq!(
+            vec![(
+                Some("bar.heavy"),
+                vec![vec![("Weight", 2.0 / 3.0, 1.0)]],
+                vec![("bar", "plus")]
+            )],
+            feature_variations
+                .rules
+                .iter()
+                .map(|rule| (
+                    rule.name.as_deref(),
+                    rule.condition_sets
+                        .iter()
+                        .map(|set| set
+                            .iter()
+                            .map(|c| (c.axis.as_str(), c.min.to_f32(), c.max.to_f32()))
+                            .collect::<Vec<_>>())
+                        .collect::<Vec<_>>(),
+                    rule.substitutions
+                        .iter()
+                        .map(|(from, to)| (from.as_str(), to.as_str()))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn lib_sets_rules_feature() {
+        let (_, context) = build_static_metadata("rules_feature_tag.designspace");
+        let static_metadata = context.get_init_static_metadata();
+        assert_eq!(
+            (Tag::new(b"calt"), 1),
+            (
+                static_metadata.feature_variations.feature_tag,
+                static_metadata.feature_variations.rules.len()
+            )
+        );
+    }
+
+    #[test]
+    fn no_rules() {
+        let (_, context) = build_static_metadata("wght_var.designspace");
+        assert!(context
+            .get_init_static_metadata()
+            .feature_variations
+            .is_empty());
+    }
+
+    #[test]
+    fn identical_fea_is_one_file() {
+        let (_, context) = build_features("variable_fea.designspace");
+        assert_eq!(
+            Features::from_file(&testdata_dir().join("VarFea-Regular.ufo/features.fea")),
+            *context.get_features()
+        );
+    }
+
+    #[test]
+    fn fea_per_master() {
+        let (_, context) = build_features("per_master_fea.designspace");
+        let Features::PerMaster(fea_files) = &*context.get_features() else {
+            panic!("Should have a fea file per master");
+        };
+        assert_eq!(
+            vec![
+                (
+                    0.0,
+                    testdata_dir().join("PerMasterFea-Regular.ufo/features.fea")
+                ),
+                (
+                    1.0,
+                    testdata_dir().join("PerMasterFea-Bold.ufo/features.fea")
+                ),
+            ],
+            fea_files
+                .iter()
+                .map(|(loc, path)| (only_coord(loc).to_f32(), path.clone()))
+                .collect::<Vec<_>>()
+        );
+    }
 }
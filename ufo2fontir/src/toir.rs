@@ -1,16 +1,19 @@
-use std::path::PathBuf;
-use std::{collections::HashMap, str::FromStr};
+use std::path::{Path, PathBuf};
+use std::{collections::HashMap, fs, str::FromStr};
 
 use font_types::Tag;
 use fontdrasil::types::GlyphName;
 use fontir::{
-    coords::{CoordConverter, DesignCoord, DesignLocation, NormalizedLocation, UserCoord},
+    coords::{
+        CoordConverter, DesignCoord, DesignLocation, NormalizedLocation, UserCoord, UserLocation,
+    },
     error::WorkError,
     ir::{self, GlyphPathBuilder},
 };
-use kurbo::{Affine, BezPath};
-use log::trace;
+use kurbo::{Affine, BezPath, Point};
+use log::{trace, warn};
 use norad::designspace::{self, Dimension};
+use serde::Deserialize;
 
 pub(crate) fn to_design_location(loc: &[Dimension]) -> DesignLocation {
     // TODO: what if Dimension uses uservalue? - new in DS5.0
@@ -65,16 +68,32 @@ fn to_ir_component(component: &norad::Component) -> ir::Component {
     }
 }
 
+fn to_ir_anchor(anchor: &norad::Anchor) -> Option<ir::Anchor> {
+    // Unnamed anchors can't participate in mark attachment
+    anchor.name.as_ref().map(|name| ir::Anchor {
+        name: name.to_string(),
+        pos: Point::new(anchor.x, anchor.y),
+    })
+}
+
 fn to_ir_glyph_instance(glyph: &norad::Glyph) -> Result<ir::GlyphInstance, WorkError> {
     let mut contours = Vec::new();
     for contour in glyph.contours.iter() {
         contours.push(to_ir_contour(glyph.name().as_str().into(), contour)?);
     }
+    // <https://unifiedfontobject.org/versions/ufo3/glyphs/glif/#publicverticalorigin>
+    let vertical_origin = glyph.lib.get("public.verticalOrigin").and_then(|value| {
+        value
+            .as_real()
+            .or_else(|| value.as_signed_integer().map(|v| v as f64))
+    });
     Ok(ir::GlyphInstance {
         width: glyph.width,
         height: Some(glyph.height),
+        vertical_origin,
         contours,
         components: glyph.components.iter().map(to_ir_component).collect(),
+        anchors: glyph.anchors.iter().filter_map(to_ir_anchor).collect(),
     })
 }
 
@@ -141,6 +160,372 @@ pub fn to_ir_axis(axis: &designspace::Axis) -> Result<ir::Axis, WorkError> {
     })
 }
 
+/// The parts of a designspace that describe STAT, new in designspace 5 and not read by norad.
+///
+/// See <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#labels-element-axis>
+/// and <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#labels-element>.
+#[derive(Deserialize)]
+struct DesignSpaceLabels {
+    #[serde(default)]
+    axes: LabelledAxes,
+    #[serde(default)]
+    labels: Labels<DsLocationLabel>,
+}
+
+#[derive(Default, Deserialize)]
+struct LabelledAxes {
+    #[serde(rename = "@elidedfallbackname")]
+    elided_fallback_name: Option<String>,
+    #[serde(default)]
+    axis: Vec<LabelledAxis>,
+}
+
+#[derive(Deserialize)]
+struct LabelledAxis {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(default)]
+    labels: Labels<DsAxisLabel>,
+}
+
+#[derive(Deserialize)]
+struct Labels<T> {
+    #[serde(default = "Vec::new")]
+    label: Vec<T>,
+}
+
+impl<T> Default for Labels<T> {
+    fn default() -> Self {
+        Labels { label: Vec::new() }
+    }
+}
+
+#[derive(Deserialize)]
+struct DsAxisLabel {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@uservalue")]
+    user_value: f32,
+    #[serde(rename = "@userminimum")]
+    user_minimum: Option<f32>,
+    #[serde(rename = "@usermaximum")]
+    user_maximum: Option<f32>,
+    #[serde(rename = "@linkeduservalue")]
+    linked_user_value: Option<f32>,
+    #[serde(default, rename = "@elidable")]
+    elidable: bool,
+    #[serde(default, rename = "@oldersibling")]
+    older_sibling: bool,
+}
+
+#[derive(Deserialize)]
+struct DsLocationLabel {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(default, rename = "@elidable")]
+    elidable: bool,
+    #[serde(default, rename = "@oldersibling")]
+    older_sibling: bool,
+    location: DsLocation,
+}
+
+#[derive(Deserialize)]
+struct DsLocation {
+    #[serde(default)]
+    dimension: Vec<Dimension>,
+}
+
+fn to_ir_location_label(
+    axes: &HashMap<&String, &ir::Axis>,
+    label: DsLocationLabel,
+) -> Option<ir::LocationLabel> {
+    let mut location = UserLocation::new();
+    for dim in label.location.dimension.iter() {
+        let Some(axis) = axes.get(&dim.name) else {
+            warn!(
+                "Location label {} uses undefined axis {}, dropping it",
+                label.name, dim.name
+            );
+            return None;
+        };
+        let value = match (dim.uservalue, dim.xvalue) {
+            (Some(user), _) => UserCoord::new(user),
+            (None, Some(design)) => DesignCoord::new(design).to_user(&axis.converter),
+            (None, None) => axis.default,
+        };
+        location.set_pos(dim.name.clone(), value);
+    }
+    Some(ir::LocationLabel {
+        name: label.name,
+        location,
+        elidable: label.elidable,
+        older_sibling: label.older_sibling,
+    })
+}
+
+/// Named instances from the instances of a designspace.
+///
+/// Instances without a style name are skipped, there is nothing to call them.
+pub fn to_ir_named_instances(
+    instances: &[designspace::Instance],
+    axes: &[ir::Axis],
+) -> Vec<ir::NamedInstance> {
+    let axes: HashMap<_, _> = axes.iter().map(|a| (&a.name, a)).collect();
+    instances
+        .iter()
+        .filter_map(|instance| {
+            let Some(name) = &instance.stylename else {
+                warn!(
+                    "Instance {} has no style name, not naming it",
+                    instance.name
+                );
+                return None;
+            };
+            let location = instance
+                .location
+                .iter()
+                .filter_map(|dim| {
+                    let Some(axis) = axes.get(&dim.name) else {
+                        warn!("Instance {} is on unknown axis {}", instance.name, dim.name);
+                        return None;
+                    };
+                    let value = match (dim.xvalue, dim.uservalue) {
+                        (Some(xvalue), _) => DesignCoord::new(xvalue).to_user(&axis.converter),
+                        (None, Some(uservalue)) => UserCoord::new(uservalue),
+                        (None, None) => return None,
+                    };
+                    Some((dim.name.clone(), value))
+                })
+                .collect();
+            Some(ir::NamedInstance {
+                name: name.clone(),
+                postscript_name: instance.postscriptfontname.clone(),
+                location,
+            })
+        })
+        .collect()
+}
+
+/// Read the axis and location labels from a designspace.
+pub fn to_ir_stat_labels(
+    designspace_file: &Path,
+    axes: &[ir::Axis],
+) -> Result<ir::StatLabels, WorkError> {
+    let xml = fs::read_to_string(designspace_file).map_err(WorkError::IoError)?;
+    let labels: DesignSpaceLabels = quick_xml::de::from_str(&xml)
+        .map_err(|e| WorkError::ParseError(designspace_file.to_path_buf(), format!("{e}")))?;
+    let axes: HashMap<_, _> = axes.iter().map(|a| (&a.name, a)).collect();
+
+    let axis_labels = labels
+        .axes
+        .axis
+        .into_iter()
+        .filter(|axis| !axis.labels.label.is_empty())
+        .map(|axis| {
+            let labels = axis
+                .labels
+                .label
+                .into_iter()
+                .map(|label| ir::AxisLabel {
+                    name: label.name,
+                    value: UserCoord::new(label.user_value),
+                    min: label.user_minimum.map(UserCoord::new),
+                    max: label.user_maximum.map(UserCoord::new),
+                    linked_value: label.linked_user_value.map(UserCoord::new),
+                    elidable: label.elidable,
+                    older_sibling: label.older_sibling,
+                })
+                .collect();
+            (axis.name, labels)
+        })
+        .collect();
+    let location_labels = labels
+        .labels
+        .label
+        .into_iter()
+        .filter_map(|label| to_ir_location_label(&axes, label))
+        .collect();
+
+    Ok(ir::StatLabels {
+        axis_labels,
+        location_labels,
+        elided_fallback_name: labels.axes.elided_fallback_name,
+    })
+}
+
+/// The rules of a designspace, which norad doesn't read.
+///
+/// See <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#rules-element>
+#[derive(Deserialize)]
+struct DesignSpaceRules {
+    #[serde(default)]
+    rules: DsRules,
+    #[serde(default)]
+    lib: DsLib,
+}
+
+#[derive(Default, Deserialize)]
+struct DsRules {
+    #[serde(rename = "@processing")]
+    processing: Option<String>,
+    #[serde(default)]
+    rule: Vec<DsRule>,
+}
+
+#[derive(Deserialize)]
+struct DsRule {
+    #[serde(rename = "@name")]
+    name: Option<String>,
+    #[serde(default)]
+    conditionset: Vec<DsConditionSet>,
+    /// Conditions outside a conditionset, from format 3, form a set of their own
+    #[serde(default)]
+    condition: Vec<DsCondition>,
+    #[serde(default)]
+    sub: Vec<DsSub>,
+}
+
+#[derive(Deserialize)]
+struct DsConditionSet {
+    #[serde(default)]
+    condition: Vec<DsCondition>,
+}
+
+#[derive(Deserialize)]
+struct DsCondition {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@minimum")]
+    minimum: Option<f32>,
+    #[serde(rename = "@maximum")]
+    maximum: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct DsSub {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@with")]
+    with: String,
+}
+
+/// The root lib of a designspace, a plist dict.
+#[derive(Default, Deserialize)]
+struct DsLib {
+    #[serde(default)]
+    dict: DsDict,
+}
+
+/// Alternating keys and values, only string values are of interest.
+#[derive(Default, Deserialize)]
+struct DsDict {
+    #[serde(rename = "$value", default)]
+    entries: Vec<DsPlistValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DsPlistValue {
+    Key(String),
+    String(String),
+    #[serde(other)]
+    Other,
+}
+
+impl DsDict {
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.entries.windows(2).find_map(|pair| match pair {
+            [DsPlistValue::Key(k), DsPlistValue::String(value)] if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+}
+
+fn to_ir_condition_set(
+    axes: &HashMap<&String, &ir::Axis>,
+    rule_name: Option<&str>,
+    conditions: &[DsCondition],
+) -> Option<Vec<ir::Condition>> {
+    let mut condition_set = Vec::new();
+    for condition in conditions {
+        let Some(axis) = axes.get(&condition.name) else {
+            warn!(
+                "Rule {:?} has a condition on undefined axis {}, dropping the condition set",
+                rule_name, condition.name
+            );
+            return None;
+        };
+        // Condition values are design coordinates; a missing bound is open ended
+        let to_normalized = |value: Option<f32>, bound: UserCoord| match value {
+            Some(value) => DesignCoord::new(value).to_normalized(&axis.converter),
+            None => bound.to_normalized(&axis.converter),
+        };
+        condition_set.push(ir::Condition {
+            axis: axis.name.clone(),
+            min: to_normalized(condition.minimum, axis.min),
+            max: to_normalized(condition.maximum, axis.max),
+        });
+    }
+    Some(condition_set)
+}
+
+/// Read the substitution rules from a designspace.
+pub fn to_ir_feature_variations(
+    designspace_file: &Path,
+    axes: &[ir::Axis],
+) -> Result<ir::FeatureVariations, WorkError> {
+    let xml = fs::read_to_string(designspace_file).map_err(WorkError::IoError)?;
+    let ds_rules: DesignSpaceRules = quick_xml::de::from_str(&xml)
+        .map_err(|e| WorkError::ParseError(designspace_file.to_path_buf(), format!("{e}")))?;
+    let axes: HashMap<_, _> = axes.iter().map(|a| (&a.name, a)).collect();
+
+    // processing="last" means apply the rules after all other substitutions
+    // <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#rules-element>
+    // The lib can name any feature, see <https://fonttools.readthedocs.io/en/latest/designspaceLib/xml.html#lib-element>
+    let feature_tag = match (
+        ds_rules
+            .lib
+            .dict
+            .get_str("com.github.fonttools.varLib.featureVarsFeatureTag"),
+        ds_rules.rules.processing.as_deref(),
+    ) {
+        (Some(tag), _) => Tag::from_str(tag).map_err(WorkError::InvalidTag)?,
+        (None, Some("last")) => Tag::new(b"rclt"),
+        (None, _) => Tag::new(b"rvrn"),
+    };
+
+    let rules = ds_rules
+        .rules
+        .rule
+        .into_iter()
+        .map(|rule| {
+            let mut condition_sets: Vec<_> = rule
+                .conditionset
+                .iter()
+                .filter_map(|set| to_ir_condition_set(&axes, rule.name.as_deref(), &set.condition))
+                .collect();
+            if !rule.condition.is_empty() {
+                condition_sets.extend(to_ir_condition_set(
+                    &axes,
+                    rule.name.as_deref(),
+                    &rule.condition,
+                ));
+            }
+            ir::SubstitutionRule {
+                name: rule.name,
+                condition_sets,
+                substitutions: rule
+                    .sub
+                    .into_iter()
+                    .map(|sub| (sub.name.into(), sub.with.into()))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(ir::FeatureVariations { feature_tag, rules })
+}
+
 pub fn to_ir_glyph(
     glyph_name: GlyphName,
     glif_files: &HashMap<&PathBuf, Vec<NormalizedLocation>>,
@@ -172,7 +557,7 @@ mod tests {
     use fontir::coords::{NormalizedCoord, NormalizedLocation};
     use norad::ContourPoint;
 
-    use super::{to_ir_contour, to_ir_glyph};
+    use super::{to_ir_contour, to_ir_glyph, to_ir_glyph_instance};
 
     fn testdata_dir() -> PathBuf {
         let dir = Path::new("../resources/testdata");
@@ -232,4 +617,18 @@ mod tests {
         .unwrap();
         assert_eq!(HashSet::from([0x007C]), glyph.codepoints);
     }
+
+    #[test]
+    fn captures_vertical_metrics() {
+        let mut glyph = norad::Glyph::new("ideograph");
+        glyph.height = 1000.0;
+        glyph
+            .lib
+            .insert("public.verticalOrigin".to_string(), 880.into());
+        let instance = to_ir_glyph_instance(&glyph).unwrap();
+        assert_eq!(
+            (Some(1000.0), Some(880.0)),
+            (instance.height, instance.vertical_origin)
+        );
+    }
 }